@@ -0,0 +1,2 @@
+drop index transactions_metadata_idx;
+alter table transactions drop column metadata;
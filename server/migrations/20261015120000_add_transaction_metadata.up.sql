@@ -0,0 +1,2 @@
+alter table transactions add column metadata jsonb;
+create index transactions_metadata_idx on transactions using gin (metadata);
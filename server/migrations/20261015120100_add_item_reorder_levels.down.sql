@@ -0,0 +1,2 @@
+alter table items drop column max_quantity;
+alter table items drop column reorder_point;
@@ -0,0 +1,2 @@
+alter table items add column reorder_point integer;
+alter table items add column max_quantity integer;
@@ -0,0 +1,2 @@
+drop index if exists items_name_unaccent_trgm_idx;
+drop function if exists immutable_unaccent(text);
@@ -0,0 +1,17 @@
+-- accent-insensitive search is only enabled when the extensions are available
+do $$
+begin
+    if exists (select 1 from pg_available_extensions where name = 'unaccent')
+        and exists (select 1 from pg_available_extensions where name = 'pg_trgm') then
+        create extension if not exists unaccent;
+        create extension if not exists pg_trgm;
+        -- unaccent is only stable, so an immutable wrapper is required to index it
+        create or replace function immutable_unaccent(text) returns text as
+            $function$ select public.unaccent('public.unaccent', $1) $function$
+            language sql immutable parallel safe strict;
+        -- accents are removed before lowering, as lowering accented characters depends on locale
+        create index items_name_unaccent_trgm_idx on items
+            using gin (lower(immutable_unaccent(name)) gin_trgm_ops);
+    end if;
+end
+$$;
@@ -0,0 +1,2 @@
+drop index items_unique_name_idx;
+alter table items drop column unique_name;
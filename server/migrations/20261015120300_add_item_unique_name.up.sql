@@ -0,0 +1,3 @@
+-- names are only unique among items created or updated while uniqueness is enforced
+alter table items add column unique_name boolean not null default false;
+create unique index items_unique_name_idx on items (upper(name)) where unique_name;
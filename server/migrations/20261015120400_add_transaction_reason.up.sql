@@ -0,0 +1,4 @@
+create type transaction_reason as enum (
+    'receipt', 'sale', 'return', 'adjustment', 'damage', 'transfer', 'opening_balance'
+);
+alter table transactions add column reason transaction_reason;
@@ -0,0 +1,4 @@
+drop index locations_lat_idx;
+alter table locations drop constraint locations_coordinates_check;
+alter table locations drop column lng;
+alter table locations drop column lat;
@@ -0,0 +1,5 @@
+alter table locations add column lat double precision check (lat between -90 and 90);
+alter table locations add column lng double precision check (lng between -180 and 180);
+alter table locations add constraint locations_coordinates_check check ((lat is null) = (lng is null));
+-- nearby locations are first narrowed to a latitude band, which this index serves
+create index locations_lat_idx on locations (lat);
@@ -0,0 +1,2 @@
+drop table reservations;
+drop type reservation_status;
@@ -0,0 +1,13 @@
+create type reservation_status as enum ('active', 'committed', 'released');
+create table reservations(
+    id serial primary key,
+    item_id integer not null,
+    location_id integer null,
+    quantity integer not null check (quantity > 0),
+    status reservation_status not null default 'active',
+    transaction_id integer null,
+    foreign key (item_id) references items on delete cascade,
+    foreign key (location_id) references locations on delete set null,
+    foreign key (transaction_id) references transactions on delete set null
+);
+create index reservations_active_item_id_idx on reservations (item_id) where status = 'active';
@@ -0,0 +1,2 @@
+drop index transactions_transaction_date_idx;
+drop index transactions_item_id_idx;
@@ -0,0 +1,2 @@
+create index transactions_item_id_idx on transactions (item_id);
+create index transactions_transaction_date_idx on transactions (transaction_date);
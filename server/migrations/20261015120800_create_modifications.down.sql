@@ -0,0 +1,3 @@
+drop table modifications;
+drop type modification_type;
+drop type modification_entity;
@@ -0,0 +1,10 @@
+create type modification_entity as enum ('item', 'location', 'transaction');
+create type modification_type as enum ('create', 'update', 'delete');
+create table modifications(
+    id serial primary key,
+    entity modification_entity not null,
+    modification modification_type not null,
+    data jsonb not null,
+    modified_at timestamptz not null default now()
+);
+create index modifications_modified_at_id_idx on modifications (modified_at desc, id desc);
@@ -0,0 +1,14 @@
+create table transaction_revisions(
+    id serial primary key,
+    transaction_id integer not null,
+    revision modification_type not null,
+    item_id integer not null,
+    location_id integer,
+    transaction_date timestamptz,
+    quantity integer not null,
+    comment text,
+    metadata jsonb,
+    reason transaction_reason,
+    revised_at timestamptz not null default now()
+);
+create index transaction_revisions_transaction_id_idx on transaction_revisions (transaction_id);
@@ -0,0 +1,8 @@
+create table item_attachments(
+    id serial primary key,
+    item_id integer not null,
+    url text not null,
+    sort_index integer not null,
+    foreign key (item_id) references items on delete cascade
+);
+create index item_attachments_item_id_sort_index_idx on item_attachments (item_id, sort_index, id);
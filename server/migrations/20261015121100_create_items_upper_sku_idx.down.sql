@@ -0,0 +1 @@
+drop index items_upper_sku_idx;
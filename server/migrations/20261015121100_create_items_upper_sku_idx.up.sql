@@ -0,0 +1,2 @@
+-- skus are unique ignoring case, which upserts by sku conflict on
+create unique index items_upper_sku_idx on items (upper(sku));
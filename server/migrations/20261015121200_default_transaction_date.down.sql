@@ -0,0 +1 @@
+alter table transactions alter column transaction_date drop default;
@@ -0,0 +1,2 @@
+-- new transactions are dated now when no date is given, while existing undated transactions are kept
+alter table transactions alter column transaction_date set default now();
@@ -0,0 +1,2 @@
+alter table items add column source text;
+alter table transactions add column source text;
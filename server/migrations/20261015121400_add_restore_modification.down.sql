@@ -0,0 +1,9 @@
+-- values cannot be removed from an enum, so the type is recreated without the restore value
+delete from modifications where modification = 'restore';
+alter type modification_type rename to modification_type_old;
+create type modification_type as enum ('create', 'update', 'delete');
+alter table modifications
+    alter column modification type modification_type using modification::text::modification_type;
+alter table transaction_revisions
+    alter column revision type modification_type using revision::text::modification_type;
+drop type modification_type_old;
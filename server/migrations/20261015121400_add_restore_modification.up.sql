@@ -0,0 +1 @@
+alter type modification_type add value 'restore';
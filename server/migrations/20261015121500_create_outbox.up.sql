@@ -0,0 +1,9 @@
+create table outbox(
+    id bigserial primary key,
+    channel text not null,
+    payload text not null,
+    enqueued_at timestamptz not null default now(),
+    delivered_at timestamptz
+);
+create index outbox_undelivered_idx on outbox (id) where delivered_at is null;
+create index outbox_delivered_at_idx on outbox (delivered_at) where delivered_at is not null;
@@ -0,0 +1,5 @@
+alter table transactions add column transfer_group bigint;
+-- the sequence is owned by the column, so a reset restarts it, and it is dropped with the column
+create sequence transfer_groups as bigint owned by transactions.transfer_group;
+create index transactions_transfer_group_idx on transactions (transfer_group)
+    where transfer_group is not null;
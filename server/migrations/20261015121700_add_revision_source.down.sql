@@ -0,0 +1,2 @@
+alter table transaction_revisions drop column transfer_group;
+alter table transaction_revisions drop column source;
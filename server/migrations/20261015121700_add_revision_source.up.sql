@@ -0,0 +1,2 @@
+alter table transaction_revisions add column source text;
+alter table transaction_revisions add column transfer_group bigint;
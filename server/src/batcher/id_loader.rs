@@ -4,12 +4,16 @@ use std::fmt::Debug;
 use std::future::Future;
 use std::hash::Hash;
 use std::pin::Pin;
+use std::sync::Arc;
 
 use async_graphql::{Error, ErrorExtensions, Result};
 use dataloader::non_cached::Loader;
 use dataloader::BatchFn;
+use tracing::Instrument;
 
 use crate::batcher;
+use crate::metrics::{self, LoaderMetrics};
+use crate::model::error::code;
 
 /// A function which retrieves results by ids and constructs a map for them.
 /// The keys, `K` are mapped to the values, `T`.
@@ -20,7 +24,10 @@ pub(crate) type IdMapper<K, T, C> =
 /// Handles batched loading of results by ids.
 pub(crate) struct IdBatcher<K, T, C> {
     context: C,
+    /// The name of the loader, in its metrics and the spans of its batches.
+    name: &'static str,
     results_by_id: Box<IdMapper<K, T, C>>,
+    metrics: Arc<LoaderMetrics>,
 }
 
 /// Batch loader for results by ids.
@@ -29,31 +36,47 @@ pub(crate) type IdLoader<K, T, C> = Loader<K, Result<T>, IdBatcher<K, T, C>>;
 #[async_trait::async_trait]
 impl<K, T, C> BatchFn<K, Result<T>> for IdBatcher<K, T, C>
 where
-    K: Eq + Hash + Send + Sync + Copy + Clone + Debug,
+    K: Eq + Hash + Send + Sync + Clone + Debug,
     T: Send + Clone,
     C: Send + Sync,
 {
     async fn load(&mut self, ids: &[K]) -> HashMap<K, Result<T>> {
         let mut results_map = HashMap::new();
-        // get the results by ids
-        match (self.results_by_id)(&self.context, ids.to_vec()).await {
+        // there is nothing to retrieve, so skip the round-trip
+        if ids.is_empty() {
+            return results_map;
+        }
+
+        // get the results by ids, in a span so each batched database query is traced
+        let span = tracing::info_span!("load", loader = self.name, ids = ids.len());
+        match (self.results_by_id)(&self.context, ids.to_vec())
+            .instrument(span)
+            .await
+        {
             Ok(results) => {
                 // add the results to the map
+                #[allow(clippy::map_identity)]
                 results_map.extend(results.into_iter().map(|(id, result)| (id, result)));
 
                 // for each result not found, create an error
+                let mut not_found = 0;
                 ids.iter().for_each(|id| {
-                    results_map.entry(*id).or_insert_with(|| {
-                        Err(Error::new("not found")
-                            .extend_with(|_, e| e.set("id", format!("{:?}", id))))
+                    results_map.entry(id.clone()).or_insert_with(|| {
+                        not_found += 1;
+                        Err(Error::new("not found").extend_with(|_, e| {
+                            e.set("code", code::NOT_FOUND);
+                            e.set("id", format!("{:?}", id));
+                        }))
                     });
                 });
+                self.metrics.record_batch(ids.len(), not_found);
             }
             Err(e) => {
                 // each request will fail with the error of the batched request
                 ids.iter().for_each(|id| {
-                    results_map.insert(*id, Err(e.clone()));
+                    results_map.insert(id.clone(), Err(e.clone()));
                 });
+                self.metrics.record_batch(ids.len(), 0);
             }
         }
 
@@ -61,19 +84,23 @@ where
     }
 }
 
-/// Gets an id loader with the given mapping function.
+/// Gets an id loader with the given mapping function, which records its batches in the metrics
+/// of the given name.
 pub(crate) fn get_loader<K, T, C>(
     context: &C,
+    name: &'static str,
     results_by_id: IdMapper<K, T, C>,
 ) -> IdLoader<K, T, C>
 where
-    K: Eq + Hash + Send + Sync + Copy + Clone + Debug,
+    K: Eq + Hash + Send + Sync + Clone + Debug,
     T: Send + Clone,
     C: Send + Sync + Clone,
 {
     Loader::new(IdBatcher {
         context: context.clone(),
+        name,
         results_by_id: Box::new(results_by_id),
+        metrics: metrics::loader_metrics(name),
     })
     .with_yield_count(batcher::LOADER_YIELD_COUNT)
 }
@@ -81,6 +108,9 @@ where
 /// Unit tests for the batch loader.
 #[cfg(test)]
 mod test {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
     use super::*;
 
     /// A fake that adds ids to the context.
@@ -95,6 +125,32 @@ mod test {
         Ok(result)
     }
 
+    /// A fake that finds no results.
+    async fn mapper_empty_fake(_: &Option<i32>, _: Vec<i32>) -> Result<HashMap<i32, Result<i32>>> {
+        Ok(HashMap::new())
+    }
+
+    /// A fake that adds the length of string ids to the context, counting the batches.
+    async fn mapper_string_fake(
+        context: &(i32, Arc<AtomicUsize>),
+        ids: Vec<String>,
+    ) -> Result<HashMap<String, Result<i32>>> {
+        context.1.fetch_add(1, Ordering::SeqCst);
+        let mut result = HashMap::new();
+        ids.into_iter().for_each(|id| {
+            result.insert(id.clone(), Ok(id.len() as i32 + context.0));
+        });
+        Ok(result)
+    }
+
+    /// A fake that fails the test if it is called.
+    async fn mapper_unreachable_fake(
+        _: &Option<i32>,
+        _: Vec<i32>,
+    ) -> Result<HashMap<i32, Result<i32>>> {
+        panic!("mapper called without ids")
+    }
+
     /// A fake that returns an error.
     async fn mapper_fail_fake(_: &Option<i32>, _: Vec<i32>) -> Result<HashMap<i32, Result<i32>>> {
         Err(Error::new("error"))
@@ -103,7 +159,9 @@ mod test {
     #[actix_rt::test]
     async fn test_mapper() {
         let context = Some(1);
-        let loader = get_loader(&context, |clients, ids| Box::pin(mapper_fake(clients, ids)));
+        let loader = get_loader(&context, "test", |clients, ids| {
+            Box::pin(mapper_fake(clients, ids))
+        });
         let f1 = loader.load(5);
         let f2 = loader.load(10);
         let f3 = loader.load(1);
@@ -113,7 +171,7 @@ mod test {
     #[actix_rt::test]
     async fn test_mapper_fail() {
         let context = Some(1);
-        let loader = get_loader(&context, |clients, ids| {
+        let loader = get_loader(&context, "test", |clients, ids| {
             Box::pin(mapper_fail_fake(clients, ids))
         });
         let f1 = loader.load(5);
@@ -125,4 +183,80 @@ mod test {
             (Err(e.clone()), Err(e.clone()), Err(e.clone()))
         );
     }
+
+    #[actix_rt::test]
+    async fn test_mapper_no_ids() {
+        let context = Some(1);
+        let mut batcher = IdBatcher {
+            context,
+            name: "test",
+            results_by_id: Box::new(|clients, ids| Box::pin(mapper_unreachable_fake(clients, ids))),
+            metrics: Arc::new(LoaderMetrics::default()),
+        };
+        assert!(BatchFn::load(&mut batcher, &[]).await.is_empty());
+
+        let loader = get_loader(&context, "test", |clients, ids| {
+            Box::pin(mapper_unreachable_fake(clients, ids))
+        });
+        assert!(loader.load_many(Vec::new()).await.is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn test_mapper_not_found() {
+        let context = Some(1);
+        let loader = get_loader(&context, "test", |clients, ids| {
+            Box::pin(mapper_empty_fake(clients, ids))
+        });
+        let e = Error::new("not found").extend_with(|_, e| {
+            e.set("code", "NOT_FOUND");
+            e.set("id", "5");
+        });
+        assert_eq!(loader.load(5).await, Err(e));
+    }
+
+    #[actix_rt::test]
+    async fn test_mapper_string_ids() {
+        let batches = Arc::new(AtomicUsize::new(0));
+        let context = (1, batches.clone());
+        let loader = get_loader(&context, "test", |clients, ids| {
+            Box::pin(mapper_string_fake(clients, ids))
+        });
+        let f1 = loader.load("a".to_string());
+        let f2 = loader.load("bc".to_string());
+        let f3 = loader.load("a".to_string());
+        assert_eq!(futures::join!(f1, f2, f3), (Ok(2), Ok(3), Ok(2)));
+        assert_eq!(batches.load(Ordering::SeqCst), 1);
+    }
+
+    #[actix_rt::test]
+    async fn test_mapper_metrics() {
+        let context = Some(1);
+        let loader = get_loader(&context, "test_mapper_metrics", |clients, ids| {
+            Box::pin(mapper_empty_fake(clients, ids))
+        });
+        let f1 = loader.load(5);
+        let f2 = loader.load(10);
+        let f3 = loader.load(1);
+        let not_found = |id: &str| {
+            let id = id.to_string();
+            Err(Error::new("not found").extend_with(|_, e| {
+                e.set("code", "NOT_FOUND");
+                e.set("id", id.as_str());
+            }))
+        };
+        assert_eq!(
+            futures::join!(f1, f2, f3),
+            (not_found("5"), not_found("10"), not_found("1"))
+        );
+
+        // check that one batch of 3 ids was recorded, none of which were found
+        let metrics = metrics::loader_metrics("test_mapper_metrics");
+        assert_eq!(metrics.batch_sizes.count(), 1);
+        assert_eq!(metrics.batch_sizes.sum(), 3);
+        assert_eq!(
+            metrics.batch_sizes.cumulative_counts(),
+            vec![0, 1, 1, 1, 1, 1, 1, 1, 1]
+        );
+        assert_eq!(metrics.not_found.load(Ordering::Relaxed), 3);
+    }
 }
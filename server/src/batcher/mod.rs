@@ -1,7 +1,7 @@
 pub(crate) mod id_loader;
 
 use crate::graphql::Clients;
-use crate::model::{item, location, transaction};
+use crate::model::{attachment, item, location, reservation, transaction};
 
 /// The default loader yield count.
 pub(crate) const LOADER_YIELD_COUNT: usize = 100;
@@ -12,28 +12,108 @@ pub(crate) fn register_loaders(
     loaders: &mut anymap2::Map<dyn anymap2::any::Any + Send + Sync>,
 ) {
     // get an item by id
-    loaders.insert(id_loader::get_loader(clients, |clients, ids| {
+    loaders.insert(id_loader::get_loader(clients, "item", |clients, ids| {
         Box::pin(item::get_items_by_ids(clients, ids))
     }));
+    // get an item by sku
+    loaders.insert(id_loader::get_loader(
+        clients,
+        "item_by_sku",
+        |clients, skus| Box::pin(item::get_items_by_skus(clients, skus)),
+    ));
     // get a location by id
-    loaders.insert(id_loader::get_loader(clients, |clients, ids| {
-        Box::pin(location::get_locations_by_ids(clients, ids))
-    }));
+    loaders.insert(id_loader::get_loader(
+        clients,
+        "location",
+        |clients, ids| Box::pin(location::get_locations_by_ids(clients, ids)),
+    ));
     // get a transaction by id
-    loaders.insert(id_loader::get_loader(clients, |clients, ids| {
-        Box::pin(transaction::get_transactions_by_ids(clients, ids))
-    }));
+    loaders.insert(id_loader::get_loader(
+        clients,
+        "transaction",
+        |clients, ids| Box::pin(transaction::get_transactions_by_ids(clients, ids)),
+    ));
+    // get the revisions of a transaction
+    loaders.insert(id_loader::get_loader(
+        clients,
+        "transaction_revisions",
+        |clients, ids| Box::pin(transaction::get_revisions_by_transaction_ids(clients, ids)),
+    ));
 
     // get all transactions for an item
-    loaders.insert(id_loader::get_loader(clients, |clients, ids| {
-        Box::pin(item::get_transactions_by_item_ids(clients, ids))
-    }));
+    loaders.insert(id_loader::get_loader(
+        clients,
+        "item_transactions",
+        |clients, ids| Box::pin(item::get_transactions_by_item_ids(clients, ids)),
+    ));
+    // get a page of transactions for an item
+    loaders.insert(id_loader::get_loader(
+        clients,
+        "item_transaction_page",
+        |clients, keys| Box::pin(item::get_transaction_pages_by_keys(clients, keys)),
+    ));
     // get an item quantity
-    loaders.insert(id_loader::get_loader(clients, |clients, ids| {
-        Box::pin(item::get_quantities_by_item_ids(clients, ids))
-    }));
+    loaders.insert(id_loader::get_loader(
+        clients,
+        "item_quantity",
+        |clients, ids| Box::pin(item::get_quantities_by_item_ids(clients, ids)),
+    ));
+    // get the attachments of an item
+    loaders.insert(id_loader::get_loader(
+        clients,
+        "item_attachments",
+        |clients, ids| Box::pin(attachment::get_attachments_by_item_ids(clients, ids)),
+    ));
+    // get the quantity of an item as of a date
+    loaders.insert(id_loader::get_loader(
+        clients,
+        "item_quantity_as_of",
+        |clients, keys| Box::pin(item::get_quantities_as_of_keys(clients, keys)),
+    ));
+    // get the reserved quantity of an item
+    loaders.insert(id_loader::get_loader(
+        clients,
+        "item_quantity_reserved",
+        |clients, ids| {
+            Box::pin(reservation::get_reserved_quantities_by_item_ids(
+                clients, ids,
+            ))
+        },
+    ));
+    // get the ledger of an item
+    loaders.insert(id_loader::get_loader(
+        clients,
+        "item_ledger",
+        |clients, ids| Box::pin(item::get_ledgers_by_item_ids(clients, ids)),
+    ));
+    // get the activity of an item by transaction reason
+    loaders.insert(id_loader::get_loader(
+        clients,
+        "item_activity",
+        |clients, ids| Box::pin(item::get_activities_by_item_ids(clients, ids)),
+    ));
+    // get the average daily consumption of an item
+    loaders.insert(id_loader::get_loader(
+        clients,
+        "item_consumption",
+        |clients, windows| Box::pin(item::get_consumptions_by_windows(clients, windows)),
+    ));
+    // get the distinct locations of the transactions of an item
+    loaders.insert(id_loader::get_loader(
+        clients,
+        "item_locations",
+        |clients, ids| Box::pin(location::get_locations_by_item_ids(clients, ids)),
+    ));
     // get all transactions at a location
-    loaders.insert(id_loader::get_loader(clients, |clients, ids| {
-        Box::pin(location::get_transactions_by_location_ids(clients, ids))
-    }));
+    loaders.insert(id_loader::get_loader(
+        clients,
+        "location_transactions",
+        |clients, ids| Box::pin(location::get_transactions_by_location_ids(clients, ids)),
+    ));
+    // get a page of the items with stock at a location
+    loaders.insert(id_loader::get_loader(
+        clients,
+        "location_stock_page",
+        |clients, keys| Box::pin(location::get_stock_pages_by_keys(clients, keys)),
+    ));
 }
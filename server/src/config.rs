@@ -0,0 +1,751 @@
+use std::collections::HashSet;
+use std::env;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+use std::time::Duration;
+
+use log::LevelFilter;
+use regex::Regex;
+
+use crate::graphql::fanout::LagPolicy;
+use crate::graphql::DEFAULT_COMPLEXITY_LIMIT;
+use crate::model::transaction::TransactionReason;
+use crate::retry::RetryPolicy;
+
+/// The default number of max database connections.
+const DEFAULT_DATABASE_MAX_CONNECTIONS: u32 = 100;
+/// Whether pending migrations are run at startup by default.
+const DEFAULT_DATABASE_AUTO_MIGRATE: bool = true;
+/// Whether startup fails on pending migrations by default, when they are not run.
+const DEFAULT_DATABASE_FAIL_ON_PENDING_MIGRATIONS: bool = true;
+/// The default number of attempts to connect to the database and redis at startup.
+const DEFAULT_CONNECT_ATTEMPTS: u32 = 5;
+/// The default delay before retrying a connection in milliseconds, which doubles after each retry.
+const DEFAULT_CONNECT_RETRY_DELAY_MS: u64 = 500;
+/// The default maximum size of GraphQL request bodies in bytes.
+const DEFAULT_GRAPHQL_MAX_BODY_BYTES: usize = 256 * 1024;
+/// The default maximum size of the bodies of bulk imports in bytes.
+const DEFAULT_GRAPHQL_IMPORT_MAX_BODY_BYTES: usize = 10 * 1024 * 1024;
+/// The default maximum level of logged messages.
+const DEFAULT_LOG_LEVEL: LevelFilter = LevelFilter::Info;
+/// Whether the seed dataset is inserted at startup by default.
+const DEFAULT_SEED_ON_STARTUP: bool = false;
+/// Whether traces are exported over OTLP by default.
+const DEFAULT_TRACING_OTLP_ENABLED: bool = false;
+
+/// The default interval of subscription keepalive messages in seconds.
+const DEFAULT_SUBSCRIPTION_KEEPALIVE_INTERVAL_SECS: u64 = 15;
+/// The default maximum number of concurrent subscriptions of a websocket connection.
+const DEFAULT_SUBSCRIPTION_MAX_PER_CONNECTION: usize = 100;
+/// The default max age of cacheable GraphQL queries over GET in seconds.
+const DEFAULT_GRAPHQL_CACHE_MAX_AGE_SECS: u32 = 60;
+/// The default timeout of GraphQL operations in milliseconds.
+const DEFAULT_GRAPHQL_OPERATION_TIMEOUT_MS: u64 = 30_000;
+/// Whether the schema can be composed into a federated supergraph by default.
+const DEFAULT_FEDERATION_ENABLED: bool = false;
+/// Whether item names must be unique by default.
+const DEFAULT_ENFORCE_UNIQUE_ITEM_NAME: bool = false;
+/// Whether transactions can be dated in the future by default.
+const DEFAULT_ALLOW_FUTURE_TRANSACTIONS: bool = true;
+/// The default skew allowed past now when future transactions are not allowed, in seconds.
+const DEFAULT_FUTURE_TRANSACTION_SKEW_SECS: u64 = 300;
+/// Whether transactions can take the quantity of an item below zero by default.
+const DEFAULT_ALLOW_NEGATIVE_STOCK: bool = true;
+/// The default number of messages buffered for subscribers of each channel.
+const DEFAULT_SUBSCRIPTION_BUFFER_SIZE: usize = 256;
+/// The default maximum number of characters of skus.
+const DEFAULT_SKU_MAX_LENGTH: usize = 64;
+/// Whether all data can be deleted by the admin reset by default.
+const DEFAULT_TEST_RESET_ENABLED: bool = false;
+/// Whether mutations are rejected for maintenance by default.
+const DEFAULT_MAINTENANCE_MODE: bool = false;
+/// Whether unsafe markup is removed from item descriptions by default.
+const DEFAULT_SANITIZE_ITEM_DESCRIPTIONS: bool = false;
+
+/// A regular expression which values must match in full, which is compared by its source.
+#[derive(Clone, Debug)]
+pub(crate) struct FullMatch(pub(crate) Regex);
+
+impl FromStr for FullMatch {
+    type Err = regex::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // the pattern is anchored, so it reads the same in the server and in clients
+        Regex::new(&format!("^(?:{})$", s)).map(FullMatch)
+    }
+}
+
+impl PartialEq for FullMatch {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.as_str() == other.0.as_str()
+    }
+}
+
+/// Gets the entries of a comma separated list, ignoring empty entries, as the flags which are
+/// lists do.
+fn list_entries(val: &str) -> impl Iterator<Item = &str> {
+    val.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+}
+
+/// Gets a GraphQL name, such as of a field or an operation, or none if the value is not one.
+fn graphql_name(val: &str) -> Option<String> {
+    let mut chars = val.chars();
+    let is_name = matches!(chars.next(), Some(c) if c == '_' || c.is_ascii_alphabetic())
+        && chars.all(|c| c == '_' || c.is_ascii_alphanumeric());
+    is_name.then(|| val.to_string())
+}
+
+/// The configuration of the server, which is validated once at startup.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Config {
+    /// The address the server binds to.
+    pub(crate) actix_address: String,
+    /// The port the server binds to.
+    pub(crate) port: u16,
+    /// The url of the database.
+    pub(crate) database_url: String,
+    /// The maximum number of database connections.
+    pub(crate) database_max_connections: u32,
+    /// Whether pending migrations are run at startup.
+    pub(crate) database_auto_migrate: bool,
+    /// Whether startup fails on pending migrations, when they are not run.
+    pub(crate) database_fail_on_pending_migrations: bool,
+    /// The url of redis.
+    pub(crate) redis_url: String,
+    /// The number of attempts to connect to the database and redis at startup.
+    pub(crate) connect_attempts: u32,
+    /// The delay before retrying a connection, which doubles after each retry.
+    pub(crate) connect_retry_delay: Duration,
+    /// The maximum size of GraphQL request bodies in bytes.
+    pub(crate) graphql_max_body_bytes: usize,
+    /// The maximum size of the bodies of bulk imports in bytes.
+    pub(crate) graphql_import_max_body_bytes: usize,
+    /// The maximum level of logged messages.
+    pub(crate) log_level: LevelFilter,
+    /// The duration after which queries are logged as slow, or none if they are not.
+    pub(crate) slow_query: Option<Duration>,
+    /// Whether the seed dataset is inserted at startup.
+    pub(crate) seed_on_startup: bool,
+    /// The path of the seed dataset, or none for the built-in dataset.
+    pub(crate) seed_file: Option<String>,
+    /// Whether traces are exported over OTLP, to the collector of the `OTEL_EXPORTER_OTLP_*`
+    /// variables.
+    pub(crate) tracing_otlp_enabled: bool,
+    /// The interval of subscription keepalive messages, or none if they are disabled.
+    pub(crate) subscription_keepalive_interval: Option<Duration>,
+    /// The maximum number of concurrent subscriptions of a websocket connection, or none if it is
+    /// unlimited.
+    pub(crate) subscription_max_per_connection: Option<usize>,
+    /// The max age of cacheable GraphQL queries over GET in seconds, or 0 if they are not cached.
+    pub(crate) graphql_cache_max_age_secs: u32,
+    /// The timeout of GraphQL operations, or none if they do not time out.
+    pub(crate) graphql_operation_timeout: Option<Duration>,
+    /// The maximum complexity of GraphQL operations, or none if it is unlimited.
+    pub(crate) graphql_complexity_limit: Option<usize>,
+    /// Whether the schema can be composed into an Apollo Federation supergraph.
+    pub(crate) federation_enabled: bool,
+    /// Whether item names must be unique, ignoring case.
+    pub(crate) enforce_unique_item_name: bool,
+    /// The maximum number of transactions of an item, or none if there is no maximum.
+    pub(crate) max_transactions_per_item: Option<i64>,
+    /// Whether transactions can be dated in the future.
+    pub(crate) allow_future_transactions: bool,
+    /// How far past now transaction dates can be when future transactions are not allowed.
+    pub(crate) future_transaction_skew: Duration,
+    /// Whether transactions can take the quantity of an item below zero.
+    pub(crate) allow_negative_stock: bool,
+    /// The reasons of transactions which must have a comment explaining them.
+    pub(crate) transaction_comment_required_reasons: HashSet<TransactionReason>,
+    /// The number of messages buffered for subscribers of each channel.
+    pub(crate) subscription_buffer_size: usize,
+    /// What happens to a subscriber which falls behind the buffer.
+    pub(crate) subscription_lag_policy: LagPolicy,
+    /// The pattern which skus must match in full, or none if skus are not restricted.
+    pub(crate) sku_pattern: Option<FullMatch>,
+    /// The maximum number of characters of skus.
+    pub(crate) sku_max_length: usize,
+    /// Whether all data can be deleted by the admin reset, which is only for test environments.
+    pub(crate) test_reset_enabled: bool,
+    /// Whether mutations are rejected for maintenance, regardless of the mode set at runtime.
+    pub(crate) maintenance_mode: bool,
+    /// Whether unsafe markup is removed from item descriptions before they are stored.
+    pub(crate) sanitize_item_descriptions: bool,
+    /// The bearer token of administrators, or none if there are no administrators.
+    pub(crate) admin_token: Option<String>,
+    /// The root query fields which can be cached.
+    pub(crate) graphql_cache_fields: HashSet<String>,
+    /// The names of the operations which are allowed over GET, or none if they are not restricted.
+    pub(crate) graphql_get_allowed_operations: Option<HashSet<String>>,
+    /// The lowercase hex encoded sha256 hashes of the query documents which are allowed over GET,
+    /// or none if they are not restricted.
+    pub(crate) graphql_get_allowed_query_hashes: Option<HashSet<String>>,
+}
+
+/// The missing or invalid variables of a configuration.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct ConfigError {
+    pub(crate) errors: Vec<String>,
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid configuration:")?;
+        for error in &self.errors {
+            write!(f, "\n  {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl Config {
+    /// Gets the configuration from the environment, returning the result, or all of the missing or
+    /// invalid variables.
+    pub(crate) fn from_env() -> Result<Self, ConfigError> {
+        Config::from_vars(|key| env::var(key).ok())
+    }
+
+    /// Gets the configuration from a lookup of variables, returning the result, or all of the
+    /// missing or invalid variables.
+    pub(crate) fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        let mut errors = Vec::new();
+
+        let mut required = |key: &str| {
+            var(key).unwrap_or_else(|| {
+                errors.push(format!("{} must be set", key));
+                String::new()
+            })
+        };
+        let actix_address = required("ACTIX_ADDRESS");
+        let port = required("PORT");
+        let database_url = required("DATABASE_URL");
+        let redis_url = required("REDIS_URL");
+
+        let port = if port.is_empty() {
+            0
+        } else {
+            parse(&mut errors, "PORT", &port, "a port number").unwrap_or(0)
+        };
+
+        let database_max_connections = parse_optional(
+            &mut errors,
+            &var,
+            "DATABASE_MAX_CONNECTIONS",
+            "a positive integer",
+        )
+        .unwrap_or(DEFAULT_DATABASE_MAX_CONNECTIONS);
+        let database_auto_migrate =
+            parse_optional(&mut errors, &var, "DATABASE_AUTO_MIGRATE", "true or false")
+                .unwrap_or(DEFAULT_DATABASE_AUTO_MIGRATE);
+        let database_fail_on_pending_migrations = parse_optional(
+            &mut errors,
+            &var,
+            "DATABASE_FAIL_ON_PENDING_MIGRATIONS",
+            "true or false",
+        )
+        .unwrap_or(DEFAULT_DATABASE_FAIL_ON_PENDING_MIGRATIONS);
+        let connect_attempts =
+            parse_optional(&mut errors, &var, "CONNECT_ATTEMPTS", "a positive integer")
+                .unwrap_or(DEFAULT_CONNECT_ATTEMPTS);
+        let connect_retry_delay_ms = parse_optional(
+            &mut errors,
+            &var,
+            "CONNECT_RETRY_DELAY_MS",
+            "a positive integer",
+        )
+        .unwrap_or(DEFAULT_CONNECT_RETRY_DELAY_MS);
+        let graphql_max_body_bytes = parse_optional(
+            &mut errors,
+            &var,
+            "GRAPHQL_MAX_BODY_BYTES",
+            "a positive integer",
+        )
+        .unwrap_or(DEFAULT_GRAPHQL_MAX_BODY_BYTES);
+        let graphql_import_max_body_bytes = parse_optional(
+            &mut errors,
+            &var,
+            "GRAPHQL_IMPORT_MAX_BODY_BYTES",
+            "a positive integer",
+        )
+        .unwrap_or(DEFAULT_GRAPHQL_IMPORT_MAX_BODY_BYTES);
+        let log_level = parse_optional(
+            &mut errors,
+            &var,
+            "LOG_LEVEL",
+            "one of off, error, warn, info, debug, trace",
+        )
+        .unwrap_or(DEFAULT_LOG_LEVEL);
+        let slow_query_ms: Option<u64> =
+            parse_optional(&mut errors, &var, "SLOW_QUERY_MS", "a positive integer");
+        let seed_on_startup = parse_optional(&mut errors, &var, "SEED_ON_STARTUP", "true or false")
+            .unwrap_or(DEFAULT_SEED_ON_STARTUP);
+        let seed_file = var("SEED_FILE").filter(|seed_file| !seed_file.is_empty());
+        let tracing_otlp_enabled =
+            parse_optional(&mut errors, &var, "TRACING_OTLP_ENABLED", "true or false")
+                .unwrap_or(DEFAULT_TRACING_OTLP_ENABLED);
+
+        let subscription_keepalive_interval_secs = parse_optional(
+            &mut errors,
+            &var,
+            "SUBSCRIPTION_KEEPALIVE_INTERVAL_SECS",
+            "a positive integer",
+        )
+        .unwrap_or(DEFAULT_SUBSCRIPTION_KEEPALIVE_INTERVAL_SECS);
+        let subscription_max_per_connection = parse_optional(
+            &mut errors,
+            &var,
+            "SUBSCRIPTION_MAX_PER_CONNECTION",
+            "a positive integer",
+        )
+        .unwrap_or(DEFAULT_SUBSCRIPTION_MAX_PER_CONNECTION);
+        let graphql_cache_max_age_secs = parse_optional(
+            &mut errors,
+            &var,
+            "GRAPHQL_CACHE_MAX_AGE_SECS",
+            "a positive integer",
+        )
+        .unwrap_or(DEFAULT_GRAPHQL_CACHE_MAX_AGE_SECS);
+        let graphql_operation_timeout_ms = parse_optional(
+            &mut errors,
+            &var,
+            "GRAPHQL_OPERATION_TIMEOUT_MS",
+            "a positive integer",
+        )
+        .unwrap_or(DEFAULT_GRAPHQL_OPERATION_TIMEOUT_MS);
+        let graphql_complexity_limit = parse_optional(
+            &mut errors,
+            &var,
+            "GRAPHQL_COMPLEXITY_LIMIT",
+            "a positive integer",
+        )
+        .unwrap_or(DEFAULT_COMPLEXITY_LIMIT);
+        let federation_enabled =
+            parse_optional(&mut errors, &var, "FEDERATION_ENABLED", "true or false")
+                .unwrap_or(DEFAULT_FEDERATION_ENABLED);
+        let enforce_unique_item_name = parse_optional(
+            &mut errors,
+            &var,
+            "ENFORCE_UNIQUE_ITEM_NAME",
+            "true or false",
+        )
+        .unwrap_or(DEFAULT_ENFORCE_UNIQUE_ITEM_NAME);
+        let max_transactions_per_item: Option<i64> =
+            parse_optional(&mut errors, &var, "MAX_TRANSACTIONS_PER_ITEM", "an integer");
+        let allow_future_transactions = parse_optional(
+            &mut errors,
+            &var,
+            "ALLOW_FUTURE_TRANSACTIONS",
+            "true or false",
+        )
+        .unwrap_or(DEFAULT_ALLOW_FUTURE_TRANSACTIONS);
+        let future_transaction_skew_secs = parse_optional(
+            &mut errors,
+            &var,
+            "FUTURE_TRANSACTION_SKEW_SECS",
+            "a positive integer",
+        )
+        .unwrap_or(DEFAULT_FUTURE_TRANSACTION_SKEW_SECS);
+        let allow_negative_stock =
+            parse_optional(&mut errors, &var, "ALLOW_NEGATIVE_STOCK", "true or false")
+                .unwrap_or(DEFAULT_ALLOW_NEGATIVE_STOCK);
+        let reason_names: Vec<_> = TransactionReason::NAMES
+            .iter()
+            .map(|(name, _)| *name)
+            .collect();
+        let transaction_comment_required_reasons = parse_list(
+            &mut errors,
+            &var,
+            "TRANSACTION_COMMENT_REQUIRED_REASONS",
+            &format!("a comma separated list of {}", reason_names.join(", ")),
+            TransactionReason::from_name,
+        )
+        .unwrap_or_default();
+        let subscription_buffer_size = parse_optional_nonzero(
+            &mut errors,
+            &var,
+            "SUBSCRIPTION_BUFFER_SIZE",
+            "a positive integer",
+        )
+        .unwrap_or(DEFAULT_SUBSCRIPTION_BUFFER_SIZE);
+        let subscription_lag_policy = parse_optional(
+            &mut errors,
+            &var,
+            "SUBSCRIPTION_LAG_POLICY",
+            "one of drop_oldest, disconnect",
+        )
+        .unwrap_or(LagPolicy::DropOldest);
+        let sku_pattern = parse_optional(&mut errors, &var, "SKU_PATTERN", "a regular expression");
+        let sku_max_length =
+            parse_optional_nonzero(&mut errors, &var, "SKU_MAX_LENGTH", "a positive integer")
+                .unwrap_or(DEFAULT_SKU_MAX_LENGTH);
+        let test_reset_enabled =
+            parse_optional(&mut errors, &var, "TEST_RESET_ENABLED", "true or false")
+                .unwrap_or(DEFAULT_TEST_RESET_ENABLED);
+        let maintenance_mode =
+            parse_optional(&mut errors, &var, "MAINTENANCE_MODE", "true or false")
+                .unwrap_or(DEFAULT_MAINTENANCE_MODE);
+        let sanitize_item_descriptions = parse_optional(
+            &mut errors,
+            &var,
+            "SANITIZE_ITEM_DESCRIPTIONS",
+            "true or false",
+        )
+        .unwrap_or(DEFAULT_SANITIZE_ITEM_DESCRIPTIONS);
+        let admin_token = var("ADMIN_TOKEN").filter(|token| !token.is_empty());
+        let graphql_cache_fields = parse_list(
+            &mut errors,
+            &var,
+            "GRAPHQL_CACHE_FIELDS",
+            "a comma separated list of GraphQL names",
+            graphql_name,
+        )
+        .unwrap_or_default();
+        let graphql_get_allowed_operations = parse_list(
+            &mut errors,
+            &var,
+            "GRAPHQL_GET_ALLOWED_OPERATIONS",
+            "a comma separated list of GraphQL names",
+            graphql_name,
+        );
+        let graphql_get_allowed_query_hashes = parse_list(
+            &mut errors,
+            &var,
+            "GRAPHQL_GET_ALLOWED_QUERY_HASHES",
+            "a comma separated list of sha256 hashes",
+            |hash| {
+                (hash.len() == 64 && hash.chars().all(|c| c.is_ascii_hexdigit()))
+                    .then(|| hash.to_lowercase())
+            },
+        );
+
+        if errors.is_empty() {
+            Ok(Config {
+                actix_address,
+                port,
+                database_url,
+                database_max_connections,
+                database_auto_migrate,
+                database_fail_on_pending_migrations,
+                redis_url,
+                connect_attempts,
+                connect_retry_delay: Duration::from_millis(connect_retry_delay_ms),
+                graphql_max_body_bytes,
+                graphql_import_max_body_bytes,
+                log_level,
+                slow_query: slow_query_ms.map(Duration::from_millis),
+                seed_on_startup,
+                seed_file,
+                tracing_otlp_enabled,
+                subscription_keepalive_interval: (subscription_keepalive_interval_secs > 0)
+                    .then(|| Duration::from_secs(subscription_keepalive_interval_secs)),
+                subscription_max_per_connection: (subscription_max_per_connection > 0)
+                    .then_some(subscription_max_per_connection),
+                graphql_cache_max_age_secs,
+                graphql_operation_timeout: (graphql_operation_timeout_ms > 0)
+                    .then(|| Duration::from_millis(graphql_operation_timeout_ms)),
+                graphql_complexity_limit: (graphql_complexity_limit > 0)
+                    .then_some(graphql_complexity_limit),
+                federation_enabled,
+                enforce_unique_item_name,
+                // a maximum which is not positive means there is no maximum
+                max_transactions_per_item: max_transactions_per_item.filter(|max| *max > 0),
+                allow_future_transactions,
+                future_transaction_skew: Duration::from_secs(future_transaction_skew_secs),
+                allow_negative_stock,
+                transaction_comment_required_reasons,
+                subscription_buffer_size,
+                subscription_lag_policy,
+                sku_pattern,
+                sku_max_length,
+                test_reset_enabled,
+                maintenance_mode,
+                sanitize_item_descriptions,
+                admin_token,
+                graphql_cache_fields,
+                graphql_get_allowed_operations,
+                graphql_get_allowed_query_hashes,
+            })
+        } else {
+            Err(ConfigError { errors })
+        }
+    }
+
+    /// Gets the policy for retrying connections at startup.
+    pub(crate) fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            attempts: self.connect_attempts,
+            delay: self.connect_retry_delay,
+        }
+    }
+}
+
+/// Parses the value of a variable, adding an error if it is invalid.
+fn parse<T: FromStr>(errors: &mut Vec<String>, key: &str, val: &str, expected: &str) -> Option<T> {
+    let parsed = val.parse::<T>().ok();
+    if parsed.is_none() {
+        errors.push(format!("{} must be {}, but is {:?}", key, expected, val));
+    }
+    parsed
+}
+
+/// Parses the value of a variable if it is set, adding an error if it is invalid.
+fn parse_optional<T: FromStr>(
+    errors: &mut Vec<String>,
+    var: impl Fn(&str) -> Option<String>,
+    key: &str,
+    expected: &str,
+) -> Option<T> {
+    var(key).and_then(|val| parse(errors, key, &val, expected))
+}
+
+/// Parses the value of a variable if it is set, adding an error if it is invalid or zero.
+fn parse_optional_nonzero<T: FromStr + Default + PartialEq>(
+    errors: &mut Vec<String>,
+    var: impl Fn(&str) -> Option<String>,
+    key: &str,
+    expected: &str,
+) -> Option<T> {
+    let val = var(key)?;
+    let parsed = parse::<T>(errors, key, &val, expected)?;
+    if parsed == T::default() {
+        errors.push(format!("{} must be {}, but is {:?}", key, expected, val));
+        return None;
+    }
+    Some(parsed)
+}
+
+/// Parses the entries of a comma separated list if it is set, adding an error if any entry is
+/// invalid.
+fn parse_list<T, C: FromIterator<T>>(
+    errors: &mut Vec<String>,
+    var: impl Fn(&str) -> Option<String>,
+    key: &str,
+    expected: &str,
+    parse_entry: impl Fn(&str) -> Option<T>,
+) -> Option<C> {
+    let val = var(key)?;
+    let parsed: Option<C> = list_entries(&val).map(parse_entry).collect();
+    if parsed.is_none() {
+        errors.push(format!("{} must be {}, but is {:?}", key, expected, val));
+    }
+    parsed
+}
+
+/// Unit tests for the configuration.
+#[cfg(test)]
+mod test {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    /// Gets the configuration from the given variables.
+    fn config_from(vars: &[(&str, &str)]) -> Result<Config, ConfigError> {
+        let vars = vars
+            .iter()
+            .map(|(key, val)| (key.to_string(), val.to_string()))
+            .collect::<HashMap<String, String>>();
+        Config::from_vars(|key| vars.get(key).cloned())
+    }
+
+    /// The required variables.
+    const REQUIRED: [(&str, &str); 4] = [
+        ("ACTIX_ADDRESS", "0.0.0.0"),
+        ("PORT", "8000"),
+        ("DATABASE_URL", "postgres://postgres@db/inv-track"),
+        ("REDIS_URL", "redis://redis:6379/"),
+    ];
+
+    #[test]
+    fn test_defaults() {
+        let config = config_from(&REQUIRED).unwrap();
+        assert_eq!(
+            config,
+            Config {
+                actix_address: "0.0.0.0".to_string(),
+                port: 8000,
+                database_url: "postgres://postgres@db/inv-track".to_string(),
+                database_max_connections: DEFAULT_DATABASE_MAX_CONNECTIONS,
+                database_auto_migrate: DEFAULT_DATABASE_AUTO_MIGRATE,
+                database_fail_on_pending_migrations: DEFAULT_DATABASE_FAIL_ON_PENDING_MIGRATIONS,
+                redis_url: "redis://redis:6379/".to_string(),
+                connect_attempts: DEFAULT_CONNECT_ATTEMPTS,
+                connect_retry_delay: Duration::from_millis(DEFAULT_CONNECT_RETRY_DELAY_MS),
+                graphql_max_body_bytes: DEFAULT_GRAPHQL_MAX_BODY_BYTES,
+                graphql_import_max_body_bytes: DEFAULT_GRAPHQL_IMPORT_MAX_BODY_BYTES,
+                log_level: DEFAULT_LOG_LEVEL,
+                slow_query: None,
+                seed_on_startup: DEFAULT_SEED_ON_STARTUP,
+                seed_file: None,
+                tracing_otlp_enabled: DEFAULT_TRACING_OTLP_ENABLED,
+                subscription_keepalive_interval: Some(Duration::from_secs(
+                    DEFAULT_SUBSCRIPTION_KEEPALIVE_INTERVAL_SECS
+                )),
+                subscription_max_per_connection: Some(DEFAULT_SUBSCRIPTION_MAX_PER_CONNECTION),
+                graphql_cache_max_age_secs: DEFAULT_GRAPHQL_CACHE_MAX_AGE_SECS,
+                graphql_operation_timeout: Some(Duration::from_millis(
+                    DEFAULT_GRAPHQL_OPERATION_TIMEOUT_MS
+                )),
+                graphql_complexity_limit: Some(DEFAULT_COMPLEXITY_LIMIT),
+                federation_enabled: DEFAULT_FEDERATION_ENABLED,
+                enforce_unique_item_name: DEFAULT_ENFORCE_UNIQUE_ITEM_NAME,
+                max_transactions_per_item: None,
+                allow_future_transactions: DEFAULT_ALLOW_FUTURE_TRANSACTIONS,
+                future_transaction_skew: Duration::from_secs(DEFAULT_FUTURE_TRANSACTION_SKEW_SECS),
+                allow_negative_stock: DEFAULT_ALLOW_NEGATIVE_STOCK,
+                transaction_comment_required_reasons: HashSet::new(),
+                subscription_buffer_size: DEFAULT_SUBSCRIPTION_BUFFER_SIZE,
+                subscription_lag_policy: LagPolicy::DropOldest,
+                sku_pattern: None,
+                sku_max_length: DEFAULT_SKU_MAX_LENGTH,
+                test_reset_enabled: DEFAULT_TEST_RESET_ENABLED,
+                maintenance_mode: DEFAULT_MAINTENANCE_MODE,
+                sanitize_item_descriptions: DEFAULT_SANITIZE_ITEM_DESCRIPTIONS,
+                admin_token: None,
+                graphql_cache_fields: HashSet::new(),
+                graphql_get_allowed_operations: None,
+                graphql_get_allowed_query_hashes: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_overrides() {
+        let mut vars = REQUIRED.to_vec();
+        vars.extend([
+            ("DATABASE_MAX_CONNECTIONS", "50"),
+            ("DATABASE_AUTO_MIGRATE", "false"),
+            ("GRAPHQL_MAX_BODY_BYTES", "1024"),
+            ("GRAPHQL_IMPORT_MAX_BODY_BYTES", "4096"),
+            ("FEDERATION_ENABLED", "true"),
+            ("SUBSCRIPTION_KEEPALIVE_INTERVAL_SECS", "0"),
+            ("GRAPHQL_OPERATION_TIMEOUT_MS", "5000"),
+            ("MAX_TRANSACTIONS_PER_ITEM", "0"),
+            ("ALLOW_FUTURE_TRANSACTIONS", "false"),
+            ("TRANSACTION_COMMENT_REQUIRED_REASONS", "damage, transfer"),
+            ("SUBSCRIPTION_LAG_POLICY", "disconnect"),
+            ("SKU_PATTERN", "[A-Z]+"),
+            ("ADMIN_TOKEN", "secret token"),
+            ("GRAPHQL_CACHE_FIELDS", "items, _service,"),
+            (
+                "GRAPHQL_GET_ALLOWED_QUERY_HASHES",
+                "E3B0C44298FC1C149AFBF4C8996FB92427AE41E4649B934CA495991B7852B855",
+            ),
+            ("LOG_LEVEL", "debug"),
+            ("SLOW_QUERY_MS", "250"),
+            ("SEED_ON_STARTUP", "true"),
+            ("SEED_FILE", "/etc/inv-track/seed.json"),
+            ("TRACING_OTLP_ENABLED", "true"),
+        ]);
+        let config = config_from(&vars).unwrap();
+        assert_eq!(config.database_max_connections, 50);
+        assert!(!config.database_auto_migrate);
+        assert_eq!(config.graphql_max_body_bytes, 1024);
+        assert_eq!(config.graphql_import_max_body_bytes, 4096);
+        assert_eq!(config.log_level, LevelFilter::Debug);
+        assert_eq!(config.slow_query, Some(Duration::from_millis(250)));
+        assert!(config.seed_on_startup);
+        assert_eq!(
+            config.seed_file.as_deref(),
+            Some("/etc/inv-track/seed.json")
+        );
+        assert!(config.tracing_otlp_enabled);
+        assert!(config.federation_enabled);
+        assert_eq!(config.subscription_keepalive_interval, None);
+        assert_eq!(
+            config.graphql_operation_timeout,
+            Some(Duration::from_millis(5000))
+        );
+        assert_eq!(config.max_transactions_per_item, None);
+        assert!(!config.allow_future_transactions);
+        assert_eq!(
+            config.transaction_comment_required_reasons,
+            HashSet::from([TransactionReason::Damage, TransactionReason::Transfer])
+        );
+        assert_eq!(config.subscription_lag_policy, LagPolicy::Disconnect);
+        assert!(config.sku_pattern.unwrap().0.is_match("ABC"));
+        assert_eq!(config.admin_token.as_deref(), Some("secret token"));
+        assert_eq!(
+            config.graphql_cache_fields,
+            HashSet::from(["items".to_string(), "_service".to_string()])
+        );
+        assert_eq!(config.graphql_get_allowed_operations, None);
+        assert_eq!(
+            config.graphql_get_allowed_query_hashes,
+            Some(HashSet::from([
+                "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855".to_string()
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_missing() {
+        let error = config_from(&[("ACTIX_ADDRESS", "0.0.0.0")]).unwrap_err();
+        assert_eq!(
+            error.errors,
+            vec![
+                "PORT must be set",
+                "DATABASE_URL must be set",
+                "REDIS_URL must be set",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        let mut vars = REQUIRED.to_vec();
+        vars.retain(|(key, _)| *key != "PORT");
+        vars.extend([
+            ("PORT", "80000"),
+            ("DATABASE_AUTO_MIGRATE", "no"),
+            ("LOG_LEVEL", "loud"),
+            ("GRAPHQL_OPERATION_TIMEOUT_MS", "-1"),
+            ("MAX_TRANSACTIONS_PER_ITEM", "ten"),
+            ("TRANSACTION_COMMENT_REQUIRED_REASONS", "damage,theft"),
+            ("SUBSCRIPTION_BUFFER_SIZE", "0"),
+            ("SUBSCRIPTION_LAG_POLICY", "block"),
+            ("SKU_PATTERN", "[A-Z"),
+            ("MAINTENANCE_MODE", "yes"),
+            ("GRAPHQL_CACHE_FIELDS", "items,items { id }"),
+            ("GRAPHQL_GET_ALLOWED_QUERY_HASHES", "abc"),
+        ]);
+        let error = config_from(&vars).unwrap_err();
+        assert_eq!(
+            error.errors,
+            vec![
+                r#"PORT must be a port number, but is "80000""#,
+                r#"DATABASE_AUTO_MIGRATE must be true or false, but is "no""#,
+                r#"LOG_LEVEL must be one of off, error, warn, info, debug, trace, but is "loud""#,
+                r#"GRAPHQL_OPERATION_TIMEOUT_MS must be a positive integer, but is "-1""#,
+                r#"MAX_TRANSACTIONS_PER_ITEM must be an integer, but is "ten""#,
+                r#"TRANSACTION_COMMENT_REQUIRED_REASONS must be a comma separated list of receipt, sale, return, adjustment, damage, transfer, opening_balance, but is "damage,theft""#,
+                r#"SUBSCRIPTION_BUFFER_SIZE must be a positive integer, but is "0""#,
+                r#"SUBSCRIPTION_LAG_POLICY must be one of drop_oldest, disconnect, but is "block""#,
+                r#"SKU_PATTERN must be a regular expression, but is "[A-Z""#,
+                r#"MAINTENANCE_MODE must be true or false, but is "yes""#,
+                r#"GRAPHQL_CACHE_FIELDS must be a comma separated list of GraphQL names, but is "items,items { id }""#,
+                r#"GRAPHQL_GET_ALLOWED_QUERY_HASHES must be a comma separated list of sha256 hashes, but is "abc""#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_missing_and_invalid() {
+        let error =
+            config_from(&[("PORT", "http"), ("REDIS_URL", "redis://redis:6379/")]).unwrap_err();
+        assert_eq!(
+            error.errors,
+            vec![
+                "ACTIX_ADDRESS must be set",
+                "DATABASE_URL must be set",
+                r#"PORT must be a port number, but is "http""#,
+            ]
+        );
+        assert_eq!(
+            error.to_string(),
+            "invalid configuration:\n  ACTIX_ADDRESS must be set\n  DATABASE_URL must be set\n  PORT must be a port number, but is \"http\""
+        );
+    }
+}
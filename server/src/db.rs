@@ -1,20 +1,104 @@
-use std::env;
+use std::str::FromStr;
+use std::time::Duration;
 
-use sqlx::postgres::PgPoolOptions;
-use sqlx::{Pool, Postgres};
+use log::LevelFilter;
+use serde::Serialize;
+use sqlx::migrate::Migrator;
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions};
+use sqlx::{ConnectOptions, Pool, Postgres, Row};
 
-/// The default number of max connections.
-const DEFAULT_MAX_CONNECTIONS: u32 = 100;
+use crate::config::Config;
+use crate::retry;
 
-/// Gets the database connection pool.
-pub(crate) async fn get_pool() -> Pool<Postgres> {
-    let max_connections = env::var("DATABASE_MAX_CONNECTIONS")
-        .map(|val| val.parse::<u32>().unwrap_or(DEFAULT_MAX_CONNECTIONS))
-        .unwrap_or(DEFAULT_MAX_CONNECTIONS);
+/// The migrations embedded from the migrations directory.
+static MIGRATOR: Migrator = sqlx::migrate!();
 
-    PgPoolOptions::new()
-        .max_connections(max_connections)
-        .connect(&env::var("DATABASE_URL").expect("DATABASE_URL must be set"))
-        .await
-        .expect("unable to establish database pool")
+/// The versions of applied and pending migrations.
+#[derive(Debug, PartialEq, Serialize)]
+pub(crate) struct MigrationStatus {
+    pub(crate) applied: Vec<i64>,
+    pub(crate) pending: Vec<i64>,
+}
+
+/// Gets the options of database connections, which log queries slower than the configured
+/// duration as warnings, and other queries at the debug level.
+fn connect_options(config: &Config) -> PgConnectOptions {
+    let mut options =
+        PgConnectOptions::from_str(&config.database_url).expect("unable to parse database url");
+    options.log_statements(LevelFilter::Debug);
+    match config.slow_query {
+        Some(slow_query) => options.log_slow_statements(LevelFilter::Warn, slow_query),
+        None => options.log_slow_statements(LevelFilter::Off, Duration::MAX),
+    };
+    options
+}
+
+/// Gets the database connection pool, retrying until the database is ready.
+pub(crate) async fn get_pool(config: &Config) -> Pool<Postgres> {
+    let options = connect_options(config);
+    // the database may still be starting, so retry the connection with backoff
+    retry::with_backoff("postgres", config.retry_policy(), || {
+        PgPoolOptions::new()
+            .max_connections(config.database_max_connections)
+            .connect_with(options.clone())
+    })
+    .await
+    .expect("unable to establish database pool")
+}
+
+/// Runs pending migrations if enabled, otherwise fails if enabled and migrations are pending.
+pub(crate) async fn prepare_migrations(pool: &Pool<Postgres>, config: &Config) {
+    if config.database_auto_migrate {
+        MIGRATOR.run(pool).await.expect("unable to run migrations");
+    } else if config.database_fail_on_pending_migrations {
+        let status = get_migration_status(pool)
+            .await
+            .expect("unable to get migration status");
+        assert!(
+            status.pending.is_empty(),
+            "pending migrations {:?} must be run",
+            status.pending
+        );
+    }
+}
+
+/// Gets the versions of the applied and pending migrations, returning the result, or an error.
+pub(crate) async fn get_migration_status(
+    pool: &Pool<Postgres>,
+) -> Result<MigrationStatus, sqlx::Error> {
+    // the migrations table only exists once migrations have been run
+    let table_exists: bool = sqlx::query(
+        r#"
+        select to_regclass('_sqlx_migrations') is not null as exists
+    "#,
+    )
+    .fetch_one(pool)
+    .await?
+    .try_get("exists")?;
+
+    let applied = if table_exists {
+        sqlx::query(
+            r#"
+            select version from _sqlx_migrations
+            where success
+            order by version
+        "#,
+        )
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|row| row.try_get("version"))
+        .collect::<Result<Vec<i64>, _>>()?
+    } else {
+        Vec::new()
+    };
+
+    let pending = MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .map(|migration| migration.version)
+        .filter(|version| !applied.contains(version))
+        .collect();
+
+    Ok(MigrationStatus { applied, pending })
 }
@@ -0,0 +1,120 @@
+use rust_xlsxwriter::{ColNum, Format, RowNum, Workbook, XlsxError};
+
+use crate::model::item::ItemExport;
+use crate::model::location::StockSnapshotRow;
+
+/// The content type of XLSX workbooks.
+pub(crate) const XLSX_CONTENT_TYPE: &str =
+    "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet";
+
+/// The content type of CSV files.
+pub(crate) const CSV_CONTENT_TYPE: &str = "text/csv; charset=utf-8";
+
+/// The headers of the columns of an items export.
+const ITEM_HEADERS: [&str; 5] = ["id", "sku", "name", "supplier", "quantity"];
+
+/// The headers of the columns of a stock snapshot.
+const STOCK_SNAPSHOT_HEADERS: [&str; 6] = [
+    "item_id",
+    "sku",
+    "name",
+    "location_id",
+    "location_name",
+    "quantity",
+];
+
+/// Writes the items as an XLSX workbook with a single sheet, whose first row is a bold header
+/// frozen above the items, returning the bytes of the workbook, or an error if there are more items
+/// than rows of a sheet.
+pub(crate) fn items_xlsx(items: &[ItemExport]) -> Result<Vec<u8>, XlsxError> {
+    let mut workbook = Workbook::new();
+    let header = Format::new().set_bold();
+    let worksheet = workbook.add_worksheet();
+    worksheet.set_name("Items")?;
+    for (column, name) in ITEM_HEADERS.iter().enumerate() {
+        worksheet.write_string(0, column as ColNum, name, &header)?;
+    }
+    worksheet.set_freeze_panes(1, 0)?;
+
+    for (index, item) in items.iter().enumerate() {
+        let row = RowNum::try_from(index + 1).map_err(|_| XlsxError::RowColumnLimitError)?;
+        worksheet.write_number_only(row, 0, i32::from(item.id))?;
+        if let Some(sku) = &item.sku {
+            worksheet.write_string_only(row, 1, sku)?;
+        }
+        worksheet.write_string_only(row, 2, &item.name)?;
+        if let Some(supplier) = &item.supplier {
+            worksheet.write_string_only(row, 3, supplier)?;
+        }
+        // the quantity is a sum of 32-bit quantities, which a double holds exactly
+        worksheet.write_number_only(row, 4, item.quantity as f64)?;
+    }
+
+    workbook.save_to_buffer()
+}
+
+/// Writes the header line of a stock snapshot CSV.
+pub(crate) fn stock_snapshot_csv_header() -> String {
+    csv_line(
+        STOCK_SNAPSHOT_HEADERS
+            .iter()
+            .map(|header| header.to_string()),
+    )
+}
+
+/// Writes a line of a stock snapshot CSV, where the columns of a missing location are empty.
+pub(crate) fn stock_snapshot_csv_line(row: &StockSnapshotRow) -> String {
+    csv_line([
+        i32::from(row.item_id).to_string(),
+        row.sku.clone().unwrap_or_default(),
+        row.name.clone(),
+        row.location_id
+            .map(|location_id| i32::from(location_id).to_string())
+            .unwrap_or_default(),
+        row.location_name.clone().unwrap_or_default(),
+        row.quantity.to_string(),
+    ])
+}
+
+/// Writes the fields as a CSV line, ending with CRLF as in RFC 4180.
+fn csv_line(fields: impl IntoIterator<Item = String>) -> String {
+    let mut line = fields
+        .into_iter()
+        .map(|field| escape_csv(&field))
+        .collect::<Vec<_>>()
+        .join(",");
+    line.push_str("\r\n");
+
+    line
+}
+
+/// Escapes a CSV field, quoting it if it has a delimiter, quote or line break.
+fn escape_csv(field: &str) -> String {
+    if field.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// Unit tests for exports.
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_escape_csv() {
+        assert_eq!(escape_csv("bolt"), "bolt");
+        assert_eq!(escape_csv("nuts, bolts"), r#""nuts, bolts""#);
+        assert_eq!(escape_csv(r#"3" bolt"#), r#""3"" bolt""#);
+        assert_eq!(escape_csv("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn test_csv_line() {
+        assert_eq!(
+            csv_line(["1".to_string(), String::new(), "a,b".to_string()]),
+            "1,,\"a,b\"\r\n"
+        );
+    }
+}
@@ -0,0 +1,49 @@
+use std::collections::HashSet;
+
+use sha2::{Digest, Sha256};
+
+use crate::config::Config;
+
+/// The queries which can be executed over GET, so that ad-hoc queries must use POST.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct GetAllowlist {
+    /// Whether GET requests are restricted to the allowlist.
+    pub(crate) enabled: bool,
+    /// The names of the operations which are allowed, which narrow the allowed query documents to
+    /// those operations, or empty if any operation of an allowed document is.
+    pub(crate) operation_names: HashSet<String>,
+    /// The hex encoded sha256 hashes of the query documents which are allowed.
+    pub(crate) query_hashes: HashSet<String>,
+}
+
+impl GetAllowlist {
+    /// Gets the allowlist from the configuration of the server, which is enabled if either list is
+    /// set.
+    pub(crate) fn from_config(config: &Config) -> Self {
+        let operation_names = config.graphql_get_allowed_operations.clone();
+        let query_hashes = config.graphql_get_allowed_query_hashes.clone();
+
+        GetAllowlist {
+            enabled: operation_names.is_some() || query_hashes.is_some(),
+            operation_names: operation_names.unwrap_or_default(),
+            query_hashes: query_hashes.unwrap_or_default(),
+        }
+    }
+
+    /// Whether the request can be executed over GET, which it can if the hash of its query document
+    /// is allowed, and if operation names are allowed, its operation name is also one of them. An
+    /// allowed operation name never allows a query on its own, as any query can borrow the name.
+    pub(crate) fn allows(&self, request: &async_graphql::Request) -> bool {
+        if !self.enabled {
+            return true;
+        }
+
+        let operation_allowed = self.operation_names.is_empty()
+            || matches!(&request.operation_name, Some(name) if self.operation_names.contains(name));
+
+        operation_allowed
+            && self
+                .query_hashes
+                .contains(&hex::encode(Sha256::digest(request.query.as_bytes())))
+    }
+}
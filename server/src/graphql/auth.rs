@@ -0,0 +1,74 @@
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::HttpRequest;
+use async_graphql::{Context, Error, ErrorExtensions, Guard, Result};
+use sha2::{Digest, Sha256};
+
+use crate::config::Config;
+use crate::graphql::AppContext;
+use crate::model::error::code;
+
+/// The role of an authenticated requester.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum Role {
+    Admin,
+}
+
+/// The configuration of authentication.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct AuthConfig {
+    /// The bearer token of administrators, or none if there are no administrators.
+    pub(crate) admin_token: Option<String>,
+}
+
+impl AuthConfig {
+    /// Gets the authentication configuration from the configuration of the server.
+    pub(crate) fn from_config(config: &Config) -> Self {
+        AuthConfig {
+            admin_token: config.admin_token.clone(),
+        }
+    }
+
+    /// Gets the role of a request from its bearer token, if any.
+    pub(crate) fn role(&self, req: &HttpRequest) -> Option<Role> {
+        let admin_token = self.admin_token.as_ref()?;
+        let token = req
+            .headers()
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))?;
+
+        // compare digests, so the comparison does not depend on the position of a mismatch
+        (Sha256::digest(token.as_bytes()) == Sha256::digest(admin_token.as_bytes()))
+            .then_some(Role::Admin)
+    }
+}
+
+/// A guard for fields which require the admin role.
+pub(crate) struct AdminGuard;
+
+#[async_trait::async_trait]
+impl Guard for AdminGuard {
+    async fn check(&self, context: &Context<'_>) -> Result<()> {
+        if context.data_opt::<Role>() == Some(&Role::Admin) {
+            Ok(())
+        } else {
+            Err(Error::new("admin role required")
+                .extend_with(|_, e| e.set("code", code::FORBIDDEN)))
+        }
+    }
+}
+
+/// A guard for fields which are only enabled in test environments, by `TEST_RESET_ENABLED`.
+pub(crate) struct ResetGuard;
+
+#[async_trait::async_trait]
+impl Guard for ResetGuard {
+    async fn check(&self, context: &Context<'_>) -> Result<()> {
+        if context.data_unchecked::<AppContext>().config.reset_enabled {
+            Ok(())
+        } else {
+            Err(Error::new("resetting data is not enabled")
+                .extend_with(|_, e| e.set("code", code::FORBIDDEN)))
+        }
+    }
+}
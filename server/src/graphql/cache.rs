@@ -0,0 +1,71 @@
+use std::collections::HashSet;
+
+use async_graphql::parser::types::{DocumentOperations, OperationType, Selection};
+
+use crate::config::Config;
+
+/// The cache control of responses which must not be cached.
+pub(crate) const NO_STORE: &str = "no-store";
+
+/// The configuration of HTTP caching of GraphQL queries over GET.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct CacheConfig {
+    /// The max age of cacheable queries in seconds, or 0 if queries are not cached.
+    pub(crate) max_age: u32,
+    /// The root query fields which can be cached.
+    pub(crate) fields: HashSet<String>,
+}
+
+impl CacheConfig {
+    /// Gets the cache configuration from the configuration of the server.
+    pub(crate) fn from_config(config: &Config) -> Self {
+        CacheConfig {
+            max_age: config.graphql_cache_max_age_secs,
+            fields: config.graphql_cache_fields.clone(),
+        }
+    }
+
+    /// Gets the cache control of a request, which is cacheable only if it is a query selecting
+    /// allowed root fields.
+    pub(crate) fn cache_control(&self, request: &async_graphql::Request) -> String {
+        if self.max_age > 0 && self.is_cacheable(request) {
+            format!("public, max-age={}", self.max_age)
+        } else {
+            NO_STORE.to_string()
+        }
+    }
+
+    /// Whether the operation of a request only selects allowed root fields of the query.
+    fn is_cacheable(&self, request: &async_graphql::Request) -> bool {
+        let document = match async_graphql::parser::parse_query(&request.query) {
+            Ok(document) => document,
+            Err(_) => return false,
+        };
+
+        let operation = match (&document.operations, &request.operation_name) {
+            (DocumentOperations::Single(operation), _) => Some(operation),
+            (DocumentOperations::Multiple(operations), Some(name)) => operations
+                .iter()
+                .find(|(operation_name, _)| operation_name.as_str() == name)
+                .map(|(_, operation)| operation),
+            (DocumentOperations::Multiple(_), None) => None,
+        };
+
+        // fragments are not followed, so operations using them are not cached
+        let operation = match operation {
+            Some(operation) => operation,
+            None => return false,
+        };
+        operation.node.ty == OperationType::Query
+            && operation
+                .node
+                .selection_set
+                .node
+                .items
+                .iter()
+                .all(|selection| match &selection.node {
+                    Selection::Field(field) => self.fields.contains(field.node.name.node.as_str()),
+                    _ => false,
+                })
+    }
+}
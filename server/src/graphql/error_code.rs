@@ -0,0 +1,143 @@
+use std::sync::Arc;
+
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextParseQuery, NextRequest, NextSubscribe,
+    NextValidation,
+};
+use async_graphql::parser::types::ExecutableDocument;
+use async_graphql::{Response, ServerError, ServerResult, ValidationResult, Variables};
+use futures::stream::BoxStream;
+use futures::StreamExt;
+
+use crate::model::error::code;
+
+/// The prefixes of the messages of errors which async-graphql raises when an argument cannot be
+/// parsed or fails a validator, which are raised while resolving, but are invalid input.
+const INPUT_ERROR_PREFIXES: [&str; 2] = ["Failed to parse \"", "Expected input type \""];
+/// The messages of errors which async-graphql raises when an operation exceeds the limits of the
+/// schema, which are raised after validation, but are invalid operations.
+const LIMIT_ERROR_MESSAGES: [&str; 2] = ["Query is too complex.", "Query is nested too deep."];
+
+/// An extension which sets the `code` extension of every error which does not have one, so that
+/// clients can always distinguish errors by code. Errors in parsing and validating operations are
+/// validation errors, and other errors without a code are internal errors.
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct ErrorCodes;
+
+impl ExtensionFactory for ErrorCodes {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(*self)
+    }
+}
+
+/// Sets the code of an error, if it does not have one.
+fn set_default_code(mut error: ServerError, default_code: &str) -> ServerError {
+    let extensions = error.extensions.get_or_insert_with(Default::default);
+    if extensions.get("code").is_none() {
+        extensions.set("code", default_code);
+    }
+    error
+}
+
+/// Sets the codes of the errors of a response, if they do not have one.
+fn set_response_codes(mut response: Response) -> Response {
+    response.errors = response
+        .errors
+        .into_iter()
+        .map(|error| {
+            let default_code = if INPUT_ERROR_PREFIXES
+                .iter()
+                .any(|prefix| error.message.starts_with(prefix))
+                || LIMIT_ERROR_MESSAGES.contains(&error.message.as_str())
+            {
+                code::VALIDATION
+            } else {
+                code::INTERNAL
+            };
+            set_default_code(error, default_code)
+        })
+        .collect();
+    response
+}
+
+#[async_trait::async_trait]
+impl Extension for ErrorCodes {
+    async fn request(&self, ctx: &ExtensionContext<'_>, next: NextRequest<'_>) -> Response {
+        set_response_codes(next.run(ctx).await)
+    }
+
+    fn subscribe<'s>(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        stream: BoxStream<'s, Response>,
+        next: NextSubscribe<'_>,
+    ) -> BoxStream<'s, Response> {
+        next.run(ctx, stream).map(set_response_codes).boxed()
+    }
+
+    async fn parse_query(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        query: &str,
+        variables: &Variables,
+        next: NextParseQuery<'_>,
+    ) -> ServerResult<ExecutableDocument> {
+        next.run(ctx, query, variables)
+            .await
+            .map_err(|error| set_default_code(error, code::VALIDATION))
+    }
+
+    async fn validation(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        next: NextValidation<'_>,
+    ) -> Result<ValidationResult, Vec<ServerError>> {
+        next.run(ctx).await.map_err(|errors| {
+            errors
+                .into_iter()
+                .map(|error| set_default_code(error, code::VALIDATION))
+                .collect()
+        })
+    }
+}
+
+/// Unit tests for error codes.
+#[cfg(test)]
+mod test {
+    use async_graphql::{Pos, Value};
+
+    use super::*;
+
+    /// Gets the code of an error.
+    fn get_code(error: &ServerError) -> Option<&Value> {
+        error.extensions.as_ref().and_then(|e| e.get("code"))
+    }
+
+    #[test]
+    fn test_set_response_codes() {
+        let mut coded = ServerError::new("not found", Some(Pos::default()));
+        coded
+            .extensions
+            .get_or_insert_with(Default::default)
+            .set("code", code::NOT_FOUND);
+        let response = set_response_codes(Response::from_errors(vec![
+            coded,
+            ServerError::new(r#"Failed to parse "ItemId": invalid"#, None),
+            ServerError::new(r#"Expected input type "Int", found "a"."#, None),
+            ServerError::new("Query is too complex.", None),
+            ServerError::new("connection refused", None),
+        ]));
+
+        let codes = response.errors.iter().map(get_code).collect::<Vec<_>>();
+        assert_eq!(
+            codes,
+            vec![
+                Some(&Value::from(code::NOT_FOUND)),
+                Some(&Value::from(code::VALIDATION)),
+                Some(&Value::from(code::VALIDATION)),
+                Some(&Value::from(code::VALIDATION)),
+                Some(&Value::from(code::INTERNAL)),
+            ]
+        );
+    }
+}
@@ -0,0 +1,224 @@
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+
+use futures::{future, FutureExt, Stream, StreamExt};
+use redis::RedisError;
+use tokio::sync::{broadcast, Notify};
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::wrappers::BroadcastStream;
+
+use crate::config::Config;
+
+/// What happens to a subscriber which falls behind by more messages than are buffered.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) enum LagPolicy {
+    /// The oldest messages are dropped, and the subscriber continues from the oldest buffered one.
+    DropOldest,
+    /// The subscription is ended.
+    Disconnect,
+}
+
+impl FromStr for LagPolicy {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "drop_oldest" => Ok(LagPolicy::DropOldest),
+            "disconnect" => Ok(LagPolicy::Disconnect),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The configuration of the fan-out of messages to subscribers.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct FanoutConfig {
+    /// The number of messages buffered for subscribers of each channel.
+    pub(crate) buffer_size: usize,
+    /// What happens to a subscriber which falls behind the buffer.
+    pub(crate) lag_policy: LagPolicy,
+}
+
+impl FanoutConfig {
+    /// Gets the fan-out configuration from the configuration of the server.
+    pub(crate) fn from_config(config: &Config) -> Self {
+        FanoutConfig {
+            buffer_size: config.subscription_buffer_size,
+            lag_policy: config.subscription_lag_policy,
+        }
+    }
+}
+
+/// A stream of the messages of a channel.
+pub(crate) type MessageStream = Pin<Box<dyn Stream<Item = String> + Send + Sync>>;
+
+/// A channel which is subscribed to in redis, with its fan-out to subscribers.
+struct Channel {
+    sender: broadcast::Sender<String>,
+    /// Notified when a subscriber of the channel is dropped, so its redis subscription is dropped
+    /// once the last subscriber is gone.
+    released: Arc<Notify>,
+}
+
+/// A subscription to the fan-out of a channel, which notifies the channel when it is dropped.
+struct Subscription {
+    /// The stream of messages, which is dropped before the channel is notified, so the channel
+    /// counts one subscriber fewer when it checks for subscribers.
+    stream: Option<MessageStream>,
+    released: Arc<Notify>,
+}
+
+impl Stream for Subscription {
+    type Item = String;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<String>> {
+        match self.stream.as_mut() {
+            Some(stream) => stream.as_mut().poll_next(cx),
+            None => Poll::Ready(None),
+        }
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.stream.take();
+        // the permit is kept if the channel is busy forwarding, so the drop is not missed
+        self.released.notify_one();
+    }
+}
+
+/// Fans out the messages of redis channels to subscribers, with one redis subscription per channel
+/// and a bounded buffer, so a slow subscriber only falls behind itself rather than stalling redis
+/// or other subscribers. The redis subscription of a channel is dropped with its last subscriber.
+pub(crate) struct Fanout {
+    redis: Arc<redis::Client>,
+    config: FanoutConfig,
+    channels: Arc<Mutex<HashMap<String, Channel>>>,
+}
+
+impl Fanout {
+    /// Creates a fan-out of the channels of a redis client.
+    pub(crate) fn new(redis: Arc<redis::Client>, config: FanoutConfig) -> Self {
+        Fanout {
+            redis,
+            config,
+            channels: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Subscribes to a channel, returning a stream of its messages, or the redis error if the
+    /// channel could not be subscribed to.
+    pub(crate) async fn subscribe(&self, channel_name: &str) -> Result<MessageStream, RedisError> {
+        let (receiver, released) = match self.existing_receiver(channel_name) {
+            Some(existing) => existing,
+            None => self.subscribe_redis(channel_name).await?,
+        };
+
+        let channel_name = channel_name.to_string();
+        let lag_policy = self.config.lag_policy;
+        let stream = BroadcastStream::new(receiver)
+            .take_while(move |result| {
+                future::ready(match result {
+                    Err(BroadcastStreamRecvError::Lagged(dropped)) => {
+                        log::warn!(
+                            "subscriber to {} lagged, and {} messages were dropped ({:?})",
+                            channel_name,
+                            dropped,
+                            lag_policy
+                        );
+                        lag_policy == LagPolicy::DropOldest
+                    }
+                    Ok(_) => true,
+                })
+            })
+            .filter_map(|result| future::ready(result.ok()));
+
+        Ok(Box::pin(Subscription {
+            stream: Some(Box::pin(stream)),
+            released,
+        }))
+    }
+
+    /// Subscribes to the fan-out of a channel which is already subscribed to in redis.
+    fn existing_receiver(
+        &self,
+        channel_name: &str,
+    ) -> Option<(broadcast::Receiver<String>, Arc<Notify>)> {
+        self.channels
+            .lock()
+            .unwrap()
+            .get(channel_name)
+            .map(|channel| (channel.sender.subscribe(), channel.released.clone()))
+    }
+
+    /// Subscribes to a channel in redis, forwarding its messages to the fan-out until the
+    /// connection is lost or the last subscriber is dropped, returning a subscription to the
+    /// fan-out, or the redis error.
+    async fn subscribe_redis(
+        &self,
+        channel_name: &str,
+    ) -> Result<(broadcast::Receiver<String>, Arc<Notify>), RedisError> {
+        let mut pubsub = self.redis.get_async_connection().await?.into_pubsub();
+        pubsub.subscribe(channel_name).await?;
+
+        let (sender, receiver, released) = {
+            let mut channels = self.channels.lock().unwrap();
+            // another subscriber may have subscribed to the channel in the meantime
+            if let Some(channel) = channels.get(channel_name) {
+                return Ok((channel.sender.subscribe(), channel.released.clone()));
+            }
+            let (sender, receiver) = broadcast::channel(self.config.buffer_size);
+            let released = Arc::new(Notify::new());
+            channels.insert(
+                channel_name.to_string(),
+                Channel {
+                    sender: sender.clone(),
+                    released: released.clone(),
+                },
+            );
+            (sender, receiver, released)
+        };
+
+        let channels = self.channels.clone();
+        let channel_name = channel_name.to_string();
+        let subscription_released = released.clone();
+        tokio::spawn(async move {
+            let mut messages = pubsub.into_on_message().fuse();
+            loop {
+                futures::select! {
+                    message = messages.next() => match message {
+                        Some(message) => {
+                            if let Ok(payload) = message.get_payload::<String>() {
+                                // sending only fails when there are no subscribers, who are
+                                // released separately
+                                let _ = sender.send(payload);
+                            }
+                        }
+                        None => {
+                            // dropping the sender ends the subscriptions, and the next subscriber
+                            // reconnects
+                            log::warn!("lost the redis subscription to {}", channel_name);
+                            channels.lock().unwrap().remove(&channel_name);
+                            return;
+                        }
+                    },
+                    _ = released.notified().fuse() => {
+                        // subscribers are added while the channels are locked, so none can be
+                        // added between the check and the removal
+                        let mut channels = channels.lock().unwrap();
+                        if sender.receiver_count() == 0 {
+                            channels.remove(&channel_name);
+                            // dropping the connection drops the redis subscription
+                            return;
+                        }
+                    },
+                }
+            }
+        });
+
+        Ok((receiver, subscription_released))
+    }
+}
@@ -0,0 +1,39 @@
+use async_graphql::parser::types::{DocumentOperations, OperationType, Selection};
+
+/// The root mutation field of bulk imports.
+const IMPORT_FIELD: &str = "importItems";
+
+/// Whether the operation of a request is a mutation which only selects bulk imports, so it can be
+/// sent to the import route, whose bodies can be larger.
+pub(crate) fn is_import(request: &async_graphql::Request) -> bool {
+    let document = match async_graphql::parser::parse_query(&request.query) {
+        Ok(document) => document,
+        Err(_) => return false,
+    };
+
+    let operation = match (&document.operations, &request.operation_name) {
+        (DocumentOperations::Single(operation), _) => Some(operation),
+        (DocumentOperations::Multiple(operations), Some(name)) => operations
+            .iter()
+            .find(|(operation_name, _)| operation_name.as_str() == name)
+            .map(|(_, operation)| operation),
+        (DocumentOperations::Multiple(_), None) => None,
+    };
+
+    // fragments are not followed, so operations using them are not imports
+    let operation = match operation {
+        Some(operation) => operation,
+        None => return false,
+    };
+    operation.node.ty == OperationType::Mutation
+        && operation
+            .node
+            .selection_set
+            .node
+            .items
+            .iter()
+            .all(|selection| match &selection.node {
+                Selection::Field(field) => field.node.name.node.as_str() == IMPORT_FIELD,
+                _ => false,
+            })
+}
@@ -0,0 +1,93 @@
+use std::sync::Arc;
+
+use async_graphql::extensions::{
+    Extension, ExtensionContext, ExtensionFactory, NextResolve, ResolveInfo,
+};
+use async_graphql::{Error, ErrorExtensions, OutputType, Pos, ServerResult, Value};
+use redis::{AsyncCommands, RedisError};
+
+use crate::graphql::mutation::RootMutation;
+use crate::graphql::AppContext;
+use crate::model::error::code;
+
+/// The redis key which is set while maintenance mode is turned on at runtime, so it applies to
+/// every server sharing the redis.
+const MAINTENANCE_MODE_KEY: &str = "maintenance_mode";
+
+/// The mutation which turns maintenance mode on and off, which is accepted during maintenance.
+const SET_MAINTENANCE_MODE_FIELD: &str = "setMaintenanceMode";
+
+/// Whether maintenance mode is on, either by `MAINTENANCE_MODE` or at runtime. If redis is
+/// unavailable, only `MAINTENANCE_MODE` is checked, so an outage of redis does not reject writes.
+pub(crate) async fn is_enabled(context: &AppContext) -> bool {
+    if context.config.maintenance_mode {
+        return true;
+    }
+
+    let enabled = async {
+        let mut redis_conn = context.clients.redis.get_async_connection().await?;
+        redis_conn.exists::<_, bool>(MAINTENANCE_MODE_KEY).await
+    };
+    enabled.await.unwrap_or_else(|e| {
+        log::warn!("unable to check maintenance mode: {}", e);
+        false
+    })
+}
+
+/// Turns maintenance mode on or off at runtime, returning whether it is on, which it stays while
+/// `MAINTENANCE_MODE` is set, or an error.
+pub(crate) async fn set_enabled(context: &AppContext, enabled: bool) -> Result<bool, RedisError> {
+    let mut redis_conn = context.clients.redis.get_async_connection().await?;
+    if enabled {
+        redis_conn
+            .set::<_, _, ()>(MAINTENANCE_MODE_KEY, true)
+            .await?;
+    } else {
+        redis_conn.del::<_, ()>(MAINTENANCE_MODE_KEY).await?;
+    }
+    log::warn!(
+        "maintenance mode turned {} at runtime",
+        if enabled { "on" } else { "off" }
+    );
+
+    Ok(enabled || context.config.maintenance_mode)
+}
+
+/// An extension which rejects every mutation while maintenance mode is on, except the mutation
+/// which turns it off, while queries and subscriptions continue. The mode is checked for each
+/// field of the root mutation, so no mutation needs a guard of its own.
+#[derive(Copy, Clone, Debug, Default)]
+pub(crate) struct MaintenanceMode;
+
+impl ExtensionFactory for MaintenanceMode {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(*self)
+    }
+}
+
+#[async_trait::async_trait]
+impl Extension for MaintenanceMode {
+    async fn resolve(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        info: ResolveInfo<'_>,
+        next: NextResolve<'_>,
+    ) -> ServerResult<Option<Value>> {
+        let is_mutation = info.path_node.parent.is_none()
+            && info.parent_type == RootMutation::type_name()
+            && info.name != SET_MAINTENANCE_MODE_FIELD;
+        if is_mutation {
+            if let Some(context) = ctx.data_opt::<AppContext>() {
+                if is_enabled(context).await {
+                    return Err(Error::new(
+                        "service in maintenance, so changes are not accepted, try again later",
+                    )
+                    .extend_with(|_, e| e.set("code", code::MAINTENANCE))
+                    .into_server_error(Pos::default()));
+                }
+            }
+        }
+
+        next.run(ctx, info).await
+    }
+}
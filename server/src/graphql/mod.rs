@@ -1,21 +1,41 @@
+pub(crate) mod allowlist;
+pub(crate) mod auth;
+pub(crate) mod cache;
+mod error_code;
+pub(crate) mod fanout;
+pub(crate) mod import;
+pub(crate) mod maintenance;
 mod mutation;
 mod query;
+pub(crate) mod source;
+pub(crate) mod sse;
 mod subscription;
+mod throttle;
+pub(crate) mod timeout;
+pub(crate) mod websocket;
 
 use std::sync::Arc;
 
 use async_graphql::{Schema, SchemaBuilder};
+use sha2::{Digest, Sha256};
 use sqlx::{Pool, Postgres};
 
+use crate::config::Config;
+use crate::graphql::error_code::ErrorCodes;
+use crate::graphql::fanout::Fanout;
+use crate::graphql::maintenance::MaintenanceMode;
 use crate::graphql::mutation::RootMutation;
 use crate::graphql::query::RootQuery;
 use crate::graphql::subscription::RootSubscription;
+use crate::graphql::timeout::OperationTimeout;
+use crate::model::config::ModelConfig;
 
 /// The clients of the application.
 #[derive(Clone)]
 pub(crate) struct Clients {
     pub(crate) postgres: Arc<Pool<Postgres>>,
     pub(crate) redis: Arc<redis::Client>,
+    pub(crate) fanout: Arc<Fanout>,
 }
 
 /// The context of the application.
@@ -23,15 +43,86 @@ pub(crate) struct Clients {
 pub(crate) struct AppContext {
     pub(crate) clients: Clients,
     pub(crate) loaders: Arc<anymap2::Map<dyn anymap2::any::Any + Send + Sync>>,
+    pub(crate) config: Arc<ModelConfig>,
 }
 
 pub(crate) type AppSchema = Schema<RootQuery, RootMutation, RootSubscription>;
 
-/// Returns a created schema for the application.
+/// The default maximum complexity of GraphQL operations, where each field counts 1 unless it is
+/// weighted, and the fields of lists count once for each element.
+pub(crate) const DEFAULT_COMPLEXITY_LIMIT: usize = 5000;
+/// The complexity of `quantityAsOf`, which runs an aggregate over the dated transactions of its
+/// item.
+pub(crate) const QUANTITY_AS_OF_COMPLEXITY: usize = 100;
+/// The complexity of `transactionsSummary`, which runs an aggregate over all transactions.
+pub(crate) const TRANSACTIONS_SUMMARY_COMPLEXITY: usize = 500;
+/// The number of elements counted for lists of all items, locations or transactions whose length is
+/// not bounded by an argument.
+pub(crate) const LIST_COMPLEXITY_MULTIPLIER: usize = 100;
+/// The number of elements counted for the lists of a record, such as the transactions of an item,
+/// whose length is not bounded by an argument.
+pub(crate) const NESTED_LIST_COMPLEXITY_MULTIPLIER: usize = 10;
+/// The maximum complexity of a list, so the complexity of nested lists cannot overflow.
+const MAX_LIST_COMPLEXITY: usize = u32::MAX as usize;
+
+/// Computes the complexity of a list, which counts the complexity of the fields of its elements
+/// once for each element.
+pub(crate) fn list_complexity(length: usize, child_complexity: usize) -> usize {
+    length
+        .saturating_mul(child_complexity)
+        .min(MAX_LIST_COMPLEXITY)
+}
+
+/// Computes the complexity of a page of a list, which counts the `first` elements of the page, or
+/// all elements of the list if it is not given.
+pub(crate) fn page_complexity(first: Option<i64>, child_complexity: usize) -> usize {
+    // a negative page size is rejected by its validator, after the complexity is computed
+    let length = first.map_or(LIST_COMPLEXITY_MULTIPLIER, |first| {
+        usize::try_from(first).unwrap_or(0)
+    });
+    list_complexity(length, child_complexity)
+}
+
+/// The header of GraphQL responses with the hash of the schema, so clients can detect changes to
+/// the schema without introspection.
+pub(crate) const SCHEMA_HASH_HEADER: &str = "X-Schema-Hash";
+
+/// Computes the hash of the SDL of a schema, which only changes when the schema does.
+pub(crate) fn schema_hash(schema: &AppSchema) -> String {
+    hex::encode(Sha256::digest(schema.sdl().as_bytes()))
+}
+
+/// Returns a created schema for the application, which sets the code of every error, and rejects
+/// mutations in maintenance mode.
 pub(crate) fn schema_builder() -> SchemaBuilder<RootQuery, RootMutation, RootSubscription> {
     Schema::build(
         RootQuery::default(),
         RootMutation::default(),
         RootSubscription::default(),
     )
+    .extension(ErrorCodes)
+    .extension(MaintenanceMode)
+}
+
+/// Returns a created schema for the application, with operations limited by the timeout and
+/// complexity limit of the configuration, which can be composed into an Apollo Federation
+/// supergraph if federation is enabled, and traces operations if traces are exported.
+pub(crate) fn schema_builder_from_config(
+    config: &Config,
+) -> SchemaBuilder<RootQuery, RootMutation, RootSubscription> {
+    let mut builder = schema_builder();
+    if let Some(timeout) = OperationTimeout::from_config(config) {
+        builder = builder.extension(timeout);
+    }
+    if let Some(complexity_limit) = config.graphql_complexity_limit {
+        builder = builder.limit_complexity(complexity_limit);
+    }
+    if config.federation_enabled {
+        builder = builder.enable_federation();
+    }
+    if config.tracing_otlp_enabled {
+        builder = builder.extension(async_graphql::extensions::Tracing);
+    }
+
+    builder
 }
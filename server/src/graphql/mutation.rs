@@ -1,7 +1,11 @@
-use async_graphql::{Context, Result};
+use async_graphql::{Context, Error, ErrorExtensions, Result};
 
+use crate::graphql::auth::{AdminGuard, ResetGuard};
+use crate::graphql::maintenance;
+use crate::graphql::source::{self, IMPORT_SOURCE};
 use crate::graphql::AppContext;
-use crate::model::{item, location, transaction};
+use crate::model::error::code;
+use crate::model::{attachment, item, location, reservation, reset, transaction};
 
 /// The item mutation.
 #[derive(Default)]
@@ -12,10 +16,18 @@ struct LocationMutation;
 /// The transaction mutation.
 #[derive(Default)]
 struct TransactionMutation;
+/// The reservation mutation.
+#[derive(Default)]
+struct ReservationMutation;
 
 /// The root mutation.
 #[derive(async_graphql::MergedObject, Default)]
-pub(crate) struct RootMutation(ItemMutation, LocationMutation, TransactionMutation);
+pub(crate) struct RootMutation(
+    ItemMutation,
+    LocationMutation,
+    TransactionMutation,
+    ReservationMutation,
+);
 
 /// The item mutation for the inventory tracking system.
 #[async_graphql::Object]
@@ -26,7 +38,13 @@ impl ItemMutation {
         context: &Context<'_>,
         item: item::InsertableItem,
     ) -> Result<item::Item> {
-        item::create_item(context.data_unchecked::<AppContext>(), item).await
+        item::create_item(
+            context.data_unchecked::<AppContext>(),
+            item,
+            source::client_source(context),
+        )
+        .await
+        .map_err(Error::from)
     }
 
     /// The mutation to update an item with the given id.
@@ -36,12 +54,121 @@ impl ItemMutation {
         id: item::ItemId,
         item: item::InsertableItem,
     ) -> Result<item::Item> {
-        item::update_item(context.data_unchecked::<AppContext>(), id, item).await
+        item::update_item(context.data_unchecked::<AppContext>(), id, item)
+            .await
+            .map_err(Error::from)
+    }
+
+    /// The mutation to create an item, or update the item with the same sku, ignoring case, which
+    /// requires a sku.
+    async fn upsert_item_by_sku(
+        &self,
+        context: &Context<'_>,
+        item: item::InsertableItem,
+    ) -> Result<item::ItemUpsert> {
+        item::upsert_item_by_sku(
+            context.data_unchecked::<AppContext>(),
+            item,
+            source::client_source(context),
+        )
+        .await
+        .map_err(Error::from)
+    }
+
+    /// The mutation to apply the same changes to all items with the given ids.
+    async fn bulk_update_items(
+        &self,
+        context: &Context<'_>,
+        ids: Vec<item::ItemId>,
+        patch: item::ItemPatch,
+    ) -> Result<Vec<item::Item>> {
+        item::bulk_update_items(context.data_unchecked::<AppContext>(), ids, patch)
+            .await
+            .map_err(Error::from)
+    }
+
+    /// The mutation to import many items at once, reporting the items whose skus already exist.
+    /// Created items are attributed to the import unless a client source is given.
+    async fn import_items(
+        &self,
+        context: &Context<'_>,
+        items: Vec<item::InsertableItem>,
+        #[graphql(default)] on_conflict: item::ImportConflictMode,
+    ) -> Result<item::ItemImport> {
+        item::import_items(
+            context.data_unchecked::<AppContext>(),
+            items,
+            on_conflict,
+            source::client_source(context).unwrap_or(IMPORT_SOURCE),
+        )
+        .await
+        .map_err(Error::from)
     }
 
     /// The mutation to delete an item with the given id.
     async fn delete_item(&self, context: &Context<'_>, id: item::ItemId) -> Result<item::Item> {
-        item::delete_item(context.data_unchecked::<AppContext>(), id).await
+        item::delete_item(context.data_unchecked::<AppContext>(), id)
+            .await
+            .map_err(Error::from)
+    }
+
+    /// The mutation to add an attachment to an item.
+    async fn add_item_attachment(
+        &self,
+        context: &Context<'_>,
+        attachment: attachment::InsertableItemAttachment,
+    ) -> Result<attachment::ItemAttachment> {
+        attachment::add_item_attachment(context.data_unchecked::<AppContext>(), attachment)
+            .await
+            .map_err(Error::from)
+    }
+
+    /// The mutation to remove an attachment with the given id from its item.
+    async fn remove_item_attachment(
+        &self,
+        context: &Context<'_>,
+        id: attachment::ItemAttachmentId,
+    ) -> Result<attachment::ItemAttachment> {
+        attachment::remove_item_attachment(context.data_unchecked::<AppContext>(), id)
+            .await
+            .map_err(Error::from)
+    }
+
+    /// The admin mutation to recalculate item quantities from their transactions, as a consistency
+    /// check of the loaded quantities.
+    #[graphql(guard = "AdminGuard")]
+    async fn recalculate_quantities(
+        &self,
+        context: &Context<'_>,
+        #[graphql(default = false)] discrepancies_only: bool,
+    ) -> Result<Vec<item::QuantityRecalculation>> {
+        item::recalculate_quantities(context.data_unchecked::<AppContext>(), discrepancies_only)
+            .await
+            .map_err(Error::from)
+    }
+
+    /// The admin mutation to delete all items, locations and transactions, with their reservations,
+    /// attachments and history, which is only enabled in test environments by
+    /// `TEST_RESET_ENABLED`.
+    #[graphql(guard = "AdminGuard.and(ResetGuard)")]
+    async fn reset_all_data(&self, context: &Context<'_>) -> Result<bool> {
+        reset::reset_all_data(context.data_unchecked::<AppContext>())
+            .await
+            .map(|_| true)
+            .map_err(Error::from)
+    }
+
+    /// The admin mutation to turn maintenance mode on or off for every server, returning whether
+    /// it is on, which it stays while `MAINTENANCE_MODE` is set. Other mutations are rejected
+    /// while it is on, but this one is still accepted.
+    #[graphql(guard = "AdminGuard")]
+    async fn set_maintenance_mode(&self, context: &Context<'_>, enabled: bool) -> Result<bool> {
+        maintenance::set_enabled(context.data_unchecked::<AppContext>(), enabled)
+            .await
+            .map_err(|e| {
+                Error::new(format!("unable to set maintenance mode: {}", e))
+                    .extend_with(|_, e| e.set("code", code::INTERNAL))
+            })
     }
 }
 
@@ -54,7 +181,21 @@ impl LocationMutation {
         context: &Context<'_>,
         location: location::InsertableLocation,
     ) -> Result<location::Location> {
-        location::create_location(context.data_unchecked::<AppContext>(), location).await
+        location::create_location(context.data_unchecked::<AppContext>(), location)
+            .await
+            .map_err(Error::from)
+    }
+
+    /// The mutation to create many locations at once, which are all created, or none are if any
+    /// are invalid.
+    async fn create_locations(
+        &self,
+        context: &Context<'_>,
+        locations: Vec<location::InsertableLocation>,
+    ) -> Result<Vec<location::Location>> {
+        location::create_locations(context.data_unchecked::<AppContext>(), locations)
+            .await
+            .map_err(Error::from)
     }
 
     /// The mutation to update a location with the given id.
@@ -64,7 +205,9 @@ impl LocationMutation {
         id: location::LocationId,
         location: location::InsertableLocation,
     ) -> Result<location::Location> {
-        location::update_location(context.data_unchecked::<AppContext>(), id, location).await
+        location::update_location(context.data_unchecked::<AppContext>(), id, location)
+            .await
+            .map_err(Error::from)
     }
 
     /// The mutation to delete a location with the given id.
@@ -73,7 +216,9 @@ impl LocationMutation {
         context: &Context<'_>,
         id: location::LocationId,
     ) -> Result<location::Location> {
-        location::delete_location(context.data_unchecked::<AppContext>(), id).await
+        location::delete_location(context.data_unchecked::<AppContext>(), id)
+            .await
+            .map_err(Error::from)
     }
 }
 
@@ -86,7 +231,48 @@ impl TransactionMutation {
         context: &Context<'_>,
         transaction: transaction::InsertableTransaction,
     ) -> Result<transaction::Transaction> {
-        transaction::create_transaction(context.data_unchecked::<AppContext>(), transaction).await
+        transaction::create_transaction(
+            context.data_unchecked::<AppContext>(),
+            transaction,
+            source::client_source(context),
+        )
+        .await
+        .map_err(Error::from)
+    }
+
+    /// The mutation to create many transactions at once, which are all created, or none are if
+    /// any cannot be created, unless the mode is `BEST_EFFORT`.
+    async fn create_transactions(
+        &self,
+        context: &Context<'_>,
+        transactions: Vec<transaction::InsertableTransaction>,
+        #[graphql(default)] mode: transaction::BulkCreateMode,
+    ) -> Result<transaction::TransactionBulkCreate> {
+        transaction::create_transactions(
+            context.data_unchecked::<AppContext>(),
+            transactions,
+            mode,
+            source::client_source(context),
+        )
+        .await
+        .map_err(Error::from)
+    }
+
+    /// The mutation to set the opening balances of items at locations, which creates an
+    /// `OPENING_BALANCE` transaction for each balance which differs from the current quantity of its
+    /// item at its location, so setting the same balances again creates no transactions.
+    async fn set_opening_balances(
+        &self,
+        context: &Context<'_>,
+        balances: Vec<transaction::InsertableOpeningBalance>,
+    ) -> Result<Vec<transaction::Transaction>> {
+        transaction::set_opening_balances(
+            context.data_unchecked::<AppContext>(),
+            balances,
+            source::client_source(context),
+        )
+        .await
+        .map_err(Error::from)
     }
 
     /// The mutation to update a transaction with the given id.
@@ -98,6 +284,23 @@ impl TransactionMutation {
     ) -> Result<transaction::Transaction> {
         transaction::update_transaction(context.data_unchecked::<AppContext>(), id, transaction)
             .await
+            .map_err(Error::from)
+    }
+
+    /// The mutation to transfer stock of an item between locations, which creates a transaction out
+    /// of one location and a transaction into the other.
+    async fn transfer_stock(
+        &self,
+        context: &Context<'_>,
+        transfer: transaction::InsertableTransfer,
+    ) -> Result<transaction::Transfer> {
+        transaction::transfer_stock(
+            context.data_unchecked::<AppContext>(),
+            transfer,
+            source::client_source(context),
+        )
+        .await
+        .map_err(Error::from)
     }
 
     /// The mutation to delete a transaction with the given id.
@@ -106,6 +309,66 @@ impl TransactionMutation {
         context: &Context<'_>,
         id: transaction::TransactionId,
     ) -> Result<transaction::Transaction> {
-        transaction::delete_transaction(context.data_unchecked::<AppContext>(), id).await
+        transaction::delete_transaction(context.data_unchecked::<AppContext>(), id)
+            .await
+            .map_err(Error::from)
+    }
+
+    /// The mutation to restore a deleted transaction with the given id, with the values it had
+    /// when it was deleted.
+    async fn restore_transaction(
+        &self,
+        context: &Context<'_>,
+        id: transaction::TransactionId,
+    ) -> Result<transaction::Transaction> {
+        transaction::restore_transaction(context.data_unchecked::<AppContext>(), id)
+            .await
+            .map_err(Error::from)
+    }
+}
+
+/// The reservation mutation for the inventory tracking system.
+#[async_graphql::Object]
+impl ReservationMutation {
+    /// The mutation to reserve a quantity of an item, which is no longer available but is still on
+    /// hand.
+    async fn reserve_stock(
+        &self,
+        context: &Context<'_>,
+        item_id: item::ItemId,
+        location_id: Option<location::LocationId>,
+        quantity: item::ItemQuantity,
+    ) -> Result<reservation::Reservation> {
+        reservation::reserve_stock(
+            context.data_unchecked::<AppContext>(),
+            item_id,
+            location_id,
+            quantity,
+        )
+        .await
+        .map_err(Error::from)
+    }
+
+    /// The mutation to commit a reservation with the given id, taking out the reserved stock.
+    async fn commit_reservation(
+        &self,
+        context: &Context<'_>,
+        id: reservation::ReservationId,
+    ) -> Result<reservation::Reservation> {
+        reservation::commit_reservation(context.data_unchecked::<AppContext>(), id)
+            .await
+            .map_err(Error::from)
+    }
+
+    /// The mutation to release a reservation with the given id, making the reserved stock
+    /// available again.
+    async fn release_reservation(
+        &self,
+        context: &Context<'_>,
+        id: reservation::ReservationId,
+    ) -> Result<reservation::Reservation> {
+        reservation::release_reservation(context.data_unchecked::<AppContext>(), id)
+            .await
+            .map_err(Error::from)
     }
 }
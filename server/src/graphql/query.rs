@@ -1,7 +1,14 @@
-use async_graphql::{Context, Result};
+use async_graphql::{Context, Error, Result};
+use chrono::{DateTime, Utc};
 
-use crate::graphql::AppContext;
-use crate::model::{item, location, transaction};
+use crate::graphql::maintenance;
+use crate::graphql::{
+    list_complexity, page_complexity, AppContext, LIST_COMPLEXITY_MULTIPLIER,
+    TRANSACTIONS_SUMMARY_COMPLEXITY,
+};
+use crate::model::validation::rules;
+use crate::model::validation::transaction::TransactionMetadataValidator;
+use crate::model::{dashboard, item, location, modification, transaction};
 
 /// The item query.
 #[derive(Default)]
@@ -13,30 +20,129 @@ struct LocationQuery;
 #[derive(Default)]
 struct TransactionQuery;
 
+/// The dashboard query.
+#[derive(Default)]
+struct DashboardQuery;
+
+/// The modification query.
+#[derive(Default)]
+struct ModificationQuery;
+
+/// The validation query.
+#[derive(Default)]
+struct ValidationQuery;
+
 /// The root query.
 #[derive(async_graphql::MergedObject, Default)]
-pub(crate) struct RootQuery(ItemQuery, LocationQuery, TransactionQuery);
+pub(crate) struct RootQuery(
+    ItemQuery,
+    LocationQuery,
+    TransactionQuery,
+    DashboardQuery,
+    ModificationQuery,
+    ValidationQuery,
+);
 
 /// The item query for the inventory tracking system.
 #[async_graphql::Object]
 impl ItemQuery {
-    /// The query to retrieve all items.
-    async fn items(&self, context: &Context<'_>) -> Result<Vec<item::Item>> {
-        item::get_items(context.data_unchecked::<AppContext>()).await
+    /// The query to retrieve all items, in alphabetical order of their names unless another order is
+    /// given, optionally with names containing the given text, with names or skus containing the
+    /// given search text, created by a client source, and with quantities within inclusive bounds,
+    /// where items without transactions have a quantity of zero.
+    #[graphql(complexity = "list_complexity(LIST_COMPLEXITY_MULTIPLIER, child_complexity)")]
+    #[allow(clippy::too_many_arguments)]
+    async fn items(
+        &self,
+        context: &Context<'_>,
+        name_contains: Option<String>,
+        search: Option<String>,
+        source: Option<String>,
+        min_quantity: Option<item::ItemQuantity>,
+        max_quantity: Option<item::ItemQuantity>,
+        #[graphql(default)] order_by: item::ItemOrderBy,
+    ) -> Result<Vec<item::Item>> {
+        // only fetch the columns of the selected fields, since item rows can be wide, and join the
+        // quantities if they are selected rather than loading them in another round-trip
+        let look_ahead = context.look_ahead();
+        let columns = item::get_item_columns(|field| look_ahead.field(field).exists());
+        item::get_items(
+            context.data_unchecked::<AppContext>(),
+            name_contains,
+            search,
+            source,
+            min_quantity,
+            max_quantity,
+            order_by,
+            &columns,
+            look_ahead.field("quantity").exists() || look_ahead.field("quantityAvailable").exists(),
+        )
+        .await
+        .map_err(Error::from)
+    }
+
+    /// The query to retrieve existing suppliers starting with the given prefix, to keep the
+    /// suppliers of items consistent.
+    async fn supplier_suggestions(
+        &self,
+        context: &Context<'_>,
+        prefix: String,
+    ) -> Result<Vec<String>> {
+        item::get_supplier_suggestions(context.data_unchecked::<AppContext>(), prefix)
+            .await
+            .map_err(Error::from)
+    }
+
+    /// The query to retrieve the number of items and their total quantity for each supplier, with
+    /// the most items first. Items without a supplier are grouped under a null supplier.
+    async fn supplier_summary(&self, context: &Context<'_>) -> Result<Vec<item::SupplierSummary>> {
+        item::get_supplier_summaries(context.data_unchecked::<AppContext>())
+            .await
+            .map_err(Error::from)
+    }
+
+    /// The query to retrieve suggestions to reorder items below their reorder point.
+    async fn reorder_suggestions(
+        &self,
+        context: &Context<'_>,
+        #[graphql(
+            default = 30,
+            validator(minimum = 1),
+            desc = "The number of days of consumption to restock."
+        )]
+        window_days: i32,
+    ) -> Result<Vec<item::ReorderSuggestion>> {
+        item::get_reorder_suggestions(context.data_unchecked::<AppContext>(), window_days)
+            .await
+            .map_err(Error::from)
     }
 
     /// The query to retrieve a single item by id.
     async fn item(&self, context: &Context<'_>, id: item::ItemId) -> Result<item::Item> {
         item::get_item(context.data_unchecked::<AppContext>(), id).await
     }
+
+    /// The query to retrieve a single item by sku, ignoring case.
+    async fn item_by_sku(&self, context: &Context<'_>, sku: String) -> Result<item::Item> {
+        item::get_item_by_sku(context.data_unchecked::<AppContext>(), &sku).await
+    }
+
+    /// The federation entity resolver for items, which are keyed by id.
+    #[graphql(entity)]
+    async fn find_item_by_id(&self, context: &Context<'_>, id: item::ItemId) -> Result<item::Item> {
+        item::get_item(context.data_unchecked::<AppContext>(), id).await
+    }
 }
 
 /// The location query for the inventory tracking system.
 #[async_graphql::Object]
 impl LocationQuery {
     /// The query to retrieve all locations.
+    #[graphql(complexity = "list_complexity(LIST_COMPLEXITY_MULTIPLIER, child_complexity)")]
     async fn locations(&self, context: &Context<'_>) -> Result<Vec<location::Location>> {
-        location::get_locations(context.data_unchecked::<AppContext>()).await
+        location::get_locations(context.data_unchecked::<AppContext>())
+            .await
+            .map_err(Error::from)
     }
 
     /// The query to retrieve a single location by id.
@@ -47,14 +153,216 @@ impl LocationQuery {
     ) -> Result<location::Location> {
         location::get_location(context.data_unchecked::<AppContext>(), id).await
     }
+
+    /// The query to retrieve locations by ids in one round-trip, in the order of the ids, where
+    /// ids of locations which do not exist resolve to null.
+    #[graphql(complexity = "list_complexity(ids.len(), child_complexity)")]
+    async fn locations_by_ids(
+        &self,
+        context: &Context<'_>,
+        #[graphql(validator(max_items = 100))] ids: Vec<location::LocationId>,
+    ) -> Result<Vec<Option<location::Location>>> {
+        location::get_locations_in_order(context.data_unchecked::<AppContext>(), ids).await
+    }
+
+    /// The query to retrieve all locations within a radius in kilometres of a point, nearest first.
+    #[graphql(complexity = "list_complexity(LIST_COMPLEXITY_MULTIPLIER, child_complexity)")]
+    async fn locations_near(
+        &self,
+        context: &Context<'_>,
+        lat: f64,
+        lng: f64,
+        radius_km: f64,
+    ) -> Result<Vec<location::NearbyLocation>> {
+        location::get_locations_near(context.data_unchecked::<AppContext>(), lat, lng, radius_km)
+            .await
+            .map_err(Error::from)
+    }
+
+    /// The federation entity resolver for locations, which are keyed by id.
+    #[graphql(entity)]
+    async fn find_location_by_id(
+        &self,
+        context: &Context<'_>,
+        id: location::LocationId,
+    ) -> Result<location::Location> {
+        location::get_location(context.data_unchecked::<AppContext>(), id).await
+    }
 }
 
 /// The transaction query for the inventory tracking system.
 #[async_graphql::Object]
 impl TransactionQuery {
-    /// The query to retrieve all transactions.
-    async fn transactions(&self, context: &Context<'_>) -> Result<Vec<transaction::Transaction>> {
-        transaction::get_transactions(context.data_unchecked::<AppContext>()).await
+    /// The query to retrieve transactions, most recent first, optionally of an item, containing the
+    /// given metadata, only those without a location, with a reason, dated within a range, in a
+    /// direction, or created by a client source. Undated transactions are last, unless `nulls` is
+    /// `FIRST`. When `afterId` is given, transactions with greater ids are retrieved in the order of
+    /// their ids, so the transactions can be synced by passing the last id of each page.
+    #[graphql(complexity = "page_complexity(first, child_complexity)")]
+    #[allow(clippy::too_many_arguments)]
+    async fn transactions(
+        &self,
+        context: &Context<'_>,
+        item_id: Option<item::ItemId>,
+        #[graphql(validator(custom = "TransactionMetadataValidator {}"))] metadata_contains: Option<
+            transaction::TransactionMetadata,
+        >,
+        #[graphql(default = false)] unassigned_only: bool,
+        reason: Option<transaction::TransactionReason>,
+        #[graphql(desc = "The earliest date in RFC 3339 format, inclusive.")] since: Option<
+            DateTime<Utc>,
+        >,
+        #[graphql(desc = "The latest date in RFC 3339 format, exclusive.")] until: Option<
+            DateTime<Utc>,
+        >,
+        #[graphql(default)] direction: transaction::TransactionDirection,
+        source: Option<String>,
+        #[graphql(default)] nulls: transaction::NullsOrder,
+        after_id: Option<transaction::TransactionId>,
+        #[graphql(validator(minimum = 1, maximum = 1000))] first: Option<i64>,
+        #[graphql(default = 0, validator(minimum = 0))] offset: i64,
+    ) -> Result<Vec<transaction::Transaction>> {
+        let filter = transaction::TransactionFilter {
+            item_id,
+            metadata_contains,
+            unassigned_only,
+            reason,
+            since,
+            until,
+            direction,
+            source,
+        };
+        transaction::get_transactions(
+            context.data_unchecked::<AppContext>(),
+            filter,
+            nulls,
+            after_id,
+            first,
+            offset,
+        )
+        .await
+        .map_err(Error::from)
+    }
+
+    /// The query to retrieve a page of transactions, filtered and ordered like `transactions`, with
+    /// the number of transactions on all pages, which is counted in the same query as the page.
+    #[graphql(complexity = "page_complexity(Some(first), child_complexity)")]
+    #[allow(clippy::too_many_arguments)]
+    async fn transactions_connection(
+        &self,
+        context: &Context<'_>,
+        item_id: Option<item::ItemId>,
+        #[graphql(validator(custom = "TransactionMetadataValidator {}"))] metadata_contains: Option<
+            transaction::TransactionMetadata,
+        >,
+        #[graphql(default = false)] unassigned_only: bool,
+        reason: Option<transaction::TransactionReason>,
+        #[graphql(desc = "The earliest date in RFC 3339 format, inclusive.")] since: Option<
+            DateTime<Utc>,
+        >,
+        #[graphql(desc = "The latest date in RFC 3339 format, exclusive.")] until: Option<
+            DateTime<Utc>,
+        >,
+        #[graphql(default)] direction: transaction::TransactionDirection,
+        source: Option<String>,
+        #[graphql(default)] nulls: transaction::NullsOrder,
+        #[graphql(default = 50, validator(minimum = 1, maximum = 1000))] first: i64,
+        #[graphql(default = 0, validator(minimum = 0))] offset: i64,
+    ) -> Result<transaction::TransactionConnection> {
+        let filter = transaction::TransactionFilter {
+            item_id,
+            metadata_contains,
+            unassigned_only,
+            reason,
+            since,
+            until,
+            direction,
+            source,
+        };
+        transaction::get_transaction_connection(
+            context.data_unchecked::<AppContext>(),
+            filter,
+            nulls,
+            first,
+            offset,
+        )
+        .await
+        .map_err(Error::from)
+    }
+
+    /// The query to retrieve the counts and total quantities of transactions, grouped by
+    /// combinations of their item, location, reason, and the day, week or month of their date, in
+    /// the order of the dimensions. Dates are bucketed in the given time zone, UTC by default.
+    /// Transactions can be limited to those dated within a range, and at most 1000 groups are
+    /// returned.
+    #[graphql(complexity = "TRANSACTIONS_SUMMARY_COMPLEXITY")]
+    async fn transactions_summary(
+        &self,
+        context: &Context<'_>,
+        group_by: Vec<transaction::TransactionGroupDimension>,
+        #[graphql(desc = "The earliest date in RFC 3339 format, inclusive.")] from: Option<
+            DateTime<Utc>,
+        >,
+        #[graphql(desc = "The latest date in RFC 3339 format, exclusive.")] to: Option<
+            DateTime<Utc>,
+        >,
+        #[graphql(
+            desc = "The IANA time zone of the date buckets, such as America/Toronto.",
+            default_with = "String::from(\"UTC\")"
+        )]
+        timezone: String,
+    ) -> Result<Vec<transaction::TransactionSummary>> {
+        transaction::get_transaction_summaries(
+            context.data_unchecked::<AppContext>(),
+            group_by,
+            from,
+            to,
+            timezone,
+        )
+        .await
+        .map_err(Error::from)
+    }
+
+    /// The query to retrieve transfers of stock between locations, each with the transaction out of
+    /// one location paired with the transaction into the other, most recent first, optionally of an
+    /// item or dated within a range. Transfers which no longer have both transactions are omitted.
+    #[graphql(complexity = "page_complexity(first, child_complexity)")]
+    async fn transfers(
+        &self,
+        context: &Context<'_>,
+        item_id: Option<item::ItemId>,
+        #[graphql(desc = "The earliest date in RFC 3339 format, inclusive.")] since: Option<
+            DateTime<Utc>,
+        >,
+        #[graphql(desc = "The latest date in RFC 3339 format, exclusive.")] until: Option<
+            DateTime<Utc>,
+        >,
+        #[graphql(validator(minimum = 1, maximum = 1000))] first: Option<i64>,
+        #[graphql(default = 0, validator(minimum = 0))] offset: i64,
+    ) -> Result<Vec<transaction::Transfer>> {
+        transaction::get_transfers(
+            context.data_unchecked::<AppContext>(),
+            item_id,
+            since,
+            until,
+            None,
+            first,
+            offset,
+        )
+        .await
+        .map_err(Error::from)
+    }
+
+    /// The query to retrieve a single transfer of stock between locations by the transfer group
+    /// of its transactions.
+    async fn transfer(
+        &self,
+        context: &Context<'_>,
+        transfer_group: transaction::TransferGroupId,
+    ) -> Result<transaction::Transfer> {
+        transaction::get_transfer(context.data_unchecked::<AppContext>(), transfer_group)
+            .await
+            .map_err(Error::from)
     }
 
     /// The query to retrieve a single transaction by id.
@@ -65,4 +373,85 @@ impl TransactionQuery {
     ) -> Result<transaction::Transaction> {
         transaction::get_transaction(context.data_unchecked::<AppContext>(), id).await
     }
+
+    /// The query to retrieve transactions by ids in one round-trip, in the order of the ids, where
+    /// ids of transactions which do not exist resolve to null.
+    #[graphql(complexity = "list_complexity(ids.len(), child_complexity)")]
+    async fn transactions_by_ids(
+        &self,
+        context: &Context<'_>,
+        #[graphql(validator(max_items = 100))] ids: Vec<transaction::TransactionId>,
+    ) -> Result<Vec<Option<transaction::Transaction>>> {
+        transaction::get_transactions_in_order(context.data_unchecked::<AppContext>(), ids).await
+    }
+
+    /// The federation entity resolver for transactions, which are keyed by id.
+    #[graphql(entity)]
+    async fn find_transaction_by_id(
+        &self,
+        context: &Context<'_>,
+        id: transaction::TransactionId,
+    ) -> Result<transaction::Transaction> {
+        transaction::get_transaction(context.data_unchecked::<AppContext>(), id).await
+    }
+}
+
+/// The dashboard query for the inventory tracking system.
+#[async_graphql::Object]
+impl DashboardQuery {
+    /// The query to retrieve an overview of the inventory, whose selected aggregates are computed
+    /// concurrently.
+    async fn dashboard(&self) -> dashboard::Dashboard {
+        dashboard::Dashboard
+    }
+}
+
+/// The modification query for the inventory tracking system.
+#[async_graphql::Object]
+impl ModificationQuery {
+    /// The query to retrieve the audit feed of modifications, most recent first, optionally of an
+    /// entity, of the given types, or made since a date. Pages continue after the id of the last
+    /// modification of the previous page.
+    #[graphql(complexity = "page_complexity(Some(first), child_complexity)")]
+    async fn recent_modifications(
+        &self,
+        context: &Context<'_>,
+        entity: Option<modification::ModificationEntity>,
+        types: Option<Vec<modification::ModificationType>>,
+        #[graphql(desc = "The earliest date in RFC 3339 format, inclusive.")] since: Option<
+            DateTime<Utc>,
+        >,
+        after: Option<modification::ModificationId>,
+        #[graphql(default = 50, validator(minimum = 1, maximum = 1000))] first: i64,
+    ) -> Result<Vec<modification::RecordedModification>> {
+        let filter = modification::ModificationFilter {
+            entity,
+            types,
+            since,
+        };
+        modification::get_recent_modifications(
+            context.data_unchecked::<AppContext>(),
+            filter,
+            after,
+            first,
+        )
+        .await
+        .map_err(Error::from)
+    }
+}
+
+/// The validation query for the inventory tracking system.
+#[async_graphql::Object]
+impl ValidationQuery {
+    /// The query to retrieve the constraints on the fields of inputs, with the configuration
+    /// applied, so clients can check inputs in the same way as the server.
+    async fn validation_rules(&self, context: &Context<'_>) -> Vec<rules::ValidationRule> {
+        rules::get_validation_rules(context.data_unchecked::<AppContext>())
+    }
+
+    /// The query to check whether the service is in maintenance, in which case mutations are
+    /// rejected, so clients can disable changes until it ends.
+    async fn maintenance_mode(&self, context: &Context<'_>) -> bool {
+        maintenance::is_enabled(context.data_unchecked::<AppContext>()).await
+    }
 }
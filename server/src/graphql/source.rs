@@ -0,0 +1,63 @@
+use actix_web::HttpRequest;
+use async_graphql::Context;
+
+/// The header which names the client making a request, such as a scanner app or a sync job.
+pub(crate) const CLIENT_SOURCE_HEADER: &str = "X-Client-Source";
+
+/// The maximum length of a client source, so the header cannot fill rows with arbitrary text.
+const MAX_SOURCE_LENGTH: usize = 64;
+
+/// The source which created items and transactions when importing items.
+pub(crate) const IMPORT_SOURCE: &str = "import";
+
+/// The client which made a request, which is recorded on the items and transactions it creates.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct ClientSource(pub(crate) String);
+
+impl ClientSource {
+    /// Gets the client source of a request from its header, ignoring blank or overlong sources.
+    pub(crate) fn from_request(req: &HttpRequest) -> Option<Self> {
+        req.headers()
+            .get(CLIENT_SOURCE_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(str::trim)
+            .filter(|source| !source.is_empty() && source.len() <= MAX_SOURCE_LENGTH)
+            .map(|source| ClientSource(source.to_string()))
+    }
+}
+
+/// Gets the client source of the request of an operation, if it was given.
+pub(crate) fn client_source<'a>(context: &'a Context<'_>) -> Option<&'a str> {
+    context
+        .data_opt::<ClientSource>()
+        .map(|source| source.0.as_str())
+}
+
+/// Unit tests for client sources.
+#[cfg(test)]
+mod test {
+    use actix_web::test::TestRequest;
+
+    use super::*;
+
+    #[test]
+    fn test_from_request() {
+        let source = |value: &str| {
+            ClientSource::from_request(
+                &TestRequest::default()
+                    .insert_header((CLIENT_SOURCE_HEADER, value))
+                    .to_http_request(),
+            )
+        };
+        assert_eq!(
+            source(" scanner "),
+            Some(ClientSource("scanner".to_string()))
+        );
+        assert_eq!(source("  "), None);
+        assert_eq!(source(&"a".repeat(MAX_SOURCE_LENGTH + 1)), None);
+        assert_eq!(
+            ClientSource::from_request(&TestRequest::default().to_http_request()),
+            None
+        );
+    }
+}
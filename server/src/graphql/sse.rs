@@ -0,0 +1,66 @@
+use std::convert::Infallible;
+
+use actix_web::http::header::{self, ContentEncoding};
+use actix_web::web::Bytes;
+use actix_web::{HttpRequest, HttpResponse};
+use futures::{future, stream, StreamExt};
+
+use crate::graphql::websocket::SubscriptionConfig;
+use crate::graphql::AppSchema;
+
+/// The content type of server-sent events.
+pub(crate) const EVENT_STREAM: &str = "text/event-stream";
+
+/// Whether a request accepts server-sent events, rather than being upgraded to a websocket.
+pub(crate) fn accepts_event_stream(request: &HttpRequest) -> bool {
+    let upgrade = request.headers().contains_key(header::UPGRADE);
+    let accept = request
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default();
+    !upgrade && accept.contains(EVENT_STREAM)
+}
+
+/// Formats a server-sent event with a name and data on one line.
+fn event(name: &str, data: &str) -> Bytes {
+    Bytes::from(format!("event: {}\ndata: {}\n\n", name, data))
+}
+
+/// Starts a GraphQL subscription over server-sent events for the request, which sends each
+/// response as a `next` event and a `complete` event when the subscription ends. The subscription
+/// is dropped with the response body when the client disconnects, which unsubscribes it from the
+/// fan-out of its redis channel, and drops the redis subscription if it was the last subscriber.
+pub(crate) fn start(
+    schema: AppSchema,
+    config: SubscriptionConfig,
+    request: async_graphql::Request,
+) -> HttpResponse {
+    // the end of the subscription is marked with none, so the keepalives end with it
+    let events = schema
+        .execute_stream(request)
+        .map(|response| {
+            let data = serde_json::to_string(&response).expect("unable to serialize response");
+            Some(event("next", &data))
+        })
+        .chain(stream::iter([Some(event("complete", "")), None]));
+    // comments are ignored by clients, but keep idle connections open through proxies
+    let keepalives = match config.keepalive_interval {
+        Some(keepalive_interval) => stream::unfold((), move |_| async move {
+            tokio::time::sleep(keepalive_interval).await;
+            Some((Some(Bytes::from_static(b": keepalive\n\n")), ()))
+        })
+        .boxed(),
+        None => stream::pending().boxed(),
+    };
+    let body = stream::select(events, keepalives)
+        .take_while(|event| future::ready(event.is_some()))
+        .filter_map(|event| future::ready(event.map(Ok::<_, Infallible>)));
+
+    HttpResponse::Ok()
+        .content_type(EVENT_STREAM)
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        // compression buffers the body, which would delay events
+        .insert_header(ContentEncoding::Identity)
+        .streaming(body)
+}
@@ -1,15 +1,24 @@
+use std::collections::HashSet;
+use std::hash::Hash;
 use std::pin::Pin;
+use std::time::Duration;
 
 use async_graphql::{Context, Error, Result};
+use chrono::{DateTime, Utc};
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use tokio_stream::StreamExt;
 
+use crate::graphql::throttle::Throttle;
 use crate::graphql::{AppContext, Clients};
-use crate::model::item::Item;
+use crate::model::error::ModelError;
+use crate::model::item::{Item, ItemId};
 use crate::model::location::Location;
-use crate::model::modification::Modification;
-use crate::model::transaction::Transaction;
+use crate::model::modification::{Modification, ModificationEntity, ModificationType};
+use crate::model::transaction::{self, Transaction};
+
+/// The maximum number of transactions replayed when a subscription catches up.
+const BACKFILL_LIMIT: i64 = 1000;
 
 /// The item subscription.
 #[derive(Default)]
@@ -33,55 +42,179 @@ pub(crate) struct RootSubscription(
 pub(crate) type ModificationStream<T> =
     Pin<Box<dyn futures::Stream<Item = Result<Modification<T>>> + Send + Sync>>;
 
-/// Returns a subscription stream for a given type and channel name.
+/// Returns a subscription stream for a given type and a channel its modifications are published
+/// to, or an error if the channel cannot be subscribed to.
 async fn subscription_stream<T: Serialize + DeserializeOwned + async_graphql::OutputType>(
     clients: &Clients,
-    channel_name: &str,
+    channel: &str,
+) -> Result<ModificationStream<T>> {
+    let stream = clients
+        .fanout
+        .subscribe(channel)
+        .await?
+        .map(|payload| serde_json::from_str(&payload).map_err(Error::from));
+
+    Ok(Box::pin(stream))
+}
+
+/// Filters a subscription stream to the given types of modification, or all types if none are given.
+fn filter_types<T: Serialize + async_graphql::OutputType + Send + Sync + 'static>(
+    stream: ModificationStream<T>,
+    types: Option<Vec<ModificationType>>,
 ) -> ModificationStream<T> {
-    let redis_conn = clients.redis.get_async_connection().await.unwrap();
-    let mut pubsub = redis_conn.into_pubsub();
-    pubsub
-        .subscribe(channel_name)
-        .await
-        .expect("unable to subscribe to channel");
-    let stream = pubsub.into_on_message().map(|message| {
-        let payload: String = message.get_payload()?;
-        serde_json::from_str(&payload).map_err(Error::from)
-    });
+    match types {
+        Some(types) => Box::pin(stream.filter(move |result| match result {
+            Ok(modification) => types.contains(&modification.modification),
+            Err(_) => true,
+        })),
+        None => stream,
+    }
+}
 
-    Box::pin(stream)
+/// Throttles a subscription stream to at most one modification per interval for each key, which
+/// is the latest modification of the key, or leaves the stream unchanged if no interval is given.
+fn throttle<T, K>(
+    stream: ModificationStream<T>,
+    min_interval_ms: Option<i32>,
+    key: fn(&T) -> K,
+) -> ModificationStream<T>
+where
+    T: Serialize + async_graphql::OutputType + Send + Sync + Unpin + 'static,
+    K: Eq + Hash + Clone + Send + Sync + Unpin + 'static,
+{
+    match min_interval_ms {
+        Some(min_interval_ms) => Box::pin(Throttle::new(
+            stream,
+            Duration::from_millis(min_interval_ms as u64),
+            key,
+        )),
+        None => stream,
+    }
 }
 
 /// The item subscription for the inventory tracking system.
 #[async_graphql::Subscription]
 impl ItemSubscription {
-    /// The subscription to modifications of items.
-    async fn item_subscription(&self, context: &Context<'_>) -> ModificationStream<Item> {
-        subscription_stream(&context.data_unchecked::<AppContext>().clients, "items").await
+    /// The subscription to modifications of items, optionally of the given types. If a minimum
+    /// interval is given, at most one modification of each item is received per interval, which is
+    /// its latest modification, so intermediate modifications may be skipped.
+    async fn item_subscription(
+        &self,
+        context: &Context<'_>,
+        types: Option<Vec<ModificationType>>,
+        #[graphql(validator(minimum = 1))] min_interval_ms: Option<i32>,
+    ) -> Result<ModificationStream<Item>> {
+        let stream = subscription_stream(
+            &context.data_unchecked::<AppContext>().clients,
+            ModificationEntity::Item.channel_name(),
+        )
+        .await?;
+        Ok(throttle(
+            filter_types(stream, types),
+            min_interval_ms,
+            |item: &Item| item.id,
+        ))
     }
 }
 
 /// The location subscription for the inventory tracking system.
 #[async_graphql::Subscription]
 impl LocationSubscription {
-    /// The subscription to modifications of locations.
-    async fn location_subscription(&self, context: &Context<'_>) -> ModificationStream<Location> {
-        subscription_stream(&context.data_unchecked::<AppContext>().clients, "locations").await
+    /// The subscription to modifications of locations, optionally of the given types. If a minimum
+    /// interval is given, at most one modification of each location is received per interval,
+    /// which is its latest modification, so intermediate modifications may be skipped.
+    async fn location_subscription(
+        &self,
+        context: &Context<'_>,
+        types: Option<Vec<ModificationType>>,
+        #[graphql(validator(minimum = 1))] min_interval_ms: Option<i32>,
+    ) -> Result<ModificationStream<Location>> {
+        let stream = subscription_stream(
+            &context.data_unchecked::<AppContext>().clients,
+            ModificationEntity::Location.channel_name(),
+        )
+        .await?;
+        Ok(throttle(
+            filter_types(stream, types),
+            min_interval_ms,
+            |location: &Location| location.id,
+        ))
     }
 }
 
 /// The Transaction subscription for the inventory tracking system.
 #[async_graphql::Subscription]
 impl TransactionSubscription {
-    /// The subscription to modifications of transactions.
+    /// The subscription to modifications of transactions, optionally of the given types, and of an
+    /// item, which only receives the modifications of the transactions of the item. If a date is
+    /// given, the transactions dated since then are first replayed as creations, before live
+    /// modifications are received, which fails if there are more transactions to replay than the
+    /// limit, so none are skipped without the client knowing. If a minimum interval is
+    /// given, at most one transaction of each item is received per interval, which is the latest
+    /// modification of the transactions of the item, so transactions of high-velocity items may be
+    /// skipped.
     async fn transaction_subscription(
         &self,
         context: &Context<'_>,
-    ) -> ModificationStream<Transaction> {
-        subscription_stream(
-            &context.data_unchecked::<AppContext>().clients,
-            "transactions",
-        )
-        .await
+        since: Option<DateTime<Utc>>,
+        types: Option<Vec<ModificationType>>,
+        #[graphql(validator(minimum = 1))] min_interval_ms: Option<i32>,
+        item_id: Option<ItemId>,
+    ) -> Result<ModificationStream<Transaction>> {
+        let clients = &context.data_unchecked::<AppContext>().clients;
+        // subscribe before the backfill is queried, so no modifications are missed in between, and
+        // subscribe to the channel of the item if one is given, so other items are not received
+        let live = match item_id {
+            Some(item_id) => {
+                subscription_stream(clients, &transaction::item_channel_name(item_id)).await?
+            }
+            None => {
+                subscription_stream(clients, ModificationEntity::Transaction.channel_name()).await?
+            }
+        };
+        let item_id_key = |transaction: &Transaction| transaction.item_id;
+        let since = match since {
+            Some(since) => since,
+            None => {
+                return Ok(throttle(
+                    filter_types(live, types),
+                    min_interval_ms,
+                    item_id_key,
+                ))
+            }
+        };
+
+        // one more than the limit is queried to know whether transactions would be skipped
+        let backfill =
+            transaction::get_transactions_since(clients, since, item_id, BACKFILL_LIMIT + 1)
+                .await?;
+        if backfill.len() as i64 > BACKFILL_LIMIT {
+            return Err(ModelError::validation(&format!(
+                "more than {} transactions were made since the date, so subscribe with a later date",
+                BACKFILL_LIMIT
+            ))
+            .into());
+        }
+        // creations received live which were already replayed are duplicates
+        let replayed: HashSet<_> = backfill.iter().map(|transaction| transaction.id).collect();
+        let live = live.filter(move |result| match result {
+            Ok(modification) => {
+                modification.modification != ModificationType::Create
+                    || !replayed.contains(&modification.data.id)
+            }
+            Err(_) => true,
+        });
+        let backfill = futures::stream::iter(backfill.into_iter().map(|data| {
+            Ok(Modification {
+                modification: ModificationType::Create,
+                data,
+            })
+        }));
+
+        Ok(throttle(
+            filter_types(Box::pin(backfill.chain(live)), types),
+            min_interval_ms,
+            item_id_key,
+        ))
     }
 }
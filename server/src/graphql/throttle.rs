@@ -0,0 +1,154 @@
+use std::collections::hash_map::Entry;
+use std::collections::{HashMap, VecDeque};
+use std::future::Future;
+use std::hash::Hash;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use async_graphql::Result;
+use futures::Stream;
+use serde::Serialize;
+use tokio::time::{Instant, Sleep};
+
+use crate::graphql::subscription::ModificationStream;
+use crate::model::modification::Modification;
+
+/// A subscription stream which delivers at most one modification per interval for each key, such
+/// as the id of a record. The first modification of a key is delivered immediately, and later
+/// modifications within the interval are coalesced into the latest one, which is delivered when the
+/// interval ends. Intermediate modifications are skipped by design.
+pub(crate) struct Throttle<T: Serialize + async_graphql::OutputType, K> {
+    stream: ModificationStream<T>,
+    interval: Duration,
+    key: fn(&T) -> K,
+    /// When a modification of each key was last delivered, for keys delivered within the interval.
+    delivered: HashMap<K, Instant>,
+    /// The latest modification of each key which is waiting for its interval to end.
+    pending: HashMap<K, Modification<T>>,
+    /// The modifications which are ready to be delivered.
+    ready: VecDeque<Modification<T>>,
+    /// A timer for the end of the earliest interval with a pending modification.
+    timer: Option<Pin<Box<Sleep>>>,
+    ended: bool,
+}
+
+impl<T, K> Throttle<T, K>
+where
+    T: Serialize + async_graphql::OutputType + Unpin,
+    K: Eq + Hash + Clone + Unpin,
+{
+    /// Creates a throttle over a stream, with the interval and the key of the modified data.
+    pub(crate) fn new(stream: ModificationStream<T>, interval: Duration, key: fn(&T) -> K) -> Self {
+        Throttle {
+            stream,
+            interval,
+            key,
+            delivered: HashMap::new(),
+            pending: HashMap::new(),
+            ready: VecDeque::new(),
+            timer: None,
+            ended: false,
+        }
+    }
+
+    /// Delivers a received modification if its key was not delivered within the interval, and
+    /// otherwise keeps it as the pending modification of its key.
+    fn receive(&mut self, modification: Modification<T>) {
+        let now = Instant::now();
+        let (interval, pending) = (self.interval, &self.pending);
+        self.delivered.retain(|key, delivered| {
+            now.duration_since(*delivered) < interval || pending.contains_key(key)
+        });
+
+        match self.delivered.entry((self.key)(&modification.data)) {
+            Entry::Occupied(delivered) => {
+                self.pending.insert(delivered.key().clone(), modification);
+            }
+            Entry::Vacant(delivered) => {
+                delivered.insert(now);
+                self.ready.push_back(modification);
+            }
+        }
+    }
+
+    /// Delivers the pending modifications whose intervals have ended, returning when the earliest
+    /// remaining interval ends, if any modifications are still pending.
+    fn deliver_pending(&mut self) -> Option<Instant> {
+        let now = Instant::now();
+        let mut due = self
+            .pending
+            .keys()
+            .filter(|key| self.delivered[*key] + self.interval <= now)
+            .cloned()
+            .collect::<Vec<K>>();
+        due.sort_by_key(|key| self.delivered[key]);
+        for key in due {
+            if let Some(modification) = self.pending.remove(&key) {
+                self.delivered.insert(key, now);
+                self.ready.push_back(modification);
+            }
+        }
+
+        self.pending
+            .keys()
+            .map(|key| self.delivered[key] + self.interval)
+            .min()
+    }
+}
+
+impl<T, K> Stream for Throttle<T, K>
+where
+    T: Serialize + async_graphql::OutputType + Unpin,
+    K: Eq + Hash + Clone + Unpin,
+{
+    type Item = Result<Modification<T>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(modification) = this.ready.pop_front() {
+                return Poll::Ready(Some(Ok(modification)));
+            }
+
+            if !this.ended {
+                match this.stream.as_mut().poll_next(cx) {
+                    Poll::Ready(Some(Ok(modification))) => {
+                        this.receive(modification);
+                        continue;
+                    }
+                    Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e))),
+                    Poll::Ready(None) => this.ended = true,
+                    Poll::Pending => {}
+                }
+            }
+
+            // once the stream ends, there is nothing left to coalesce, so the pending modifications
+            // are delivered without waiting
+            if this.ended {
+                let pending = this.pending.drain().map(|(_, modification)| modification);
+                this.ready.extend(pending);
+                if this.ready.is_empty() {
+                    return Poll::Ready(None);
+                }
+                continue;
+            }
+
+            let next_deadline = this.deliver_pending();
+            if !this.ready.is_empty() {
+                continue;
+            }
+            let deadline = match next_deadline {
+                Some(deadline) => deadline,
+                None => return Poll::Pending,
+            };
+            let timer = this
+                .timer
+                .get_or_insert_with(|| Box::pin(tokio::time::sleep_until(deadline)));
+            timer.as_mut().reset(deadline);
+            if timer.as_mut().poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+        }
+    }
+}
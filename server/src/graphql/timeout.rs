@@ -0,0 +1,54 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_graphql::extensions::{Extension, ExtensionContext, ExtensionFactory, NextExecute};
+use async_graphql::{Error, ErrorExtensions, Pos, Response};
+
+use crate::config::Config;
+use crate::model::error::code;
+
+/// An extension which cancels GraphQL operations which take longer than a timeout.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct OperationTimeout {
+    timeout: Duration,
+}
+
+impl OperationTimeout {
+    /// Creates an operation timeout extension with the given timeout.
+    pub(crate) fn new(timeout: Duration) -> Self {
+        OperationTimeout { timeout }
+    }
+
+    /// Gets the operation timeout from the configuration of the server, or none if it is
+    /// disabled.
+    pub(crate) fn from_config(config: &Config) -> Option<Self> {
+        config.graphql_operation_timeout.map(OperationTimeout::new)
+    }
+}
+
+impl ExtensionFactory for OperationTimeout {
+    fn create(&self) -> Arc<dyn Extension> {
+        Arc::new(*self)
+    }
+}
+
+#[async_trait::async_trait]
+impl Extension for OperationTimeout {
+    async fn execute(
+        &self,
+        ctx: &ExtensionContext<'_>,
+        operation_name: Option<&str>,
+        next: NextExecute<'_>,
+    ) -> Response {
+        // on timeout the operation is dropped, which cancels its in-flight resolvers
+        match tokio::time::timeout(self.timeout, next.run(ctx, operation_name)).await {
+            Ok(response) => response,
+            Err(_) => Response::from_errors(vec![Error::new(format!(
+                "operation timed out after {} ms",
+                self.timeout.as_millis()
+            ))
+            .extend_with(|_, e| e.set("code", code::TIMEOUT))
+            .into_server_error(Pos::default())]),
+        }
+    }
+}
@@ -0,0 +1,311 @@
+use std::collections::HashSet;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use actix::{
+    Actor, ActorContext, ActorFutureExt, ActorStreamExt, AsyncContext, ContextFutureSpawner,
+    StreamHandler, WrapFuture, WrapStream,
+};
+use actix_http::ws::Item;
+use actix_web::{error, web, Error, HttpRequest, HttpResponse};
+use actix_web_actors::ws::{self, CloseReason, Message, ProtocolError, WebsocketContext};
+use async_graphql::http::{WebSocket, WebSocketProtocols, WsMessage, ALL_WEBSOCKET_PROTOCOLS};
+
+use crate::config::Config;
+use crate::graphql::AppSchema;
+use crate::model::error::code;
+
+/// The interval of websocket pings, which detect disconnected clients.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(5);
+/// The duration after which a client which has not responded to pings is disconnected.
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The configuration of GraphQL subscription websockets.
+#[derive(Copy, Clone, Debug)]
+pub(crate) struct SubscriptionConfig {
+    /// The interval of protocol keepalive messages, or none if they are disabled.
+    pub(crate) keepalive_interval: Option<Duration>,
+    /// The maximum number of concurrent subscriptions of a connection, or none if it is unlimited.
+    pub(crate) max_subscriptions: Option<usize>,
+}
+
+impl SubscriptionConfig {
+    /// Gets the subscription configuration from the configuration of the server.
+    pub(crate) fn from_config(config: &Config) -> Self {
+        SubscriptionConfig {
+            keepalive_interval: config.subscription_keepalive_interval,
+            max_subscriptions: config.subscription_max_per_connection,
+        }
+    }
+}
+
+/// Starts a GraphQL subscription websocket for the request.
+pub(crate) fn start(
+    schema: AppSchema,
+    config: SubscriptionConfig,
+    request: &HttpRequest,
+    payload: web::Payload,
+) -> Result<HttpResponse, Error> {
+    let protocol = request
+        .headers()
+        .get("sec-websocket-protocol")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|protocols| {
+            protocols
+                .split(',')
+                .find_map(|protocol| WebSocketProtocols::from_str(protocol.trim()).ok())
+        })
+        .ok_or_else(|| error::ErrorBadRequest("unsupported websocket protocol"))?;
+
+    let actor = SubscriptionActor {
+        schema,
+        config,
+        protocol,
+        last_heartbeat: Instant::now(),
+        messages: None,
+        continuation: Vec::new(),
+        active: ActiveOperations::default(),
+    };
+
+    ws::WsResponseBuilder::new(actor, request, payload)
+        .protocols(&ALL_WEBSOCKET_PROTOCOLS)
+        .start()
+}
+
+/// An actor which forwards websocket messages to and from the GraphQL schema.
+struct SubscriptionActor {
+    schema: AppSchema,
+    config: SubscriptionConfig,
+    protocol: WebSocketProtocols,
+    last_heartbeat: Instant,
+    messages: Option<async_channel::Sender<Vec<u8>>>,
+    continuation: Vec<u8>,
+    active: ActiveOperations,
+}
+
+/// The ids of the operations of a connection which have started and not completed, so the number
+/// of concurrent subscriptions of the connection can be limited.
+#[derive(Debug, Default)]
+struct ActiveOperations {
+    ids: HashSet<String>,
+}
+
+impl ActiveOperations {
+    /// Tracks a message from the client, returning the id of the operation it starts if it is past
+    /// the limit, in which case the message is not tracked.
+    fn track_client_message(&mut self, message: &[u8], limit: usize) -> Option<String> {
+        let (message_type, id) = message_type_and_id(message)?;
+        match message_type.as_str() {
+            // graphql-ws starts operations with start, and graphql-transport-ws with subscribe
+            "start" | "subscribe" => {
+                if !self.ids.contains(&id) && self.ids.len() >= limit {
+                    return Some(id);
+                }
+                self.ids.insert(id);
+            }
+            // graphql-ws stops operations with stop, and graphql-transport-ws with complete
+            "stop" | "complete" => {
+                self.ids.remove(&id);
+            }
+            _ => {}
+        }
+        None
+    }
+
+    /// Tracks a message from the server, which ends its operation if it is complete or an error.
+    fn track_server_message(&mut self, message: &str) {
+        if let Some((message_type, id)) = message_type_and_id(message.as_bytes()) {
+            if message_type == "complete" || message_type == "error" {
+                self.ids.remove(&id);
+            }
+        }
+    }
+}
+
+/// Gets the type and operation id of a protocol message, or none if it is not for an operation.
+fn message_type_and_id(message: &[u8]) -> Option<(String, String)> {
+    let message = serde_json::from_slice::<serde_json::Value>(message).ok()?;
+    Some((
+        message["type"].as_str()?.to_string(),
+        message["id"].as_str()?.to_string(),
+    ))
+}
+
+/// Creates the error message of an operation which is rejected, as the connection has the maximum
+/// number of subscriptions.
+fn limit_exceeded_message(protocol: WebSocketProtocols, id: &str, limit: usize) -> String {
+    let error = serde_json::json!({
+        "message": format!("a connection can have at most {} subscriptions", limit),
+        "extensions": { "code": code::LIMIT_EXCEEDED },
+    });
+    // graphql-ws sends a single error, while graphql-transport-ws sends a list of errors
+    let payload = match protocol {
+        WebSocketProtocols::SubscriptionsTransportWS => error,
+        WebSocketProtocols::GraphQLWS => serde_json::json!([error]),
+    };
+    serde_json::json!({ "type": "error", "id": id, "payload": payload }).to_string()
+}
+
+impl SubscriptionActor {
+    /// Pings the client on an interval, disconnecting it if it stops responding.
+    fn send_heartbeats(&self, ctx: &mut WebsocketContext<Self>) {
+        ctx.run_interval(HEARTBEAT_INTERVAL, |act, ctx| {
+            if Instant::now().duration_since(act.last_heartbeat) > CLIENT_TIMEOUT {
+                ctx.stop();
+            }
+            ctx.ping(b"");
+        });
+    }
+
+    /// Sends protocol keepalive messages on an interval, so idle connections are not closed by
+    /// proxies which only consider data messages as activity.
+    fn send_keepalives(&self, ctx: &mut WebsocketContext<Self>) {
+        if let Some(keepalive_interval) = self.config.keepalive_interval {
+            // graphql-ws has a dedicated keepalive message, while graphql-transport-ws uses pings
+            let message = match self.protocol {
+                WebSocketProtocols::SubscriptionsTransportWS => r#"{"type":"ka"}"#,
+                WebSocketProtocols::GraphQLWS => r#"{"type":"ping"}"#,
+            };
+            ctx.run_interval(keepalive_interval, move |_, ctx| ctx.text(message));
+        }
+    }
+}
+
+impl Actor for SubscriptionActor {
+    type Context = WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        self.send_heartbeats(ctx);
+        self.send_keepalives(ctx);
+
+        let (tx, rx) = async_channel::unbounded();
+
+        WebSocket::new(self.schema.clone(), rx, self.protocol)
+            .into_actor(self)
+            .map(|response, act, ctx| match response {
+                WsMessage::Text(text) => {
+                    if act.config.max_subscriptions.is_some() {
+                        act.active.track_server_message(&text);
+                    }
+                    ctx.text(text)
+                }
+                WsMessage::Close(code, message) => ctx.close(Some(CloseReason {
+                    code: code.into(),
+                    description: Some(message),
+                })),
+            })
+            .finish()
+            .spawn(ctx);
+
+        self.messages = Some(tx);
+    }
+}
+
+impl StreamHandler<Result<Message, ProtocolError>> for SubscriptionActor {
+    fn handle(&mut self, message: Result<Message, ProtocolError>, ctx: &mut Self::Context) {
+        let message = match message {
+            Ok(message) => message,
+            Err(_) => {
+                ctx.stop();
+                return;
+            }
+        };
+
+        let message = match message {
+            Message::Ping(message) => {
+                self.last_heartbeat = Instant::now();
+                ctx.pong(&message);
+                None
+            }
+            Message::Pong(_) => {
+                self.last_heartbeat = Instant::now();
+                None
+            }
+            Message::Continuation(item) => match item {
+                Item::FirstText(bytes) | Item::FirstBinary(bytes) => {
+                    self.continuation = bytes.to_vec();
+                    None
+                }
+                Item::Continue(bytes) => {
+                    self.continuation.extend_from_slice(&bytes);
+                    None
+                }
+                Item::Last(bytes) => {
+                    self.continuation.extend_from_slice(&bytes);
+                    Some(std::mem::take(&mut self.continuation))
+                }
+            },
+            Message::Text(text) => Some(text.into_bytes().to_vec()),
+            Message::Binary(bytes) => Some(bytes.to_vec()),
+            Message::Close(_) => {
+                ctx.stop();
+                None
+            }
+            Message::Nop => None,
+        };
+
+        if let (Some(message), Some(limit)) = (&message, self.config.max_subscriptions) {
+            // operations past the limit are rejected rather than forwarded to the schema
+            if let Some(id) = self.active.track_client_message(message, limit) {
+                ctx.text(limit_exceeded_message(self.protocol, &id, limit));
+                return;
+            }
+        }
+
+        if let Some(message) = message {
+            let sender = self.messages.as_ref().unwrap().clone();
+            async move { sender.send(message).await }
+                .into_actor(self)
+                .map(|result, _, ctx| {
+                    if result.is_err() {
+                        ctx.stop();
+                    }
+                })
+                .spawn(ctx);
+        }
+    }
+}
+
+/// Unit tests for subscription websockets.
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_active_operations() {
+        let mut active = ActiveOperations::default();
+        let start = |id: &str| format!(r#"{{"type":"start","id":"{}","payload":{{}}}}"#, id);
+
+        // check that operations past the limit are rejected, without counting repeated ids
+        assert_eq!(active.track_client_message(start("1").as_bytes(), 2), None);
+        assert_eq!(active.track_client_message(start("1").as_bytes(), 2), None);
+        assert_eq!(active.track_client_message(start("2").as_bytes(), 2), None);
+        assert_eq!(
+            active.track_client_message(start("3").as_bytes(), 2),
+            Some("3".to_string())
+        );
+
+        // check that operations stopped by the client or completed by the server free the limit
+        assert_eq!(
+            active.track_client_message(br#"{"type":"stop","id":"1"}"#, 2),
+            None
+        );
+        assert_eq!(active.track_client_message(start("3").as_bytes(), 2), None);
+        active.track_server_message(r#"{"type":"complete","id":"2"}"#);
+        assert_eq!(
+            active.track_client_message(br#"{"type":"subscribe","id":"4","payload":{}}"#, 2),
+            None
+        );
+        assert_eq!(
+            active.track_client_message(start("5").as_bytes(), 2),
+            Some("5".to_string())
+        );
+
+        // check that messages which are not for operations are ignored
+        assert_eq!(
+            active.track_client_message(br#"{"type":"connection_init"}"#, 2),
+            None
+        );
+        assert_eq!(active.track_client_message(b"not json", 2), None);
+    }
+}
@@ -0,0 +1,69 @@
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::graphql::Clients;
+
+/// The time a dependency has to respond to a readiness check before it is not ready, so probes do
+/// not hang on a dependency which does not respond.
+const READINESS_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The readiness of a dependency of the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum DependencyStatus {
+    Ok,
+    Unavailable,
+}
+
+/// The readiness of the dependencies which are needed to serve traffic.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct Readiness {
+    pub(crate) postgres: DependencyStatus,
+    pub(crate) redis: DependencyStatus,
+}
+
+impl Readiness {
+    /// Whether every dependency is ready.
+    pub(crate) fn is_ready(&self) -> bool {
+        self.postgres == DependencyStatus::Ok && self.redis == DependencyStatus::Ok
+    }
+}
+
+/// Checks that a dependency responds in time, logging why it is unavailable otherwise.
+async fn check<E: std::fmt::Display>(
+    name: &str,
+    ping: impl std::future::Future<Output = Result<(), E>>,
+) -> DependencyStatus {
+    match tokio::time::timeout(READINESS_TIMEOUT, ping).await {
+        Ok(Ok(())) => DependencyStatus::Ok,
+        Ok(Err(e)) => {
+            log::warn!("{} is not ready: {}", name, e);
+            DependencyStatus::Unavailable
+        }
+        Err(_) => {
+            log::warn!("{} is not ready: timed out", name);
+            DependencyStatus::Unavailable
+        }
+    }
+}
+
+/// Checks whether Postgres and Redis can be queried, concurrently.
+pub(crate) async fn get_readiness(clients: &Clients) -> Readiness {
+    let postgres = check("postgres", async {
+        sqlx::query("select 1")
+            .execute(&*clients.postgres)
+            .await
+            .map(|_| ())
+    });
+    let redis = check("redis", async {
+        let mut connection = clients.redis.get_async_connection().await?;
+        redis::cmd("PING")
+            .query_async::<_, String>(&mut connection)
+            .await
+            .map(|_| ())
+    });
+    let (postgres, redis) = futures::join!(postgres, redis);
+
+    Readiness { postgres, redis }
+}
@@ -0,0 +1,82 @@
+use chrono::{SecondsFormat, Utc};
+use log::{LevelFilter, Log, Metadata, Record};
+
+/// The target of the query logs of sqlx, including slow queries.
+const QUERY_TARGET: &str = "sqlx::query";
+/// The maximum number of characters of logged queries, since queries can be very long.
+const MAX_QUERY_CHARS: usize = 1000;
+
+/// A logger which writes messages to stderr.
+struct StderrLogger;
+
+/// The logger of the application.
+static LOGGER: StderrLogger = StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if self.enabled(record.metadata()) {
+            eprintln!(
+                "{} {:<5} {}: {}",
+                Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+                record.level(),
+                record.target(),
+                format_message(record.target(), &record.args().to_string())
+            );
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Writes log messages up to the given level to stderr.
+pub(crate) fn init(level: LevelFilter) {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(level);
+    }
+}
+
+/// Formats a message, truncating the text of queries. Queries are logged by sqlx without their
+/// bound parameters, so parameter values are never logged.
+fn format_message(target: &str, message: &str) -> String {
+    if target == QUERY_TARGET && message.chars().count() > MAX_QUERY_CHARS {
+        format!(
+            "{} … (truncated)",
+            message.chars().take(MAX_QUERY_CHARS).collect::<String>()
+        )
+    } else {
+        message.to_string()
+    }
+}
+
+/// Unit tests for logging.
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_format_message() {
+        assert_eq!(format_message("inv_track", "started"), "started");
+        assert_eq!(
+            format_message(QUERY_TARGET, "select 1; rows: 1, elapsed: 2s"),
+            "select 1; rows: 1, elapsed: 2s"
+        );
+    }
+
+    #[test]
+    fn test_format_message_truncates_queries() {
+        let query = format!("select {} from items", "x, ".repeat(MAX_QUERY_CHARS));
+        let message = format_message(QUERY_TARGET, &query);
+        assert!(message.ends_with(" … (truncated)"));
+        assert_eq!(
+            message.chars().count(),
+            MAX_QUERY_CHARS + " … (truncated)".chars().count()
+        );
+
+        // other messages are not truncated
+        assert_eq!(format_message("inv_track", &query), query);
+    }
+}
@@ -7,19 +7,59 @@
 extern crate derive_more;
 
 mod batcher;
+mod config;
 mod db;
+mod export;
 mod graphql;
+mod health;
+mod logging;
+mod metrics;
 mod model;
+mod retry;
+mod seed;
 mod store;
+mod telemetry;
 
-use std::env;
+use std::process;
 use std::sync::Arc;
 
-use actix_web::{http, middleware, web, App, Error, HttpRequest, HttpResponse, HttpServer};
-use async_graphql::http::GraphQLPlaygroundConfig;
-use async_graphql_actix_web::{GraphQLRequest, GraphQLResponse, GraphQLSubscription};
+use actix_web::error::PayloadError;
+use actix_web::http::header::{EntityTag, Header, HeaderValue, IfNoneMatch, CACHE_CONTROL, ETAG};
+use actix_web::{
+    http, middleware, web, App, Error, HttpRequest, HttpResponse, HttpServer, Responder,
+};
+use async_graphql::http::{GraphQLPlaygroundConfig, MultipartOptions};
+use async_graphql_actix_web::{GraphQLRequest, GraphQLResponse};
+use futures::StreamExt;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
+use crate::config::Config;
+use crate::graphql::allowlist::GetAllowlist;
+use crate::graphql::auth::AuthConfig;
+use crate::graphql::cache::{self, CacheConfig};
+use crate::graphql::fanout::{Fanout, FanoutConfig};
+use crate::graphql::source::ClientSource;
+use crate::graphql::sse;
+use crate::graphql::websocket::{self, SubscriptionConfig};
 use crate::graphql::{AppContext, AppSchema, Clients};
+use crate::model::config::ModelConfig;
+use crate::model::item::{self, ItemId};
+use crate::model::location;
+
+/// The format of the access log, which is the default format with the W3C `traceparent` header,
+/// so requests can be correlated with the traces of their callers.
+const ACCESS_LOG_FORMAT: &str =
+    r#"%a "%r" %s %b "%{Referer}i" "%{User-Agent}i" %T traceparent="%{traceparent}i""#;
+
+/// The variable which limits the size of GraphQL request bodies.
+const MAX_BODY_BYTES_VAR: &str = "GRAPHQL_MAX_BODY_BYTES";
+/// The variable which limits the size of the bodies of bulk imports.
+const IMPORT_MAX_BODY_BYTES_VAR: &str = "GRAPHQL_IMPORT_MAX_BODY_BYTES";
+/// The header of exports with the time they were generated.
+const GENERATED_AT_HEADER: &str = "X-Generated-At";
+/// The number of lines of a stock snapshot which are buffered ahead of the client.
+const STOCK_SNAPSHOT_BUFFER_SIZE: usize = 64;
 
 /// The route for the GraphQL playground.
 async fn playground_route() -> Result<HttpResponse, Error> {
@@ -31,98 +71,635 @@ async fn playground_route() -> Result<HttpResponse, Error> {
         .body(source))
 }
 
-/// The route for the GraphQL endpoint.
-async fn graphql_route(req: GraphQLRequest, schema: web::Data<AppSchema>) -> GraphQLResponse {
-    schema.execute(req.into_inner()).await.into()
+/// Responds with a GraphQL response, or a batch of responses, replacing its cache control.
+fn graphql_response(
+    http_req: &HttpRequest,
+    response: impl Into<GraphQLResponse>,
+    cache_control: &str,
+) -> HttpResponse {
+    let mut http_resp = response.into().respond_to(http_req);
+    if let Ok(cache_control) = HeaderValue::from_str(cache_control) {
+        http_resp.headers_mut().insert(CACHE_CONTROL, cache_control);
+    }
+    http_resp
+}
+
+/// Parses a GraphQL request from a body, which is limited by the payload config of the route.
+async fn graphql_body_request(
+    http_req: &HttpRequest,
+    body: Result<web::Bytes, Error>,
+) -> Result<async_graphql::Request, Error> {
+    graphql_batch_body_request(http_req, body, MAX_BODY_BYTES_VAR)
+        .await?
+        .into_single()
+        .map_err(actix_web::error::ErrorBadRequest)
+}
+
+/// Parses a GraphQL request, or a batch of requests if the body is a JSON array, from a body,
+/// which is limited by the payload config of the route, set by the given variable.
+async fn graphql_batch_body_request(
+    http_req: &HttpRequest,
+    body: Result<web::Bytes, Error>,
+    limit_var: &str,
+) -> Result<async_graphql::BatchRequest, Error> {
+    let body = body.map_err(|e| match e.as_error::<PayloadError>() {
+        Some(PayloadError::Overflow) => actix_web::error::ErrorPayloadTooLarge(format!(
+            "request body is larger than {}",
+            limit_var
+        )),
+        _ => e,
+    })?;
+    let content_type = http_req
+        .headers()
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string());
+    let options = http_req
+        .app_data::<MultipartOptions>()
+        .cloned()
+        .unwrap_or_default();
+
+    async_graphql::http::receive_batch_body(content_type, body.as_ref(), options)
+        .await
+        .map_err(actix_web::error::ErrorBadRequest)
+}
+
+/// The route for the GraphQL endpoint, whose responses are not cached. A JSON array of requests is
+/// executed as a batch, responding with an array of their responses in order.
+async fn graphql_route(
+    http_req: HttpRequest,
+    body: Result<web::Bytes, Error>,
+    schema: web::Data<AppSchema>,
+    auth_config: web::Data<AuthConfig>,
+) -> Result<HttpResponse, Error> {
+    let req = graphql_batch_body_request(&http_req, body, MAX_BODY_BYTES_VAR).await?;
+    Ok(execute_graphql_batch(&http_req, req, &schema, &auth_config).await)
+}
+
+/// The route for bulk imports, whose bodies are limited separately so they can be larger than
+/// those of other operations, which are rejected.
+async fn graphql_import_route(
+    http_req: HttpRequest,
+    body: Result<web::Bytes, Error>,
+    schema: web::Data<AppSchema>,
+    auth_config: web::Data<AuthConfig>,
+) -> Result<HttpResponse, Error> {
+    let req = graphql_batch_body_request(&http_req, body, IMPORT_MAX_BODY_BYTES_VAR).await?;
+    if !req.iter().all(graphql::import::is_import) {
+        return Err(actix_web::error::ErrorBadRequest(
+            "only importItems mutations can be sent to /graphql/import",
+        ));
+    }
+    Ok(execute_graphql_batch(&http_req, req, &schema, &auth_config).await)
+}
+
+/// Executes a GraphQL request or batch of requests with the role and source of the HTTP request,
+/// whose responses are not cached.
+async fn execute_graphql_batch(
+    http_req: &HttpRequest,
+    mut req: async_graphql::BatchRequest,
+    schema: &AppSchema,
+    auth_config: &AuthConfig,
+) -> HttpResponse {
+    if let Some(role) = auth_config.role(http_req) {
+        req = req.data(role);
+    }
+    if let Some(source) = ClientSource::from_request(http_req) {
+        req = req.data(source);
+    }
+    let response = schema.execute_batch(req).await;
+    graphql_response(http_req, response, cache::NO_STORE)
+}
+
+/// The standard parameters of GraphQL queries over GET which async-graphql does not read.
+#[derive(Deserialize)]
+struct GetParams {
+    #[serde(rename = "operationName")]
+    operation_name: Option<String>,
+}
+
+/// The route for GraphQL queries over GET, which are restricted to the allowlist if enabled, and
+/// cached if they only select cacheable fields.
+async fn graphql_get_route(
+    http_req: HttpRequest,
+    req: GraphQLRequest,
+    params: web::Query<GetParams>,
+    schema: web::Data<AppSchema>,
+    allowlist: web::Data<GetAllowlist>,
+    cache_config: web::Data<CacheConfig>,
+    auth_config: web::Data<AuthConfig>,
+) -> Result<HttpResponse, Error> {
+    let mut req = req.into_inner();
+    if let Some(role) = auth_config.role(&http_req) {
+        req = req.data(role);
+    }
+    if let Some(source) = ClientSource::from_request(&http_req) {
+        req = req.data(source);
+    }
+    // async-graphql only reads `operation_name` from the query string
+    if req.operation_name.is_none() {
+        req.operation_name = params.into_inner().operation_name;
+    }
+    if !allowlist.allows(&req) {
+        return Err(actix_web::error::ErrorForbidden(
+            "query is not allowed over GET, use POST instead",
+        ));
+    }
+
+    let cache_control = cache_config.cache_control(&req);
+    let response = schema.execute(req).await;
+    // errors may be transient, so they are not cached
+    let cache_control = if response.is_ok() {
+        cache_control.as_str()
+    } else {
+        cache::NO_STORE
+    };
+    Ok(graphql_response(&http_req, response, cache_control))
 }
 
-/// The route for the GraphQL subscriptions.
+/// The route for the GraphQL subscriptions, which are sent over server-sent events to requests
+/// which accept them instead of upgrading to a websocket.
 async fn subscription_route(
     req: HttpRequest,
     payload: web::Payload,
     schema: web::Data<AppSchema>,
+    config: web::Data<SubscriptionConfig>,
 ) -> Result<HttpResponse, Error> {
-    GraphQLSubscription::new(async_graphql::Schema::clone(&*schema)).start(&req, payload)
+    if sse::accepts_event_stream(&req) {
+        let request = async_graphql::http::parse_query_string(req.query_string())
+            .map_err(actix_web::error::ErrorBadRequest)?;
+        return Ok(sse::start(
+            async_graphql::Schema::clone(&*schema),
+            **config,
+            request,
+        ));
+    }
+
+    websocket::start(
+        async_graphql::Schema::clone(&*schema),
+        **config,
+        &req,
+        payload,
+    )
+}
+
+/// The route for the GraphQL subscriptions over server-sent events, with the request in the query
+/// string of GET requests, or in the body of POST requests.
+async fn subscription_sse_route(
+    http_req: HttpRequest,
+    body: Result<web::Bytes, Error>,
+    schema: web::Data<AppSchema>,
+    config: web::Data<SubscriptionConfig>,
+) -> Result<HttpResponse, Error> {
+    let request = if http_req.method() == http::Method::GET {
+        async_graphql::http::parse_query_string(http_req.query_string())
+            .map_err(actix_web::error::ErrorBadRequest)?
+    } else {
+        graphql_body_request(&http_req, body).await?
+    };
+    Ok(sse::start(
+        async_graphql::Schema::clone(&*schema),
+        **config,
+        request,
+    ))
+}
+
+/// Computes a strong entity tag from the serialized representation of a value.
+fn entity_tag<T: Serialize>(value: &T) -> EntityTag {
+    let bytes = serde_json::to_vec(value).expect("unable to serialize value");
+    EntityTag::new_strong(hex::encode(Sha256::digest(&bytes)))
+}
+
+/// The route for retrieving a single item as JSON, supporting conditional requests.
+async fn item_route(
+    req: HttpRequest,
+    id: web::Path<ItemId>,
+    context: web::Data<AppContext>,
+) -> Result<HttpResponse, Error> {
+    let item = match item::get_item(&context, id.into_inner()).await {
+        Ok(item) => item,
+        Err(_) => return Ok(HttpResponse::NotFound().finish()),
+    };
+
+    let etag = entity_tag(&item);
+    let not_modified = match IfNoneMatch::parse(&req) {
+        Ok(IfNoneMatch::Any) => true,
+        Ok(IfNoneMatch::Items(tags)) => tags.iter().any(|tag| tag.weak_eq(&etag)),
+        Err(_) => false,
+    };
+
+    if not_modified {
+        Ok(HttpResponse::NotModified()
+            .insert_header((ETAG, etag.to_string()))
+            .finish())
+    } else {
+        Ok(HttpResponse::Ok()
+            .insert_header((ETAG, etag.to_string()))
+            .json(item))
+    }
+}
+
+/// The route for exporting all items with their quantities as an XLSX workbook.
+async fn export_items_xlsx_route(context: web::Data<AppContext>) -> Result<HttpResponse, Error> {
+    let items = item::get_item_exports(&context).await.map_err(|e| {
+        actix_web::error::ErrorInternalServerError(async_graphql::Error::from(e).message)
+    })?;
+    let workbook =
+        export::items_xlsx(&items).map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok()
+        .content_type(export::XLSX_CONTENT_TYPE)
+        .insert_header((
+            http::header::CONTENT_DISPOSITION,
+            r#"attachment; filename="items.xlsx""#,
+        ))
+        .body(workbook))
+}
+
+/// The route for exporting the stock of every item at each location as CSV, for reconciling against
+/// a physical count, which streams the lines as they are read from the database.
+async fn export_stock_snapshot_csv_route(context: web::Data<AppContext>) -> HttpResponse {
+    let context = context.into_inner();
+    let generated_at = chrono::Utc::now();
+    // the rows borrow the context, so they are read in a task which owns it, until the client
+    // disconnects
+    let (sender, receiver) = async_channel::bounded(STOCK_SNAPSHOT_BUFFER_SIZE);
+    actix_web::rt::spawn(async move {
+        if sender
+            .send(Ok(web::Bytes::from(export::stock_snapshot_csv_header())))
+            .await
+            .is_err()
+        {
+            return;
+        }
+        let mut rows = location::get_stock_snapshot(&context);
+        while let Some(row) = rows.next().await {
+            // `io::Error::other` needs Rust 1.74, newer than the 1.66 the images are built with
+            #[allow(unknown_lints, clippy::io_other_error)]
+            let line = row
+                .map(|row| web::Bytes::from(export::stock_snapshot_csv_line(&row)))
+                .map_err(|e| {
+                    std::io::Error::new(
+                        std::io::ErrorKind::Other,
+                        async_graphql::Error::from(e).message,
+                    )
+                });
+            // an error aborts the response, so a partial snapshot is not mistaken for a full one
+            let failed = line.is_err();
+            if sender.send(line).await.is_err() || failed {
+                return;
+            }
+        }
+    });
+
+    HttpResponse::Ok()
+        .content_type(export::CSV_CONTENT_TYPE)
+        .insert_header((
+            http::header::CONTENT_DISPOSITION,
+            r#"attachment; filename="stock-snapshot.csv""#,
+        ))
+        .insert_header((GENERATED_AT_HEADER, generated_at.to_rfc3339()))
+        .streaming(receiver)
+}
+
+/// The route for the status of database migrations.
+async fn migrations_route(context: web::Data<AppContext>) -> Result<HttpResponse, Error> {
+    let status = db::get_migration_status(&context.clients.postgres)
+        .await
+        .map_err(actix_web::error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok().json(status))
+}
+
+/// The route for the metrics of the batching loaders, in the Prometheus text exposition format.
+async fn metrics_route() -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(metrics::PROMETHEUS_CONTENT_TYPE)
+        .body(metrics::render())
+}
+
+/// The route for liveness probes, which responds as long as the server is running, without checking
+/// its dependencies, so an outage of a dependency does not restart the server.
+async fn livez_route() -> HttpResponse {
+    HttpResponse::Ok().content_type("text/plain").body("ok")
+}
+
+/// The route for readiness probes, which responds with the readiness of Postgres and Redis, and
+/// `503 Service Unavailable` if either is not ready, so traffic is not routed to the server.
+async fn readyz_route(context: web::Data<AppContext>) -> HttpResponse {
+    let readiness = health::get_readiness(&context.clients).await;
+    if readiness.is_ready() {
+        HttpResponse::Ok().json(readiness)
+    } else {
+        HttpResponse::ServiceUnavailable().json(readiness)
+    }
+}
+
+/// Gets the context for the application, whose database must already be migrated.
+#[cfg(test)]
+async fn get_context(config: &Config) -> AppContext {
+    get_context_from_clients(get_clients(config).await, config).await
 }
 
-/// Gets the context for the application.
-async fn get_context() -> AppContext {
+/// Gets the clients of the application, without preparing the database.
+async fn get_clients(config: &Config) -> Clients {
     // create the redis client and db pool, storing them in the context
     let redis = Arc::new(
-        store::get_client()
+        store::get_client(config)
             .await
             .expect("unable to connect to redis"),
     );
-    let postgres = Arc::new(db::get_pool().await);
+    let postgres = Arc::new(db::get_pool(config).await);
+
+    let fanout = Arc::new(Fanout::new(
+        redis.clone(),
+        FanoutConfig::from_config(config),
+    ));
 
-    let clients = Clients { postgres, redis };
+    Clients {
+        postgres,
+        redis,
+        fanout,
+    }
+}
 
+/// Gets the context for the application from its clients, detecting the features of the database,
+/// so its migrations must already be run.
+async fn get_context_from_clients(clients: Clients, config: &Config) -> AppContext {
     let mut loaders = anymap2::Map::new();
     batcher::register_loaders(&clients, &mut loaders);
 
+    let config = ModelConfig::new(&clients.postgres, config).await;
+
     AppContext {
         clients,
         loaders: Arc::new(loaders),
+        config: Arc::new(config),
     }
 }
 
 /// Entrypoint for the actix web application.
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
-    let context = get_context().await;
-    let schema = graphql::schema_builder().data(context).finish();
+    // check all of the configuration before connecting to anything
+    let config = Config::from_env().unwrap_or_else(|e| {
+        eprintln!("{}", e);
+        process::exit(1);
+    });
+    logging::init(config.log_level);
+    if config.tracing_otlp_enabled {
+        telemetry::init().unwrap_or_else(|e| {
+            eprintln!("unable to export traces: {}", e);
+            process::exit(1);
+        });
+    }
+
+    let clients = get_clients(&config).await;
+    // the model configuration detects functions created by migrations, so they are run first
+    db::prepare_migrations(&clients.postgres, &config).await;
+    let context = get_context_from_clients(clients, &config).await;
+    seed::seed_on_startup(&context.clients.postgres, &config).await;
+    // deliver the events which were enqueued but not published, such as after a crash
+    actix_web::rt::spawn(model::outbox::relay(context.clients.clone()));
+    let schema = graphql::schema_builder_from_config(&config)
+        .data(context.clone())
+        .finish();
+    let subscription_config = SubscriptionConfig::from_config(&config);
+    let get_allowlist = GetAllowlist::from_config(&config);
+    let cache_config = CacheConfig::from_config(&config);
+    let auth_config = AuthConfig::from_config(&config);
+    let max_body_bytes = config.graphql_max_body_bytes;
+    let import_max_body_bytes = config.graphql_import_max_body_bytes;
+    let schema_hash = graphql::schema_hash(&schema);
 
     HttpServer::new(move || {
         App::new()
             .app_data(web::Data::new(schema.clone()))
+            .app_data(web::Data::new(context.clone()))
+            .app_data(web::Data::new(subscription_config))
+            .app_data(web::Data::new(get_allowlist.clone()))
+            .app_data(web::Data::new(cache_config.clone()))
+            .app_data(web::Data::new(auth_config.clone()))
             .wrap(middleware::Compress::default())
-            .wrap(middleware::Logger::default())
+            .wrap(middleware::Logger::new(ACCESS_LOG_FORMAT))
             .wrap(
                 actix_cors::Cors::default()
                     .allow_any_origin()
                     .allowed_methods(vec!["POST", "GET"])
-                    .allowed_headers(vec![http::header::ACCEPT, http::header::CONTENT_TYPE])
+                    .allowed_headers(vec![
+                        http::header::ACCEPT,
+                        http::header::CONTENT_TYPE,
+                        http::header::HeaderName::from_static("traceparent"),
+                        http::header::HeaderName::from_static("tracestate"),
+                    ])
+                    .expose_headers(vec![graphql::SCHEMA_HASH_HEADER])
                     .max_age(3600),
             )
+            .wrap_fn(telemetry::trace_request)
             .service(
                 web::resource("/graphql")
+                    .app_data(web::PayloadConfig::new(max_body_bytes))
+                    .wrap(
+                        middleware::DefaultHeaders::new()
+                            .add((graphql::SCHEMA_HASH_HEADER, schema_hash.as_str())),
+                    )
                     .route(web::post().to(graphql_route))
-                    .route(web::get().to(graphql_route)),
+                    .route(web::get().to(graphql_get_route)),
+            )
+            .service(
+                web::resource("/graphql/import")
+                    .app_data(web::PayloadConfig::new(import_max_body_bytes))
+                    .wrap(
+                        middleware::DefaultHeaders::new()
+                            .add((graphql::SCHEMA_HASH_HEADER, schema_hash.as_str())),
+                    )
+                    .route(web::post().to(graphql_import_route)),
             )
             .service(web::resource("/subscriptions").route(web::get().to(subscription_route)))
+            .service(
+                web::resource("/subscriptions/sse")
+                    .app_data(web::PayloadConfig::new(max_body_bytes))
+                    .route(web::post().to(subscription_sse_route))
+                    .route(web::get().to(subscription_sse_route)),
+            )
             .service(web::resource("/playground").route(web::get().to(playground_route)))
+            .service(web::resource("/items/{id}.json").route(web::get().to(item_route)))
+            .service(web::resource("/migrations").route(web::get().to(migrations_route)))
+            .service(web::resource("/metrics").route(web::get().to(metrics_route)))
+            .service(web::resource("/livez").route(web::get().to(livez_route)))
+            .service(web::resource("/readyz").route(web::get().to(readyz_route)))
+            .service(
+                web::resource("/export/items.xlsx").route(web::get().to(export_items_xlsx_route)),
+            )
+            .service(
+                web::resource("/export/stock-snapshot.csv")
+                    .route(web::get().to(export_stock_snapshot_csv_route)),
+            )
             .default_service(web::route().to(HttpResponse::NotFound))
     })
-    .bind(format!(
-        "{}:{}",
-        env::var("ACTIX_ADDRESS").expect("ACTIX_ADDRESS must be set"),
-        env::var("PORT").expect("PORT must be set")
-    ))?
+    .bind((config.actix_address.as_str(), config.port))?
     .run()
-    .await
+    .await?;
+
+    telemetry::shutdown();
+    Ok(())
 }
 
 /// Integration level tests.
 #[cfg(test)]
+#[allow(clippy::unnecessary_mut_passed)]
 mod test {
     use super::*;
 
     use actix_web::test;
 
+    /// The bearer token of administrators of the test server.
+    const TEST_ADMIN_TOKEN: &str = "test-admin-token";
+
     /// Macro to set up the test server.
     macro_rules! test_server {
         () => {{
-            let context = get_context().await;
-            let schema = graphql::schema_builder().data(context).finish();
+            let context = get_context(&Config::from_env().unwrap()).await;
+            let schema = graphql::schema_builder().data(context.clone()).finish();
             test::init_service(
                 App::new()
                     .app_data(web::Data::new(schema.clone()))
-                    .service(web::resource("/graphql").route(web::post().to(graphql_route))),
+                    .app_data(web::Data::new(context.clone()))
+                    .app_data(web::Data::new(AuthConfig {
+                        admin_token: Some(TEST_ADMIN_TOKEN.to_string()),
+                    }))
+                    .service(
+                        web::resource("/graphql")
+                            .wrap(middleware::DefaultHeaders::new().add((
+                                graphql::SCHEMA_HASH_HEADER,
+                                graphql::schema_hash(&schema).as_str(),
+                            )))
+                            .route(web::post().to(graphql_route)),
+                    )
+                    .service(web::resource("/items/{id}.json").route(web::get().to(item_route)))
+                    .service(web::resource("/migrations").route(web::get().to(migrations_route)))
+                    .service(web::resource("/metrics").route(web::get().to(metrics_route)))
+                    .service(web::resource("/livez").route(web::get().to(livez_route)))
+                    .service(web::resource("/readyz").route(web::get().to(readyz_route)))
+                    .service(
+                        web::resource("/export/items.xlsx")
+                            .route(web::get().to(export_items_xlsx_route)),
+                    )
+                    .service(
+                        web::resource("/export/stock-snapshot.csv")
+                            .route(web::get().to(export_stock_snapshot_csv_route)),
+                    ),
             )
             .await
         }};
     }
 
+    /// Creates a transaction of an item with an explicit date, returning its id, so tests which
+    /// check the order of transactions do not depend on when the transactions are created.
+    async fn create_dated_transaction(
+        schema: &AppSchema,
+        item_id: i64,
+        quantity: i32,
+        transaction_date: &str,
+    ) -> i64 {
+        let resp = schema
+            .execute(
+                async_graphql::Request::new(
+                    r#"mutation ($transaction: InsertableTransaction!) {
+                        createTransaction(transaction: $transaction) { id }
+                    }"#,
+                )
+                .variables(async_graphql::Variables::from_json(
+                    serde_json::json!({
+                        "transaction": {
+                            "itemId": item_id,
+                            "quantity": quantity,
+                            "transactionDate": transaction_date
+                        }
+                    }),
+                )),
+            )
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        resp["createTransaction"]["id"].as_i64().unwrap()
+    }
+
+    /// Waits for a channel to have the given number of subscribers in redis, returning whether it
+    /// has them within a few seconds, as subscriptions are dropped once their connections close.
+    async fn wait_for_redis_subscribers(
+        context: &AppContext,
+        channel_name: &str,
+        subscribers: i64,
+    ) -> bool {
+        let mut redis_conn = context.clients.redis.get_async_connection().await.unwrap();
+        for _ in 0..50 {
+            let (_, count): (String, i64) = redis::cmd("PUBSUB")
+                .arg("NUMSUB")
+                .arg(channel_name)
+                .query_async(&mut redis_conn)
+                .await
+                .unwrap();
+            if count == subscribers {
+                return true;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+        }
+        false
+    }
+
+    #[actix_rt::test]
+    async fn test_schema_hash_header() {
+        let app = test_server!();
+        let expected = graphql::schema_hash(&graphql::schema_builder().finish());
+        // check that the header is the hash of the schema on successful and failed requests
+        for query in ["{ __typename }", "{ notAField }"] {
+            let req = test::TestRequest::post()
+                .uri("/graphql")
+                .insert_header(http::header::ContentType::json())
+                .set_json(serde_json::json!({ "query": query }))
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(
+                resp.headers().get(graphql::SCHEMA_HASH_HEADER).unwrap(),
+                expected.as_str()
+            );
+        }
+        assert_eq!(
+            expected,
+            graphql::schema_hash(&graphql::schema_builder().finish())
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_batch_request() {
+        let app = test_server!();
+        // check that a batch of operations is answered with their responses in order
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!([
+                { "query": "query First { __typename }" },
+                { "query": "query Second($id: ItemId!) { item(id: $id) { id } }", "variables": { "id": -1 } }
+            ]))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        let responses = resp.as_array().unwrap();
+        assert_eq!(responses.len(), 2);
+        assert_eq!(responses[0]["data"]["__typename"], "RootQuery");
+        assert_eq!(responses[1]["errors"][0]["extensions"]["code"], "NOT_FOUND");
+
+        // check that a single operation is still answered with a single response
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({ "query": "{ __typename }" }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(resp["data"]["__typename"], "RootQuery");
+    }
+
     #[actix_rt::test]
     async fn test_empty_item_name() {
         let mut app = test_server!();
@@ -134,7 +711,7 @@ mod test {
             }))
             .to_request();
         let resp: serde_json::value::Value = test::call_and_read_body_json(&mut app, req).await;
-        assert!(!resp["errors"][0]["message"].is_null());
+        assert_eq!(resp["errors"][0]["extensions"]["code"], "VALIDATION");
     }
 
     #[actix_rt::test]
@@ -151,7 +728,48 @@ mod test {
                 .to_request();
             resp = test::call_and_read_body_json(&mut app, req).await;
         }
-        assert!(!resp["errors"][0]["message"].is_null());
+        assert_eq!(resp["errors"][0]["extensions"]["code"], "CONFLICT");
+    }
+
+    #[actix_rt::test]
+    async fn test_duplicate_item_sku_concurrent() {
+        let schema = graphql::schema_builder()
+            .data(get_context(&Config::from_env().unwrap()).await)
+            .finish();
+        let sku = format!("RACE-{}", chrono::Utc::now().timestamp_nanos());
+
+        // create items with the same sku from two tasks, so both can pass the uniqueness check
+        let create_item = || {
+            let schema = schema.clone();
+            let query = format!(
+                r#"mutation {{ createItem(item: {{ name: "TestItem", sku: "{}" }}) {{ id }} }}"#,
+                sku
+            );
+            actix_rt::spawn(async move { schema.execute(query).await })
+        };
+        let (first, second) = futures::join!(create_item(), create_item());
+        let mut responses = vec![first.unwrap(), second.unwrap()];
+        responses.sort_by_key(|resp| !resp.errors.is_empty());
+
+        // check that one item is created, and the other fails with a sku conflict
+        assert!(responses[0].errors.is_empty(), "{:?}", responses[0].errors);
+        let extensions = responses[1].errors[0].extensions.as_ref().unwrap();
+        assert_eq!(
+            extensions.get("code"),
+            Some(&async_graphql::Value::from("CONFLICT"))
+        );
+        assert_eq!(
+            extensions.get("itemId"),
+            Some(&async_graphql::Value::from(format!(
+                "sku {:?} not unique",
+                sku
+            )))
+        );
+
+        let id = responses.remove(0).data.into_json().unwrap()["createItem"]["id"].clone();
+        schema
+            .execute(format!("mutation {{ deleteItem(id: {}) {{ id }} }}", id))
+            .await;
     }
 
     #[actix_rt::test]
@@ -161,7 +779,132 @@ mod test {
             "query": r#"mutation { createTransaction(transaction: { itemId: 0, quantity: 10 }) { id } }"#
         })).to_request();
         let resp: serde_json::value::Value = test::call_and_read_body_json(&mut app, req).await;
-        assert!(!resp["errors"][0]["message"].is_null());
+        assert_eq!(resp["errors"][0]["extensions"]["code"], "VALIDATION");
+    }
+
+    #[actix_rt::test]
+    async fn test_transaction_item_deleted_concurrently() {
+        let context = get_context(&Config::from_env().unwrap()).await;
+        let schema = graphql::schema_builder().data(context.clone()).finish();
+
+        // create a test item
+        let resp = schema
+            .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let item_id = resp["createItem"]["id"].as_i64().unwrap();
+
+        // hold the lock of the quantity of the item, so the transaction is validated and then
+        // waits to be inserted until the item is deleted
+        let mut lock = context.clients.postgres.begin().await.unwrap();
+        sqlx::query("select pg_advisory_xact_lock($1)")
+            .bind(item_id)
+            .execute(&mut lock)
+            .await
+            .unwrap();
+        let create = schema.execute(format!(
+            r#"mutation {{ createTransaction(transaction: {{ itemId: {}, quantity: 10 }}) {{ id }} }}"#,
+            item_id
+        ));
+        let delete = async move {
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+            sqlx::query("delete from items where id = $1")
+                .bind(item_id as i32)
+                .execute(&mut lock)
+                .await
+                .unwrap();
+            lock.commit().await.unwrap();
+        };
+        let (resp, _) = futures::join!(create, delete);
+
+        // check that the error is the same as if the item did not exist when it was validated
+        let resp = resp.into_result().unwrap_err();
+        let extensions = resp[0].extensions.as_ref().unwrap();
+        assert_eq!(
+            extensions.get("code"),
+            Some(&async_graphql::Value::from("VALIDATION"))
+        );
+        assert_eq!(
+            extensions.get("itemId"),
+            Some(&async_graphql::Value::from(format!(
+                "item with id ItemId({}) not found",
+                item_id
+            )))
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_transfer_location_deleted_concurrently() {
+        let context = get_context(&Config::from_env().unwrap()).await;
+        let schema = graphql::schema_builder().data(context.clone()).finish();
+
+        // create a test item, and test locations to transfer it between
+        let resp = schema
+            .execute(
+                r#"mutation {
+                    createItem(item: { name: "TestItem" }) { id }
+                    from: createLocation(location: { name: "TestLocation" }) { id }
+                    to: createLocation(location: { name: "TestLocation" }) { id }
+                }"#,
+            )
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let item_id = resp["createItem"]["id"].as_i64().unwrap();
+        let from_location_id = resp["from"]["id"].as_i64().unwrap();
+        let to_location_id = resp["to"]["id"].as_i64().unwrap();
+
+        // hold the lock of the quantity of the item, so the transfer is validated and then waits
+        // to be inserted until the location it is transferred to is deleted
+        let mut lock = context.clients.postgres.begin().await.unwrap();
+        sqlx::query("select pg_advisory_xact_lock($1)")
+            .bind(item_id)
+            .execute(&mut lock)
+            .await
+            .unwrap();
+        let transfer = schema.execute(format!(
+            r#"mutation {{ transferStock(transfer: {{ itemId: {}, fromLocationId: {}, toLocationId: {}, quantity: 1 }}) {{ from {{ id }} }} }}"#,
+            item_id, from_location_id, to_location_id
+        ));
+        let delete = async move {
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+            sqlx::query("delete from locations where id = $1")
+                .bind(to_location_id as i32)
+                .execute(&mut lock)
+                .await
+                .unwrap();
+            lock.commit().await.unwrap();
+        };
+        let (resp, _) = futures::join!(transfer, delete);
+
+        // check that the error is the same as if the location did not exist when it was validated
+        let resp = resp.into_result().unwrap_err();
+        let extensions = resp[0].extensions.as_ref().unwrap();
+        assert_eq!(
+            extensions.get("code"),
+            Some(&async_graphql::Value::from("VALIDATION"))
+        );
+        assert_eq!(
+            extensions.get("locationId"),
+            Some(&async_graphql::Value::from(format!(
+                "location with id Some(LocationId({})) not found",
+                to_location_id
+            )))
+        );
+
+        schema
+            .execute(format!(
+                "mutation {{ deleteItem(id: {}) {{ id }} deleteLocation(id: {}) {{ id }} }}",
+                item_id, from_location_id
+            ))
+            .await;
     }
 
     #[actix_rt::test]
@@ -192,7 +935,7 @@ mod test {
             }))
             .to_request();
         let resp: serde_json::value::Value = test::call_and_read_body_json(&mut app, req).await;
-        assert!(!resp["errors"][0]["message"].is_null());
+        assert_eq!(resp["errors"][0]["extensions"]["code"], "VALIDATION");
     }
 
     #[actix_rt::test]
@@ -227,9 +970,11 @@ mod test {
             .set_json(serde_json::json!({
                 "query":
                     format!(
-                        "{}{}{}",
+                        "{}{}{}{}{}",
                         r#"mutation { createTransaction(transaction: { itemId: "#,
                         item_id,
+                        r#", locationId: "#,
+                        location_id,
                         r#", quantity: 10 }) { id } }"#
                     )
             }))
@@ -301,6 +1046,7160 @@ mod test {
             }))
             .to_request();
         let resp: serde_json::value::Value = test::call_and_read_body_json(&mut app, req).await;
-        assert_eq!(resp["errors"][0]["message"].as_str().unwrap(), "not found");
+        assert_eq!(resp["errors"][0]["extensions"]["code"], "NOT_FOUND");
+    }
+
+    #[actix_rt::test]
+    async fn test_item_etag_not_modified() {
+        let app = test_server!();
+        // create a test item
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#
+            }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        let id = resp["data"]["createItem"]["id"].as_i64().unwrap();
+
+        // fetch the item and its entity tag
+        let req = test::TestRequest::get()
+            .uri(&format!("/items/{}.json", id))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        let etag = resp.headers().get(ETAG).unwrap().clone();
+
+        // fetch the item again with a matching entity tag
+        let req = test::TestRequest::get()
+            .uri(&format!("/items/{}.json", id))
+            .insert_header((http::header::IF_NONE_MATCH, etag.clone()))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::NOT_MODIFIED);
+        assert_eq!(resp.headers().get(ETAG).unwrap(), &etag);
+    }
+
+    #[actix_rt::test]
+    async fn test_misspelled_input_field() {
+        let app = test_server!();
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": r#"mutation { createTransaction(transaction: { itemId: 0, locationid: 0, quantity: 10 }) { id } }"#
+            }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(resp["errors"][0]["extensions"]["code"], "VALIDATION");
+        assert!(resp["errors"][0]["message"]
+            .as_str()
+            .unwrap()
+            .contains(r#"unknown field "locationid""#));
+    }
+
+    #[actix_rt::test]
+    async fn test_misspelled_input_variable_field() {
+        let app = test_server!();
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": r#"mutation ($transaction: InsertableTransaction!) { createTransaction(transaction: $transaction) { id } }"#,
+                "variables": { "transaction": { "itemId": 0, "quantity": 10, "coment": "typo" } }
+            }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(resp["errors"][0]["extensions"]["code"], "VALIDATION");
+        assert!(resp["errors"][0]["message"]
+            .as_str()
+            .unwrap()
+            .contains(r#"unknown field "coment""#));
+    }
+
+    #[actix_rt::test]
+    async fn test_transaction_metadata_contains() {
+        let app = test_server!();
+        // create a test item
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#
+            }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        let item_id = resp["data"]["createItem"]["id"].as_i64().unwrap();
+
+        // create a test transaction with metadata referencing the item
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": r#"mutation ($transaction: InsertableTransaction!) { createTransaction(transaction: $transaction) { id metadata } }"#,
+                "variables": { "transaction": {
+                    "itemId": item_id,
+                    "quantity": 10,
+                    "metadata": { "itemRef": item_id, "po": "PO-1" }
+                } }
+            }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        let transaction_id = resp["data"]["createTransaction"]["id"].as_i64().unwrap();
+        assert_eq!(
+            resp["data"]["createTransaction"]["metadata"]["po"],
+            serde_json::json!("PO-1")
+        );
+
+        // check that the transaction is found by metadata containment
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": r#"query ($metadata: JSON!) { transactions(metadataContains: $metadata) { id } }"#,
+                "variables": { "metadata": { "itemRef": item_id } }
+            }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            resp["data"]["transactions"],
+            serde_json::json!([{ "id": transaction_id }])
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_transaction_mutations_return_all_columns() {
+        let app = test_server!();
+        const FIELDS: &str =
+            "id itemId locationId transactionDate quantity comment metadata reason";
+        // create a test item and location
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": r#"mutation {
+                    createItem(item: { name: "TestItem" }) { id }
+                    createLocation(location: { name: "TestLocation" }) { id }
+                }"#
+            }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        let item_id = resp["data"]["createItem"]["id"].as_i64().unwrap();
+        let location_id = resp["data"]["createLocation"]["id"].as_i64().unwrap();
+
+        // create, update and delete a transaction with every field set, checking that each returns
+        // the transaction as it is stored
+        let mut transaction = serde_json::json!({
+            "itemId": item_id,
+            "locationId": location_id,
+            "transactionDate": "2022-01-01T00:00:00+00:00",
+            "quantity": 10,
+            "comment": "TestComment",
+            "metadata": { "po": "PO-1" },
+            "reason": "RECEIPT"
+        });
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": format!(
+                    "mutation ($transaction: InsertableTransaction!) {{ createTransaction(transaction: $transaction) {{ {} }} }}",
+                    FIELDS
+                ),
+                "variables": { "transaction": transaction }
+            }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        let created = resp["data"]["createTransaction"].clone();
+        let transaction_id = created["id"].as_i64().unwrap();
+        transaction["id"] = serde_json::json!(transaction_id);
+        assert_eq!(created, transaction);
+
+        transaction["quantity"] = serde_json::json!(5);
+        transaction["comment"] = serde_json::json!("UpdatedComment");
+        let mut input = transaction.clone();
+        input.as_object_mut().unwrap().remove("id");
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": format!(
+                    "mutation ($id: TransactionId!, $transaction: InsertableTransaction!) {{ updateTransaction(id: $id, transaction: $transaction) {{ {} }} }}",
+                    FIELDS
+                ),
+                "variables": { "id": transaction_id, "transaction": input }
+            }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(resp["data"]["updateTransaction"], transaction);
+
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": format!(
+                    "mutation ($id: TransactionId!) {{ deleteTransaction(id: $id) {{ {} }} }}",
+                    FIELDS
+                ),
+                "variables": { "id": transaction_id }
+            }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(resp["data"]["deleteTransaction"], transaction);
+
+        // clean up the test item and location
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": format!(
+                    "mutation {{ deleteItem(id: {}) {{ id }} deleteLocation(id: {}) {{ id }} }}",
+                    item_id, location_id
+                )
+            }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        assert!(resp["errors"].is_null());
+    }
+
+    #[actix_rt::test]
+    async fn test_transaction_metadata_not_object() {
+        let app = test_server!();
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": r#"mutation ($transaction: InsertableTransaction!) { createTransaction(transaction: $transaction) { id } }"#,
+                "variables": { "transaction": { "itemId": 0, "quantity": 10, "metadata": [1, 2] } }
+            }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(resp["errors"][0]["extensions"]["code"], "VALIDATION");
+        assert!(resp["errors"][0]["message"]
+            .as_str()
+            .unwrap()
+            .contains("metadata must be an object"));
+    }
+
+    #[actix_rt::test]
+    async fn test_transaction_subscription_backfill() {
+        use futures::StreamExt;
+
+        let schema = graphql::schema_builder()
+            .data(get_context(&Config::from_env().unwrap()).await)
+            .finish();
+        let since = chrono::Utc::now();
+
+        // create a test item
+        let resp = schema
+            .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let item_id = resp["createItem"]["id"].as_i64().unwrap();
+        let create_transaction = format!(
+            "{}{}{}{}{}",
+            r#"mutation { createTransaction(transaction: { itemId: "#,
+            item_id,
+            r#", quantity: 10, transactionDate: ""#,
+            chrono::Utc::now().to_rfc3339(),
+            r#"" }) { id } }"#
+        );
+
+        // create a transaction before subscribing
+        let resp = schema
+            .execute(create_transaction.as_str())
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let backfilled_id = resp["createTransaction"]["id"].as_i64().unwrap();
+
+        // subscribe, and check that the earlier transaction is replayed
+        let mut stream = schema.execute_stream(format!(
+            "{}{}{}",
+            r#"subscription { transactionSubscription(since: ""#,
+            since.to_rfc3339(),
+            r#"") { modification data { id } } }"#
+        ));
+        let resp = stream.next().await.unwrap().data.into_json().unwrap();
+        assert_eq!(
+            resp["transactionSubscription"]["data"]["id"]
+                .as_i64()
+                .unwrap(),
+            backfilled_id
+        );
+
+        // create a transaction after subscribing, and check that it is received live
+        let resp = schema
+            .execute(create_transaction.as_str())
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let live_id = resp["createTransaction"]["id"].as_i64().unwrap();
+        let resp = stream.next().await.unwrap().data.into_json().unwrap();
+        assert_eq!(
+            resp["transactionSubscription"]["data"]["id"]
+                .as_i64()
+                .unwrap(),
+            live_id
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_transaction_subscription_backfill_limit() {
+        use futures::StreamExt;
+
+        let context = get_context(&Config::from_env().unwrap()).await;
+        let schema = graphql::schema_builder().data(context.clone()).finish();
+        let since = chrono::Utc::now();
+
+        // create a test item with more transactions than can be replayed
+        let resp = schema
+            .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let item_id = resp["createItem"]["id"].as_i64().unwrap();
+        sqlx::query(
+            r#"
+            insert into transactions (item_id, quantity, transaction_date)
+            select $1, 1, $2 from generate_series(1, 1001)
+        "#,
+        )
+        .bind(item_id as i32)
+        .bind(chrono::Utc::now())
+        .execute(&*context.clients.postgres)
+        .await
+        .unwrap();
+
+        // check that subscribing fails rather than skipping transactions
+        let mut stream = schema.execute_stream(format!(
+            r#"subscription {{ transactionSubscription(itemId: {}, since: "{}") {{ data {{ id }} }} }}"#,
+            item_id,
+            since.to_rfc3339()
+        ));
+        let resp = stream.next().await.unwrap();
+        assert_eq!(
+            resp.errors[0].message,
+            "more than 1000 transactions were made since the date, so subscribe with a later date"
+        );
+        assert_eq!(
+            resp.errors[0].extensions.as_ref().unwrap().get("code"),
+            Some(&async_graphql::Value::from("VALIDATION"))
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_subscription_redis_unavailable() {
+        use futures::StreamExt;
+
+        // use a redis client which cannot connect, so no channel can be subscribed to
+        let config = Config::from_env().unwrap();
+        let mut clients = get_clients(&config).await;
+        let redis = Arc::new(redis::Client::open("redis://127.0.0.1:1").unwrap());
+        clients.fanout = Arc::new(Fanout::new(
+            redis.clone(),
+            FanoutConfig::from_config(&config),
+        ));
+        clients.redis = redis;
+        let schema = graphql::schema_builder()
+            .data(get_context_from_clients(clients, &config).await)
+            .finish();
+
+        // check that each subscription fails with an error, rather than panicking
+        for subscription in [
+            r#"subscription { itemSubscription { data { id } } }"#,
+            r#"subscription { locationSubscription { data { id } } }"#,
+            r#"subscription { transactionSubscription { data { id } } }"#,
+        ] {
+            let resp = schema.execute_stream(subscription).next().await.unwrap();
+            assert_eq!(resp.errors.len(), 1);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_subscription_types() {
+        use futures::StreamExt;
+
+        let schema = graphql::schema_builder()
+            .data(get_context(&Config::from_env().unwrap()).await)
+            .finish();
+        let suffix = chrono::Utc::now().timestamp_nanos();
+
+        // create a test item before subscribing
+        let resp = schema
+            .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let updated_id = resp["createItem"]["id"].clone();
+
+        // subscribe to creations only, then update the item and create another once subscribed
+        let mut stream = schema.execute_stream(
+            r#"subscription { itemSubscription(types: [CREATE]) { modification data { id name } } }"#,
+        );
+        let modify = async {
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+            schema
+                .execute(format!(
+                    r#"mutation {{ updateItem(id: {}, item: {{ name: "UpdatedItem{}" }}) {{ id }} }}"#,
+                    updated_id, suffix
+                ))
+                .await;
+            schema
+                .execute(format!(
+                    r#"mutation {{ createItem(item: {{ name: "CreatedItem{}" }}) {{ id }} }}"#,
+                    suffix
+                ))
+                .await;
+        };
+        let receive = async {
+            let mut received = Vec::new();
+            while let Some(resp) = stream.next().await {
+                let modification = resp.data.into_json().unwrap()["itemSubscription"].clone();
+                received.push(modification.clone());
+                if modification["data"]["name"] == format!("CreatedItem{}", suffix) {
+                    break;
+                }
+            }
+            received
+        };
+        let (_, received) = tokio::time::timeout(std::time::Duration::from_secs(10), async {
+            futures::join!(modify, receive)
+        })
+        .await
+        .expect("creation not received");
+
+        // check that only creations were delivered, so the update was not
+        assert!(received
+            .iter()
+            .all(|modification| modification["modification"] == "CREATE"));
+        assert!(received
+            .iter()
+            .all(|modification| modification["data"]["id"] != updated_id));
+    }
+
+    #[actix_rt::test]
+    async fn test_subscription_sse() {
+        use actix_web::body::MessageBody;
+
+        let context = get_context(&Config::from_env().unwrap()).await;
+        let schema = graphql::schema_builder().data(context).finish();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(schema.clone()))
+                .app_data(web::Data::new(SubscriptionConfig {
+                    keepalive_interval: None,
+                    max_subscriptions: None,
+                }))
+                .service(web::resource("/subscriptions").route(web::get().to(subscription_route)))
+                .service(
+                    web::resource("/subscriptions/sse")
+                        .route(web::post().to(subscription_sse_route))
+                        .route(web::get().to(subscription_sse_route)),
+                ),
+        )
+        .await;
+        let suffix = chrono::Utc::now().timestamp_nanos();
+
+        // check that subscriptions which accept server-sent events are not upgraded to websockets
+        let req = test::TestRequest::get()
+            .uri("/subscriptions?query=subscription%20%7B%20itemSubscription%20%7B%20modification%20%7D%20%7D")
+            .insert_header((http::header::ACCEPT, sse::EVENT_STREAM))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(http::header::CONTENT_TYPE).unwrap(),
+            sse::EVENT_STREAM
+        );
+
+        // subscribe over server-sent events, then create an item once subscribed
+        let req = test::TestRequest::post()
+            .uri("/subscriptions/sse")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": r#"subscription { itemSubscription(types: [CREATE]) { modification data { name } } }"#
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        let mut body = Box::pin(resp.into_body());
+        let create = async {
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+            schema
+                .execute(format!(
+                    r#"mutation {{ createItem(item: {{ name: "CreatedItem{}" }}) {{ id }} }}"#,
+                    suffix
+                ))
+                .await
+                .into_result()
+                .unwrap()
+                .data
+                .into_json()
+                .unwrap()
+        };
+        let receive = async {
+            loop {
+                let chunk = futures::future::poll_fn(|cx| body.as_mut().poll_next(cx))
+                    .await
+                    .unwrap()
+                    .unwrap();
+                let event = String::from_utf8(chunk.to_vec()).unwrap();
+                if event.contains(&format!("CreatedItem{}", suffix)) {
+                    return event;
+                }
+            }
+        };
+        let (resp, event) = tokio::time::timeout(std::time::Duration::from_secs(10), async {
+            futures::join!(create, receive)
+        })
+        .await
+        .expect("creation not received");
+
+        // check that the modification is sent as a next event with the response as its data
+        let data = event
+            .strip_prefix("event: next\ndata: ")
+            .and_then(|data| data.strip_suffix("\n\n"))
+            .unwrap();
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(data).unwrap()["data"],
+            serde_json::json!({ "itemSubscription": {
+                "modification": "CREATE",
+                "data": { "name": format!("CreatedItem{}", suffix) }
+            } })
+        );
+
+        // dropping the body disconnects the subscription
+        drop(body);
+        schema
+            .execute(format!(
+                "mutation {{ deleteItem(id: {}) {{ id }} }}",
+                resp["createItem"]["id"]
+            ))
+            .await;
+    }
+
+    #[actix_rt::test]
+    async fn test_subscription_sse_disconnect() {
+        use actix_web::body::MessageBody;
+
+        let context = get_context(&Config::from_env().unwrap()).await;
+        let schema = graphql::schema_builder().data(context.clone()).finish();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(schema.clone()))
+                .app_data(web::Data::new(SubscriptionConfig {
+                    keepalive_interval: None,
+                    max_subscriptions: None,
+                }))
+                .service(
+                    web::resource("/subscriptions/sse")
+                        .route(web::post().to(subscription_sse_route)),
+                ),
+        )
+        .await;
+
+        // create a test item, so its transactions have a channel of their own
+        let resp = schema
+            .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let item_id = resp["createItem"]["id"].as_i64().unwrap();
+        let channel_name = format!("transactions:item:{}", item_id);
+
+        // check that a client subscribed over server-sent events subscribes to the channel in redis
+        let req = test::TestRequest::post()
+            .uri("/subscriptions/sse")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": format!(
+                    "subscription {{ transactionSubscription(itemId: {}) {{ modification }} }}",
+                    item_id
+                )
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        let mut body = Box::pin(resp.into_body());
+        let next_event = futures::future::poll_fn(|cx| body.as_mut().poll_next(cx));
+        let subscribed = Box::pin(wait_for_redis_subscribers(&context, &channel_name, 1));
+        match futures::future::select(next_event, subscribed).await {
+            futures::future::Either::Right((subscribed, _)) => assert!(subscribed),
+            futures::future::Either::Left(_) => panic!("subscription ended"),
+        }
+
+        // check that disconnecting the client drops the redis subscription of the channel
+        drop(body);
+        assert!(wait_for_redis_subscribers(&context, &channel_name, 0).await);
+
+        schema
+            .execute(format!(
+                "mutation {{ deleteItem(id: {}) {{ id }} }}",
+                item_id
+            ))
+            .await;
+    }
+
+    #[actix_rt::test]
+    async fn test_metrics() {
+        let app = test_server!();
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .set_json(serde_json::json!({ "query": "{ item(id: -1) { id } }" }))
+            .to_request();
+        test::call_service(&app, req).await;
+
+        // check that the batch of the item loader is exported
+        let req = test::TestRequest::get().uri("/metrics").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(
+            resp.headers().get(http::header::CONTENT_TYPE).unwrap(),
+            metrics::PROMETHEUS_CONTENT_TYPE
+        );
+        let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+        assert!(body.contains("# TYPE inv_track_loader_batch_size histogram"));
+        assert!(body.contains(r#"inv_track_loader_batch_size_count{loader="item"}"#));
+        assert!(body.contains(r#"inv_track_loader_not_found_total{loader="item"}"#));
+    }
+
+    #[actix_rt::test]
+    async fn test_probes() {
+        let app = test_server!();
+
+        // check that the server is live, and ready while postgres and redis are available
+        let req = test::TestRequest::get().uri("/livez").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        let req = test::TestRequest::get().uri("/readyz").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(body, serde_json::json!({ "postgres": "ok", "redis": "ok" }));
+    }
+
+    #[actix_rt::test]
+    async fn test_probes_redis_unavailable() {
+        // redis clients only connect when they are used, so the client can refer to a closed port
+        let mut context = get_context(&Config::from_env().unwrap()).await;
+        context.clients.redis = Arc::new(redis::Client::open("redis://127.0.0.1:1/").unwrap());
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(context))
+                .service(web::resource("/livez").route(web::get().to(livez_route)))
+                .service(web::resource("/readyz").route(web::get().to(readyz_route))),
+        )
+        .await;
+
+        // check that the server is not ready, but is still live, so it is not restarted
+        let req = test::TestRequest::get().uri("/readyz").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::SERVICE_UNAVAILABLE);
+        let body: serde_json::Value = test::read_body_json(resp).await;
+        assert_eq!(
+            body,
+            serde_json::json!({ "postgres": "ok", "redis": "unavailable" })
+        );
+        let req = test::TestRequest::get().uri("/livez").to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+    }
+
+    #[actix_rt::test]
+    async fn test_seed_idempotent() {
+        let context = get_context(&Config::from_env().unwrap()).await;
+        let pool = &context.clients.postgres;
+        let suffix = chrono::Utc::now().timestamp_nanos();
+        let dataset = seed::SeedDataset {
+            locations: vec![seed::SeedLocation {
+                name: format!("SeedLocation {}", suffix),
+                address: None,
+            }],
+            items: vec![
+                seed::SeedItem {
+                    sku: Some(format!("SEED-{}", suffix)),
+                    name: "SeedItem".to_string(),
+                    supplier: None,
+                    description: None,
+                },
+                seed::SeedItem {
+                    sku: None,
+                    name: format!("SeedItem {}", suffix),
+                    supplier: None,
+                    description: None,
+                },
+            ],
+        };
+
+        // check that the first seed inserts the dataset, and the second inserts nothing
+        let report = seed::seed(pool, &dataset).await.unwrap();
+        assert_eq!(report.locations, vec![format!("SeedLocation {}", suffix)]);
+        assert_eq!(
+            report.items,
+            vec!["SeedItem".to_string(), format!("SeedItem {}", suffix)]
+        );
+        assert_eq!(
+            seed::seed(pool, &dataset).await.unwrap(),
+            seed::SeedReport::default()
+        );
+
+        // check that there is one row of each seeded location and item
+        let count = |query: &'static str| async move {
+            sqlx::query_scalar::<_, i64>(query)
+                .bind(suffix.to_string())
+                .fetch_one(&**pool)
+                .await
+                .unwrap()
+        };
+        assert_eq!(
+            count("select count(id) from locations where name = 'SeedLocation ' || $1").await,
+            1
+        );
+        assert_eq!(
+            count(
+                "select count(id) from items where sku = 'SEED-' || $1 or name = 'SeedItem ' || $1"
+            )
+            .await,
+            2
+        );
+
+        sqlx::query("delete from items where sku = 'SEED-' || $1 or name = 'SeedItem ' || $1")
+            .bind(suffix.to_string())
+            .execute(&**pool)
+            .await
+            .unwrap();
+        sqlx::query("delete from locations where name = 'SeedLocation ' || $1")
+            .bind(suffix.to_string())
+            .execute(&**pool)
+            .await
+            .unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_migration_status() {
+        let app = test_server!();
+        let req = test::TestRequest::get().uri("/migrations").to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        let applied = resp["applied"].as_array().unwrap();
+        let pending = resp["pending"].as_array().unwrap();
+        // every migration is either applied or pending
+        assert!(pending.iter().all(|version| !applied.contains(version)));
+        assert_eq!(
+            applied.len() + pending.len(),
+            std::fs::read_dir("migrations")
+                .unwrap()
+                .filter(|entry| {
+                    entry
+                        .as_ref()
+                        .unwrap()
+                        .file_name()
+                        .to_string_lossy()
+                        .ends_with(".up.sql")
+                })
+                .count()
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_model_error_codes() {
+        let app = test_server!();
+        let queries = [
+            (r#"mutation { deleteItem(id: 0) { id } }"#, "NOT_FOUND"),
+            (
+                r#"mutation { createTransaction(transaction: { itemId: 0, quantity: 10 }) { id } }"#,
+                "VALIDATION",
+            ),
+            (
+                r#"mutation { createItem(item: { name: "name", sku: "CODE" }) { id } }"#,
+                "CONFLICT",
+            ),
+            (
+                r#"mutation { createItem(item: { name: "name", sku: "code" }) { id } }"#,
+                "CONFLICT",
+            ),
+        ];
+
+        // ensure that the sku exists
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({ "query": queries[2].0 }))
+            .to_request();
+        test::call_service(&app, req).await;
+
+        for (query, code) in queries {
+            let req = test::TestRequest::post()
+                .uri("/graphql")
+                .insert_header(http::header::ContentType::json())
+                .set_json(serde_json::json!({ "query": query }))
+                .to_request();
+            let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+            assert_eq!(
+                resp["errors"][0]["extensions"]["code"].as_str().unwrap(),
+                code
+            );
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_reorder_suggestions() {
+        let app = test_server!();
+        let mut item_ids = Vec::new();
+        for (item, quantities) in [
+            (
+                r#"{ name: "TestItem", reorderPoint: 10, maxQuantity: 50 }"#,
+                vec![4],
+            ),
+            (r#"{ name: "TestItem", reorderPoint: 5 }"#, vec![10, -8]),
+            (r#"{ name: "TestItem", reorderPoint: 5 }"#, vec![10]),
+            (r#"{ name: "TestItem" }"#, vec![-1]),
+        ] {
+            // create a test item
+            let req = test::TestRequest::post()
+                .uri("/graphql")
+                .insert_header(http::header::ContentType::json())
+                .set_json(serde_json::json!({
+                    "query": format!("{}{}{}", r#"mutation { createItem(item: "#, item, r#") { id } }"#)
+                }))
+                .to_request();
+            let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+            let item_id = resp["data"]["createItem"]["id"].as_i64().unwrap();
+            item_ids.push(item_id);
+
+            // create test transactions dated now
+            for quantity in quantities {
+                let req = test::TestRequest::post()
+                    .uri("/graphql")
+                    .insert_header(http::header::ContentType::json())
+                    .set_json(serde_json::json!({
+                        "query": format!(
+                            "{}{}{}{}{}{}{}",
+                            r#"mutation { createTransaction(transaction: { itemId: "#,
+                            item_id,
+                            r#", quantity: "#,
+                            quantity,
+                            r#", transactionDate: ""#,
+                            chrono::Utc::now().to_rfc3339(),
+                            r#"" }) { id } }"#
+                        )
+                    }))
+                    .to_request();
+                test::call_service(&app, req).await;
+            }
+        }
+
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": r#"{ reorderSuggestions { item { id } currentQuantity suggestedQuantity } }"#
+            }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        let suggestions: Vec<_> = resp["data"]["reorderSuggestions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|suggestion| item_ids.contains(&suggestion["item"]["id"].as_i64().unwrap()))
+            .collect();
+        // restocked to the max quantity, and to the reorder point plus consumption
+        assert_eq!(
+            suggestions,
+            vec![
+                &serde_json::json!({ "item": { "id": item_ids[0] }, "currentQuantity": 4, "suggestedQuantity": 46 }),
+                &serde_json::json!({ "item": { "id": item_ids[1] }, "currentQuantity": 2, "suggestedQuantity": 11 }),
+            ]
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_subscription_keepalive() {
+        use std::io::{Read, Write};
+
+        let context = get_context(&Config::from_env().unwrap()).await;
+        let schema = graphql::schema_builder().data(context).finish();
+        let config = SubscriptionConfig {
+            keepalive_interval: Some(std::time::Duration::from_millis(100)),
+            max_subscriptions: None,
+        };
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(web::Data::new(schema.clone()))
+                .app_data(web::Data::new(config))
+                .service(web::resource("/subscriptions").route(web::get().to(subscription_route)))
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let address = server.addrs()[0];
+        actix_rt::spawn(server.run());
+
+        let frame = actix_rt::task::spawn_blocking(move || {
+            let mut stream = std::net::TcpStream::connect(address).unwrap();
+            stream
+                .set_read_timeout(Some(std::time::Duration::from_secs(5)))
+                .unwrap();
+            stream
+                .write_all(
+                    concat!(
+                        "GET /subscriptions HTTP/1.1\r\n",
+                        "Host: localhost\r\n",
+                        "Upgrade: websocket\r\n",
+                        "Connection: Upgrade\r\n",
+                        "Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n",
+                        "Sec-WebSocket-Version: 13\r\n",
+                        "Sec-WebSocket-Protocol: graphql-ws\r\n\r\n"
+                    )
+                    .as_bytes(),
+                )
+                .unwrap();
+
+            // read the handshake response
+            let mut response = Vec::new();
+            while !response.ends_with(b"\r\n\r\n") {
+                let mut byte = [0];
+                stream.read_exact(&mut byte).unwrap();
+                response.push(byte[0]);
+            }
+            assert!(response.starts_with(b"HTTP/1.1 101"));
+
+            // read the first frame, which is a short unmasked text frame
+            let mut header = [0; 2];
+            stream.read_exact(&mut header).unwrap();
+            let mut payload = vec![0; header[1] as usize];
+            stream.read_exact(&mut payload).unwrap();
+            (header[0], String::from_utf8(payload).unwrap())
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(frame, (0x81, r#"{"type":"ka"}"#.to_string()));
+    }
+
+    #[actix_rt::test]
+    async fn test_subscription_limit() {
+        use std::io::{Read, Write};
+
+        let context = get_context(&Config::from_env().unwrap()).await;
+        let schema = graphql::schema_builder().data(context).finish();
+        let config = SubscriptionConfig {
+            keepalive_interval: None,
+            max_subscriptions: Some(2),
+        };
+        let server = HttpServer::new(move || {
+            App::new()
+                .app_data(web::Data::new(schema.clone()))
+                .app_data(web::Data::new(config))
+                .service(web::resource("/subscriptions").route(web::get().to(subscription_route)))
+        })
+        .workers(1)
+        .bind(("127.0.0.1", 0))
+        .unwrap();
+        let address = server.addrs()[0];
+        actix_rt::spawn(server.run());
+
+        let error = actix_rt::task::spawn_blocking(move || {
+            let mut stream = std::net::TcpStream::connect(address).unwrap();
+            stream
+                .set_read_timeout(Some(std::time::Duration::from_secs(5)))
+                .unwrap();
+            stream
+                .write_all(
+                    concat!(
+                        "GET /subscriptions HTTP/1.1\r\n",
+                        "Host: localhost\r\n",
+                        "Upgrade: websocket\r\n",
+                        "Connection: Upgrade\r\n",
+                        "Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n",
+                        "Sec-WebSocket-Version: 13\r\n",
+                        "Sec-WebSocket-Protocol: graphql-ws\r\n\r\n"
+                    )
+                    .as_bytes(),
+                )
+                .unwrap();
+
+            // read the handshake response
+            let mut response = Vec::new();
+            while !response.ends_with(b"\r\n\r\n") {
+                let mut byte = [0];
+                stream.read_exact(&mut byte).unwrap();
+                response.push(byte[0]);
+            }
+            assert!(response.starts_with(b"HTTP/1.1 101"));
+
+            // client frames must be masked, and a zero mask leaves the payload unchanged
+            let mut send = |message: String| {
+                assert!(message.len() < 126);
+                let mut frame = vec![0x81, 0x80 | message.len() as u8, 0, 0, 0, 0];
+                frame.extend_from_slice(message.as_bytes());
+                stream.write_all(&frame).unwrap();
+            };
+            send(r#"{"type":"connection_init","payload":{}}"#.to_string());
+            // start one more subscription than the limit
+            for id in 1..=3 {
+                send(
+                    serde_json::json!({
+                        "type": "start",
+                        "id": id.to_string(),
+                        "payload": { "query": "subscription { itemSubscription { data { id } } }" }
+                    })
+                    .to_string(),
+                );
+            }
+
+            // read unmasked server frames until the error of the rejected subscription
+            loop {
+                let mut header = [0; 2];
+                stream.read_exact(&mut header).unwrap();
+                let length = match header[1] {
+                    126 => {
+                        let mut length = [0; 2];
+                        stream.read_exact(&mut length).unwrap();
+                        u16::from_be_bytes(length) as usize
+                    }
+                    length => length as usize,
+                };
+                let mut payload = vec![0; length];
+                stream.read_exact(&mut payload).unwrap();
+                let message: serde_json::Value = serde_json::from_slice(&payload).unwrap();
+                if message["type"] == "error" {
+                    break message;
+                }
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(error["id"], "3");
+        assert_eq!(error["payload"]["extensions"]["code"], "LIMIT_EXCEEDED");
+        assert_eq!(
+            error["payload"]["message"],
+            "a connection can have at most 2 subscriptions"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_item_name_search_ignores_accents() {
+        let app = test_server!();
+        let suffix = chrono::Utc::now().timestamp_nanos();
+        // create a test item with an accented name
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": r#"mutation ($item: InsertableItem!) { createItem(item: $item) { id } }"#,
+                "variables": { "item": { "name": format!("Crème Brûlée {}", suffix) } }
+            }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        let item_id = resp["data"]["createItem"]["id"].as_i64().unwrap();
+
+        for search in ["creme brulee", "CRÈME BRÛLÉE", "creme"] {
+            let req = test::TestRequest::post()
+                .uri("/graphql")
+                .insert_header(http::header::ContentType::json())
+                .set_json(serde_json::json!({
+                    "query": r#"query ($name: String!) { items(nameContains: $name) { id } }"#,
+                    "variables": { "name": format!("{} {}", search, suffix) }
+                }))
+                .to_request();
+            let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+            if search == "creme" {
+                // the words are not adjacent
+                assert_eq!(resp["data"]["items"], serde_json::json!([]));
+            } else {
+                assert_eq!(
+                    resp["data"]["items"],
+                    serde_json::json!([{ "id": item_id }])
+                );
+            }
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_bulk_update_items() {
+        let app = test_server!();
+        let mut item_ids = Vec::new();
+        for _ in 0..2 {
+            // create a test item
+            let req = test::TestRequest::post()
+                .uri("/graphql")
+                .insert_header(http::header::ContentType::json())
+                .set_json(serde_json::json!({
+                    "query": r#"mutation { createItem(item: { name: "TestItem", description: "TestDescription" }) { id } }"#
+                }))
+                .to_request();
+            let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+            item_ids.push(resp["data"]["createItem"]["id"].as_i64().unwrap());
+        }
+
+        // update the supplier of both items, leaving the description unchanged
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": r#"mutation ($ids: [ItemId!]!) { bulkUpdateItems(ids: $ids, patch: { supplier: "TestSupplier" }) { id supplier description } }"#,
+                "variables": { "ids": item_ids }
+            }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            resp["data"]["bulkUpdateItems"],
+            serde_json::json!([
+                { "id": item_ids[0], "supplier": "TestSupplier", "description": "TestDescription" },
+                { "id": item_ids[1], "supplier": "TestSupplier", "description": "TestDescription" },
+            ])
+        );
+
+        // no item is updated if any item does not exist
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": r#"mutation ($ids: [ItemId!]!) { bulkUpdateItems(ids: $ids, patch: { supplier: "OtherSupplier" }) { id } }"#,
+                "variables": { "ids": [item_ids[0], -1] }
+            }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(resp["errors"][0]["extensions"]["code"], "NOT_FOUND");
+
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": format!("{}{}{}", r#"{ item(id: "#, item_ids[0], r#") { supplier } }"#)
+            }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(resp["data"]["item"]["supplier"], "TestSupplier");
+
+        // an empty patch is rejected
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": r#"mutation ($ids: [ItemId!]!) { bulkUpdateItems(ids: $ids, patch: {}) { id } }"#,
+                "variables": { "ids": item_ids }
+            }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(resp["errors"][0]["extensions"]["code"], "VALIDATION");
+        assert_eq!(
+            resp["errors"][0]["extensions"]["patch"],
+            "no fields to update"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_delete_location_broadcasts_transaction_updates() {
+        use futures::StreamExt;
+
+        let schema = graphql::schema_builder()
+            .data(get_context(&Config::from_env().unwrap()).await)
+            .finish();
+        let since = chrono::Utc::now();
+
+        // create a test item and location
+        let resp = schema
+            .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let item_id = resp["createItem"]["id"].as_i64().unwrap();
+        let resp = schema
+            .execute(r#"mutation { createLocation(location: { name: "Toronto" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let location_id = resp["createLocation"]["id"].as_i64().unwrap();
+
+        // create a test transaction at the location
+        let resp = schema
+            .execute(format!(
+                "{}{}{}{}{}{}{}",
+                r#"mutation { createTransaction(transaction: { itemId: "#,
+                item_id,
+                r#", locationId: "#,
+                location_id,
+                r#", quantity: 10, transactionDate: ""#,
+                chrono::Utc::now().to_rfc3339(),
+                r#"" }) { id } }"#
+            ))
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let transaction_id = resp["createTransaction"]["id"].as_i64().unwrap();
+
+        // subscribe, waiting for the transaction to be replayed so that the subscription is live
+        let mut stream = schema.execute_stream(format!(
+            "{}{}{}",
+            r#"subscription { transactionSubscription(since: ""#,
+            since.to_rfc3339(),
+            r#"") { modification data { id location { id } } } }"#
+        ));
+        loop {
+            let resp = stream.next().await.unwrap().data.into_json().unwrap();
+            if resp["transactionSubscription"]["data"]["id"] == transaction_id {
+                break;
+            }
+        }
+
+        // delete the test location
+        schema
+            .execute(format!(
+                "{}{}{}",
+                r#"mutation { deleteLocation(id: "#, location_id, r#") { id } }"#
+            ))
+            .await
+            .into_result()
+            .unwrap();
+
+        // check that the transaction update is received
+        let resp = loop {
+            let resp = stream.next().await.unwrap().data.into_json().unwrap();
+            if resp["transactionSubscription"]["data"]["id"] == transaction_id {
+                break resp;
+            }
+        };
+        assert_eq!(
+            resp["transactionSubscription"],
+            serde_json::json!({
+                "modification": "UPDATE",
+                "data": { "id": transaction_id, "location": null }
+            })
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_max_transactions_per_item() {
+        let mut context = get_context(&Config::from_env().unwrap()).await;
+        context.config = Arc::new(ModelConfig {
+            max_transactions_per_item: Some(2),
+            ..(*context.config).clone()
+        });
+        let schema = graphql::schema_builder().data(context).finish();
+
+        // create a test item
+        let resp = schema
+            .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let item_id = resp["createItem"]["id"].as_i64().unwrap();
+        let create_transaction = format!(
+            "{}{}{}",
+            r#"mutation { createTransaction(transaction: { itemId: "#,
+            item_id,
+            r#", quantity: 10 }) { id } }"#
+        );
+
+        // create transactions up to the maximum
+        for _ in 0..2 {
+            let resp = schema.execute(create_transaction.as_str()).await;
+            assert!(resp.errors.is_empty());
+        }
+
+        // check that creating past the maximum is rejected
+        let resp = schema
+            .execute(create_transaction.as_str())
+            .await
+            .into_result()
+            .unwrap_err();
+        assert_eq!(
+            resp[0].extensions.as_ref().unwrap().get("itemId"),
+            Some(&async_graphql::Value::from(format!(
+                "item with id ItemId({}) has reached the maximum of 2 transactions",
+                item_id
+            )))
+        );
+
+        // check that a bulk create past the maximum is rejected with the index of the first
+        // transaction past it, although each is below the maximum when counted alone
+        let resp = schema
+            .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let item_id = resp["createItem"]["id"].as_i64().unwrap();
+        let resp = schema
+            .execute(format!(
+                r#"mutation {{ createTransactions(transactions: [
+                    {{ itemId: {0}, quantity: 1 }},
+                    {{ itemId: {0}, quantity: 1 }},
+                    {{ itemId: {0}, quantity: 1 }}
+                ]) {{ created {{ id }} }} }}"#,
+                item_id
+            ))
+            .await
+            .into_result()
+            .unwrap_err();
+        assert_eq!(
+            resp[0].extensions.as_ref().unwrap().get("index"),
+            Some(&async_graphql::Value::from("2"))
+        );
+
+        // check that a transfer, which creates two transactions, is rejected with room for one
+        let mut location_ids = Vec::new();
+        for _ in 0..2 {
+            let resp = schema
+                .execute(
+                    r#"mutation { createLocation(location: { name: "TestLocation" }) { id } }"#,
+                )
+                .await
+                .into_result()
+                .unwrap()
+                .data
+                .into_json()
+                .unwrap();
+            location_ids.push(resp["createLocation"]["id"].as_i64().unwrap());
+        }
+        let resp = schema
+            .execute(format!(
+                "mutation {{ createTransaction(transaction: {{ itemId: {}, quantity: 1 }}) {{ id }} }}",
+                item_id
+            ))
+            .await;
+        assert!(resp.errors.is_empty());
+        let resp = schema
+            .execute(format!(
+                "mutation {{ transferStock(transfer: {{ itemId: {}, fromLocationId: {}, toLocationId: {}, quantity: 1 }}) {{ quantity }} }}",
+                item_id, location_ids[0], location_ids[1]
+            ))
+            .await
+            .into_result()
+            .unwrap_err();
+        assert!(resp[0].extensions.as_ref().unwrap().get("itemId").is_some());
+
+        schema
+            .execute(format!(
+                "mutation {{ deleteItem(id: {}) {{ id }} }}",
+                item_id
+            ))
+            .await;
+        for location_id in location_ids {
+            schema
+                .execute(format!(
+                    "mutation {{ deleteLocation(id: {}) {{ id }} }}",
+                    location_id
+                ))
+                .await;
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_comment_required_reasons() {
+        let mut context = get_context(&Config::from_env().unwrap()).await;
+        context.config = Arc::new(ModelConfig {
+            comment_required_reasons: [
+                model::transaction::TransactionReason::Damage,
+                model::transaction::TransactionReason::Adjustment,
+                model::transaction::TransactionReason::Transfer,
+            ]
+            .into_iter()
+            .collect(),
+            ..(*context.config).clone()
+        });
+        let schema = graphql::schema_builder().data(context).finish();
+
+        // create a test item, and test locations to transfer it between
+        let resp = schema
+            .execute(
+                r#"mutation {
+                    createItem(item: { name: "TestItem" }) { id }
+                    from: createLocation(location: { name: "TestLocation" }) { id }
+                    to: createLocation(location: { name: "TestLocation" }) { id }
+                }"#,
+            )
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let item_id = resp["createItem"]["id"].as_i64().unwrap();
+        let location_ids = [
+            resp["from"]["id"].as_i64().unwrap(),
+            resp["to"]["id"].as_i64().unwrap(),
+        ];
+        let create_transaction = |fields: &str| {
+            format!(
+                r#"mutation {{ createTransaction(transaction: {{ itemId: {}, quantity: -1, {} }}) {{ id }} }}"#,
+                item_id, fields
+            )
+        };
+        let expected_error = Some(async_graphql::Value::from(
+            "transactions with reason DAMAGE require a comment",
+        ));
+
+        // check that a mandating reason is rejected without a comment, or with a blank one
+        for fields in ["reason: DAMAGE", r#"reason: DAMAGE, comment: "  ""#] {
+            let resp = schema
+                .execute(create_transaction(fields))
+                .await
+                .into_result()
+                .unwrap_err();
+            assert_eq!(
+                resp[0].extensions.as_ref().unwrap().get("comment"),
+                expected_error.as_ref()
+            );
+        }
+
+        // check that a mandating reason is accepted with a comment, and other reasons without one
+        let resp = schema
+            .execute(create_transaction(
+                r#"reason: DAMAGE, comment: "Dropped in the warehouse""#,
+            ))
+            .await;
+        assert!(resp.errors.is_empty(), "{:?}", resp.errors);
+        let transaction_id = resp.data.into_json().unwrap()["createTransaction"]["id"]
+            .as_i64()
+            .unwrap();
+        let resp = schema.execute(create_transaction("reason: SALE")).await;
+        assert!(resp.errors.is_empty(), "{:?}", resp.errors);
+
+        // check that removing the comment on update is rejected
+        let resp = schema
+            .execute(format!(
+                r#"mutation {{ updateTransaction(id: {}, transaction: {{ itemId: {}, quantity: -1, reason: DAMAGE }}) {{ id }} }}"#,
+                transaction_id, item_id
+            ))
+            .await
+            .into_result()
+            .unwrap_err();
+        assert_eq!(
+            resp[0].extensions.as_ref().unwrap().get("comment"),
+            expected_error.as_ref()
+        );
+
+        // check that a transfer is rejected without a comment, when its reason mandates one
+        let transfer_stock = |fields: &str| {
+            format!(
+                r#"mutation {{ transferStock(transfer: {{ itemId: {}, fromLocationId: {}, toLocationId: {}, quantity: 1{} }}) {{ from {{ id }} }} }}"#,
+                item_id, location_ids[0], location_ids[1], fields
+            )
+        };
+        let resp = schema
+            .execute(transfer_stock(""))
+            .await
+            .into_result()
+            .unwrap_err();
+        assert_eq!(
+            resp[0].extensions.as_ref().unwrap().get("comment"),
+            Some(&async_graphql::Value::from(
+                "transactions with reason TRANSFER require a comment"
+            ))
+        );
+        let resp = schema
+            .execute(transfer_stock(r#", comment: "Restocking the shelf""#))
+            .await;
+        assert!(resp.errors.is_empty(), "{:?}", resp.errors);
+
+        schema
+            .execute(format!(
+                "mutation {{ deleteItem(id: {}) {{ id }} deleteLocation(id: {}) {{ id }} l: deleteLocation(id: {}) {{ id }} }}",
+                item_id, location_ids[0], location_ids[1]
+            ))
+            .await;
+    }
+
+    #[actix_rt::test]
+    async fn test_future_transactions() {
+        let mut context = get_context(&Config::from_env().unwrap()).await;
+        context.config = Arc::new(ModelConfig {
+            max_future_transaction_skew: None,
+            ..(*context.config).clone()
+        });
+        let schema = graphql::schema_builder().data(context.clone()).finish();
+
+        // create a test item
+        let resp = schema
+            .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let item_id = resp["createItem"]["id"].as_i64().unwrap();
+        let future_date = "2100-01-01T00:00:00+00:00";
+        let create_transaction = |transaction_date: &str| {
+            format!(
+                r#"mutation {{ createTransaction(transaction: {{ itemId: {}, quantity: 10, transactionDate: "{}" }}) {{ id }} }}"#,
+                item_id, transaction_date
+            )
+        };
+
+        // check that future transactions are allowed by default
+        let resp = schema
+            .execute(create_transaction(future_date).as_str())
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let transaction_id = resp["createTransaction"]["id"].as_i64().unwrap();
+
+        context.config = Arc::new(ModelConfig {
+            max_future_transaction_skew: Some(chrono::Duration::seconds(300)),
+            ..(*context.config).clone()
+        });
+        let schema = graphql::schema_builder().data(context).finish();
+        let expected_error = Some(async_graphql::Value::from(format!(
+            "transaction date {} is more than 300 seconds in the future",
+            future_date
+        )));
+
+        // check that creating a future transaction is rejected
+        let resp = schema
+            .execute(create_transaction(future_date).as_str())
+            .await
+            .into_result()
+            .unwrap_err();
+        assert_eq!(
+            resp[0].extensions.as_ref().unwrap().get("transactionDate"),
+            expected_error.as_ref()
+        );
+
+        // check that updating a transaction to a future date is rejected
+        let resp = schema
+            .execute(format!(
+                r#"mutation {{ updateTransaction(id: {}, transaction: {{ itemId: {}, quantity: 10, transactionDate: "{}" }}) {{ id }} }}"#,
+                transaction_id, item_id, future_date
+            ))
+            .await
+            .into_result()
+            .unwrap_err();
+        assert_eq!(
+            resp[0].extensions.as_ref().unwrap().get("transactionDate"),
+            expected_error.as_ref()
+        );
+
+        // check that dates within the skew are allowed
+        let within_skew = (chrono::Utc::now() + chrono::Duration::seconds(60)).to_rfc3339();
+        let resp = schema
+            .execute(create_transaction(&within_skew).as_str())
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let within_skew_id = resp["createTransaction"]["id"].as_i64().unwrap();
+
+        // delete the future transactions, so they are not replayed by other subscriptions
+        for id in [transaction_id, within_skew_id] {
+            let resp = schema
+                .execute(format!(
+                    "mutation {{ deleteTransaction(id: {}) {{ id }} }}",
+                    id
+                ))
+                .await;
+            assert!(resp.errors.is_empty());
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_get_query_allowlist() {
+        let context = get_context(&Config::from_env().unwrap()).await;
+        let schema = graphql::schema_builder().data(context).finish();
+        let allowed_query = "query AllowedLocations { locations { id } }";
+        let allowlist = GetAllowlist {
+            enabled: true,
+            operation_names: ["AllowedLocations".to_string()].into_iter().collect(),
+            query_hashes: [hex::encode(Sha256::digest(allowed_query.as_bytes()))]
+                .into_iter()
+                .collect(),
+        };
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(schema))
+                .app_data(web::Data::new(allowlist))
+                .app_data(web::Data::new(AuthConfig::default()))
+                .app_data(web::Data::new(CacheConfig::default()))
+                .service(
+                    web::resource("/graphql")
+                        .route(web::post().to(graphql_route))
+                        .route(web::get().to(graphql_get_route)),
+                ),
+        )
+        .await;
+
+        let get_request = |query: &str, operation_name: Option<&str>| {
+            let mut params = vec![("query", query)];
+            params.extend(operation_name.map(|operation_name| ("operationName", operation_name)));
+            test::TestRequest::get()
+                .uri(&format!(
+                    "/graphql?{}",
+                    serde_urlencoded::to_string(params).unwrap()
+                ))
+                .to_request()
+        };
+
+        // check that a query with an allowed hash and operation name can be executed over GET
+        let req = get_request(allowed_query, Some("AllowedLocations"));
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        assert!(resp["errors"].is_null());
+
+        // check that an ad-hoc query is rejected over GET, even if it borrows an allowed name, and
+        // that an allowed query must name an allowed operation
+        let query = "query AllowedLocations { locations { id name } }";
+        for (query, operation_name) in [
+            (query, None),
+            (query, Some("AllowedLocations")),
+            (allowed_query, None),
+        ] {
+            let resp = test::call_service(&app, get_request(query, operation_name)).await;
+            assert_eq!(resp.status(), http::StatusCode::FORBIDDEN);
+        }
+
+        // check that the same query is allowed over POST
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({ "query": query }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        assert!(resp["errors"].is_null());
+    }
+
+    #[actix_rt::test]
+    async fn test_average_daily_consumption() {
+        let app = test_server!();
+        let suffix = chrono::Utc::now().timestamp_nanos();
+        let mut item_ids = Vec::new();
+        for quantities in [vec![(20, 0), (-10, 0), (-4, 1), (-100, 10)], vec![(20, 0)]] {
+            // create a test item
+            let req = test::TestRequest::post()
+                .uri("/graphql")
+                .insert_header(http::header::ContentType::json())
+                .set_json(serde_json::json!({
+                    "query": r#"mutation ($item: InsertableItem!) { createItem(item: $item) { id } }"#,
+                    "variables": { "item": { "name": format!("TestItem {}", suffix) } }
+                }))
+                .to_request();
+            let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+            let item_id = resp["data"]["createItem"]["id"].as_i64().unwrap();
+            item_ids.push(item_id);
+
+            // create test transactions dated the given number of days ago
+            for (quantity, days_ago) in quantities {
+                let req = test::TestRequest::post()
+                    .uri("/graphql")
+                    .insert_header(http::header::ContentType::json())
+                    .set_json(serde_json::json!({
+                        "query": format!(
+                            "{}{}{}{}{}{}{}",
+                            r#"mutation { createTransaction(transaction: { itemId: "#,
+                            item_id,
+                            r#", quantity: "#,
+                            quantity,
+                            r#", transactionDate: ""#,
+                            (chrono::Utc::now() - chrono::Duration::days(days_ago)).to_rfc3339(),
+                            r#"" }) { id } }"#
+                        )
+                    }))
+                    .to_request();
+                test::call_service(&app, req).await;
+            }
+        }
+
+        // only outbound transactions within the window are counted, and receipts are ignored
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": r#"query ($name: String!) { items(nameContains: $name) { id averageDailyConsumption(days: 7) } }"#,
+                "variables": { "name": suffix.to_string() }
+            }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            resp["data"]["items"],
+            serde_json::json!([
+                { "id": item_ids[0], "averageDailyConsumption": 2.0 },
+                { "id": item_ids[1], "averageDailyConsumption": 0.0 },
+            ])
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_unassigned_transactions() {
+        let app = test_server!();
+        // create a test item
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#
+            }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        let item_id = resp["data"]["createItem"]["id"].as_i64().unwrap();
+
+        // create a test location
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": r#"mutation { createLocation(location: { name: "Toronto" }) { id } }"#
+            }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        let location_id = resp["data"]["createLocation"]["id"].as_i64().unwrap();
+
+        // create test transactions with and without the location
+        let mut transaction_ids = Vec::new();
+        for location_id in [Some(location_id), None] {
+            let req = test::TestRequest::post()
+                .uri("/graphql")
+                .insert_header(http::header::ContentType::json())
+                .set_json(serde_json::json!({
+                    "query": r#"mutation ($transaction: InsertableTransaction!) { createTransaction(transaction: $transaction) { id } }"#,
+                    "variables": { "transaction": {
+                        "itemId": item_id,
+                        "locationId": location_id,
+                        "quantity": 10,
+                        "metadata": { "itemRef": item_id }
+                    } }
+                }))
+                .to_request();
+            let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+            transaction_ids.push(resp["data"]["createTransaction"]["id"].as_i64().unwrap());
+        }
+
+        // check that only the transaction without a location is found
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": r#"query ($metadata: JSON!) { transactions(metadataContains: $metadata, unassignedOnly: true) { id location { id } } }"#,
+                "variables": { "metadata": { "itemRef": item_id } }
+            }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            resp["data"]["transactions"],
+            serde_json::json!([{ "id": transaction_ids[1], "location": null }])
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_unique_item_name() {
+        let suffix = chrono::Utc::now().timestamp_nanos();
+        for enforce_unique_item_name in [false, true] {
+            let mut context = get_context(&Config::from_env().unwrap()).await;
+            context.config = Arc::new(ModelConfig {
+                enforce_unique_item_name,
+                ..(*context.config).clone()
+            });
+            let schema = graphql::schema_builder().data(context).finish();
+
+            // create test items with names differing only in case
+            let create_item = |name: String| {
+                async_graphql::Request::new(
+                    r#"mutation ($item: InsertableItem!) { createItem(item: $item) { id } }"#,
+                )
+                .variables(async_graphql::Variables::from_json(
+                    serde_json::json!({ "item": { "name": name } }),
+                ))
+            };
+            let name = format!("TestItem {} {}", enforce_unique_item_name, suffix);
+            let resp = schema.execute(create_item(name.clone())).await;
+            assert!(resp.errors.is_empty());
+            let resp = schema.execute(create_item(name.to_uppercase())).await;
+
+            if enforce_unique_item_name {
+                // check that the duplicate name is rejected
+                let extensions = resp.errors[0].extensions.as_ref().unwrap();
+                assert_eq!(
+                    extensions.get("code"),
+                    Some(&async_graphql::Value::from("CONFLICT"))
+                );
+                assert_eq!(
+                    extensions.get("name"),
+                    Some(&async_graphql::Value::from(format!(
+                        "name {:?} not unique",
+                        name.to_uppercase()
+                    )))
+                );
+            } else {
+                assert!(resp.errors.is_empty());
+            }
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_sanitize_item_descriptions() {
+        let description = r#"<p onclick="alert(1)"><b>Blue</b> widget</p><script>alert("xss")</script><img src="x" onerror="alert(1)"><a href="javascript:alert(1)">link</a>"#;
+        for sanitize_item_descriptions in [false, true] {
+            let mut context = get_context(&Config::from_env().unwrap()).await;
+            context.config = Arc::new(ModelConfig {
+                sanitize_item_descriptions,
+                ..(*context.config).clone()
+            });
+            let schema = graphql::schema_builder().data(context).finish();
+
+            // create a test item with malicious markup in its description
+            let resp = schema
+                .execute(
+                    async_graphql::Request::new(
+                        r#"mutation ($item: InsertableItem!) {
+                            createItem(item: $item) { id description }
+                        }"#,
+                    )
+                    .variables(async_graphql::Variables::from_json(
+                        serde_json::json!({
+                            "item": { "name": "TestItem", "description": description }
+                        }),
+                    )),
+                )
+                .await
+                .into_result()
+                .unwrap()
+                .data
+                .into_json()
+                .unwrap();
+            let item_id = resp["createItem"]["id"].clone();
+
+            if sanitize_item_descriptions {
+                // check that the unsafe markup is removed, while the formatting is kept
+                assert_eq!(
+                    resp["createItem"]["description"],
+                    r#"<p><b>Blue</b> widget</p><a rel="noopener noreferrer">link</a>"#
+                );
+            } else {
+                // check that the description is stored verbatim
+                assert_eq!(resp["createItem"]["description"], description);
+            }
+
+            // check that a description which is only unsafe markup is not given
+            let resp = schema
+                .execute(format!(
+                    r#"mutation {{
+                        bulkUpdateItems(ids: [{}], patch: {{ description: "<script>alert(1)</script>" }}) {{
+                            description
+                        }}
+                    }}"#,
+                    item_id
+                ))
+                .await
+                .into_result()
+                .unwrap()
+                .data
+                .into_json()
+                .unwrap();
+            if sanitize_item_descriptions {
+                assert_eq!(
+                    resp["bulkUpdateItems"][0]["description"],
+                    r#"<p><b>Blue</b> widget</p><a rel="noopener noreferrer">link</a>"#
+                );
+            } else {
+                assert_eq!(
+                    resp["bulkUpdateItems"][0]["description"],
+                    "<script>alert(1)</script>"
+                );
+            }
+
+            schema
+                .execute(format!(
+                    "mutation {{ deleteItem(id: {}) {{ id }} }}",
+                    item_id
+                ))
+                .await;
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_cache_control() {
+        let context = get_context(&Config::from_env().unwrap()).await;
+        let schema = graphql::schema_builder().data(context).finish();
+        let cache_config = CacheConfig {
+            max_age: 60,
+            fields: ["items".to_string()].into_iter().collect(),
+        };
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(schema))
+                .app_data(web::Data::new(GetAllowlist::default()))
+                .app_data(web::Data::new(AuthConfig::default()))
+                .app_data(web::Data::new(cache_config))
+                .service(
+                    web::resource("/graphql")
+                        .route(web::post().to(graphql_route))
+                        .route(web::get().to(graphql_get_route)),
+                ),
+        )
+        .await;
+
+        // check that only queries selecting cacheable fields are cached
+        for (query, cache_control) in [
+            ("{ items { id } }", "public, max-age=60"),
+            ("{ items { id } locations { id } }", "no-store"),
+            ("{ locations { id } }", "no-store"),
+        ] {
+            let req = test::TestRequest::get()
+                .uri(&format!(
+                    "/graphql?{}",
+                    serde_urlencoded::to_string([("query", query)]).unwrap()
+                ))
+                .to_request();
+            let resp = test::call_service(&app, req).await;
+            assert_eq!(
+                resp.headers().get(http::header::CACHE_CONTROL).unwrap(),
+                cache_control
+            );
+        }
+
+        // check that mutations are not cached
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(
+            resp.headers().get(http::header::CACHE_CONTROL).unwrap(),
+            "no-store"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_item_transaction_pages() {
+        let app = test_server!();
+        let suffix = chrono::Utc::now().timestamp_nanos();
+        let mut item_ids = Vec::new();
+        for transaction_count in [0, 2, 3] {
+            // create a test item
+            let req = test::TestRequest::post()
+                .uri("/graphql")
+                .insert_header(http::header::ContentType::json())
+                .set_json(serde_json::json!({
+                    "query": r#"mutation ($item: InsertableItem!) { createItem(item: $item) { id } }"#,
+                    "variables": { "item": { "name": format!("TestItem {}", suffix) } }
+                }))
+                .to_request();
+            let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+            let item_id = resp["data"]["createItem"]["id"].as_i64().unwrap();
+            item_ids.push(item_id);
+
+            // create test transactions
+            for _ in 0..transaction_count {
+                let req = test::TestRequest::post()
+                    .uri("/graphql")
+                    .insert_header(http::header::ContentType::json())
+                    .set_json(serde_json::json!({
+                        "query": format!(
+                            "{}{}{}",
+                            r#"mutation { createTransaction(transaction: { itemId: "#,
+                            item_id,
+                            r#", quantity: 10 }) { id } }"#
+                        )
+                    }))
+                    .to_request();
+                test::call_service(&app, req).await;
+            }
+        }
+
+        // check that each page is limited, and has a next page only if there are more transactions
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": r#"query ($name: String!) { items(nameContains: $name) { id transactionPage(first: 2) { transactions { id } hasNextPage } } }"#,
+                "variables": { "name": suffix.to_string() }
+            }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        let pages: Vec<_> = resp["data"]["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|item| {
+                (
+                    item["id"].as_i64().unwrap(),
+                    item["transactionPage"]["transactions"]
+                        .as_array()
+                        .unwrap()
+                        .len(),
+                    item["transactionPage"]["hasNextPage"].as_bool().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            pages,
+            vec![
+                (item_ids[0], 0, false),
+                (item_ids[1], 2, false),
+                (item_ids[2], 2, true),
+            ]
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_recalculate_quantities() {
+        let app = test_server!();
+        // create a test item
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#
+            }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        let item_id = resp["data"]["createItem"]["id"].as_i64().unwrap();
+
+        // create test transactions
+        for quantity in [10, -4] {
+            let req = test::TestRequest::post()
+                .uri("/graphql")
+                .insert_header(http::header::ContentType::json())
+                .set_json(serde_json::json!({
+                    "query": format!(
+                        "{}{}{}{}{}",
+                        r#"mutation { createTransaction(transaction: { itemId: "#,
+                        item_id,
+                        r#", quantity: "#,
+                        quantity,
+                        r#" }) { id } }"#
+                    )
+                }))
+                .to_request();
+            test::call_service(&app, req).await;
+        }
+
+        let recalculate_quantities =
+            r#"mutation { recalculateQuantities { item { id } quantity loadedQuantity } }"#;
+
+        // check that the mutation requires the admin role
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({ "query": recalculate_quantities }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(resp["errors"][0]["extensions"]["code"], "FORBIDDEN");
+
+        // check that the recalculated quantity matches the loaded quantity
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .insert_header((
+                http::header::AUTHORIZATION,
+                format!("Bearer {}", TEST_ADMIN_TOKEN),
+            ))
+            .set_json(serde_json::json!({ "query": recalculate_quantities }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        let recalculation = resp["data"]["recalculateQuantities"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|recalculation| recalculation["item"]["id"] == item_id)
+            .unwrap();
+        assert_eq!(
+            recalculation,
+            &serde_json::json!({ "item": { "id": item_id }, "quantity": 6, "loadedQuantity": 6 })
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_item_search() {
+        let app = test_server!();
+        let suffix = chrono::Utc::now().timestamp_nanos();
+        let mut item_ids = Vec::new();
+        for (name, sku) in [
+            (format!("Widget {}", suffix), format!("A-{}", suffix)),
+            (format!("Gadget {}", suffix), format!("WIDGET-{}", suffix)),
+            (format!("Gizmo {}", suffix), format!("B-{}", suffix)),
+        ] {
+            // create a test item
+            let req = test::TestRequest::post()
+                .uri("/graphql")
+                .insert_header(http::header::ContentType::json())
+                .set_json(serde_json::json!({
+                    "query": r#"mutation ($item: InsertableItem!) { createItem(item: $item) { id } }"#,
+                    "variables": { "item": { "name": name, "sku": sku } }
+                }))
+                .to_request();
+            let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+            item_ids.push(resp["data"]["createItem"]["id"].as_i64().unwrap());
+        }
+
+        for (variables, expected_ids) in [
+            // matches either the name or the sku, in the order of the names
+            (
+                serde_json::json!({ "search": "widget" }),
+                vec![item_ids[1], item_ids[0]],
+            ),
+            // matches only the sku
+            (serde_json::json!({ "search": "b-" }), vec![item_ids[2]]),
+            // matches only the name
+            (serde_json::json!({ "search": "gizmo" }), vec![item_ids[2]]),
+            // matches neither
+            (serde_json::json!({ "search": "sprocket" }), vec![]),
+            // combined with the name filter
+            (
+                serde_json::json!({ "search": "widget", "nameContains": "gadget" }),
+                vec![item_ids[1]],
+            ),
+        ] {
+            let req = test::TestRequest::post()
+                .uri("/graphql")
+                .insert_header(http::header::ContentType::json())
+                .set_json(serde_json::json!({
+                    "query": r#"query ($search: String, $nameContains: String) { items(search: $search, nameContains: $nameContains) { id } }"#,
+                    "variables": variables
+                }))
+                .to_request();
+            let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+            let ids: Vec<_> = resp["data"]["items"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|item| item["id"].as_i64().unwrap())
+                .filter(|id| item_ids.contains(id))
+                .collect();
+            assert_eq!(ids, expected_ids);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_items_quantity_filter() {
+        let schema = graphql::schema_builder()
+            .data(get_context(&Config::from_env().unwrap()).await)
+            .finish();
+        let suffix = chrono::Utc::now().timestamp_nanos();
+
+        // create test items across a range of quantities, including one without transactions
+        let mut item_ids = Vec::new();
+        for quantity in [None, Some(1), Some(3), Some(5), Some(8)] {
+            let resp = schema
+                .execute(format!(
+                    r#"mutation {{ createItem(item: {{ name: "Restock {}" }}) {{ id }} }}"#,
+                    suffix
+                ))
+                .await
+                .into_result()
+                .unwrap()
+                .data
+                .into_json()
+                .unwrap();
+            let item_id = resp["createItem"]["id"].as_i64().unwrap();
+            if let Some(quantity) = quantity {
+                let resp = schema
+                    .execute(format!(
+                        r#"mutation {{ createTransaction(transaction: {{ itemId: {}, quantity: {} }}) {{ id }} }}"#,
+                        item_id, quantity
+                    ))
+                    .await;
+                assert!(resp.errors.is_empty(), "{:?}", resp.errors);
+            }
+            item_ids.push(item_id);
+        }
+
+        for (bounds, expected_ids) in [
+            // the bounds are inclusive
+            (
+                "minQuantity: 1, maxQuantity: 5",
+                vec![item_ids[1], item_ids[2], item_ids[3]],
+            ),
+            // items without transactions have a quantity of zero
+            ("maxQuantity: 0", vec![item_ids[0]]),
+            (
+                "minQuantity: 0, maxQuantity: 1",
+                vec![item_ids[0], item_ids[1]],
+            ),
+            ("minQuantity: 4", vec![item_ids[3], item_ids[4]]),
+            ("minQuantity: 9", vec![]),
+        ] {
+            // the bounds are combined with the other filters, and the quantity is not selected
+            let resp = schema
+                .execute(format!(
+                    r#"{{ items(nameContains: "Restock {}", {}, orderBy: NEWEST_FIRST) {{ id }} }}"#,
+                    suffix, bounds
+                ))
+                .await
+                .into_result()
+                .unwrap()
+                .data
+                .into_json()
+                .unwrap();
+            let mut ids: Vec<_> = resp["items"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|item| item["id"].as_i64().unwrap())
+                .collect();
+            ids.reverse();
+            assert_eq!(ids, expected_ids, "{}", bounds);
+        }
+
+        // check that a minimum greater than the maximum is rejected
+        let resp = schema
+            .execute("{ items(minQuantity: 5, maxQuantity: 1) { id } }")
+            .await;
+        let extensions = resp.errors[0].extensions.as_ref().unwrap();
+        assert_eq!(
+            extensions.get("minQuantity"),
+            Some(&async_graphql::Value::from(
+                "minimum quantity 5 is greater than the maximum quantity 1"
+            ))
+        );
+
+        for item_id in item_ids {
+            schema
+                .execute(format!(
+                    "mutation {{ deleteItem(id: {}) {{ id }} }}",
+                    item_id
+                ))
+                .await;
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_items_joined_quantity() {
+        let schema = graphql::schema_builder()
+            .data(get_context(&Config::from_env().unwrap()).await)
+            .finish();
+        let suffix = chrono::Utc::now().timestamp_nanos();
+
+        // create a test item with stock, and one without transactions
+        let mut item_ids = Vec::new();
+        for quantity in [Some(4), None] {
+            let resp = schema
+                .execute(format!(
+                    r#"mutation {{ createItem(item: {{ name: "Stock {}" }}) {{ id }} }}"#,
+                    suffix
+                ))
+                .await
+                .into_result()
+                .unwrap()
+                .data
+                .into_json()
+                .unwrap();
+            let item_id = resp["createItem"]["id"].as_i64().unwrap();
+            if let Some(quantity) = quantity {
+                let resp = schema
+                    .execute(format!(
+                        r#"mutation {{ createTransaction(transaction: {{ itemId: {}, quantity: {} }}) {{ id }} }}"#,
+                        item_id, quantity
+                    ))
+                    .await;
+                assert!(resp.errors.is_empty(), "{:?}", resp.errors);
+            }
+            item_ids.push(item_id);
+        }
+
+        // check that listing items with their quantities does not load the quantities again
+        let quantity_batches = || metrics::loader_metrics("item_quantity").batch_sizes.count();
+        let batches = quantity_batches();
+        let resp = schema
+            .execute(format!(
+                r#"{{ items(nameContains: "Stock {}", orderBy: NEWEST_FIRST) {{ id quantity quantityAvailable }} }}"#,
+                suffix
+            ))
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(
+            resp["items"],
+            serde_json::json!([
+                { "id": item_ids[1], "quantity": 0, "quantityAvailable": 0 },
+                { "id": item_ids[0], "quantity": 4, "quantityAvailable": 4 }
+            ])
+        );
+        assert_eq!(quantity_batches(), batches);
+
+        // check that the quantity of an item which was not listed is still loaded
+        let resp = schema
+            .execute(format!("{{ item(id: {}) {{ quantity }} }}", item_ids[0]))
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(resp["item"]["quantity"], 4);
+        assert_eq!(quantity_batches(), batches + 1);
+
+        for item_id in item_ids {
+            schema
+                .execute(format!(
+                    "mutation {{ deleteItem(id: {}) {{ id }} }}",
+                    item_id
+                ))
+                .await;
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_item_order() {
+        let schema = graphql::schema_builder()
+            .data(get_context(&Config::from_env().unwrap()).await)
+            .finish();
+        let suffix = chrono::Utc::now().timestamp_nanos();
+
+        // create test items whose names are not in the order of their creation, with quantities
+        let mut item_ids = Vec::new();
+        for (name, quantity) in [("Bravo", 5), ("Alpha", 1), ("Charlie", 9), ("Alpha", 3)] {
+            let resp = schema
+                .execute(format!(
+                    r#"mutation {{ createItem(item: {{ name: "{} {}" }}) {{ id }} }}"#,
+                    name, suffix
+                ))
+                .await
+                .into_result()
+                .unwrap()
+                .data
+                .into_json()
+                .unwrap();
+            let item_id = resp["createItem"]["id"].as_i64().unwrap();
+            let resp = schema
+                .execute(format!(
+                    r#"mutation {{ createTransaction(transaction: {{ itemId: {}, quantity: {} }}) {{ id }} }}"#,
+                    item_id, quantity
+                ))
+                .await;
+            assert!(resp.errors.is_empty(), "{:?}", resp.errors);
+            item_ids.push(item_id);
+        }
+
+        let items = |order_by: &str| {
+            let schema = schema.clone();
+            let query = format!(
+                r#"{{ items(nameContains: "{}"{}) {{ id }} }}"#,
+                suffix, order_by
+            );
+            async move {
+                let resp = schema
+                    .execute(query)
+                    .await
+                    .into_result()
+                    .unwrap()
+                    .data
+                    .into_json()
+                    .unwrap();
+                resp["items"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|item| item["id"].as_i64().unwrap())
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        // check that items are in alphabetical order by default, with ties broken by id, on every
+        // call, even when the names are not selected
+        for _ in 0..3 {
+            assert_eq!(
+                items("").await,
+                vec![item_ids[1], item_ids[3], item_ids[0], item_ids[2]]
+            );
+        }
+
+        // check that the order can be overridden
+        for (order_by, expected) in [
+            (
+                "NAME_ASC",
+                vec![item_ids[1], item_ids[3], item_ids[0], item_ids[2]],
+            ),
+            (
+                "NAME_DESC",
+                vec![item_ids[2], item_ids[0], item_ids[3], item_ids[1]],
+            ),
+            (
+                "NEWEST_FIRST",
+                vec![item_ids[3], item_ids[2], item_ids[1], item_ids[0]],
+            ),
+            (
+                "QUANTITY_DESC",
+                vec![item_ids[2], item_ids[0], item_ids[3], item_ids[1]],
+            ),
+        ] {
+            assert_eq!(
+                items(&format!(", orderBy: {}", order_by)).await,
+                expected,
+                "{}",
+                order_by
+            );
+        }
+
+        for item_id in item_ids {
+            schema
+                .execute(format!(
+                    "mutation {{ deleteItem(id: {}) {{ id }} }}",
+                    item_id
+                ))
+                .await;
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_graphql_body_too_large() {
+        let context = get_context(&Config::from_env().unwrap()).await;
+        let schema = graphql::schema_builder().data(context).finish();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(schema.clone()))
+                .app_data(web::Data::new(AuthConfig::default()))
+                .service(
+                    web::resource("/graphql")
+                        .app_data(web::PayloadConfig::new(128))
+                        .route(web::post().to(graphql_route)),
+                )
+                .service(
+                    web::resource("/graphql/import")
+                        .app_data(web::PayloadConfig::new(1024))
+                        .route(web::post().to(graphql_import_route)),
+                ),
+        )
+        .await;
+
+        // check that a body within the limit is accepted
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({ "query": "{ locations { id } }" }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+
+        // check that a body over the limit is rejected
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": format!("{{ locations {{ {} }} }}", "id ".repeat(100))
+            }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(
+            test::read_body(resp).await,
+            "request body is larger than GRAPHQL_MAX_BODY_BYTES"
+        );
+
+        // check that an import over the limit is rejected, but accepted by the import route
+        let import = serde_json::json!({
+            "query": "mutation ($items: [InsertableItem!]!) { importItems(items: $items) { items { id } } }",
+            "variables": { "items": [{ "name": "TestItem", "description": "a".repeat(200) }] }
+        });
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(&import)
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::PAYLOAD_TOO_LARGE);
+        let req = test::TestRequest::post()
+            .uri("/graphql/import")
+            .insert_header(http::header::ContentType::json())
+            .set_json(&import)
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        let item_id = resp["data"]["importItems"]["items"][0]["id"]
+            .as_i64()
+            .unwrap();
+        schema
+            .execute(format!(
+                "mutation {{ deleteItem(id: {}) {{ id }} }}",
+                item_id
+            ))
+            .await;
+
+        // check that other operations are rejected by the import route
+        let req = test::TestRequest::post()
+            .uri("/graphql/import")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({ "query": "{ locations { id } }" }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::BAD_REQUEST);
+        assert_eq!(
+            test::read_body(resp).await,
+            "only importItems mutations can be sent to /graphql/import"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_federation() {
+        let context = get_context(&Config::from_env().unwrap()).await;
+        let schema = graphql::schema_builder()
+            .enable_federation()
+            .data(context)
+            .finish();
+
+        // check that the entities are keyed by id
+        let resp = schema
+            .execute("{ _service { sdl } }")
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let sdl = resp["_service"]["sdl"].as_str().unwrap();
+        for entity in ["Item", "Location", "Transaction"] {
+            assert!(sdl.contains(&format!(r#"type {} @key(fields: "id")"#, entity)));
+        }
+
+        // create a test item
+        let resp = schema
+            .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let item_id = resp["createItem"]["id"].as_i64().unwrap();
+
+        // check that the item is resolved from its representation
+        let resp = schema
+            .execute(
+                async_graphql::Request::new(
+                    r#"query ($representations: [_Any!]!) { _entities(representations: $representations) { ... on Item { id name } } }"#,
+                )
+                .variables(async_graphql::Variables::from_json(serde_json::json!({
+                    "representations": [{ "__typename": "Item", "id": item_id }]
+                }))),
+            )
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(
+            resp["_entities"],
+            serde_json::json!([{ "id": item_id, "name": "TestItem" }])
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_item_activity_breakdown() {
+        let app = test_server!();
+        // create a test item
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#
+            }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        let item_id = resp["data"]["createItem"]["id"].as_i64().unwrap();
+
+        // create test transactions with and without reasons
+        for (quantity, reason) in [
+            (10, Some("RECEIPT")),
+            (-2, Some("SALE")),
+            (-3, Some("SALE")),
+            (1, None),
+        ] {
+            let req = test::TestRequest::post()
+                .uri("/graphql")
+                .insert_header(http::header::ContentType::json())
+                .set_json(serde_json::json!({
+                    "query": r#"mutation ($transaction: InsertableTransaction!) { createTransaction(transaction: $transaction) { id } }"#,
+                    "variables": { "transaction": {
+                        "itemId": item_id,
+                        "quantity": quantity,
+                        "reason": reason
+                    } }
+                }))
+                .to_request();
+            test::call_service(&app, req).await;
+        }
+
+        // check that the transactions are grouped by reason, omitting reasons without transactions
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": format!(
+                    "{}{}{}",
+                    r#"{ item(id: "#,
+                    item_id,
+                    r#") { activityBreakdown { reason count netQuantity } } }"#
+                )
+            }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            resp["data"]["item"]["activityBreakdown"],
+            serde_json::json!([
+                { "reason": "RECEIPT", "count": 1, "netQuantity": 10 },
+                { "reason": "SALE", "count": 2, "netQuantity": -5 },
+                { "reason": null, "count": 1, "netQuantity": 1 },
+            ])
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_locations_near() {
+        let app = test_server!();
+        // create test locations around a point far from other test data
+        let mut location_ids = Vec::new();
+        for (lat, lng) in [(-60.0, 100.0), (-60.05, 100.0), (-61.0, 100.0)] {
+            let req = test::TestRequest::post()
+                .uri("/graphql")
+                .insert_header(http::header::ContentType::json())
+                .set_json(serde_json::json!({
+                    "query": r#"mutation ($location: InsertableLocation!) { createLocation(location: $location) { id } }"#,
+                    "variables": { "location": { "name": "TestLocation", "lat": lat, "lng": lng } }
+                }))
+                .to_request();
+            let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+            location_ids.push(resp["data"]["createLocation"]["id"].clone());
+        }
+
+        // check that only the locations within the radius are returned, nearest first
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": r#"{ locationsNear(lat: -60.04, lng: 100.0, radiusKm: 50.0) { location { id } distanceKm } }"#
+            }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        let nearby: Vec<_> = resp["data"]["locationsNear"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|nearby| location_ids.contains(&nearby["location"]["id"]))
+            .collect();
+        assert_eq!(nearby.len(), 2);
+        assert_eq!(nearby[0]["location"]["id"], location_ids[1]);
+        assert_eq!(nearby[1]["location"]["id"], location_ids[0]);
+        let distance_km = nearby[1]["distanceKm"].as_f64().unwrap();
+        assert!((distance_km - 4.448).abs() < 0.01);
+
+        // check that coordinates must be given together
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": r#"mutation { createLocation(location: { name: "TestLocation", lat: 10.0 }) { id } }"#
+            }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(resp["errors"][0]["extensions"]["code"], "VALIDATION");
+    }
+
+    #[actix_rt::test]
+    async fn test_import_items() {
+        let app = test_server!();
+        let suffix = chrono::Utc::now().timestamp_nanos();
+        let existing_sku = format!("ImportSku{}", suffix);
+        let new_sku = format!("NewImportSku{}", suffix);
+        // create a test item with an existing sku
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": r#"mutation ($item: InsertableItem!) { createItem(item: $item) { id } }"#,
+                "variables": { "item": { "name": "TestItem", "sku": existing_sku } }
+            }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        let existing_id = resp["data"]["createItem"]["id"].clone();
+
+        let import = |on_conflict: &str| {
+            test::TestRequest::post()
+                .uri("/graphql")
+                .insert_header(http::header::ContentType::json())
+                .set_json(serde_json::json!({
+                    "query": r#"mutation ($items: [InsertableItem!]!, $onConflict: ImportConflictMode!) {
+                        importItems(items: $items, onConflict: $onConflict) {
+                            items { id sku name }
+                            conflicts { row sku existingItemId }
+                        }
+                    }"#,
+                    "variables": {
+                        "items": [
+                            { "name": "NewItem", "sku": new_sku },
+                            { "name": "UpdatedItem", "sku": existing_sku.to_lowercase() },
+                        ],
+                        "onConflict": on_conflict
+                    }
+                }))
+                .to_request()
+        };
+        let conflicts = serde_json::json!([
+            { "row": 1, "sku": existing_sku.to_lowercase(), "existingItemId": existing_id }
+        ]);
+
+        // check that nothing is imported when failing on conflicts
+        let resp: serde_json::value::Value =
+            test::call_and_read_body_json(&app, import("FAIL")).await;
+        assert_eq!(resp["data"]["importItems"]["items"], serde_json::json!([]));
+        assert_eq!(resp["data"]["importItems"]["conflicts"], conflicts);
+
+        // check that the conflicting item is skipped
+        let resp: serde_json::value::Value =
+            test::call_and_read_body_json(&app, import("SKIP")).await;
+        let items = resp["data"]["importItems"]["items"].as_array().unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0]["sku"], new_sku);
+        assert_eq!(resp["data"]["importItems"]["conflicts"], conflicts);
+
+        // check that the existing item is updated, while the imported item now conflicts
+        let resp: serde_json::value::Value =
+            test::call_and_read_body_json(&app, import("UPDATE")).await;
+        let items = resp["data"]["importItems"]["items"].as_array().unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[1]["id"], existing_id);
+        assert_eq!(items[1]["name"], "UpdatedItem");
+        assert_eq!(
+            resp["data"]["importItems"]["conflicts"]
+                .as_array()
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_client_source() {
+        use crate::graphql::source::CLIENT_SOURCE_HEADER;
+
+        let app = test_server!();
+        let source = format!("scanner{}", chrono::Utc::now().timestamp_nanos());
+        let graphql = |query: &str, variables: serde_json::Value, source: Option<&str>| {
+            let req = test::TestRequest::post()
+                .uri("/graphql")
+                .insert_header(http::header::ContentType::json())
+                .set_json(serde_json::json!({ "query": query, "variables": variables }));
+            match source {
+                Some(source) => req.insert_header((CLIENT_SOURCE_HEADER, source)),
+                None => req,
+            }
+            .to_request()
+        };
+
+        // check that an item and its transaction record the source of the request
+        let req = graphql(
+            r#"mutation { createItem(item: { name: "TestItem" }) { id source } }"#,
+            serde_json::json!({}),
+            Some(&source),
+        );
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(resp["data"]["createItem"]["source"], source.as_str());
+        let item_id = resp["data"]["createItem"]["id"].as_i64().unwrap();
+        let req = graphql(
+            r#"mutation ($itemId: ItemId!) {
+                createTransaction(transaction: { itemId: $itemId, quantity: 1 }) { id source }
+            }"#,
+            serde_json::json!({ "itemId": item_id }),
+            Some(&source),
+        );
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(resp["data"]["createTransaction"]["source"], source.as_str());
+        let transaction_id = resp["data"]["createTransaction"]["id"].as_i64().unwrap();
+
+        // check that a restored transaction keeps its source, rather than that of the restore
+        let req = graphql(
+            r#"mutation ($id: TransactionId!) { deleteTransaction(id: $id) { id } }"#,
+            serde_json::json!({ "id": transaction_id }),
+            None,
+        );
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(resp["data"]["deleteTransaction"]["id"], transaction_id);
+        let req = graphql(
+            r#"mutation ($id: TransactionId!) { restoreTransaction(id: $id) { source } }"#,
+            serde_json::json!({ "id": transaction_id }),
+            None,
+        );
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            resp["data"]["restoreTransaction"]["source"],
+            source.as_str()
+        );
+
+        // check that rows are not attributed without a source
+        let req = graphql(
+            r#"mutation { createItem(item: { name: "TestItem" }) { id source } }"#,
+            serde_json::json!({}),
+            None,
+        );
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        assert!(resp["data"]["createItem"]["source"].is_null());
+        let unattributed_id = resp["data"]["createItem"]["id"].as_i64().unwrap();
+
+        // check that imported items are attributed to the import
+        let req = graphql(
+            r#"mutation { importItems(items: [{ name: "ImportedItem" }]) { items { id source } } }"#,
+            serde_json::json!({}),
+            None,
+        );
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        let imported = &resp["data"]["importItems"]["items"][0];
+        assert_eq!(imported["source"], "import");
+        let imported_id = imported["id"].as_i64().unwrap();
+
+        // check that items and transactions can be filtered by source
+        let req = graphql(
+            r#"query ($source: String!) {
+                items(source: $source) { id }
+                transactions(source: $source) { itemId }
+            }"#,
+            serde_json::json!({ "source": source }),
+            None,
+        );
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            resp["data"]["items"],
+            serde_json::json!([{ "id": item_id }])
+        );
+        assert_eq!(
+            resp["data"]["transactions"],
+            serde_json::json!([{ "itemId": item_id }])
+        );
+
+        // clean up the test items
+        for id in [item_id, unattributed_id, imported_id] {
+            let req = graphql(
+                r#"mutation ($id: ItemId!) { deleteItem(id: $id) { id } }"#,
+                serde_json::json!({ "id": id }),
+                None,
+            );
+            let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+            assert_eq!(resp["data"]["deleteItem"]["id"], id);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_quantities_overflow() {
+        use crate::graphql::auth::Role;
+        use crate::model::item::{self, ItemId};
+
+        let context = get_context(&Config::from_env().unwrap()).await;
+        let mut item_ids = Vec::new();
+        for _ in 0..2 {
+            let item_id = sqlx::query_scalar::<_, ItemId>(
+                r#"insert into items (name) values ('TestItem') returning id"#,
+            )
+            .fetch_one(&*context.clients.postgres)
+            .await
+            .unwrap();
+            item_ids.push(item_id);
+        }
+
+        // the first item has a normal sum, while the second item overflows
+        for (item_id, quantity) in [
+            (item_ids[0], 10),
+            (item_ids[0], -3),
+            (item_ids[1], i32::MAX),
+            (item_ids[1], i32::MAX),
+        ] {
+            sqlx::query(r#"insert into transactions (item_id, quantity) values ($1, $2)"#)
+                .bind(item_id)
+                .bind(quantity)
+                .execute(&*context.clients.postgres)
+                .await
+                .unwrap();
+        }
+
+        // check that the overflowing item does not fail the whole batch
+        let quantities = item::get_quantities_by_item_ids(&context.clients, item_ids.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            quantities[&item_ids[0]]
+                .as_ref()
+                .map(|quantity| i32::from(*quantity))
+                .ok(),
+            Some(7)
+        );
+        assert_eq!(
+            quantities[&item_ids[1]]
+                .as_ref()
+                .unwrap_err()
+                .extensions
+                .as_ref()
+                .unwrap()
+                .get("code"),
+            Some(&async_graphql::Value::from("VALIDATION"))
+        );
+
+        // check that the overflowing item does not fail the whole batch of ledgers or activities
+        let ledgers = item::get_ledgers_by_item_ids(&context.clients, item_ids.clone())
+            .await
+            .unwrap();
+        assert!(ledgers[&item_ids[0]].is_ok());
+        assert_eq!(
+            ledgers[&item_ids[1]]
+                .as_ref()
+                .unwrap_err()
+                .extensions
+                .as_ref()
+                .unwrap()
+                .get("code"),
+            Some(&async_graphql::Value::from("VALIDATION"))
+        );
+        let activities = item::get_activities_by_item_ids(&context.clients, item_ids.clone())
+            .await
+            .unwrap();
+        assert_eq!(
+            activities[&item_ids[0]].as_ref().map(Vec::len).ok(),
+            Some(1)
+        );
+        assert_eq!(
+            activities[&item_ids[1]]
+                .as_ref()
+                .unwrap_err()
+                .extensions
+                .as_ref()
+                .unwrap()
+                .get("code"),
+            Some(&async_graphql::Value::from("VALIDATION"))
+        );
+
+        // check that the overflowing item is recalculated without a quantity, rather than failing
+        let schema = graphql::schema_builder().data(context.clone()).finish();
+        let resp = schema
+            .execute(
+                async_graphql::Request::new(
+                    r#"mutation { recalculateQuantities { item { id } quantity loadedQuantity } }"#,
+                )
+                .data(Role::Admin),
+            )
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let recalculations = resp["recalculateQuantities"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|recalculation| {
+                item_ids
+                    .iter()
+                    .any(|item_id| recalculation["item"]["id"] == i32::from(*item_id))
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            recalculations,
+            vec![
+                &serde_json::json!({ "item": { "id": item_ids[0] }, "quantity": 7, "loadedQuantity": 7 }),
+                &serde_json::json!({ "item": { "id": item_ids[1] }, "quantity": null, "loadedQuantity": null }),
+            ]
+        );
+
+        // delete the overflowing item, so it does not affect other aggregates
+        sqlx::query(r#"delete from items where id = $1"#)
+            .bind(item_ids[1])
+            .execute(&*context.clients.postgres)
+            .await
+            .unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn test_transaction_balances() {
+        let app = test_server!();
+        // create a test item
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#
+            }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        let item_id = resp["data"]["createItem"]["id"].as_i64().unwrap();
+
+        // create test transactions, the last of which is backdated before the others
+        for (quantity, date) in [
+            (10, "2022-01-02T00:00:00Z"),
+            (-4, "2022-01-03T00:00:00Z"),
+            (5, "2022-01-01T00:00:00Z"),
+        ] {
+            let req = test::TestRequest::post()
+                .uri("/graphql")
+                .insert_header(http::header::ContentType::json())
+                .set_json(serde_json::json!({
+                    "query": r#"mutation ($transaction: InsertableTransaction!) { createTransaction(transaction: $transaction) { id } }"#,
+                    "variables": { "transaction": {
+                        "itemId": item_id,
+                        "quantity": quantity,
+                        "transactionDate": date
+                    } }
+                }))
+                .to_request();
+            test::call_service(&app, req).await;
+        }
+
+        // check that the balances follow the transaction dates
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": format!(
+                    "{}{}{}",
+                    r#"{ item(id: "#,
+                    item_id,
+                    r#") { transactions { quantity quantityBefore balanceAfter } } }"#
+                )
+            }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        let transactions = resp["data"]["item"]["transactions"].as_array().unwrap();
+        assert_eq!(transactions.len(), 3);
+        for transaction in transactions {
+            assert_eq!(
+                transaction["quantityBefore"].as_i64().unwrap()
+                    + transaction["quantity"].as_i64().unwrap(),
+                transaction["balanceAfter"].as_i64().unwrap()
+            );
+        }
+        let mut balances = transactions
+            .iter()
+            .map(|transaction| {
+                (
+                    transaction["quantityBefore"].as_i64().unwrap(),
+                    transaction["balanceAfter"].as_i64().unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        balances.sort_unstable();
+        assert_eq!(balances, vec![(0, 5), (5, 15), (15, 11)]);
+    }
+
+    #[actix_rt::test]
+    async fn test_reservations() {
+        let app = test_server!();
+        // create a test item with stock
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#
+            }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        let item_id = resp["data"]["createItem"]["id"].as_i64().unwrap();
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": r#"mutation ($transaction: InsertableTransaction!) { createTransaction(transaction: $transaction) { id } }"#,
+                "variables": { "transaction": { "itemId": item_id, "quantity": 10 } }
+            }))
+            .to_request();
+        test::call_service(&app, req).await;
+
+        let reserve = |quantity: i32| {
+            test::TestRequest::post()
+                .uri("/graphql")
+                .insert_header(http::header::ContentType::json())
+                .set_json(serde_json::json!({
+                    "query": r#"mutation ($itemId: ItemId!, $quantity: Int!) {
+                        reserveStock(itemId: $itemId, quantity: $quantity) {
+                            id status item { quantity quantityReserved quantityAvailable }
+                        }
+                    }"#,
+                    "variables": { "itemId": item_id, "quantity": quantity }
+                }))
+                .to_request()
+        };
+        let change = |mutation: &str, id: &serde_json::Value| {
+            test::TestRequest::post()
+                .uri("/graphql")
+                .insert_header(http::header::ContentType::json())
+                .set_json(serde_json::json!({
+                    "query": format!(
+                        "mutation ($id: ReservationId!) {{ {}(id: $id) {{ status transaction {{ quantity }} item {{ quantity quantityReserved quantityAvailable }} }} }}",
+                        mutation
+                    ),
+                    "variables": { "id": id }
+                }))
+                .to_request()
+        };
+
+        // check that reserving stock makes it unavailable, but keeps it on hand
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, reserve(4)).await;
+        let reservation_id = resp["data"]["reserveStock"]["id"].clone();
+        assert_eq!(resp["data"]["reserveStock"]["status"], "ACTIVE");
+        assert_eq!(
+            resp["data"]["reserveStock"]["item"],
+            serde_json::json!({ "quantity": 10, "quantityReserved": 4, "quantityAvailable": 6 })
+        );
+
+        // check that more than the available stock cannot be reserved
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, reserve(7)).await;
+        assert_eq!(resp["errors"][0]["extensions"]["code"], "VALIDATION");
+
+        // check that committing the reservation takes the stock out
+        let resp: serde_json::value::Value =
+            test::call_and_read_body_json(&app, change("commitReservation", &reservation_id)).await;
+        assert_eq!(resp["data"]["commitReservation"]["status"], "COMMITTED");
+        assert_eq!(
+            resp["data"]["commitReservation"]["transaction"]["quantity"],
+            -4
+        );
+        assert_eq!(
+            resp["data"]["commitReservation"]["item"],
+            serde_json::json!({ "quantity": 6, "quantityReserved": 0, "quantityAvailable": 6 })
+        );
+
+        // check that releasing a reservation makes the stock available again
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, reserve(6)).await;
+        let released_id = resp["data"]["reserveStock"]["id"].clone();
+        let resp: serde_json::value::Value =
+            test::call_and_read_body_json(&app, change("releaseReservation", &released_id)).await;
+        assert_eq!(resp["data"]["releaseReservation"]["status"], "RELEASED");
+        assert_eq!(
+            resp["data"]["releaseReservation"]["item"],
+            serde_json::json!({ "quantity": 6, "quantityReserved": 0, "quantityAvailable": 6 })
+        );
+
+        // check that only active reservations can be committed or released
+        let resp: serde_json::value::Value =
+            test::call_and_read_body_json(&app, change("commitReservation", &released_id)).await;
+        assert_eq!(resp["errors"][0]["extensions"]["code"], "CONFLICT");
+        let resp: serde_json::value::Value =
+            test::call_and_read_body_json(&app, change("releaseReservation", &reservation_id))
+                .await;
+        assert_eq!(resp["errors"][0]["extensions"]["code"], "CONFLICT");
+    }
+
+    #[actix_rt::test]
+    async fn test_supplier_suggestions() {
+        let app = test_server!();
+        let suffix = chrono::Utc::now().timestamp_nanos();
+        // create test items, one of which has a supplier with extra whitespace
+        for supplier in [
+            format!("Supplier{} B", suffix),
+            format!("Supplier{} A", suffix),
+            format!("Supplier{} C", suffix),
+            format!("  Supplier{}   B ", suffix),
+            format!("Supplier{} C", suffix),
+            format!("Supplier{} B", suffix),
+        ] {
+            let req = test::TestRequest::post()
+                .uri("/graphql")
+                .insert_header(http::header::ContentType::json())
+                .set_json(serde_json::json!({
+                    "query": r#"mutation ($item: InsertableItem!) { createItem(item: $item) { supplier } }"#,
+                    "variables": { "item": { "name": "TestItem", "supplier": supplier } }
+                }))
+                .to_request();
+            let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+            assert_eq!(
+                resp["data"]["createItem"]["supplier"],
+                supplier.split_whitespace().collect::<Vec<_>>().join(" ")
+            );
+        }
+
+        // check that suggestions are ordered by frequency, then alphabetically
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": r#"query ($prefix: String!) { supplierSuggestions(prefix: $prefix) }"#,
+                "variables": { "prefix": format!("supplier{}", suffix) }
+            }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(
+            resp["data"]["supplierSuggestions"],
+            serde_json::json!([
+                format!("Supplier{} B", suffix),
+                format!("Supplier{} C", suffix),
+                format!("Supplier{} A", suffix),
+            ])
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_transactions_by_item() {
+        let app = test_server!();
+        // create test items
+        let mut item_ids = Vec::new();
+        for _ in 0..2 {
+            let req = test::TestRequest::post()
+                .uri("/graphql")
+                .insert_header(http::header::ContentType::json())
+                .set_json(serde_json::json!({
+                    "query": r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#
+                }))
+                .to_request();
+            let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+            item_ids.push(resp["data"]["createItem"]["id"].as_i64().unwrap());
+        }
+
+        // create test transactions for both items on different dates
+        for (item_id, quantity, date) in [
+            (item_ids[0], 1, "2022-01-01T00:00:00Z"),
+            (item_ids[0], 2, "2022-01-02T00:00:00Z"),
+            (item_ids[0], 3, "2022-01-03T00:00:00Z"),
+            (item_ids[0], 4, "2022-01-04T00:00:00Z"),
+            (item_ids[1], 5, "2022-01-02T00:00:00Z"),
+        ] {
+            let req = test::TestRequest::post()
+                .uri("/graphql")
+                .insert_header(http::header::ContentType::json())
+                .set_json(serde_json::json!({
+                    "query": r#"mutation ($transaction: InsertableTransaction!) { createTransaction(transaction: $transaction) { id } }"#,
+                    "variables": { "transaction": {
+                        "itemId": item_id,
+                        "quantity": quantity,
+                        "transactionDate": date
+                    } }
+                }))
+                .to_request();
+            test::call_service(&app, req).await;
+        }
+
+        let transactions = |first: Option<i64>, offset: i64| {
+            test::TestRequest::post()
+                .uri("/graphql")
+                .insert_header(http::header::ContentType::json())
+                .set_json(serde_json::json!({
+                    "query": r#"query ($itemId: ItemId, $first: Int, $offset: Int!) {
+                        transactions(
+                            itemId: $itemId,
+                            since: "2022-01-02T00:00:00Z",
+                            until: "2022-01-04T00:00:00Z",
+                            first: $first,
+                            offset: $offset
+                        ) { quantity }
+                    }"#,
+                    "variables": { "itemId": item_ids[0], "first": first, "offset": offset }
+                }))
+                .to_request()
+        };
+
+        // check that only the transactions of the item within the date range are returned
+        let resp: serde_json::value::Value =
+            test::call_and_read_body_json(&app, transactions(None, 0)).await;
+        assert_eq!(
+            resp["data"]["transactions"],
+            serde_json::json!([{ "quantity": 3 }, { "quantity": 2 }])
+        );
+
+        // check that the transactions can be paged
+        let resp: serde_json::value::Value =
+            test::call_and_read_body_json(&app, transactions(Some(1), 1)).await;
+        assert_eq!(
+            resp["data"]["transactions"],
+            serde_json::json!([{ "quantity": 2 }])
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_item_by_sku() {
+        let app = test_server!();
+        let suffix = chrono::Utc::now().timestamp_nanos();
+        // create test items with skus
+        let mut item_ids = Vec::new();
+        for sku in [format!("SkuA{}", suffix), format!("SkuB{}", suffix)] {
+            let req = test::TestRequest::post()
+                .uri("/graphql")
+                .insert_header(http::header::ContentType::json())
+                .set_json(serde_json::json!({
+                    "query": r#"mutation ($item: InsertableItem!) { createItem(item: $item) { id } }"#,
+                    "variables": { "item": { "name": "TestItem", "sku": sku } }
+                }))
+                .to_request();
+            let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+            item_ids.push(resp["data"]["createItem"]["id"].clone());
+        }
+
+        // check that skus which only differ by case load the same item
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": r#"query ($a: String!, $b: String!, $c: String!) {
+                    a: itemBySku(sku: $a) { id }
+                    b: itemBySku(sku: $b) { id }
+                    c: itemBySku(sku: $c) { id }
+                }"#,
+                "variables": {
+                    "a": format!("skua{}", suffix),
+                    "b": format!("SKUA{}", suffix),
+                    "c": format!("SkuB{}", suffix)
+                }
+            }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(resp["data"]["a"]["id"], item_ids[0]);
+        assert_eq!(resp["data"]["b"]["id"], item_ids[0]);
+        assert_eq!(resp["data"]["c"]["id"], item_ids[1]);
+
+        // check that a missing sku is not found
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": r#"query ($sku: String!) { itemBySku(sku: $sku) { id } }"#,
+                "variables": { "sku": format!("SkuC{}", suffix) }
+            }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        assert_eq!(resp["errors"][0]["extensions"]["code"], "NOT_FOUND");
+    }
+
+    #[actix_rt::test]
+    async fn test_dashboard() {
+        let app = test_server!();
+        let dashboard = || {
+            test::TestRequest::post()
+                .uri("/graphql")
+                .insert_header(http::header::ContentType::json())
+                .set_json(serde_json::json!({
+                    "query": r#"{ dashboard {
+                        totalItems totalLocations totalQuantity lowStockCount recentTransactionCount
+                    } }"#
+                }))
+                .to_request()
+        };
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, dashboard()).await;
+        let before = resp["data"]["dashboard"].clone();
+
+        // create test items, one of which is below its reorder point, and a test location
+        let mut item_ids = Vec::new();
+        for item in [
+            serde_json::json!({ "name": "TestItem" }),
+            serde_json::json!({ "name": "TestItem", "reorderPoint": 5 }),
+        ] {
+            let req = test::TestRequest::post()
+                .uri("/graphql")
+                .insert_header(http::header::ContentType::json())
+                .set_json(serde_json::json!({
+                    "query": r#"mutation ($item: InsertableItem!) { createItem(item: $item) { id } }"#,
+                    "variables": { "item": item }
+                }))
+                .to_request();
+            let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+            item_ids.push(resp["data"]["createItem"]["id"].as_i64().unwrap());
+        }
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": r#"mutation { createLocation(location: { name: "TestLocation" }) { id } }"#
+            }))
+            .to_request();
+        test::call_service(&app, req).await;
+
+        // create test transactions, one of which is not recent
+        let now = chrono::Utc::now();
+        for (item_id, quantity, date) in [
+            (item_ids[0], 10, now),
+            (item_ids[1], 2, now),
+            (item_ids[0], -1, now - chrono::Duration::days(30)),
+        ] {
+            let req = test::TestRequest::post()
+                .uri("/graphql")
+                .insert_header(http::header::ContentType::json())
+                .set_json(serde_json::json!({
+                    "query": r#"mutation ($transaction: InsertableTransaction!) { createTransaction(transaction: $transaction) { id } }"#,
+                    "variables": { "transaction": {
+                        "itemId": item_id,
+                        "quantity": quantity,
+                        "transactionDate": date.to_rfc3339()
+                    } }
+                }))
+                .to_request();
+            test::call_service(&app, req).await;
+        }
+
+        // check that the aggregates changed by the seeded data
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, dashboard()).await;
+        let after = &resp["data"]["dashboard"];
+        for (field, change) in [
+            ("totalItems", 2),
+            ("totalLocations", 1),
+            ("totalQuantity", 11),
+            ("lowStockCount", 1),
+            ("recentTransactionCount", 2),
+        ] {
+            assert_eq!(
+                after[field].as_i64().unwrap() - before[field].as_i64().unwrap(),
+                change,
+                "{}",
+                field
+            );
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_recent_modifications() {
+        let schema = graphql::schema_builder()
+            .data(get_context(&Config::from_env().unwrap()).await)
+            .finish();
+        let since = chrono::Utc::now().to_rfc3339();
+        let suffix = chrono::Utc::now().timestamp_nanos();
+
+        // create and update a test item, which records three modifications
+        let resp = schema
+            .execute(format!(
+                r#"mutation {{ createItem(item: {{ name: "TestItem{}" }}) {{ id }} }}"#,
+                suffix
+            ))
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let item_id = resp["createItem"]["id"].as_i64().unwrap();
+        for i in 1..=2 {
+            let resp = schema
+                .execute(format!(
+                    r#"mutation {{ updateItem(id: {}, item: {{ name: "UpdatedItem{}-{}" }}) {{ id }} }}"#,
+                    item_id, suffix, i
+                ))
+                .await;
+            assert!(resp.errors.is_empty());
+        }
+
+        let recent_modifications = |args: String| {
+            let schema = schema.clone();
+            let query = format!(
+                r#"{{ recentModifications(entity: ITEM, since: "{}"{}) {{ id modification data }} }}"#,
+                since, args
+            );
+            async move {
+                schema
+                    .execute(query)
+                    .await
+                    .into_result()
+                    .unwrap()
+                    .data
+                    .into_json()
+                    .unwrap()["recentModifications"]
+                    .as_array()
+                    .unwrap()
+                    .clone()
+            }
+        };
+
+        // check that the first page has the most recent modifications
+        let page = recent_modifications(", first: 2".to_string()).await;
+        assert_eq!(
+            page.iter()
+                .map(|modification| (
+                    modification["modification"].as_str().unwrap(),
+                    modification["data"]["name"].as_str().unwrap().to_string()
+                ))
+                .collect::<Vec<_>>(),
+            vec![
+                ("UPDATE", format!("UpdatedItem{}-2", suffix)),
+                ("UPDATE", format!("UpdatedItem{}-1", suffix)),
+            ]
+        );
+
+        // check that the next page continues after the last modification
+        let page = recent_modifications(format!(", first: 2, after: {}", page[1]["id"])).await;
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0]["modification"], "CREATE");
+        assert_eq!(page[0]["data"]["name"], format!("TestItem{}", suffix));
+        let page = recent_modifications(format!(", after: {}", page[0]["id"])).await;
+        assert!(page.is_empty());
+
+        // check that modifications are filtered by type
+        let page = recent_modifications(", types: [CREATE, DELETE]".to_string()).await;
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0]["data"]["id"], item_id);
+    }
+
+    #[actix_rt::test]
+    async fn test_create_locations() {
+        let schema = graphql::schema_builder()
+            .data(get_context(&Config::from_env().unwrap()).await)
+            .finish();
+        let suffix = chrono::Utc::now().timestamp_nanos();
+
+        // create locations in one mutation, and check that they are returned in order
+        let resp = schema
+            .execute(format!(
+                r#"mutation {{ createLocations(locations: [
+                    {{ name: "Warehouse{0}-1" }},
+                    {{ name: "Warehouse{0}-2", address: "2 Test St", lat: 43.65, lng: -79.38 }},
+                    {{ name: "Warehouse{0}-3" }}
+                ]) {{ id name address }} }}"#,
+                suffix
+            ))
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let created = resp["createLocations"].as_array().unwrap();
+        assert_eq!(
+            created
+                .iter()
+                .map(|location| location["name"].as_str().unwrap().to_string())
+                .collect::<Vec<String>>(),
+            (1..=3)
+                .map(|i| format!("Warehouse{}-{}", suffix, i))
+                .collect::<Vec<String>>()
+        );
+        assert_eq!(created[1]["address"], "2 Test St");
+
+        // check that an invalid location names its index, and that no locations are created
+        let resp = schema
+            .execute(format!(
+                r#"mutation {{ createLocations(locations: [
+                    {{ name: "Invalid{0}-1" }},
+                    {{ name: "Invalid{0}-2", lat: 43.65 }}
+                ]) {{ id }} }}"#,
+                suffix
+            ))
+            .await
+            .into_result()
+            .unwrap_err();
+        assert_eq!(
+            resp[0].extensions.as_ref().unwrap().get("index"),
+            Some(&async_graphql::Value::from("1"))
+        );
+        let resp = schema
+            .execute(r#"{ locations { name } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert!(!resp["locations"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|location| location["name"]
+                .as_str()
+                .unwrap()
+                .starts_with(&format!("Invalid{}", suffix))));
+    }
+
+    #[actix_rt::test]
+    async fn test_create_transactions_all_or_nothing() {
+        let schema = graphql::schema_builder()
+            .data(get_context(&Config::from_env().unwrap()).await)
+            .finish();
+
+        let resp = schema
+            .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let item_id = resp["createItem"]["id"].as_i64().unwrap();
+
+        // check that the transactions are created in order
+        let resp = schema
+            .execute(format!(
+                r#"mutation {{ createTransactions(transactions: [
+                    {{ itemId: {0}, quantity: 5 }},
+                    {{ itemId: {0}, quantity: -2 }}
+                ]) {{ created {{ itemId quantity }} errors {{ index }} }} }}"#,
+                item_id
+            ))
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(
+            resp["createTransactions"],
+            serde_json::json!({
+                "created": [
+                    { "itemId": item_id, "quantity": 5 },
+                    { "itemId": item_id, "quantity": -2 }
+                ],
+                "errors": []
+            })
+        );
+
+        // check that an invalid transaction names its index, and that no transactions are created
+        let resp = schema
+            .execute(format!(
+                r#"mutation {{ createTransactions(transactions: [
+                    {{ itemId: {}, quantity: 5 }},
+                    {{ itemId: -1, quantity: 5 }}
+                ], mode: ALL_OR_NOTHING) {{ created {{ id }} }} }}"#,
+                item_id
+            ))
+            .await
+            .into_result()
+            .unwrap_err();
+        assert_eq!(
+            resp[0].extensions.as_ref().unwrap().get("index"),
+            Some(&async_graphql::Value::from("1"))
+        );
+        let resp = schema
+            .execute(format!("{{ item(id: {}) {{ quantity }} }}", item_id))
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(resp["item"]["quantity"], 3);
+
+        schema
+            .execute(format!(
+                "mutation {{ deleteItem(id: {}) {{ id }} }}",
+                item_id
+            ))
+            .await;
+    }
+
+    #[actix_rt::test]
+    async fn test_create_transactions_best_effort() {
+        let schema = graphql::schema_builder()
+            .data(get_context(&Config::from_env().unwrap()).await)
+            .finish();
+
+        let resp = schema
+            .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let item_id = resp["createItem"]["id"].as_i64().unwrap();
+
+        // check that the valid transactions are created, and the invalid ones are reported
+        let resp = schema
+            .execute(format!(
+                r#"mutation {{ createTransactions(transactions: [
+                    {{ itemId: {0}, quantity: 5 }},
+                    {{ itemId: -1, quantity: 5 }},
+                    {{ itemId: {0}, quantity: 7 }}
+                ], mode: BEST_EFFORT) {{ created {{ quantity }} errors {{ index code message }} }} }}"#,
+                item_id
+            ))
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(
+            resp["createTransactions"],
+            serde_json::json!({
+                "created": [{ "quantity": 5 }, { "quantity": 7 }],
+                "errors": [{
+                    "index": 1,
+                    "code": "VALIDATION",
+                    "message": "validation errors on transaction: item with id ItemId(-1) not found"
+                }]
+            })
+        );
+        let resp = schema
+            .execute(format!("{{ item(id: {}) {{ quantity }} }}", item_id))
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(resp["item"]["quantity"], 12);
+
+        schema
+            .execute(format!(
+                "mutation {{ deleteItem(id: {}) {{ id }} }}",
+                item_id
+            ))
+            .await;
+    }
+
+    #[actix_rt::test]
+    async fn test_set_opening_balances() {
+        let schema = graphql::schema_builder()
+            .data(get_context(&Config::from_env().unwrap()).await)
+            .finish();
+
+        // create a test item with stock at the first of two test locations
+        let resp = schema
+            .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let item_id = resp["createItem"]["id"].as_i64().unwrap();
+        let mut location_ids = Vec::new();
+        for _ in 0..2 {
+            let resp = schema
+                .execute(
+                    r#"mutation { createLocation(location: { name: "TestLocation" }) { id } }"#,
+                )
+                .await
+                .into_result()
+                .unwrap()
+                .data
+                .into_json()
+                .unwrap();
+            location_ids.push(resp["createLocation"]["id"].as_i64().unwrap());
+        }
+        schema
+            .execute(format!(
+                "mutation {{ createTransaction(transaction: {{ itemId: {}, locationId: {}, quantity: 3 }}) {{ id }} }}",
+                item_id, location_ids[0]
+            ))
+            .await
+            .into_result()
+            .unwrap();
+
+        // check that each balance is set by a transaction of the difference
+        let set_opening_balances = format!(
+            r#"mutation {{ setOpeningBalances(balances: [
+                {{ itemId: {0}, locationId: {1}, quantity: 5 }},
+                {{ itemId: {0}, locationId: {2}, quantity: 2 }}
+            ]) {{ locationId quantity reason }} }}"#,
+            item_id, location_ids[0], location_ids[1]
+        );
+        let resp = schema
+            .execute(set_opening_balances.as_str())
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(
+            resp["setOpeningBalances"],
+            serde_json::json!([
+                { "locationId": location_ids[0], "quantity": 2, "reason": "OPENING_BALANCE" },
+                { "locationId": location_ids[1], "quantity": 2, "reason": "OPENING_BALANCE" }
+            ])
+        );
+
+        // check that setting the same balances again creates no transactions
+        let resp = schema
+            .execute(set_opening_balances.as_str())
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(resp["setOpeningBalances"], serde_json::json!([]));
+        let resp = schema
+            .execute(format!(
+                "{{ item(id: {}) {{ quantity transactions {{ id }} }} }}",
+                item_id
+            ))
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(resp["item"]["quantity"], 7);
+        assert_eq!(resp["item"]["transactions"].as_array().unwrap().len(), 3);
+
+        // check that a balance at an unknown location names its index, and that none are set
+        let resp = schema
+            .execute(format!(
+                r#"mutation {{ setOpeningBalances(balances: [
+                    {{ itemId: {0}, locationId: {1}, quantity: 1 }},
+                    {{ itemId: {0}, locationId: -1, quantity: 1 }}
+                ]) {{ id }} }}"#,
+                item_id, location_ids[0]
+            ))
+            .await
+            .into_result()
+            .unwrap_err();
+        let extensions = resp[0].extensions.as_ref().unwrap();
+        assert_eq!(
+            extensions.get("code"),
+            Some(&async_graphql::Value::from("VALIDATION"))
+        );
+        assert_eq!(
+            extensions.get("index"),
+            Some(&async_graphql::Value::from("1"))
+        );
+        let resp = schema
+            .execute(format!("{{ item(id: {}) {{ quantity }} }}", item_id))
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(resp["item"]["quantity"], 7);
+
+        schema
+            .execute(format!(
+                "mutation {{ deleteItem(id: {}) {{ id }} }}",
+                item_id
+            ))
+            .await;
+        for location_id in location_ids {
+            schema
+                .execute(format!(
+                    "mutation {{ deleteLocation(id: {}) {{ id }} }}",
+                    location_id
+                ))
+                .await;
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_transaction_broadcast_quantity() {
+        use futures::StreamExt;
+
+        let schema = graphql::schema_builder()
+            .data(get_context(&Config::from_env().unwrap()).await)
+            .finish();
+
+        // create a test item with a transaction before subscribing
+        let resp = schema
+            .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let item_id = resp["createItem"]["id"].clone();
+        let create_transaction = format!(
+            r#"mutation {{ createTransaction(transaction: {{ itemId: {}, quantity: 10 }}) {{ id }} }}"#,
+            item_id
+        );
+        assert!(schema
+            .execute(create_transaction.as_str())
+            .await
+            .errors
+            .is_empty());
+
+        // subscribe to item updates, then create another transaction once subscribed
+        let mut stream = schema.execute_stream(
+            r#"subscription { itemSubscription(types: [UPDATE]) { data { id quantity } } }"#,
+        );
+        let modify = async {
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+            schema.execute(create_transaction.as_str()).await
+        };
+        let receive = async {
+            while let Some(resp) = stream.next().await {
+                let data = resp.data.into_json().unwrap()["itemSubscription"]["data"].clone();
+                if data["id"] == item_id {
+                    return data;
+                }
+            }
+            panic!("subscription ended");
+        };
+        let (_, received) = tokio::time::timeout(std::time::Duration::from_secs(10), async {
+            futures::join!(modify, receive)
+        })
+        .await
+        .expect("update not received");
+
+        // check that the broadcast item includes the quantity of the created transaction
+        assert_eq!(received["quantity"], 20);
+    }
+
+    #[actix_rt::test]
+    async fn test_outbox_delivery_connections() {
+        use std::sync::atomic::Ordering;
+
+        use crate::model::modification::{self, ModificationEntity, ModificationType};
+
+        let context = get_context(&Config::from_env().unwrap()).await;
+        let schema = graphql::schema_builder().data(context.clone()).finish();
+        let outbox_metrics = || {
+            [
+                &metrics::OUTBOX.connections,
+                &metrics::OUTBOX.pipelines,
+                &metrics::OUTBOX.delivered,
+            ]
+            .map(|counter| counter.load(Ordering::Relaxed))
+        };
+
+        // create a test item and location, and deliver the outbox
+        let resp = schema
+            .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let item_id = resp["createItem"]["id"].clone();
+        let resp = schema
+            .execute(r#"mutation { createLocation(location: { name: "TestLocation" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let location_id = resp["createLocation"]["id"].clone();
+        model::outbox::deliver(&context.clients).await.unwrap();
+
+        // check that a transaction, which cascades to the transaction channels and the updates of
+        // its item and location, is published over one connection in one pipeline
+        let before = outbox_metrics();
+        schema
+            .execute(format!(
+                "mutation {{ createTransaction(transaction: {{ itemId: {}, locationId: {}, quantity: 1 }}) {{ id }} }}",
+                item_id, location_id
+            ))
+            .await
+            .into_result()
+            .unwrap();
+        let after = outbox_metrics();
+        assert_eq!(
+            [
+                after[0] - before[0],
+                after[1] - before[1],
+                after[2] - before[2]
+            ],
+            [1, 1, 4]
+        );
+
+        // commit more updates than are published in one batch, without delivering them
+        let mut tx = context.clients.postgres.begin().await.unwrap();
+        let item = item::fetch_item(&mut tx, serde_json::from_value(item_id.clone()).unwrap())
+            .await
+            .unwrap();
+        for _ in 0..model::outbox::DELIVERY_BATCH_SIZE + 1 {
+            modification::enqueue(
+                &mut tx,
+                ModificationEntity::Item,
+                ModificationType::Update,
+                &item,
+            )
+            .await
+            .unwrap();
+        }
+        tx.commit().await.unwrap();
+
+        // check that the delivery opens one connection for both of its batches
+        let delivery = model::outbox::deliver(&context.clients).await.unwrap();
+        assert_eq!(
+            delivery,
+            model::outbox::Delivery {
+                delivered: model::outbox::DELIVERY_BATCH_SIZE as usize + 1,
+                connections: 1,
+                pipelines: 2,
+            }
+        );
+
+        schema
+            .execute(format!(
+                "mutation {{ deleteItem(id: {}) {{ id }} }}",
+                item_id
+            ))
+            .await;
+        schema
+            .execute(format!(
+                "mutation {{ deleteLocation(id: {}) {{ id }} }}",
+                location_id
+            ))
+            .await;
+    }
+
+    #[actix_rt::test]
+    async fn test_outbox_delivery_serialized() {
+        use crate::model::modification::{self, ModificationEntity, ModificationType};
+
+        let context = get_context(&Config::from_env().unwrap()).await;
+        let schema = graphql::schema_builder().data(context.clone()).finish();
+        let undelivered = || async {
+            sqlx::query_scalar::<_, i64>("select count(*) from outbox where delivered_at is null")
+                .fetch_one(&*context.clients.postgres)
+                .await
+                .unwrap()
+        };
+
+        // create a test item, and deliver the outbox
+        let resp = schema
+            .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let item_id = resp["createItem"]["id"].clone();
+        model::outbox::deliver(&context.clients).await.unwrap();
+
+        // commit more updates than are published in one batch, without delivering them
+        let mut tx = context.clients.postgres.begin().await.unwrap();
+        let item = item::fetch_item(&mut tx, serde_json::from_value(item_id.clone()).unwrap())
+            .await
+            .unwrap();
+        for _ in 0..model::outbox::DELIVERY_BATCH_SIZE + 1 {
+            modification::enqueue(
+                &mut tx,
+                ModificationEntity::Item,
+                ModificationType::Update,
+                &item,
+            )
+            .await
+            .unwrap();
+        }
+        tx.commit().await.unwrap();
+
+        // check that nothing is delivered while another delivery is in progress
+        let mut lock_tx = context.clients.postgres.begin().await.unwrap();
+        sqlx::query("select pg_advisory_xact_lock($1)")
+            .bind(model::outbox::DELIVERY_LOCK_KEY)
+            .execute(&mut lock_tx)
+            .await
+            .unwrap();
+        let delivery = model::outbox::deliver(&context.clients).await.unwrap();
+        assert_eq!(delivery.delivered, 0);
+        lock_tx.rollback().await.unwrap();
+
+        // check that a flush after a change delivers one batch, and leaves the rest to the relay
+        model::outbox::flush(&context).await;
+        assert_eq!(undelivered().await, 1);
+        let delivery = model::outbox::deliver(&context.clients).await.unwrap();
+        assert_eq!(delivery.delivered, 1);
+        assert_eq!(undelivered().await, 0);
+
+        schema
+            .execute(format!(
+                "mutation {{ deleteItem(id: {}) {{ id }} }}",
+                item_id
+            ))
+            .await;
+    }
+
+    #[actix_rt::test]
+    async fn test_outbox_delivers_undelivered_modifications() {
+        use futures::StreamExt;
+
+        use crate::model::modification::{self, ModificationEntity, ModificationType};
+
+        let context = get_context(&Config::from_env().unwrap()).await;
+        let schema = graphql::schema_builder().data(context.clone()).finish();
+
+        // create a test item before subscribing
+        let resp = schema
+            .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let item_id = resp["createItem"]["id"].clone();
+
+        // subscribe to item updates, then commit an update without delivering it, as if the server
+        // stopped after the change was committed, and deliver the outbox as the relay would
+        let mut stream = schema.execute_stream(
+            r#"subscription { itemSubscription(types: [UPDATE]) { data { id name } } }"#,
+        );
+        let modify = async {
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+            let mut tx = context.clients.postgres.begin().await.unwrap();
+            let item = item::fetch_item(&mut tx, serde_json::from_value(item_id.clone()).unwrap())
+                .await
+                .unwrap();
+            modification::enqueue(
+                &mut tx,
+                ModificationEntity::Item,
+                ModificationType::Update,
+                &item,
+            )
+            .await
+            .unwrap();
+            tx.commit().await.unwrap();
+            model::outbox::deliver(&context.clients).await.unwrap()
+        };
+        let receive = async {
+            while let Some(resp) = stream.next().await {
+                let data = resp.data.into_json().unwrap()["itemSubscription"]["data"].clone();
+                if data["id"] == item_id {
+                    return data;
+                }
+            }
+            panic!("subscription ended");
+        };
+        let (_, received) = tokio::time::timeout(std::time::Duration::from_secs(10), async {
+            futures::join!(modify, receive)
+        })
+        .await
+        .expect("update not received");
+
+        // check that the committed update was delivered to the subscriber
+        assert_eq!(received["name"], "TestItem");
+
+        schema
+            .execute(format!(
+                "mutation {{ deleteItem(id: {}) {{ id }} }}",
+                item_id
+            ))
+            .await;
+    }
+
+    #[actix_rt::test]
+    async fn test_transaction_subscription_item() {
+        use futures::StreamExt;
+
+        let schema = graphql::schema_builder()
+            .data(get_context(&Config::from_env().unwrap()).await)
+            .finish();
+
+        // create a subscribed test item, and an unrelated one
+        let mut item_ids = Vec::new();
+        for _ in 0..2 {
+            let resp = schema
+                .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+                .await
+                .into_result()
+                .unwrap()
+                .data
+                .into_json()
+                .unwrap();
+            item_ids.push(resp["createItem"]["id"].as_i64().unwrap());
+        }
+        let create_transaction = |item_id: i64| {
+            format!(
+                r#"mutation {{ createTransaction(transaction: {{ itemId: {}, quantity: 10 }}) {{ id }} }}"#,
+                item_id
+            )
+        };
+
+        // subscribe to the transactions of the first item, then create a transaction of the
+        // unrelated item before one of the subscribed item once subscribed
+        let mut stream = schema.execute_stream(format!(
+            r#"subscription {{ transactionSubscription(itemId: {}) {{ data {{ id itemId }} }} }}"#,
+            item_ids[0]
+        ));
+        let modify = async {
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+            schema.execute(create_transaction(item_ids[1])).await;
+            schema
+                .execute(create_transaction(item_ids[0]))
+                .await
+                .data
+                .into_json()
+                .unwrap()["createTransaction"]["id"]
+                .clone()
+        };
+        let receive = async {
+            let resp = stream.next().await.expect("subscription ended");
+            resp.data.into_json().unwrap()["transactionSubscription"]["data"].clone()
+        };
+        let (transaction_id, received) =
+            tokio::time::timeout(std::time::Duration::from_secs(10), async {
+                futures::join!(modify, receive)
+            })
+            .await
+            .expect("transaction not received");
+
+        // check that the first transaction received is of the subscribed item
+        assert_eq!(
+            received,
+            serde_json::json!({ "id": transaction_id, "itemId": item_ids[0] })
+        );
+
+        for item_id in item_ids {
+            schema
+                .execute(format!(
+                    "mutation {{ deleteItem(id: {}) {{ id }} }}",
+                    item_id
+                ))
+                .await;
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_transactions_after_id() {
+        let schema = graphql::schema_builder()
+            .data(get_context(&Config::from_env().unwrap()).await)
+            .finish();
+
+        // create a test item with transactions
+        let resp = schema
+            .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let item_id = resp["createItem"]["id"].as_i64().unwrap();
+        let mut created_ids = Vec::new();
+        for _ in 0..5 {
+            let resp = schema
+                .execute(format!(
+                    r#"mutation {{ createTransaction(transaction: {{ itemId: {}, quantity: 10 }}) {{ id }} }}"#,
+                    item_id
+                ))
+                .await
+                .into_result()
+                .unwrap()
+                .data
+                .into_json()
+                .unwrap();
+            created_ids.push(resp["createTransaction"]["id"].as_i64().unwrap());
+        }
+
+        // iterate through the whole table by passing the last id of each page
+        let mut synced_ids = Vec::new();
+        let mut after_id = 0;
+        loop {
+            let resp = schema
+                .execute(format!(
+                    r#"{{ transactions(afterId: {}, first: 1000) {{ id }} }}"#,
+                    after_id
+                ))
+                .await
+                .into_result()
+                .unwrap()
+                .data
+                .into_json()
+                .unwrap();
+            let page = resp["transactions"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|transaction| transaction["id"].as_i64().unwrap())
+                .collect::<Vec<i64>>();
+            match page.last() {
+                Some(last_id) => after_id = *last_id,
+                None => break,
+            }
+            synced_ids.extend(page);
+        }
+
+        // check that every transaction is synced once, in the order of their ids
+        assert!(synced_ids.windows(2).all(|ids| ids[0] < ids[1]));
+        assert!(created_ids.iter().all(|id| synced_ids.contains(id)));
+
+        // check that pages of an item continue after the given id
+        let resp = schema
+            .execute(format!(
+                r#"{{ transactions(itemId: {}, afterId: {}, first: 2) {{ id }} }}"#,
+                item_id, created_ids[1]
+            ))
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(
+            resp["transactions"],
+            serde_json::json!([{ "id": created_ids[2] }, { "id": created_ids[3] }])
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_unique_violation_message() {
+        use crate::model::error::ModelError;
+
+        let context = get_context(&Config::from_env().unwrap()).await;
+        let sku = format!("UNIQUE-{}", chrono::Utc::now().timestamp_nanos());
+        let insert_item = || {
+            sqlx::query(r#"insert into items (name, sku) values ('TestItem', $1)"#)
+                .bind(&sku)
+                .execute(&*context.clients.postgres)
+        };
+
+        // check that the violated constraint is mapped to its friendly message
+        insert_item().await.unwrap();
+        let e = ModelError::from(insert_item().await.unwrap_err());
+        assert_eq!(
+            async_graphql::Error::from(e).message,
+            "an item with this sku already exists"
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_subscription_slow_consumer() {
+        use futures::StreamExt;
+        use redis::AsyncCommands;
+
+        use crate::graphql::fanout::LagPolicy;
+
+        let context = get_context(&Config::from_env().unwrap()).await;
+        let channel_name = format!("test-{}", chrono::Utc::now().timestamp_nanos());
+
+        for lag_policy in [LagPolicy::DropOldest, LagPolicy::Disconnect] {
+            let fanout = Fanout::new(
+                context.clients.redis.clone(),
+                FanoutConfig {
+                    buffer_size: 2,
+                    lag_policy,
+                },
+            );
+            let mut stream = fanout.subscribe(&channel_name).await.unwrap();
+
+            // publish more messages than are buffered, without the subscriber reading them
+            let mut redis_conn = context.clients.redis.get_async_connection().await.unwrap();
+            for i in 0..5 {
+                let _: () = redis_conn
+                    .publish(&channel_name, i.to_string())
+                    .await
+                    .unwrap();
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+
+            let received = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+                match lag_policy {
+                    LagPolicy::DropOldest => vec![stream.next().await, stream.next().await],
+                    LagPolicy::Disconnect => vec![stream.next().await],
+                }
+            })
+            .await
+            .expect("subscription stalled");
+
+            // check that the oldest messages are dropped, or that the subscriber is disconnected
+            match lag_policy {
+                LagPolicy::DropOldest => {
+                    assert_eq!(received, vec![Some("3".to_string()), Some("4".to_string())])
+                }
+                LagPolicy::Disconnect => assert_eq!(received, vec![None]),
+            }
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_subscription_release() {
+        let context = get_context(&Config::from_env().unwrap()).await;
+        let channel_name = format!("test-{}", chrono::Utc::now().timestamp_nanos());
+        let fanout = Fanout::new(
+            context.clients.redis.clone(),
+            FanoutConfig::from_config(&Config::from_env().unwrap()),
+        );
+
+        // check that subscribers of a channel share one redis subscription
+        let first = fanout.subscribe(&channel_name).await.unwrap();
+        let second = fanout.subscribe(&channel_name).await.unwrap();
+        assert!(wait_for_redis_subscribers(&context, &channel_name, 1).await);
+
+        // check that the redis subscription is kept until the last subscriber is dropped
+        drop(first);
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+        assert!(wait_for_redis_subscribers(&context, &channel_name, 1).await);
+        drop(second);
+        assert!(wait_for_redis_subscribers(&context, &channel_name, 0).await);
+
+        // check that the channel can be subscribed to again
+        let _third = fanout.subscribe(&channel_name).await.unwrap();
+        assert!(wait_for_redis_subscribers(&context, &channel_name, 1).await);
+    }
+
+    #[actix_rt::test]
+    async fn test_location_items_with_stock() {
+        let schema = graphql::schema_builder()
+            .data(get_context(&Config::from_env().unwrap()).await)
+            .finish();
+
+        // create two test locations and three test items
+        let mut location_ids = Vec::new();
+        for _ in 0..2 {
+            let resp = schema
+                .execute(
+                    r#"mutation { createLocation(location: { name: "TestLocation" }) { id } }"#,
+                )
+                .await
+                .into_result()
+                .unwrap()
+                .data
+                .into_json()
+                .unwrap();
+            location_ids.push(resp["createLocation"]["id"].as_i64().unwrap());
+        }
+        let mut item_ids = Vec::new();
+        for _ in 0..3 {
+            let resp = schema
+                .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+                .await
+                .into_result()
+                .unwrap()
+                .data
+                .into_json()
+                .unwrap();
+            item_ids.push(resp["createItem"]["id"].as_i64().unwrap());
+        }
+
+        // stock the first item at both locations, the second item at the first location, and
+        // take out all of the third item at the second location
+        for (item_id, location_id, quantity) in [
+            (item_ids[0], location_ids[0], 10),
+            (item_ids[0], location_ids[1], 5),
+            (item_ids[1], location_ids[0], 3),
+            (item_ids[2], location_ids[1], 4),
+            (item_ids[2], location_ids[1], -4),
+        ] {
+            let resp = schema
+                .execute(format!(
+                    r#"mutation {{ createTransaction(transaction: {{ itemId: {}, locationId: {}, quantity: {} }}) {{ id }} }}"#,
+                    item_id, location_id, quantity
+                ))
+                .await;
+            assert!(resp.errors.is_empty());
+        }
+
+        let items_with_stock = |location_id: i64, args: &str| {
+            format!(
+                r#"{{ location(id: {}) {{ itemsWithStock{} {{ stock {{ item {{ id }} quantity }} hasNextPage }} }} }}"#,
+                location_id, args
+            )
+        };
+
+        // check that each location has its own quantity of the item, and that the item which nets
+        // to zero is excluded
+        let resp = schema
+            .execute(items_with_stock(location_ids[1], ""))
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(
+            resp["location"]["itemsWithStock"],
+            serde_json::json!({
+                "stock": [{ "item": { "id": item_ids[0] }, "quantity": 5 }],
+                "hasNextPage": false
+            })
+        );
+
+        // check that the items at a location are paged
+        let resp = schema
+            .execute(items_with_stock(location_ids[0], "(first: 1)"))
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(
+            resp["location"]["itemsWithStock"],
+            serde_json::json!({
+                "stock": [{ "item": { "id": item_ids[0] }, "quantity": 10 }],
+                "hasNextPage": true
+            })
+        );
+        let resp = schema
+            .execute(items_with_stock(location_ids[0], "(first: 1, offset: 1)"))
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(
+            resp["location"]["itemsWithStock"],
+            serde_json::json!({
+                "stock": [{ "item": { "id": item_ids[1] }, "quantity": 3 }],
+                "hasNextPage": false
+            })
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_item_locations() {
+        let schema = graphql::schema_builder()
+            .data(get_context(&Config::from_env().unwrap()).await)
+            .finish();
+
+        // create two test locations, named so that the second sorts first, and a test item
+        let mut location_ids = Vec::new();
+        for name in ["TestLocationB", "TestLocationA"] {
+            let resp = schema
+                .execute(format!(
+                    r#"mutation {{ createLocation(location: {{ name: "{}" }}) {{ id }} }}"#,
+                    name
+                ))
+                .await
+                .into_result()
+                .unwrap()
+                .data
+                .into_json()
+                .unwrap();
+            location_ids.push(resp["createLocation"]["id"].as_i64().unwrap());
+        }
+        let resp = schema
+            .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let item_id = resp["createItem"]["id"].as_i64().unwrap();
+
+        // transact the item twice at the first location, once at the second, and once without a
+        // location
+        for location_id in [
+            Some(location_ids[0]),
+            Some(location_ids[0]),
+            Some(location_ids[1]),
+            None,
+        ] {
+            let location_id = location_id
+                .map(|location_id| format!(", locationId: {}", location_id))
+                .unwrap_or_default();
+            let resp = schema
+                .execute(format!(
+                    r#"mutation {{ createTransaction(transaction: {{ itemId: {}, quantity: 1{} }}) {{ id }} }}"#,
+                    item_id, location_id
+                ))
+                .await;
+            assert!(resp.errors.is_empty(), "{:?}", resp.errors);
+        }
+
+        // check that each location is listed once, in the order of their names
+        let resp = schema
+            .execute(format!(
+                "{{ item(id: {}) {{ locations {{ id name }} }} }}",
+                item_id
+            ))
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(
+            resp["item"]["locations"],
+            serde_json::json!([
+                { "id": location_ids[1], "name": "TestLocationA" },
+                { "id": location_ids[0], "name": "TestLocationB" }
+            ])
+        );
+
+        schema
+            .execute(format!(
+                "mutation {{ deleteItem(id: {}) {{ id }} }}",
+                item_id
+            ))
+            .await;
+        for location_id in location_ids {
+            schema
+                .execute(format!(
+                    "mutation {{ deleteLocation(id: {}) {{ id }} }}",
+                    location_id
+                ))
+                .await;
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_transfer_stock() {
+        let schema = graphql::schema_builder()
+            .data(get_context(&Config::from_env().unwrap()).await)
+            .finish();
+
+        // create a test item and two test locations
+        let resp = schema
+            .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let item_id = resp["createItem"]["id"].as_i64().unwrap();
+        let mut location_ids = Vec::new();
+        for _ in 0..2 {
+            let resp = schema
+                .execute(
+                    r#"mutation { createLocation(location: { name: "TestLocation" }) { id } }"#,
+                )
+                .await
+                .into_result()
+                .unwrap()
+                .data
+                .into_json()
+                .unwrap();
+            location_ids.push(resp["createLocation"]["id"].as_i64().unwrap());
+        }
+        let transfer_stock = |to_location_id: i64, quantity: i32| {
+            format!(
+                r#"mutation {{ transferStock(transfer: {{ itemId: {}, fromLocationId: {}, toLocationId: {}, quantity: {} }}) {{
+                    from {{ quantity reason location {{ id }} }}
+                    to {{ quantity reason location {{ id }} }}
+                }} }}"#,
+                item_id, location_ids[0], to_location_id, quantity
+            )
+        };
+
+        // check that a transfer creates a transaction out of and into the locations
+        let resp = schema
+            .execute(transfer_stock(location_ids[1], 4))
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(
+            resp["transferStock"],
+            serde_json::json!({
+                "from": { "quantity": -4, "reason": "TRANSFER", "location": { "id": location_ids[0] } },
+                "to": { "quantity": 4, "reason": "TRANSFER", "location": { "id": location_ids[1] } }
+            })
+        );
+
+        // check that transfers to the same location, or of non-positive quantities, are rejected
+        for (to_location_id, quantity, field) in [
+            (location_ids[0], 4, "toLocationId"),
+            (location_ids[1], 0, "quantity"),
+            (location_ids[1], -4, "quantity"),
+        ] {
+            let resp = schema
+                .execute(transfer_stock(to_location_id, quantity))
+                .await
+                .into_result()
+                .unwrap_err();
+            assert!(resp[0].extensions.as_ref().unwrap().get(field).is_some());
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_transfers() {
+        let schema = graphql::schema_builder()
+            .data(get_context(&Config::from_env().unwrap()).await)
+            .finish();
+
+        // create a test item and two test locations
+        let resp = schema
+            .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let item_id = resp["createItem"]["id"].as_i64().unwrap();
+        let mut location_ids = Vec::new();
+        for _ in 0..2 {
+            let resp = schema
+                .execute(
+                    r#"mutation { createLocation(location: { name: "TestLocation" }) { id } }"#,
+                )
+                .await
+                .into_result()
+                .unwrap()
+                .data
+                .into_json()
+                .unwrap();
+            location_ids.push(resp["createLocation"]["id"].as_i64().unwrap());
+        }
+
+        // transfer stock twice, checking that both transactions of each share a transfer group
+        let mut transfers = Vec::new();
+        for quantity in [4, 2] {
+            let resp = schema
+                .execute(format!(
+                    r#"mutation {{ transferStock(transfer: {{ itemId: {}, fromLocationId: {}, toLocationId: {}, quantity: {} }}) {{
+                        transferGroup
+                        from {{ id transferGroup }}
+                        to {{ id transferGroup }}
+                    }} }}"#,
+                    item_id, location_ids[0], location_ids[1], quantity
+                ))
+                .await
+                .into_result()
+                .unwrap()
+                .data
+                .into_json()
+                .unwrap();
+            let transfer = resp["transferStock"].clone();
+            assert!(transfer["transferGroup"].is_number());
+            assert_eq!(transfer["from"]["transferGroup"], transfer["transferGroup"]);
+            assert_eq!(transfer["to"]["transferGroup"], transfer["transferGroup"]);
+            transfers.push(transfer);
+        }
+
+        // check that a transfer is retrieved as a single record, by its transfer group
+        let resp = schema
+            .execute(format!(
+                r#"{{ transfer(transferGroup: {}) {{
+                    from {{ id location {{ id }} }}
+                    to {{ id location {{ id }} }}
+                    item {{ id }}
+                    quantity
+                    date
+                }} }}"#,
+                transfers[0]["transferGroup"]
+            ))
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let transfer = &resp["transfer"];
+        assert_eq!(
+            transfer["from"],
+            serde_json::json!({ "id": transfers[0]["from"]["id"], "location": { "id": location_ids[0] } })
+        );
+        assert_eq!(
+            transfer["to"],
+            serde_json::json!({ "id": transfers[0]["to"]["id"], "location": { "id": location_ids[1] } })
+        );
+        assert_eq!(transfer["item"]["id"], item_id);
+        assert_eq!(transfer["quantity"], 4);
+        assert!(transfer["date"].is_string());
+
+        // check that the transfers of the item are listed most recent first, and filtered by date
+        let transfers_since = |since: &str| {
+            format!(
+                r#"{{ transfers(itemId: {}, since: "{}") {{ transferGroup quantity }} }}"#,
+                item_id, since
+            )
+        };
+        let resp = schema
+            .execute(transfers_since("2000-01-01T00:00:00Z"))
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(
+            resp["transfers"],
+            serde_json::json!([
+                { "transferGroup": transfers[1]["transferGroup"], "quantity": 2 },
+                { "transferGroup": transfers[0]["transferGroup"], "quantity": 4 }
+            ])
+        );
+        let resp = schema
+            .execute(transfers_since("2100-01-01T00:00:00Z"))
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(resp["transfers"], serde_json::json!([]));
+
+        // check that a transfer without both of its transactions is not found
+        schema
+            .execute(format!(
+                "mutation {{ deleteTransaction(id: {}) {{ id }} }}",
+                transfers[0]["to"]["id"]
+            ))
+            .await;
+        let resp = schema
+            .execute(format!(
+                "{{ transfer(transferGroup: {}) {{ quantity }} }}",
+                transfers[0]["transferGroup"]
+            ))
+            .await;
+        assert_eq!(
+            resp.errors[0].extensions.as_ref().unwrap().get("code"),
+            Some(&async_graphql::Value::from("NOT_FOUND"))
+        );
+
+        // check that a restored transaction keeps its transfer group, so the transfer is found again
+        let resp = schema
+            .execute(format!(
+                "mutation {{ restoreTransaction(id: {}) {{ transferGroup }} }}",
+                transfers[0]["to"]["id"]
+            ))
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(
+            resp["restoreTransaction"]["transferGroup"],
+            transfers[0]["transferGroup"]
+        );
+        let resp = schema
+            .execute(format!(
+                "{{ transfer(transferGroup: {}) {{ quantity }} }}",
+                transfers[0]["transferGroup"]
+            ))
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(resp["transfer"]["quantity"], 4);
+
+        schema
+            .execute(format!(
+                "mutation {{ deleteItem(id: {}) {{ id }} }}",
+                item_id
+            ))
+            .await;
+        for location_id in location_ids {
+            schema
+                .execute(format!(
+                    "mutation {{ deleteLocation(id: {}) {{ id }} }}",
+                    location_id
+                ))
+                .await;
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_export_items_xlsx() {
+        let app = test_server!();
+        let name = format!("export <{}> & co", chrono::Utc::now().timestamp_nanos());
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": "mutation ($name: String!) { createItem(item: { name: $name }) { id } }",
+                "variables": { "name": name }
+            }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        let id = resp["data"]["createItem"]["id"].as_i64().unwrap();
+
+        let req = test::TestRequest::get()
+            .uri("/export/items.xlsx")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(http::header::CONTENT_TYPE).unwrap(),
+            export::XLSX_CONTENT_TYPE
+        );
+        assert_eq!(
+            resp.headers()
+                .get(http::header::CONTENT_DISPOSITION)
+                .unwrap(),
+            r#"attachment; filename="items.xlsx""#
+        );
+
+        // check that the sheet has the id of the item, and its name is a shared string
+        let body = test::read_body(resp).await;
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(body)).unwrap();
+        let mut read_entry = |name: &str| {
+            let mut contents = String::new();
+            std::io::Read::read_to_string(&mut archive.by_name(name).unwrap(), &mut contents)
+                .unwrap();
+            contents
+        };
+        assert!(read_entry("xl/worksheets/sheet1.xml").contains(&format!("<v>{}</v>", id)));
+        assert!(read_entry("xl/sharedStrings.xml").contains(
+            &name
+                .replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+        ));
+
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": format!("mutation {{ deleteItem(id: {}) {{ id }} }}", id)
+            }))
+            .to_request();
+        test::call_service(&app, req).await;
+    }
+
+    #[actix_rt::test]
+    async fn test_export_stock_snapshot_csv() {
+        let app = test_server!();
+        // create a test item with stock at a location, and stock without a location
+        let name = format!("bolts, \"3 inch\" {}", chrono::Utc::now().timestamp_nanos());
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": r#"mutation ($name: String!) {
+                    createItem(item: { name: $name }) { id }
+                    createLocation(location: { name: "TestLocation" }) { id }
+                }"#,
+                "variables": { "name": name }
+            }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        let item_id = resp["data"]["createItem"]["id"].as_i64().unwrap();
+        let location_id = resp["data"]["createLocation"]["id"].as_i64().unwrap();
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": format!(
+                    r#"mutation {{
+                        a: createTransaction(transaction: {{ itemId: {0}, locationId: {1}, quantity: 10 }}) {{ id }}
+                        b: createTransaction(transaction: {{ itemId: {0}, locationId: {1}, quantity: -3 }}) {{ id }}
+                        c: createTransaction(transaction: {{ itemId: {0}, quantity: 2 }}) {{ id }}
+                    }}"#,
+                    item_id, location_id
+                )
+            }))
+            .to_request();
+        let resp: serde_json::value::Value = test::call_and_read_body_json(&app, req).await;
+        assert!(resp["errors"].is_null(), "{}", resp);
+
+        let req = test::TestRequest::get()
+            .uri("/export/stock-snapshot.csv")
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert_eq!(resp.status(), http::StatusCode::OK);
+        assert_eq!(
+            resp.headers().get(http::header::CONTENT_TYPE).unwrap(),
+            export::CSV_CONTENT_TYPE
+        );
+        let generated_at = resp.headers().get(GENERATED_AT_HEADER).unwrap();
+        assert!(chrono::DateTime::parse_from_rfc3339(generated_at.to_str().unwrap()).is_ok());
+
+        // check that the item has a line for its stock at the location, then without a location
+        let body = String::from_utf8(test::read_body(resp).await.to_vec()).unwrap();
+        let mut lines = body.split_terminator("\r\n");
+        assert_eq!(
+            lines.next(),
+            Some("item_id,sku,name,location_id,location_name,quantity")
+        );
+        let escaped_name = format!("\"{}\"", name.replace('"', "\"\""));
+        assert_eq!(
+            lines
+                .filter(|line| line.starts_with(&format!("{},", item_id)))
+                .collect::<Vec<_>>(),
+            vec![
+                format!(
+                    "{},,{},{},TestLocation,7",
+                    item_id, escaped_name, location_id
+                ),
+                format!("{},,{},,,2", item_id, escaped_name),
+            ]
+        );
+
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({
+                "query": format!(
+                    "mutation {{ deleteItem(id: {}) {{ id }} deleteLocation(id: {}) {{ id }} }}",
+                    item_id, location_id
+                )
+            }))
+            .to_request();
+        test::call_service(&app, req).await;
+    }
+
+    #[actix_rt::test]
+    async fn test_validation_rules_sku_pattern() {
+        let mut context = get_context(&Config::from_env().unwrap()).await;
+        context.config = Arc::new(ModelConfig {
+            sku_pattern: Some(regex::Regex::new("^(?:[A-Z]{3}-[0-9]{4})$").unwrap()),
+            ..(*context.config).clone()
+        });
+        let schema = graphql::schema_builder().data(context.clone()).finish();
+
+        let resp = schema
+            .execute(r#"{ validationRules { type field required minLength pattern } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let rules = resp["validationRules"].as_array().unwrap();
+        let rule = |type_name: &str, field: &str| {
+            rules
+                .iter()
+                .find(|rule| rule["type"] == type_name && rule["field"] == field)
+                .unwrap()
+                .clone()
+        };
+        assert_eq!(
+            rule("Item", "name"),
+            serde_json::json!({
+                "type": "Item",
+                "field": "name",
+                "required": true,
+                "minLength": 1,
+                "pattern": null
+            })
+        );
+        // the reported pattern is the one the sku validator uses
+        assert_eq!(
+            rule("Item", "sku")["pattern"],
+            context.config.sku_pattern.as_ref().unwrap().as_str()
+        );
+
+        let resp = schema
+            .execute(r#"mutation { createItem(item: { name: "TestItem", sku: "abc-1" }) { id } }"#)
+            .await;
+        assert_eq!(
+            resp.errors[0].extensions.as_ref().unwrap().get("sku"),
+            Some(&async_graphql::Value::from(
+                r#"sku "abc-1" does not match the pattern ^(?:[A-Z]{3}-[0-9]{4})$"#
+            ))
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_transaction_revisions() {
+        let schema = graphql::schema_builder()
+            .data(get_context(&Config::from_env().unwrap()).await)
+            .finish();
+
+        // create a test item and transaction
+        let resp = schema
+            .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let item_id = resp["createItem"]["id"].as_i64().unwrap();
+        let resp = schema
+            .execute(format!(
+                r#"mutation {{ createTransaction(transaction: {{ itemId: {}, quantity: 5, comment: "before" }}) {{ id revisions {{ id }} }} }}"#,
+                item_id
+            ))
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let transaction_id = resp["createTransaction"]["id"].as_i64().unwrap();
+        assert_eq!(
+            resp["createTransaction"]["revisions"],
+            serde_json::json!([])
+        );
+
+        // an update records exactly one revision, with the values before the update
+        let resp = schema
+            .execute(format!(
+                r#"mutation {{ updateTransaction(id: {}, transaction: {{ itemId: {}, quantity: 7, comment: "after", reason: ADJUSTMENT }}) {{ quantity revisions {{ transactionId revision itemId quantity comment reason }} }} }}"#,
+                transaction_id, item_id
+            ))
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(resp["updateTransaction"]["quantity"], 7);
+        assert_eq!(
+            resp["updateTransaction"]["revisions"],
+            serde_json::json!([{
+                "transactionId": transaction_id,
+                "revision": "UPDATE",
+                "itemId": item_id,
+                "quantity": 5,
+                "comment": "before",
+                "reason": null
+            }])
+        );
+
+        // deleting records the values before the deletion, after the earlier revision
+        let resp = schema
+            .execute(format!(
+                r#"mutation {{ deleteTransaction(id: {}) {{ revisions {{ revision quantity comment }} }} }}"#,
+                transaction_id
+            ))
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(
+            resp["deleteTransaction"]["revisions"],
+            serde_json::json!([
+                { "revision": "UPDATE", "quantity": 5, "comment": "before" },
+                { "revision": "DELETE", "quantity": 7, "comment": "after" }
+            ])
+        );
+
+        schema
+            .execute(format!(
+                "mutation {{ deleteItem(id: {}) {{ id }} }}",
+                item_id
+            ))
+            .await;
+    }
+
+    #[actix_rt::test]
+    async fn test_restore_transaction() {
+        use futures::StreamExt;
+
+        let schema = graphql::schema_builder()
+            .data(get_context(&Config::from_env().unwrap()).await)
+            .finish();
+
+        // create a test item with a deleted transaction
+        let resp = schema
+            .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let item_id = resp["createItem"]["id"].as_i64().unwrap();
+        let resp = schema
+            .execute(format!(
+                r#"mutation {{ createTransaction(transaction: {{ itemId: {}, quantity: 5, comment: "restored" }}) {{ id }} }}"#,
+                item_id
+            ))
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let transaction_id = resp["createTransaction"]["id"].as_i64().unwrap();
+        let restore_transaction = format!(
+            "mutation {{ restoreTransaction(id: {}) {{ id quantity comment }} }}",
+            transaction_id
+        );
+        let resp = schema.execute(restore_transaction.as_str()).await;
+        assert_eq!(
+            resp.errors[0].extensions.as_ref().unwrap().get("code"),
+            Some(&async_graphql::Value::from("CONFLICT"))
+        );
+        let resp = schema
+            .execute(format!(
+                "mutation {{ deleteTransaction(id: {}) {{ id }} }}",
+                transaction_id
+            ))
+            .await;
+        assert!(resp.errors.is_empty(), "{:?}", resp.errors);
+
+        // subscribe to the transactions of the item, then restore the transaction once subscribed
+        let mut stream = schema.execute_stream(format!(
+            "subscription {{ transactionSubscription(itemId: {}) {{ modification data {{ id }} }} }}",
+            item_id
+        ));
+        let restore = async {
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+            schema.execute(restore_transaction.as_str()).await
+        };
+        let (resp, received) = tokio::time::timeout(std::time::Duration::from_secs(10), async {
+            futures::join!(restore, stream.next())
+        })
+        .await
+        .expect("restore not received");
+
+        // check that the transaction is restored with its id and values, and broadcast as a restore
+        assert_eq!(
+            resp.into_result().unwrap().data.into_json().unwrap()["restoreTransaction"],
+            serde_json::json!({ "id": transaction_id, "quantity": 5, "comment": "restored" })
+        );
+        assert_eq!(
+            received.unwrap().data.into_json().unwrap()["transactionSubscription"],
+            serde_json::json!({ "modification": "RESTORE", "data": { "id": transaction_id } })
+        );
+
+        // check that a transaction which was never deleted cannot be restored
+        let resp = schema
+            .execute("mutation { restoreTransaction(id: -1) { id } }")
+            .await;
+        assert_eq!(
+            resp.errors[0].extensions.as_ref().unwrap().get("code"),
+            Some(&async_graphql::Value::from("NOT_FOUND"))
+        );
+
+        schema
+            .execute(format!(
+                "mutation {{ deleteItem(id: {}) {{ id }} }}",
+                item_id
+            ))
+            .await;
+    }
+
+    #[actix_rt::test]
+    async fn test_item_quantity_as_of() {
+        let schema = graphql::schema_builder()
+            .data(get_context(&Config::from_env().unwrap()).await)
+            .finish();
+
+        // create a test item with transactions on several dates, and one without a date
+        let resp = schema
+            .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let item_id = resp["createItem"]["id"].as_i64().unwrap();
+        for (date, quantity) in [
+            (Some("2001-01-01T00:00:00Z"), 5),
+            (Some("2001-02-01T00:00:00Z"), -2),
+            (Some("2001-03-01T00:00:00Z"), 10),
+            (None, 100),
+        ] {
+            let date = date
+                .map(|date| format!(r#", transactionDate: "{}""#, date))
+                .unwrap_or_default();
+            let resp = schema
+                .execute(format!(
+                    r#"mutation {{ createTransaction(transaction: {{ itemId: {}, quantity: {}{} }}) {{ id }} }}"#,
+                    item_id, quantity, date
+                ))
+                .await;
+            assert!(resp.errors.is_empty(), "{:?}", resp.errors);
+        }
+
+        let resp = schema
+            .execute(format!(
+                r#"{{ item(id: {}) {{
+                    before: quantityAsOf(date: "2000-12-31T00:00:00Z")
+                    first: quantityAsOf(date: "2001-01-01T00:00:00Z")
+                    between: quantityAsOf(date: "2001-02-15T00:00:00Z")
+                    last: quantityAsOf(date: "2001-03-01T00:00:00Z")
+                    quantity
+                }} }}"#,
+                item_id
+            ))
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(
+            resp["item"],
+            serde_json::json!({
+                "before": 0,
+                "first": 5,
+                "between": 3,
+                "last": 13,
+                "quantity": 113
+            })
+        );
+
+        schema
+            .execute(format!(
+                "mutation {{ deleteItem(id: {}) {{ id }} }}",
+                item_id
+            ))
+            .await;
+    }
+
+    #[actix_rt::test]
+    async fn test_complexity_limit() {
+        let schema = graphql::schema_builder()
+            .limit_complexity(graphql::DEFAULT_COMPLEXITY_LIMIT)
+            .data(get_context(&Config::from_env().unwrap()).await)
+            .finish();
+
+        let resp = schema
+            .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let item_id = resp["createItem"]["id"].as_i64().unwrap();
+
+        // check that a narrow query of the expensive fields succeeds
+        let resp = schema
+            .execute(format!(
+                r#"{{
+                    item(id: {}) {{ quantityAsOf(date: "2001-01-01T00:00:00Z") }}
+                    transactionsSummary(groupBy: [REASON]) {{ count }}
+                }}"#,
+                item_id
+            ))
+            .await;
+        assert!(resp.errors.is_empty(), "{:?}", resp.errors);
+
+        // check that requesting an expensive field across many items is rejected before it runs
+        let items = (0..50)
+            .map(|i| {
+                format!(
+                    r#"item{}: item(id: {}) {{ quantityAsOf(date: "2001-01-01T00:00:00Z") }}"#,
+                    i, item_id
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let resp = schema.execute(format!("{{ {} }}", items)).await;
+        assert_eq!(resp.errors[0].message, "Query is too complex.");
+        assert_eq!(
+            resp.errors[0].extensions.as_ref().unwrap().get("code"),
+            Some(&async_graphql::Value::from("VALIDATION"))
+        );
+
+        // check that the same number of cheap fields is within the limit
+        let items = (0..50)
+            .map(|i| format!("item{}: item(id: {}) {{ quantity }}", i, item_id))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let resp = schema.execute(format!("{{ {} }}", items)).await;
+        assert!(resp.errors.is_empty(), "{:?}", resp.errors);
+
+        // check that an expensive field is counted for each element of a list, unless the list is
+        // bounded by a small page
+        let resp = schema
+            .execute(r#"{ items { quantityAsOf(date: "2001-01-01T00:00:00Z") } }"#)
+            .await;
+        assert_eq!(resp.errors[0].message, "Query is too complex.");
+        let resp = schema
+            .execute(r#"{ transactions { item { quantityAsOf(date: "2001-01-01T00:00:00Z") } } }"#)
+            .await;
+        assert_eq!(resp.errors[0].message, "Query is too complex.");
+        let resp = schema
+            .execute(
+                r#"{ transactions(first: 10) { item { quantityAsOf(date: "2001-01-01T00:00:00Z") } } }"#,
+            )
+            .await;
+        assert!(resp.errors.is_empty(), "{:?}", resp.errors);
+
+        // check that lists of cheap fields are within the limit
+        let resp = schema
+            .execute("{ items { id name quantity locations { id name } } }")
+            .await;
+        assert!(resp.errors.is_empty(), "{:?}", resp.errors);
+
+        schema
+            .execute(format!(
+                "mutation {{ deleteItem(id: {}) {{ id }} }}",
+                item_id
+            ))
+            .await;
+    }
+
+    /// A logger which keeps the messages logged during tests, so they can be checked.
+    struct CaptureLogger(std::sync::Mutex<Vec<String>>);
+
+    static CAPTURE_LOGGER: CaptureLogger = CaptureLogger(std::sync::Mutex::new(Vec::new()));
+
+    impl log::Log for CaptureLogger {
+        fn enabled(&self, metadata: &log::Metadata<'_>) -> bool {
+            metadata.level() <= log::max_level()
+        }
+
+        fn log(&self, record: &log::Record<'_>) {
+            self.0.lock().unwrap().push(format!(
+                "{} {}: {}",
+                record.level(),
+                record.target(),
+                record.args()
+            ));
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[actix_rt::test]
+    async fn test_slow_query_log() {
+        log::set_logger(&CAPTURE_LOGGER).unwrap();
+        log::set_max_level(log::LevelFilter::Warn);
+
+        let mut config = Config::from_env().unwrap();
+        config.slow_query = Some(std::time::Duration::from_millis(50));
+        let pool = db::get_pool(&config).await;
+
+        let suffix = chrono::Utc::now().timestamp_nanos();
+        let secret = format!("secret_{}", suffix);
+        sqlx::query(&format!(
+            "select pg_sleep(0.1), $1::text as slow_{}",
+            suffix
+        ))
+        .bind(&secret)
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query(&format!("select 1 as fast_{}", suffix))
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let logs = CAPTURE_LOGGER.0.lock().unwrap();
+        let slow = logs
+            .iter()
+            .find(|log| log.contains(&format!("slow_{}", suffix)))
+            .expect("the slow query is not logged");
+        assert!(slow.starts_with("WARN sqlx::query: "));
+        assert!(slow.contains("elapsed: "));
+        // bound parameters are not logged, and fast queries are not logged as warnings
+        assert!(!logs.iter().any(|log| log.contains(&secret)));
+        assert!(!logs
+            .iter()
+            .any(|log| log.contains(&format!("fast_{}", suffix))));
+    }
+
+    #[actix_rt::test]
+    async fn test_item_attachments() {
+        let schema = graphql::schema_builder()
+            .data(get_context(&Config::from_env().unwrap()).await)
+            .finish();
+
+        // create a test item
+        let resp = schema
+            .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let item_id = resp["createItem"]["id"].as_i64().unwrap();
+
+        // attachments without a sort index are added after the others
+        let mut attachment_ids = Vec::new();
+        for (url, sort_index) in [
+            ("https://cdn.example.com/front.png", ""),
+            ("https://cdn.example.com/back.png", ""),
+            ("https://cdn.example.com/cover.png", ", sortIndex: -1"),
+        ] {
+            let resp = schema
+                .execute(format!(
+                    r#"mutation {{ addItemAttachment(attachment: {{ itemId: {}, url: "{}"{} }}) {{ id }} }}"#,
+                    item_id, url, sort_index
+                ))
+                .await
+                .into_result()
+                .unwrap()
+                .data
+                .into_json()
+                .unwrap();
+            attachment_ids.push(resp["addItemAttachment"]["id"].as_i64().unwrap());
+        }
+
+        let resp = schema
+            .execute(format!(
+                r#"mutation {{ addItemAttachment(attachment: {{ itemId: {}, url: "cdn/front.png" }}) {{ id }} }}"#,
+                item_id
+            ))
+            .await;
+        assert_eq!(
+            resp.errors[0].extensions.as_ref().unwrap().get("url"),
+            Some(&async_graphql::Value::from(
+                r#"url "cdn/front.png" is not an absolute http or https url"#
+            ))
+        );
+
+        let query = format!(
+            r#"{{ item(id: {}) {{ attachments {{ url sortIndex }} }} }}"#,
+            item_id
+        );
+        let resp = schema
+            .execute(&query)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(
+            resp["item"]["attachments"],
+            serde_json::json!([
+                { "url": "https://cdn.example.com/cover.png", "sortIndex": -1 },
+                { "url": "https://cdn.example.com/front.png", "sortIndex": 0 },
+                { "url": "https://cdn.example.com/back.png", "sortIndex": 1 }
+            ])
+        );
+
+        // remove the first attachment that was added
+        let resp = schema
+            .execute(format!(
+                r#"mutation {{ removeItemAttachment(id: {}) {{ url }} }}"#,
+                attachment_ids[0]
+            ))
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(
+            resp["removeItemAttachment"]["url"],
+            "https://cdn.example.com/front.png"
+        );
+        let resp = schema
+            .execute(&query)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(
+            resp["item"]["attachments"],
+            serde_json::json!([
+                { "url": "https://cdn.example.com/cover.png", "sortIndex": -1 },
+                { "url": "https://cdn.example.com/back.png", "sortIndex": 1 }
+            ])
+        );
+
+        schema
+            .execute(format!(
+                "mutation {{ deleteItem(id: {}) {{ id }} }}",
+                item_id
+            ))
+            .await;
+    }
+
+    #[actix_rt::test]
+    async fn test_concurrent_outbound_transactions() {
+        let mut context = get_context(&Config::from_env().unwrap()).await;
+        context.config = Arc::new(ModelConfig {
+            allow_negative_stock: false,
+            ..(*context.config).clone()
+        });
+        let schema = graphql::schema_builder().data(context).finish();
+
+        // create a test item with 5 in stock
+        let resp = schema
+            .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let item_id = resp["createItem"]["id"].as_i64().unwrap();
+        let transaction = |quantity: i32| {
+            format!(
+                r#"mutation {{ createTransaction(transaction: {{ itemId: {}, quantity: {} }}) {{ id }} }}"#,
+                item_id, quantity
+            )
+        };
+        let resp = schema.execute(transaction(5)).await;
+        assert!(resp.errors.is_empty(), "{:?}", resp.errors);
+
+        // the outbound transactions together exceed the stock, so only one of them can succeed
+        let (first, second) = futures::join!(
+            schema.execute(transaction(-3)),
+            schema.execute(transaction(-3))
+        );
+        let failed = match (first.errors.is_empty(), second.errors.is_empty()) {
+            (true, false) => second,
+            (false, true) => first,
+            _ => panic!("{:?} {:?}", first.errors, second.errors),
+        };
+        assert_eq!(
+            failed.errors[0]
+                .extensions
+                .as_ref()
+                .unwrap()
+                .get("quantity"),
+            Some(&async_graphql::Value::from(format!(
+                "item with id ItemId({}) has only 2 in stock",
+                item_id
+            )))
+        );
+
+        let resp = schema
+            .execute(format!("{{ item(id: {}) {{ quantity }} }}", item_id))
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(resp["item"]["quantity"], 2);
+
+        schema
+            .execute(format!(
+                "mutation {{ deleteItem(id: {}) {{ id }} }}",
+                item_id
+            ))
+            .await;
+    }
+
+    #[actix_rt::test]
+    async fn test_locations_by_ids() {
+        let schema = graphql::schema_builder()
+            .data(get_context(&Config::from_env().unwrap()).await)
+            .finish();
+
+        // create test locations
+        let mut location_ids = Vec::new();
+        for name in ["Toronto", "Ottawa"] {
+            let resp = schema
+                .execute(format!(
+                    r#"mutation {{ createLocation(location: {{ name: "{}" }}) {{ id }} }}"#,
+                    name
+                ))
+                .await
+                .into_result()
+                .unwrap()
+                .data
+                .into_json()
+                .unwrap();
+            location_ids.push(resp["createLocation"]["id"].as_i64().unwrap());
+        }
+
+        // missing ids resolve to null, in the order of the requested ids
+        let resp = schema
+            .execute(format!(
+                "{{ locationsByIds(ids: [{}, {}, {}, {}]) {{ id name }} }}",
+                location_ids[1],
+                i32::MAX,
+                location_ids[0],
+                location_ids[1]
+            ))
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(
+            resp["locationsByIds"],
+            serde_json::json!([
+                { "id": location_ids[1], "name": "Ottawa" },
+                null,
+                { "id": location_ids[0], "name": "Toronto" },
+                { "id": location_ids[1], "name": "Ottawa" }
+            ])
+        );
+
+        // too many ids are rejected
+        let ids = vec![location_ids[0].to_string(); 101].join(", ");
+        let resp = schema
+            .execute(format!("{{ locationsByIds(ids: [{}]) {{ id }} }}", ids))
+            .await;
+        assert_eq!(resp.errors.len(), 1);
+
+        for location_id in location_ids {
+            schema
+                .execute(format!(
+                    "mutation {{ deleteLocation(id: {}) {{ id }} }}",
+                    location_id
+                ))
+                .await;
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_transactions_by_ids() {
+        let schema = graphql::schema_builder()
+            .data(get_context(&Config::from_env().unwrap()).await)
+            .finish();
+
+        // create a test item with transactions
+        let resp = schema
+            .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let item_id = resp["createItem"]["id"].as_i64().unwrap();
+        let mut transaction_ids = Vec::new();
+        for quantity in [5, -2] {
+            let resp = schema
+                .execute(format!(
+                    r#"mutation {{ createTransaction(transaction: {{ itemId: {}, quantity: {} }}) {{ id }} }}"#,
+                    item_id, quantity
+                ))
+                .await
+                .into_result()
+                .unwrap()
+                .data
+                .into_json()
+                .unwrap();
+            transaction_ids.push(resp["createTransaction"]["id"].as_i64().unwrap());
+        }
+
+        // missing ids resolve to null, in the order of the requested ids
+        let resp = schema
+            .execute(format!(
+                "{{ transactionsByIds(ids: [{}, {}, {}]) {{ id quantity }} }}",
+                i32::MAX,
+                transaction_ids[1],
+                transaction_ids[0]
+            ))
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(
+            resp["transactionsByIds"],
+            serde_json::json!([
+                null,
+                { "id": transaction_ids[1], "quantity": -2 },
+                { "id": transaction_ids[0], "quantity": 5 }
+            ])
+        );
+
+        // too many ids are rejected
+        let ids = vec![transaction_ids[0].to_string(); 101].join(", ");
+        let resp = schema
+            .execute(format!("{{ transactionsByIds(ids: [{}]) {{ id }} }}", ids))
+            .await;
+        assert_eq!(resp.errors.len(), 1);
+
+        schema
+            .execute(format!(
+                "mutation {{ deleteItem(id: {}) {{ id }} }}",
+                item_id
+            ))
+            .await;
+    }
+
+    #[actix_rt::test]
+    async fn test_error_codes() {
+        let schema = graphql::schema_builder()
+            .data(get_context(&Config::from_env().unwrap()).await)
+            .finish();
+
+        // each error has a code, whether it is raised by the model, the loaders, a guard, or
+        // while parsing, validating, or resolving arguments of the operation
+        let ids = vec!["0"; 101].join(", ");
+        let cases = [
+            ("{ items { id ", "VALIDATION"),
+            ("{ unknownField }", "VALIDATION"),
+            (r#"{ item(id: "a") { id } }"#, "VALIDATION"),
+            ("{ transaction(id: 0) { id } }", "NOT_FOUND"),
+            (
+                r#"mutation { createItem(item: { name: "" }) { id } }"#,
+                "VALIDATION",
+            ),
+            (r#"mutation { deleteLocation(id: 0) { id } }"#, "NOT_FOUND"),
+            (
+                r#"mutation { recalculateQuantities { quantity } }"#,
+                "FORBIDDEN",
+            ),
+        ];
+        let over_cap = format!("{{ locationsByIds(ids: [{}]) {{ id }} }}", ids);
+        for (query, code) in cases.into_iter().chain([(over_cap.as_str(), "VALIDATION")]) {
+            let resp = schema.execute(query).await;
+            assert_eq!(resp.errors.len(), 1, "{}", query);
+            assert_eq!(
+                resp.errors[0].extensions.as_ref().unwrap().get("code"),
+                Some(&async_graphql::Value::from(code)),
+                "{}: {}",
+                query,
+                resp.errors[0].message
+            );
+        }
+
+        // variables which cannot be parsed are invalid input
+        let resp = schema
+            .execute(
+                async_graphql::Request::new("query ($id: ItemId!) { item(id: $id) { id } }")
+                    .variables(async_graphql::Variables::from_json(
+                        serde_json::json!({ "id": "a" }),
+                    )),
+            )
+            .await;
+        assert_eq!(
+            resp.errors[0].extensions.as_ref().unwrap().get("code"),
+            Some(&async_graphql::Value::from("VALIDATION")),
+            "{}",
+            resp.errors[0].message
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_whitespace_normalization() {
+        let suffix = chrono::Utc::now().timestamp_nanos();
+        let mut context = get_context(&Config::from_env().unwrap()).await;
+        context.config = Arc::new(ModelConfig {
+            enforce_unique_item_name: true,
+            ..(*context.config).clone()
+        });
+        let schema = graphql::schema_builder().data(context).finish();
+        let create_item = |item: serde_json::Value| {
+            async_graphql::Request::new(
+                r#"mutation ($item: InsertableItem!) {
+                    createItem(item: $item) { id sku name supplier description }
+                }"#,
+            )
+            .variables(async_graphql::Variables::from_json(
+                serde_json::json!({ "item": item }),
+            ))
+        };
+
+        // names and suppliers are collapsed, skus (which cannot have spaces) are trimmed, and
+        // descriptions are only trimmed
+        let resp = schema
+            .execute(create_item(serde_json::json!({
+                "name": format!("  Test \t Widget  {} ", suffix),
+                "sku": format!(" WS-{} ", suffix),
+                "supplier": "\n ACME   Corp ",
+                "description": "  first line\n\n  second line  ",
+            })))
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let item_id = resp["createItem"]["id"].as_i64().unwrap();
+        assert_eq!(
+            resp["createItem"],
+            serde_json::json!({
+                "id": item_id,
+                "sku": format!("WS-{}", suffix),
+                "name": format!("Test Widget {}", suffix),
+                "supplier": "ACME Corp",
+                "description": "first line\n\n  second line",
+            })
+        );
+
+        // the normalized values are checked for uniqueness
+        let resp = schema
+            .execute(create_item(serde_json::json!({
+                "name": "TestItem",
+                "sku": format!("ws-{}", suffix),
+            })))
+            .await;
+        assert_eq!(
+            resp.errors[0].extensions.as_ref().unwrap().get("code"),
+            Some(&async_graphql::Value::from("CONFLICT"))
+        );
+        let resp = schema
+            .execute(create_item(serde_json::json!({
+                "name": format!("Test Widget   {}", suffix),
+            })))
+            .await;
+        assert_eq!(
+            resp.errors[0].extensions.as_ref().unwrap().get("name"),
+            Some(&async_graphql::Value::from(format!(
+                "name {:?} not unique",
+                format!("Test Widget {}", suffix)
+            )))
+        );
+
+        // names which are only whitespace are blank
+        let resp = schema
+            .execute(create_item(serde_json::json!({ "name": " \t " })))
+            .await;
+        assert_eq!(
+            resp.errors[0].extensions.as_ref().unwrap().get("name"),
+            Some(&async_graphql::Value::from("name must not be blank"))
+        );
+        let resp = schema
+            .execute(r#"mutation { createLocation(location: { name: "  " }) { id } }"#)
+            .await;
+        assert_eq!(
+            resp.errors[0].extensions.as_ref().unwrap().get("name"),
+            Some(&async_graphql::Value::from("name must not be blank"))
+        );
+
+        // location names are collapsed, and addresses and comments are only trimmed
+        let resp = schema
+            .execute(
+                r#"mutation { createLocation(location: { name: " Main   Depot ", address: " 1  Main St\n" }) { id name address } }"#,
+            )
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let location_id = resp["createLocation"]["id"].as_i64().unwrap();
+        assert_eq!(resp["createLocation"]["name"], "Main Depot");
+        assert_eq!(resp["createLocation"]["address"], "1  Main St");
+        let resp = schema
+            .execute(format!(
+                r#"mutation {{ createTransaction(transaction: {{ itemId: {}, quantity: 1, comment: "  counted  twice " }}) {{ comment }} }}"#,
+                item_id
+            ))
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(resp["createTransaction"]["comment"], "counted  twice");
+
+        schema
+            .execute(format!(
+                "mutation {{ deleteLocation(id: {}) {{ id }} }}",
+                location_id
+            ))
+            .await;
+        schema
+            .execute(format!(
+                "mutation {{ deleteItem(id: {}) {{ id }} }}",
+                item_id
+            ))
+            .await;
+    }
+
+    #[actix_rt::test]
+    async fn test_sku_format() {
+        let mut context = get_context(&Config::from_env().unwrap()).await;
+        context.config = Arc::new(ModelConfig {
+            sku_max_length: 8,
+            ..(*context.config).clone()
+        });
+        let schema = graphql::schema_builder().data(context).finish();
+
+        // check that overlong skus and skus with illegal characters are rejected after they are
+        // normalized, when items are created, upserted, or imported
+        for (sku, error) in [
+            (
+                " ABCDE-1234 ",
+                r#"sku "ABCDE-1234" is longer than 8 characters"#,
+            ),
+            (
+                "AB/12",
+                r#"sku "AB/12" has the character '/', but only letters, digits, '-' and '_' are allowed"#,
+            ),
+        ] {
+            for query in [
+                r#"mutation ($sku: String!) { createItem(item: { name: "TestItem", sku: $sku }) { id } }"#,
+                r#"mutation ($sku: String!) { upsertItemBySku(item: { name: "TestItem", sku: $sku }) { created } }"#,
+                r#"mutation ($sku: String!) { importItems(items: [{ name: "TestItem", sku: $sku }]) { conflicts { row } } }"#,
+            ] {
+                let resp = schema
+                    .execute(async_graphql::Request::new(query).variables(
+                        async_graphql::Variables::from_json(serde_json::json!({ "sku": sku })),
+                    ))
+                    .await;
+                assert_eq!(resp.errors.len(), 1, "{}", query);
+                let extensions = resp.errors[0].extensions.as_ref().unwrap();
+                assert_eq!(
+                    extensions.get("code"),
+                    Some(&async_graphql::Value::from("VALIDATION"))
+                );
+                assert_eq!(
+                    extensions.get("sku"),
+                    Some(&async_graphql::Value::from(error)),
+                    "{}",
+                    query
+                );
+            }
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_upsert_item_by_sku() {
+        let schema = graphql::schema_builder()
+            .data(get_context(&Config::from_env().unwrap()).await)
+            .finish();
+        let since = chrono::Utc::now().to_rfc3339();
+        let sku = format!("UPSERT-{}", chrono::Utc::now().timestamp_nanos());
+        let upsert_item = |item: serde_json::Value| {
+            async_graphql::Request::new(
+                r#"mutation ($item: InsertableItem!) {
+                    upsertItemBySku(item: $item) { created item { id sku name supplier } }
+                }"#,
+            )
+            .variables(async_graphql::Variables::from_json(
+                serde_json::json!({ "item": item }),
+            ))
+        };
+
+        // the first upsert creates the item
+        let resp = schema
+            .execute(upsert_item(
+                serde_json::json!({ "name": "TestItem", "sku": sku, "supplier": "ACME" }),
+            ))
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(resp["upsertItemBySku"]["created"], true);
+        let item_id = resp["upsertItemBySku"]["item"]["id"].as_i64().unwrap();
+
+        // the next upsert updates the item with the same sku, ignoring case
+        let resp = schema
+            .execute(upsert_item(
+                serde_json::json!({ "name": "TestItem Renamed", "sku": sku.to_lowercase() }),
+            ))
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(
+            resp["upsertItemBySku"],
+            serde_json::json!({
+                "created": false,
+                "item": {
+                    "id": item_id,
+                    "sku": sku.to_lowercase(),
+                    "name": "TestItem Renamed",
+                    "supplier": null
+                }
+            })
+        );
+
+        // check that a create and then an update of the item were broadcast
+        let resp = schema
+            .execute(format!(
+                r#"{{ recentModifications(entity: ITEM, since: "{}") {{ modification data }} }}"#,
+                since
+            ))
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(
+            resp["recentModifications"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .filter(|modification| modification["data"]["id"] == item_id)
+                .map(|modification| modification["modification"].as_str().unwrap())
+                .collect::<Vec<_>>(),
+            vec!["UPDATE", "CREATE"]
+        );
+
+        // a sku is required
+        let resp = schema
+            .execute(upsert_item(serde_json::json!({ "name": "TestItem" })))
+            .await;
+        assert_eq!(
+            resp.errors[0].extensions.as_ref().unwrap().get("code"),
+            Some(&async_graphql::Value::from("VALIDATION"))
+        );
+        assert!(resp.errors[0]
+            .extensions
+            .as_ref()
+            .unwrap()
+            .get("sku")
+            .is_some());
+
+        schema
+            .execute(format!(
+                "mutation {{ deleteItem(id: {}) {{ id }} }}",
+                item_id
+            ))
+            .await;
+    }
+
+    #[actix_rt::test]
+    async fn test_transactions_summary() {
+        let schema = graphql::schema_builder()
+            .data(get_context(&Config::from_env().unwrap()).await)
+            .finish();
+
+        // create a test item with transactions at test locations, dated in a range of their own
+        let resp = schema
+            .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let item_id = resp["createItem"]["id"].as_i64().unwrap();
+        let mut location_ids = Vec::new();
+        for _ in 0..2 {
+            let resp = schema
+                .execute(
+                    r#"mutation { createLocation(location: { name: "TestLocation" }) { id } }"#,
+                )
+                .await
+                .into_result()
+                .unwrap()
+                .data
+                .into_json()
+                .unwrap();
+            location_ids.push(resp["createLocation"]["id"].as_i64().unwrap());
+        }
+        for (location_id, reason, date, quantity) in [
+            (Some(location_ids[0]), "RECEIPT", "1987-01-05T00:00:00Z", 10),
+            (Some(location_ids[0]), "SALE", "1987-01-20T00:00:00Z", -3),
+            (Some(location_ids[1]), "RECEIPT", "1987-02-03T00:00:00Z", 7),
+            (None, "ADJUSTMENT", "1987-02-10T00:00:00Z", -1),
+        ] {
+            let location = location_id
+                .map(|location_id| format!(", locationId: {}", location_id))
+                .unwrap_or_default();
+            let resp = schema
+                .execute(format!(
+                    r#"mutation {{ createTransaction(transaction: {{ itemId: {}{}, reason: {}, transactionDate: "{}", quantity: {} }}) {{ id }} }}"#,
+                    item_id, location, reason, date, quantity
+                ))
+                .await;
+            assert!(resp.errors.is_empty(), "{:?}", resp.errors);
+        }
+
+        let summary = |group_by: &str| {
+            let schema = schema.clone();
+            let query = format!(
+                r#"{{ transactionsSummary(groupBy: [{}], from: "1987-01-01T00:00:00Z", to: "1988-01-01T00:00:00Z") {{
+                    itemId locationId reason period count totalQuantity
+                }} }}"#,
+                group_by
+            );
+            async move { schema.execute(query).await }
+        };
+
+        // check that transactions are grouped by a single dimension, with the others null
+        let resp = summary("ITEM")
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(
+            resp["transactionsSummary"],
+            serde_json::json!([{
+                "itemId": item_id,
+                "locationId": null,
+                "reason": null,
+                "period": null,
+                "count": 4,
+                "totalQuantity": 13
+            }])
+        );
+
+        // check that transactions are grouped by combinations of dimensions, in their order
+        let resp = summary("LOCATION, REASON")
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let groups = resp["transactionsSummary"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|group| {
+                (
+                    group["locationId"].as_i64(),
+                    group["reason"].as_str().unwrap().to_string(),
+                    group["count"].as_i64().unwrap(),
+                    group["totalQuantity"].as_i64().unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            groups,
+            vec![
+                (Some(location_ids[0]), "RECEIPT".to_string(), 1, 10),
+                (Some(location_ids[0]), "SALE".to_string(), 1, -3),
+                (Some(location_ids[1]), "RECEIPT".to_string(), 1, 7),
+                (None, "ADJUSTMENT".to_string(), 1, -1),
+            ]
+        );
+
+        // check that transactions are grouped by date buckets
+        let resp = summary("ITEM, MONTH")
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let groups = resp["transactionsSummary"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|group| {
+                (
+                    chrono::DateTime::parse_from_rfc3339(group["period"].as_str().unwrap())
+                        .unwrap(),
+                    group["count"].as_i64().unwrap(),
+                    group["totalQuantity"].as_i64().unwrap(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            groups,
+            vec![
+                (
+                    chrono::DateTime::parse_from_rfc3339("1987-01-01T00:00:00Z").unwrap(),
+                    2,
+                    7
+                ),
+                (
+                    chrono::DateTime::parse_from_rfc3339("1987-02-01T00:00:00Z").unwrap(),
+                    2,
+                    6
+                ),
+            ]
+        );
+
+        // check that only one date bucket can be grouped by
+        let resp = summary("DAY, MONTH").await;
+        assert_eq!(
+            resp.errors[0].extensions.as_ref().unwrap().get("code"),
+            Some(&async_graphql::Value::from("VALIDATION"))
+        );
+
+        schema
+            .execute(format!(
+                "mutation {{ deleteItem(id: {}) {{ id }} }}",
+                item_id
+            ))
+            .await;
+        for location_id in location_ids {
+            schema
+                .execute(format!(
+                    "mutation {{ deleteLocation(id: {}) {{ id }} }}",
+                    location_id
+                ))
+                .await;
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_transactions_summary_timezone() {
+        let schema = graphql::schema_builder()
+            .data(get_context(&Config::from_env().unwrap()).await)
+            .finish();
+
+        // create a test item with a transaction after midnight in UTC, but before midnight in
+        // Toronto
+        let resp = schema
+            .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let item_id = resp["createItem"]["id"].as_i64().unwrap();
+        let resp = schema
+            .execute(format!(
+                r#"mutation {{ createTransaction(transaction: {{ itemId: {}, transactionDate: "1986-03-02T03:00:00Z", quantity: 5 }}) {{ id }} }}"#,
+                item_id
+            ))
+            .await;
+        assert!(resp.errors.is_empty(), "{:?}", resp.errors);
+
+        let summary = |timezone: Option<&str>| {
+            let schema = schema.clone();
+            let timezone = timezone
+                .map(|timezone| format!(r#", timezone: "{}""#, timezone))
+                .unwrap_or_default();
+            let query = format!(
+                r#"{{ transactionsSummary(groupBy: [ITEM, DAY], from: "1986-03-01T00:00:00Z", to: "1986-03-03T00:00:00Z"{}) {{
+                    itemId period count
+                }} }}"#,
+                timezone
+            );
+            async move { schema.execute(query).await }
+        };
+        let period = |resp: async_graphql::Response| {
+            let resp = resp.into_result().unwrap().data.into_json().unwrap();
+            let group = resp["transactionsSummary"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|group| group["itemId"] == item_id)
+                .unwrap()
+                .clone();
+            chrono::DateTime::parse_from_rfc3339(group["period"].as_str().unwrap()).unwrap()
+        };
+
+        // check that the transaction is bucketed into the day of each time zone, starting at its
+        // local midnight
+        assert_eq!(
+            period(summary(None).await),
+            chrono::DateTime::parse_from_rfc3339("1986-03-02T00:00:00Z").unwrap()
+        );
+        assert_eq!(
+            period(summary(Some("UTC")).await),
+            chrono::DateTime::parse_from_rfc3339("1986-03-02T00:00:00Z").unwrap()
+        );
+        assert_eq!(
+            period(summary(Some("America/Toronto")).await),
+            chrono::DateTime::parse_from_rfc3339("1986-03-01T00:00:00-05:00").unwrap()
+        );
+
+        // check that an unknown time zone is rejected
+        let resp = summary(Some("Mars/Olympus_Mons")).await;
+        assert_eq!(
+            resp.errors[0].extensions.as_ref().unwrap().get("code"),
+            Some(&async_graphql::Value::from("VALIDATION"))
+        );
+
+        schema
+            .execute(format!(
+                "mutation {{ deleteItem(id: {}) {{ id }} }}",
+                item_id
+            ))
+            .await;
+    }
+
+    #[actix_rt::test]
+    async fn test_transactions_by_direction() {
+        let context = get_context(&Config::from_env().unwrap()).await;
+        let schema = graphql::schema_builder().data(context.clone()).finish();
+
+        // create a test item with inbound and outbound transactions
+        let resp = schema
+            .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let item_id = resp["createItem"]["id"].as_i64().unwrap();
+        for (quantity, date) in [
+            (5, "2001-01-01T00:00:00Z"),
+            (-2, "2001-01-02T00:00:00Z"),
+            (3, "2001-01-03T00:00:00Z"),
+        ] {
+            create_dated_transaction(&schema, item_id, quantity, date).await;
+        }
+
+        // a legacy transaction with a quantity of 0 cannot be created through the api
+        sqlx::query(
+            r#"
+            insert into transactions (item_id, transaction_date, quantity)
+            values ($1, '2001-01-04T00:00:00Z', 0)
+        "#,
+        )
+        .bind(item_id as i32)
+        .execute(&*context.clients.postgres)
+        .await
+        .unwrap();
+
+        // check that each direction only has transactions with quantities of its sign
+        for (direction, quantities) in [
+            ("INBOUND", serde_json::json!([3, 5])),
+            ("OUTBOUND", serde_json::json!([-2])),
+            ("ANY", serde_json::json!([0, 3, -2, 5])),
+        ] {
+            let resp = schema
+                .execute(format!(
+                    "{{ transactions(itemId: {}, direction: {}) {{ quantity }} }}",
+                    item_id, direction
+                ))
+                .await
+                .into_result()
+                .unwrap()
+                .data
+                .into_json()
+                .unwrap();
+            assert_eq!(
+                resp["transactions"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|transaction| transaction["quantity"].clone())
+                    .collect::<serde_json::Value>(),
+                quantities,
+                "{}",
+                direction
+            );
+        }
+
+        schema
+            .execute(format!(
+                "mutation {{ deleteItem(id: {}) {{ id }} }}",
+                item_id
+            ))
+            .await;
+    }
+
+    #[actix_rt::test]
+    async fn test_transactions_connection() {
+        let schema = graphql::schema_builder()
+            .data(get_context(&Config::from_env().unwrap()).await)
+            .finish();
+
+        // create a test item with transactions
+        let resp = schema
+            .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let item_id = resp["createItem"]["id"].as_i64().unwrap();
+        for quantity in 1..=5 {
+            let date = format!("2001-01-0{}T00:00:00Z", quantity);
+            create_dated_transaction(&schema, item_id, quantity, &date).await;
+        }
+        let connection = |direction: &str, offset: i64| {
+            let schema = schema.clone();
+            let query = format!(
+                r#"{{
+                    transactionsConnection(itemId: {}, direction: {}, first: 2, offset: {}) {{
+                        transactions {{ id }}
+                        totalCount
+                    }}
+                }}"#,
+                item_id, direction, offset
+            );
+            async move {
+                schema
+                    .execute(query)
+                    .await
+                    .into_result()
+                    .unwrap()
+                    .data
+                    .into_json()
+                    .unwrap()["transactionsConnection"]
+                    .clone()
+            }
+        };
+
+        // check that every page has the same total, including the empty page past the end
+        let mut ids = Vec::new();
+        for (offset, page_size) in [(0, 2), (2, 2), (4, 1), (6, 0)] {
+            let page = connection("ANY", offset).await;
+            assert_eq!(page["totalCount"], 5, "{}", offset);
+            let transactions = page["transactions"].as_array().unwrap();
+            assert_eq!(transactions.len(), page_size, "{}", offset);
+            ids.extend(
+                transactions
+                    .iter()
+                    .map(|transaction| transaction["id"].clone()),
+            );
+        }
+        ids.sort_by_key(|id| id.as_i64());
+        ids.dedup();
+        assert_eq!(ids.len(), 5);
+
+        // check that the total only counts transactions matching the filter
+        let page = connection("OUTBOUND", 0).await;
+        assert_eq!(page["totalCount"], 0);
+        assert_eq!(page["transactions"], serde_json::json!([]));
+
+        schema
+            .execute(format!(
+                "mutation {{ deleteItem(id: {}) {{ id }} }}",
+                item_id
+            ))
+            .await;
+    }
+
+    #[actix_rt::test]
+    async fn test_transactions_date_order() {
+        let context = get_context(&Config::from_env().unwrap()).await;
+        let schema = graphql::schema_builder().data(context.clone()).finish();
+
+        // create a test item with transactions which are not created in the order of their dates,
+        // two of which have the same date
+        let resp = schema
+            .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let item_id = resp["createItem"]["id"].as_i64().unwrap();
+        let mut transaction_ids = Vec::new();
+        for (quantity, date) in [
+            (1, "2001-03-01T00:00:00Z"),
+            (2, "2001-01-01T00:00:00Z"),
+            (3, "2001-02-01T00:00:00Z"),
+            (4, "2001-02-01T00:00:00Z"),
+        ] {
+            transaction_ids.push(create_dated_transaction(&schema, item_id, quantity, date).await);
+        }
+
+        // a legacy transaction without a date cannot be created through the api
+        let transaction_id = sqlx::query_scalar::<_, i32>(
+            r#"
+            insert into transactions (item_id, transaction_date, quantity) values ($1, null, 5)
+            returning id
+        "#,
+        )
+        .bind(item_id as i32)
+        .fetch_one(&*context.clients.postgres)
+        .await
+        .unwrap();
+        transaction_ids.push(transaction_id as i64);
+
+        // check that transactions are listed by date descending with undated transactions last,
+        // and transactions with the same date are listed by id descending, in every list
+        let expected_ids = serde_json::json!([
+            transaction_ids[0],
+            transaction_ids[3],
+            transaction_ids[2],
+            transaction_ids[1],
+            transaction_ids[4]
+        ]);
+        let resp = schema
+            .execute(format!(
+                r#"{{
+                    transactions(itemId: {0}) {{ id }}
+                    transactionsConnection(itemId: {0}, first: 10) {{ transactions {{ id }} }}
+                    item(id: {0}) {{ transactions {{ id }} }}
+                }}"#,
+                item_id
+            ))
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let ids = |transactions: &serde_json::Value| {
+            transactions
+                .as_array()
+                .unwrap()
+                .iter()
+                .map(|transaction| transaction["id"].clone())
+                .collect::<serde_json::Value>()
+        };
+        assert_eq!(ids(&resp["transactions"]), expected_ids);
+        assert_eq!(
+            ids(&resp["transactionsConnection"]["transactions"]),
+            expected_ids
+        );
+        assert_eq!(ids(&resp["item"]["transactions"]), expected_ids);
+
+        schema
+            .execute(format!(
+                "mutation {{ deleteItem(id: {}) {{ id }} }}",
+                item_id
+            ))
+            .await;
+    }
+
+    #[actix_rt::test]
+    async fn test_transactions_nulls_order() {
+        let context = get_context(&Config::from_env().unwrap()).await;
+        let schema = graphql::schema_builder().data(context.clone()).finish();
+
+        // create a test item with dated transactions, and one created without a date
+        let resp = schema
+            .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let item_id = resp["createItem"]["id"].as_i64().unwrap();
+        create_dated_transaction(&schema, item_id, 1, "2001-01-01T00:00:00Z").await;
+        create_dated_transaction(&schema, item_id, 2, "2001-02-01T00:00:00Z").await;
+        let resp = schema
+            .execute(format!(
+                r#"mutation {{ createTransaction(transaction: {{ itemId: {}, quantity: 3 }}) {{ transactionDate }} }}"#,
+                item_id
+            ))
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        // check that transactions created without a date are dated now
+        assert!(resp["createTransaction"]["transactionDate"].is_string());
+
+        // a legacy transaction without a date cannot be created through the api
+        sqlx::query(r#"insert into transactions (item_id, transaction_date, quantity) values ($1, null, 4)"#)
+            .bind(item_id as i32)
+            .execute(&*context.clients.postgres)
+            .await
+            .unwrap();
+
+        // check that undated transactions are last by default, and first when requested
+        for (query, quantities) in [
+            (
+                format!("{{ transactions(itemId: {}) {{ quantity }} }}", item_id),
+                serde_json::json!([3, 2, 1, 4]),
+            ),
+            (
+                format!(
+                    "{{ transactions(itemId: {}, nulls: LAST) {{ quantity }} }}",
+                    item_id
+                ),
+                serde_json::json!([3, 2, 1, 4]),
+            ),
+            (
+                format!(
+                    "{{ transactions(itemId: {}, nulls: FIRST) {{ quantity }} }}",
+                    item_id
+                ),
+                serde_json::json!([4, 3, 2, 1]),
+            ),
+        ] {
+            let resp = schema
+                .execute(query.as_str())
+                .await
+                .into_result()
+                .unwrap()
+                .data
+                .into_json()
+                .unwrap();
+            assert_eq!(
+                resp["transactions"]
+                    .as_array()
+                    .unwrap()
+                    .iter()
+                    .map(|transaction| transaction["quantity"].clone())
+                    .collect::<serde_json::Value>(),
+                quantities,
+                "{}",
+                query
+            );
+        }
+
+        // check that the transactions of the item are also listed with undated transactions last
+        let resp = schema
+            .execute(format!(
+                "{{ item(id: {}) {{ transactions {{ quantity }} }} }}",
+                item_id
+            ))
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(
+            resp["item"]["transactions"],
+            serde_json::json!([
+                { "quantity": 3 },
+                { "quantity": 2 },
+                { "quantity": 1 },
+                { "quantity": 4 }
+            ])
+        );
+
+        schema
+            .execute(format!(
+                "mutation {{ deleteItem(id: {}) {{ id }} }}",
+                item_id
+            ))
+            .await;
+    }
+
+    #[actix_rt::test]
+    async fn test_subscription_throttle() {
+        use futures::StreamExt;
+
+        let schema = graphql::schema_builder()
+            .data(get_context(&Config::from_env().unwrap()).await)
+            .finish();
+        let min_interval = std::time::Duration::from_millis(200);
+        let updates = 30;
+
+        // create a test item
+        let resp = schema
+            .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let item_id = resp["createItem"]["id"].clone();
+
+        // subscribe to throttled item updates, then update the item rapidly once subscribed
+        let mut stream = schema.execute_stream(format!(
+            r#"subscription {{ itemSubscription(types: [UPDATE], minIntervalMs: {}) {{ data {{ id name }} }} }}"#,
+            min_interval.as_millis()
+        ));
+        let modify = async {
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+            let started = std::time::Instant::now();
+            for i in 1..=updates {
+                let resp = schema
+                    .execute(format!(
+                        r#"mutation {{ updateItem(id: {}, item: {{ name: "TestItem {}" }}) {{ id }} }}"#,
+                        item_id, i
+                    ))
+                    .await;
+                assert!(resp.errors.is_empty(), "{:?}", resp.errors);
+                tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+            }
+            started.elapsed()
+        };
+        let receive = async {
+            let mut received = Vec::new();
+            while let Some(resp) = stream.next().await {
+                let data = resp.data.into_json().unwrap()["itemSubscription"]["data"].clone();
+                if data["id"] == item_id {
+                    received.push((std::time::Instant::now(), data["name"].clone()));
+                    if data["name"] == format!("TestItem {}", updates) {
+                        return received;
+                    }
+                }
+            }
+            panic!("subscription ended");
+        };
+        let (elapsed, received) = tokio::time::timeout(std::time::Duration::from_secs(10), async {
+            futures::join!(modify, receive)
+        })
+        .await
+        .expect("latest update not received");
+
+        // check that the updates were coalesced into at most one per interval, ending with the
+        // latest state, allowing for the timer resolution
+        let intervals = (elapsed.as_millis() / min_interval.as_millis()) as usize;
+        assert!(received.len() <= intervals + 2, "{:?}", received);
+        assert!(received.len() < updates);
+        for window in received.windows(2) {
+            assert!(
+                window[1].0 - window[0].0 >= min_interval - std::time::Duration::from_millis(20),
+                "{:?}",
+                received
+            );
+        }
+
+        schema
+            .execute(format!(
+                "mutation {{ deleteItem(id: {}) {{ id }} }}",
+                item_id
+            ))
+            .await;
+    }
+
+    #[actix_rt::test]
+    async fn test_supplier_summary() {
+        let schema = graphql::schema_builder()
+            .data(get_context(&Config::from_env().unwrap()).await)
+            .finish();
+        let suffix = chrono::Utc::now().timestamp_nanos();
+        let supplier_a = format!("Supplier A {}", suffix);
+        let supplier_b = format!("Supplier B {}", suffix);
+
+        // create test items across suppliers, with transactions
+        let mut item_ids = Vec::new();
+        for (supplier, quantities) in [
+            (Some(&supplier_a), vec![5]),
+            (Some(&supplier_a), vec![]),
+            (Some(&supplier_a), vec![4, -1]),
+            (Some(&supplier_b), vec![7]),
+            (None, vec![2]),
+        ] {
+            let supplier = supplier
+                .map(|supplier| format!(r#", supplier: "{}""#, supplier))
+                .unwrap_or_default();
+            let resp = schema
+                .execute(format!(
+                    r#"mutation {{ createItem(item: {{ name: "TestItem"{} }}) {{ id }} }}"#,
+                    supplier
+                ))
+                .await
+                .into_result()
+                .unwrap()
+                .data
+                .into_json()
+                .unwrap();
+            let item_id = resp["createItem"]["id"].as_i64().unwrap();
+            for quantity in quantities {
+                let resp = schema
+                    .execute(format!(
+                        r#"mutation {{ createTransaction(transaction: {{ itemId: {}, quantity: {} }}) {{ id }} }}"#,
+                        item_id, quantity
+                    ))
+                    .await;
+                assert!(resp.errors.is_empty(), "{:?}", resp.errors);
+            }
+            item_ids.push(item_id);
+        }
+
+        let resp = schema
+            .execute("{ supplierSummary { supplier itemCount totalQuantity } }")
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let summaries = resp["supplierSummary"].as_array().unwrap();
+
+        // check that the suppliers are counted, with the most items first
+        let position = |supplier: &str| {
+            summaries
+                .iter()
+                .position(|summary| summary["supplier"] == supplier)
+                .unwrap()
+        };
+        assert_eq!(
+            summaries[position(&supplier_a)],
+            serde_json::json!({ "supplier": supplier_a, "itemCount": 3, "totalQuantity": 8 })
+        );
+        assert_eq!(
+            summaries[position(&supplier_b)],
+            serde_json::json!({ "supplier": supplier_b, "itemCount": 1, "totalQuantity": 7 })
+        );
+        assert!(position(&supplier_a) < position(&supplier_b));
+
+        // check that items without a supplier are grouped under a null supplier
+        assert_eq!(
+            summaries
+                .iter()
+                .filter(|summary| summary["supplier"].is_null())
+                .count(),
+            1
+        );
+
+        for item_id in item_ids {
+            schema
+                .execute(format!(
+                    "mutation {{ deleteItem(id: {}) {{ id }} }}",
+                    item_id
+                ))
+                .await;
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_maintenance_mode() {
+        use crate::graphql::auth::Role;
+
+        // maintenance mode is forced by the configuration rather than set in redis, which is
+        // shared with the other tests
+        let mut context = get_context(&Config::from_env().unwrap()).await;
+        context.config = Arc::new(ModelConfig {
+            maintenance_mode: true,
+            ..(*context.config).clone()
+        });
+        let schema = graphql::schema_builder().data(context).finish();
+
+        // check that mutations are rejected, while queries succeed
+        let resp = schema
+            .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+            .await;
+        assert_eq!(
+            resp.errors[0].extensions.as_ref().unwrap().get("code"),
+            Some(&async_graphql::Value::from("MAINTENANCE"))
+        );
+        let resp = schema
+            .execute("{ maintenanceMode items { id } }")
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(resp["maintenanceMode"], true);
+        assert!(resp["items"].is_array());
+
+        // check that maintenance mode can still be set by the admin, but stays on while it is
+        // forced by the configuration
+        let resp = schema
+            .execute("mutation { setMaintenanceMode(enabled: false) }")
+            .await;
+        assert_eq!(
+            resp.errors[0].extensions.as_ref().unwrap().get("code"),
+            Some(&async_graphql::Value::from("FORBIDDEN"))
+        );
+        let resp = schema
+            .execute(
+                async_graphql::Request::new("mutation { setMaintenanceMode(enabled: false) }")
+                    .data(Role::Admin),
+            )
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(resp["setMaintenanceMode"], true);
+
+        // check that mutations are accepted when maintenance mode is off
+        let schema = graphql::schema_builder()
+            .data(get_context(&Config::from_env().unwrap()).await)
+            .finish();
+        let resp = schema
+            .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        schema
+            .execute(format!(
+                "mutation {{ deleteItem(id: {}) {{ id }} }}",
+                resp["createItem"]["id"]
+            ))
+            .await;
+    }
+
+    #[actix_rt::test]
+    async fn test_reset_all_data() {
+        use crate::graphql::auth::Role;
+
+        let mut context = get_context(&Config::from_env().unwrap()).await;
+        let reset_all_data =
+            || async_graphql::Request::new("mutation { resetAllData }").data(Role::Admin);
+
+        // check that the mutation requires the flag, even for the admin role
+        context.config = Arc::new(ModelConfig {
+            reset_enabled: false,
+            ..(*context.config).clone()
+        });
+        let schema = graphql::schema_builder().data(context.clone()).finish();
+        let resp = schema.execute(reset_all_data()).await;
+        assert_eq!(
+            resp.errors[0].extensions.as_ref().unwrap().get("code"),
+            Some(&async_graphql::Value::from("FORBIDDEN"))
+        );
+
+        // check that the mutation requires the admin role, even with the flag
+        context.config = Arc::new(ModelConfig {
+            reset_enabled: true,
+            ..(*context.config).clone()
+        });
+        let schema = graphql::schema_builder().data(context).finish();
+        let resp = schema.execute("mutation { resetAllData }").await;
+        assert_eq!(
+            resp.errors[0].extensions.as_ref().unwrap().get("code"),
+            Some(&async_graphql::Value::from("FORBIDDEN"))
+        );
+
+        // create a test item with a transaction at a location, which are reset
+        let resp = schema
+            .execute(r#"mutation { createLocation(location: { name: "TestLocation" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let location_id = resp["createLocation"]["id"].as_i64().unwrap();
+        let resp = schema
+            .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let item_id = resp["createItem"]["id"].as_i64().unwrap();
+        let resp = schema
+            .execute(format!(
+                r#"mutation {{ createTransaction(transaction: {{ itemId: {}, locationId: {}, quantity: 5 }}) {{ id }} }}"#,
+                item_id, location_id
+            ))
+            .await;
+        assert!(resp.errors.is_empty(), "{:?}", resp.errors);
+
+        let resp = schema
+            .execute(reset_all_data())
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(resp["resetAllData"], true);
+
+        // check that the data created after the reset is all there is, so it is isolated from the
+        // data of other tests
+        let dashboard = r#"{ dashboard { totalItems totalLocations totalQuantity } }"#;
+        let resp = schema
+            .execute(dashboard)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(
+            resp["dashboard"],
+            serde_json::json!({ "totalItems": 0, "totalLocations": 0, "totalQuantity": 0 })
+        );
+        let resp = schema
+            .execute(r#"mutation { createItem(item: { name: "TestItem" }) { id } }"#)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        let item_id = resp["createItem"]["id"].as_i64().unwrap();
+        let resp = schema
+            .execute(format!(
+                r#"mutation {{ createTransaction(transaction: {{ itemId: {}, quantity: 3 }}) {{ id }} }}"#,
+                item_id
+            ))
+            .await;
+        assert!(resp.errors.is_empty(), "{:?}", resp.errors);
+        let resp = schema
+            .execute(dashboard)
+            .await
+            .into_result()
+            .unwrap()
+            .data
+            .into_json()
+            .unwrap();
+        assert_eq!(
+            resp["dashboard"],
+            serde_json::json!({ "totalItems": 1, "totalLocations": 0, "totalQuantity": 3 })
+        );
+
+        schema
+            .execute(format!(
+                "mutation {{ deleteItem(id: {}) {{ id }} }}",
+                item_id
+            ))
+            .await;
+    }
+
+    #[actix_rt::test]
+    async fn test_operation_timeout() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::time::Duration;
+
+        use crate::graphql::timeout::OperationTimeout;
+
+        /// A query with a deliberately slow resolver.
+        struct SlowQuery;
+
+        #[async_graphql::Object]
+        impl SlowQuery {
+            /// Resolves after a second, recording that it completed.
+            async fn slow(&self, context: &async_graphql::Context<'_>) -> bool {
+                tokio::time::sleep(Duration::from_secs(1)).await;
+                context
+                    .data_unchecked::<Arc<AtomicBool>>()
+                    .store(true, Ordering::SeqCst);
+                true
+            }
+        }
+
+        let completed = Arc::new(AtomicBool::new(false));
+        let schema = async_graphql::Schema::build(
+            SlowQuery,
+            async_graphql::EmptyMutation,
+            async_graphql::EmptySubscription,
+        )
+        .extension(OperationTimeout::new(Duration::from_millis(50)))
+        .data(completed.clone())
+        .finish();
+
+        // check that the operation times out
+        let resp = schema.execute("{ slow }").await;
+        assert_eq!(resp.errors[0].message, "operation timed out after 50 ms");
+        assert_eq!(
+            resp.errors[0].extensions.as_ref().unwrap().get("code"),
+            Some(&async_graphql::Value::from("TIMEOUT"))
+        );
+
+        // check that the resolver was cancelled rather than left running
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert!(!completed.load(Ordering::SeqCst));
+    }
+
+    #[actix_rt::test]
+    async fn test_trace_spans() {
+        use std::sync::Mutex;
+
+        use futures::future::BoxFuture;
+        use opentelemetry::sdk::export::trace::{ExportResult, SpanData, SpanExporter};
+        use opentelemetry::sdk::trace::TracerProvider;
+        use opentelemetry::trace::{SpanId, TraceId, TracerProvider as _};
+        use tracing_subscriber::layer::SubscriberExt;
+
+        /// An exporter which keeps the spans it exports.
+        #[derive(Clone, Debug, Default)]
+        struct TestExporter(Arc<Mutex<Vec<SpanData>>>);
+
+        impl SpanExporter for TestExporter {
+            fn export(&mut self, batch: Vec<SpanData>) -> BoxFuture<'static, ExportResult> {
+                self.0.lock().unwrap().extend(batch);
+                Box::pin(futures::future::ready(Ok(())))
+            }
+        }
+
+        // export the spans of this test to a test exporter
+        let exporter = TestExporter::default();
+        let provider = TracerProvider::builder()
+            .with_simple_exporter(exporter.clone())
+            .build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+        let guard = tracing::subscriber::set_default(subscriber);
+
+        let context = get_context(&Config::from_env().unwrap()).await;
+        let schema = graphql::schema_builder()
+            .extension(async_graphql::extensions::Tracing)
+            .data(context.clone())
+            .finish();
+        let app = test::init_service(
+            App::new()
+                .app_data(web::Data::new(schema))
+                .app_data(web::Data::new(context))
+                .app_data(web::Data::new(AuthConfig { admin_token: None }))
+                .wrap_fn(telemetry::trace_request)
+                .service(web::resource("/graphql").route(web::post().to(graphql_route))),
+        )
+        .await;
+
+        // check that the request joins the trace of the traceparent header
+        let trace_id = "4bf92f3577b34da6a3ce929d0e0e4736";
+        let parent_id = "00f067aa0ba902b7";
+        let req = test::TestRequest::post()
+            .uri("/graphql")
+            .insert_header(("traceparent", format!("00-{}-{}-01", trace_id, parent_id)))
+            .insert_header(http::header::ContentType::json())
+            .set_json(serde_json::json!({ "query": "{ item(id: -1) { id } }" }))
+            .to_request();
+        let resp = test::call_service(&app, req).await;
+        assert!(resp.status().is_success());
+
+        // the spans are exported when the provider is shut down
+        drop(guard);
+        drop(provider);
+        let spans = exporter.0.lock().unwrap();
+        let request = spans
+            .iter()
+            .find(|span| span.name == telemetry::REQUEST_SPAN_NAME)
+            .unwrap();
+        assert_eq!(
+            request.span_context.trace_id(),
+            TraceId::from_hex(trace_id).unwrap()
+        );
+        assert_eq!(request.parent_span_id, SpanId::from_hex(parent_id).unwrap());
+        assert!(request
+            .attributes
+            .iter()
+            .any(|(key, value)| key.as_str() == "http.status_code" && value.as_str() == "200"));
+
+        // check that the operation, its fields and its database queries are in the same trace
+        for name in ["request", "execute", "field", "load"] {
+            assert!(
+                spans.iter().any(|span| span.name == name
+                    && span.span_context.trace_id() == request.span_context.trace_id()),
+                "missing span {}",
+                name
+            );
+        }
     }
 }
@@ -0,0 +1,215 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// The content type of the Prometheus text exposition format.
+pub(crate) const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// The upper bounds of the buckets of batch sizes, which include the loader yield count.
+const BATCH_SIZE_BUCKETS: [u64; 8] = [1, 5, 10, 25, 50, 100, 250, 500];
+
+/// The metrics of each loader by name, which are registered when the loader is created.
+static LOADERS: Mutex<BTreeMap<&'static str, Arc<LoaderMetrics>>> = Mutex::new(BTreeMap::new());
+
+/// A histogram of observed values, with cumulative counts for each bucket when it is rendered.
+#[derive(Debug, Default)]
+pub(crate) struct Histogram {
+    buckets: [AtomicU64; BATCH_SIZE_BUCKETS.len()],
+    sum: AtomicU64,
+    count: AtomicU64,
+}
+
+impl Histogram {
+    /// Records an observed value.
+    pub(crate) fn observe(&self, value: u64) {
+        if let Some(bucket) = BATCH_SIZE_BUCKETS.iter().position(|bound| value <= *bound) {
+            self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        }
+        self.sum.fetch_add(value, Ordering::Relaxed);
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Gets the number of observed values which are at most each bucket bound, with the total
+    /// number of observed values last, for the `+Inf` bucket.
+    pub(crate) fn cumulative_counts(&self) -> Vec<u64> {
+        let mut total = 0;
+        let mut counts = self
+            .buckets
+            .iter()
+            .map(|bucket| {
+                total += bucket.load(Ordering::Relaxed);
+                total
+            })
+            .collect::<Vec<_>>();
+        counts.push(self.count.load(Ordering::Relaxed));
+        counts
+    }
+
+    /// Gets the sum of the observed values.
+    pub(crate) fn sum(&self) -> u64 {
+        self.sum.load(Ordering::Relaxed)
+    }
+
+    /// Gets the number of observed values.
+    pub(crate) fn count(&self) -> u64 {
+        self.count.load(Ordering::Relaxed)
+    }
+}
+
+/// The metrics of a batching loader, which are used to tune `LOADER_YIELD_COUNT`.
+#[derive(Debug, Default)]
+pub(crate) struct LoaderMetrics {
+    /// The number of ids of each batch, so the number of batches is its count.
+    pub(crate) batch_sizes: Histogram,
+    /// The number of ids which were not found.
+    pub(crate) not_found: AtomicU64,
+}
+
+impl LoaderMetrics {
+    /// Records a batch of the given size, of which some ids were not found.
+    pub(crate) fn record_batch(&self, size: usize, not_found: usize) {
+        self.batch_sizes.observe(size as u64);
+        self.not_found
+            .fetch_add(not_found as u64, Ordering::Relaxed);
+    }
+}
+
+/// The metrics of the delivery of the outbox, which are used to check that modifications are
+/// published over few connections and round-trips.
+#[derive(Debug)]
+pub(crate) struct OutboxMetrics {
+    /// The number of connections which were opened to Redis.
+    pub(crate) connections: AtomicU64,
+    /// The number of pipelines which were published.
+    pub(crate) pipelines: AtomicU64,
+    /// The number of modifications which were published.
+    pub(crate) delivered: AtomicU64,
+}
+
+/// The metrics of the delivery of the outbox.
+pub(crate) static OUTBOX: OutboxMetrics = OutboxMetrics {
+    connections: AtomicU64::new(0),
+    pipelines: AtomicU64::new(0),
+    delivered: AtomicU64::new(0),
+};
+
+/// Gets the metrics of the loader with the given name, registering them if they do not exist, so
+/// loaders with the same name share their metrics.
+pub(crate) fn loader_metrics(name: &'static str) -> Arc<LoaderMetrics> {
+    LOADERS.lock().unwrap().entry(name).or_default().clone()
+}
+
+/// Renders the metrics of every loader and of the outbox in the Prometheus text exposition format.
+pub(crate) fn render() -> String {
+    let loaders = LOADERS.lock().unwrap();
+    let mut output = String::new();
+
+    output.push_str(
+        "# HELP inv_track_loader_batch_size The number of ids of each batch of a loader.\n",
+    );
+    output.push_str("# TYPE inv_track_loader_batch_size histogram\n");
+    for (name, metrics) in loaders.iter() {
+        let bounds = BATCH_SIZE_BUCKETS
+            .iter()
+            .map(|bound| bound.to_string())
+            .chain(["+Inf".to_string()]);
+        for (bound, count) in bounds.zip(metrics.batch_sizes.cumulative_counts()) {
+            writeln!(
+                output,
+                r#"inv_track_loader_batch_size_bucket{{loader="{}",le="{}"}} {}"#,
+                name, bound, count
+            )
+            .unwrap();
+        }
+        writeln!(
+            output,
+            r#"inv_track_loader_batch_size_sum{{loader="{}"}} {}"#,
+            name,
+            metrics.batch_sizes.sum()
+        )
+        .unwrap();
+        writeln!(
+            output,
+            r#"inv_track_loader_batch_size_count{{loader="{}"}} {}"#,
+            name,
+            metrics.batch_sizes.count()
+        )
+        .unwrap();
+    }
+
+    output.push_str(
+        "# HELP inv_track_loader_not_found_total The number of ids which a loader did not find.\n",
+    );
+    output.push_str("# TYPE inv_track_loader_not_found_total counter\n");
+    for (name, metrics) in loaders.iter() {
+        writeln!(
+            output,
+            r#"inv_track_loader_not_found_total{{loader="{}"}} {}"#,
+            name,
+            metrics.not_found.load(Ordering::Relaxed)
+        )
+        .unwrap();
+    }
+
+    for (name, help, counter) in [
+        (
+            "inv_track_outbox_connections_total",
+            "The number of connections to Redis opened to deliver the outbox.",
+            &OUTBOX.connections,
+        ),
+        (
+            "inv_track_outbox_pipelines_total",
+            "The number of pipelines published to deliver the outbox.",
+            &OUTBOX.pipelines,
+        ),
+        (
+            "inv_track_outbox_delivered_total",
+            "The number of modifications delivered from the outbox.",
+            &OUTBOX.delivered,
+        ),
+    ] {
+        writeln!(output, "# HELP {} {}", name, help).unwrap();
+        writeln!(output, "# TYPE {} counter", name).unwrap();
+        writeln!(output, "{} {}", name, counter.load(Ordering::Relaxed)).unwrap();
+    }
+
+    output
+}
+
+/// Unit tests for metrics.
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_histogram() {
+        let histogram = Histogram::default();
+        histogram.observe(3);
+        histogram.observe(100);
+        histogram.observe(1000);
+        assert_eq!(
+            histogram.cumulative_counts(),
+            vec![0, 1, 1, 1, 1, 2, 2, 2, 3]
+        );
+        assert_eq!(histogram.sum(), 1103);
+        assert_eq!(histogram.count(), 3);
+    }
+
+    #[test]
+    fn test_render() {
+        loader_metrics("test_render").record_batch(3, 1);
+        let output = render();
+        assert!(
+            output.contains(r#"inv_track_loader_batch_size_bucket{loader="test_render",le="1"} 0"#)
+        );
+        assert!(
+            output.contains(r#"inv_track_loader_batch_size_bucket{loader="test_render",le="5"} 1"#)
+        );
+        assert!(output
+            .contains(r#"inv_track_loader_batch_size_bucket{loader="test_render",le="+Inf"} 1"#));
+        assert!(output.contains(r#"inv_track_loader_batch_size_sum{loader="test_render"} 3"#));
+        assert!(output.contains(r#"inv_track_loader_not_found_total{loader="test_render"} 1"#));
+        assert!(output.contains("# TYPE inv_track_outbox_pipelines_total counter"));
+    }
+}
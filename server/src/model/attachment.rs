@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+use async_graphql::{Error, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::graphql::{AppContext, Clients};
+use crate::model::error::ModelError;
+use crate::model::item::{self, ItemId};
+use crate::model::modification::{self, ModificationEntity, ModificationType};
+use crate::model::outbox;
+use crate::model::validation;
+
+/// The id of an attachment of an item.
+#[derive(PartialEq, Eq, Into, Hash, Copy, Clone, Debug, sqlx::Type, Serialize, Deserialize)]
+#[sqlx(transparent)]
+pub(crate) struct ItemAttachmentId(i32);
+async_graphql::scalar!(ItemAttachmentId);
+
+/// A reference to a file of an item, such as a product image, which is stored elsewhere.
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow, async_graphql::SimpleObject)]
+pub(crate) struct ItemAttachment {
+    id: ItemAttachmentId,
+    item_id: ItemId,
+    /// The url of the file.
+    url: String,
+    /// The position of the attachment among the attachments of the item, in ascending order.
+    sort_index: i32,
+}
+
+/// Attachment to input to the inventory tracking system.
+#[derive(Debug, PartialEq, async_graphql::InputObject)]
+pub(crate) struct InsertableItemAttachment {
+    pub(crate) item_id: ItemId,
+    /// The absolute http or https url of the file.
+    pub(crate) url: String,
+    /// The position of the attachment, which is after the other attachments of the item if not
+    /// given.
+    sort_index: Option<i32>,
+}
+
+/// Gets the attachments of all items with the given ids, in the order of their sort indexes.
+pub(crate) async fn get_attachments_by_item_ids(
+    clients: &Clients,
+    ids: Vec<ItemId>,
+) -> Result<HashMap<ItemId, Result<Vec<ItemAttachment>>>> {
+    let attachments = sqlx::query_as::<_, ItemAttachment>(
+        r#"
+        select id, item_id, url, sort_index from item_attachments
+        where item_id = any($1)
+        order by sort_index, id
+    "#,
+    )
+    .bind(ids.into_iter().map(i32::from).collect::<Vec<i32>>())
+    .fetch_all(&*clients.postgres)
+    .await
+    .map_err(Error::from)?;
+
+    let mut attachments_map = HashMap::new();
+    for attachment in attachments {
+        attachments_map
+            .entry(attachment.item_id)
+            .or_insert_with(Vec::new)
+            .push(attachment);
+    }
+
+    Ok(attachments_map
+        .into_iter()
+        .map(|(key, value)| (key, Ok(value)))
+        .collect())
+}
+
+/// Adds an attachment to an item, returning the result, or a field error.
+pub(crate) async fn add_item_attachment(
+    context: &AppContext,
+    attachment: InsertableItemAttachment,
+) -> Result<ItemAttachment, ModelError> {
+    validation::attachment::validate_attachment(context, &attachment).await?;
+
+    let mut tx = context.clients.postgres.begin().await?;
+    let added = sqlx::query_as::<_, ItemAttachment>(
+        r#"
+        insert into item_attachments (item_id, url, sort_index)
+        values ($1, $2, coalesce(
+            $3,
+            (select max(sort_index) + 1 from item_attachments where item_id = $1),
+            0
+        ))
+        returning id, item_id, url, sort_index
+    "#,
+    )
+    .bind(attachment.item_id)
+    .bind(attachment.url)
+    .bind(attachment.sort_index)
+    .fetch_one(&mut tx)
+    .await?;
+    added.enqueue_update(&mut tx).await?;
+    tx.commit().await?;
+    outbox::flush(context).await;
+
+    Ok(added)
+}
+
+/// Removes an attachment, given an id, returning the result, or a field error.
+pub(crate) async fn remove_item_attachment(
+    context: &AppContext,
+    id: ItemAttachmentId,
+) -> Result<ItemAttachment, ModelError> {
+    let mut tx = context.clients.postgres.begin().await?;
+    let removed = sqlx::query_as::<_, ItemAttachment>(
+        r#"
+        delete from item_attachments
+        where id = $1
+        returning id, item_id, url, sort_index
+    "#,
+    )
+    .bind(id)
+    .fetch_one(&mut tx)
+    .await?;
+    removed.enqueue_update(&mut tx).await?;
+    tx.commit().await?;
+    outbox::flush(context).await;
+
+    Ok(removed)
+}
+
+impl ItemAttachment {
+    async fn enqueue_update(
+        &self,
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), ModelError> {
+        // the attachments of the item have changed, so enqueue an update of the item
+        let item = item::fetch_item(tx, self.item_id).await?;
+        modification::enqueue(
+            tx,
+            ModificationEntity::Item,
+            ModificationType::Update,
+            &item,
+        )
+        .await
+    }
+}
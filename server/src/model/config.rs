@@ -0,0 +1,80 @@
+use std::collections::HashSet;
+
+use chrono::Duration;
+use regex::Regex;
+
+use sqlx::{Pool, Postgres, Row};
+
+use crate::config::Config;
+use crate::model::transaction::TransactionReason;
+
+/// The configuration of the model layer.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ModelConfig {
+    /// Whether searches can ignore accents, which requires the unaccent extension.
+    pub(crate) unaccent: bool,
+    /// Whether item names must be unique, ignoring case.
+    pub(crate) enforce_unique_item_name: bool,
+    /// Whether unsafe markup is removed from item descriptions before they are stored.
+    pub(crate) sanitize_item_descriptions: bool,
+    /// The maximum number of transactions of an item, or none if there is no maximum.
+    pub(crate) max_transactions_per_item: Option<i64>,
+    /// How far past now transaction dates can be, or none if future transactions are allowed.
+    pub(crate) max_future_transaction_skew: Option<Duration>,
+    /// Whether transactions can take the quantity of an item below zero.
+    pub(crate) allow_negative_stock: bool,
+    /// The pattern which skus must match in full, or none if skus are not restricted.
+    pub(crate) sku_pattern: Option<Regex>,
+    /// The maximum number of characters of skus.
+    pub(crate) sku_max_length: usize,
+    /// The reasons of transactions which must have a comment explaining them.
+    pub(crate) comment_required_reasons: HashSet<TransactionReason>,
+    /// Whether all data can be deleted by the admin reset, which is only for test environments.
+    pub(crate) reset_enabled: bool,
+    /// Whether mutations are rejected for maintenance, regardless of the mode set at runtime.
+    pub(crate) maintenance_mode: bool,
+}
+
+impl ModelConfig {
+    /// Gets the model configuration from the configuration of the server, detecting the features
+    /// supported by the database.
+    pub(crate) async fn new(pool: &Pool<Postgres>, config: &Config) -> Self {
+        let unaccent = sqlx::query(
+            r#"
+            select to_regprocedure('immutable_unaccent(text)') is not null as exists
+        "#,
+        )
+        .fetch_one(pool)
+        .await
+        .and_then(|row| row.try_get("exists"))
+        .unwrap_or(false);
+
+        if !unaccent {
+            log::warn!("unaccent is unavailable, so searches will not ignore accents");
+        }
+
+        if config.test_reset_enabled {
+            log::warn!("resetting all data is enabled, which must not be used in production");
+        }
+        if config.maintenance_mode {
+            log::warn!("maintenance mode is on, so mutations will be rejected");
+        }
+
+        ModelConfig {
+            unaccent,
+            enforce_unique_item_name: config.enforce_unique_item_name,
+            sanitize_item_descriptions: config.sanitize_item_descriptions,
+            max_transactions_per_item: config.max_transactions_per_item,
+            max_future_transaction_skew: (!config.allow_future_transactions).then(|| {
+                Duration::from_std(config.future_transaction_skew)
+                    .unwrap_or_else(|_| Duration::max_value())
+            }),
+            allow_negative_stock: config.allow_negative_stock,
+            sku_pattern: config.sku_pattern.as_ref().map(|pattern| pattern.0.clone()),
+            sku_max_length: config.sku_max_length,
+            comment_required_reasons: config.transaction_comment_required_reasons.clone(),
+            reset_enabled: config.test_reset_enabled,
+            maintenance_mode: config.maintenance_mode,
+        }
+    }
+}
@@ -0,0 +1,123 @@
+use async_graphql::{Error, Result};
+use sqlx::postgres::PgArguments;
+use sqlx::query::Query;
+use sqlx::{Postgres, Row};
+
+use crate::graphql::AppContext;
+use crate::model::error::ModelError;
+
+/// Aggregates of the inventory for an overview, each of which is computed by one query when it is
+/// selected, so selected aggregates are computed concurrently.
+#[derive(Default)]
+pub(crate) struct Dashboard;
+
+/// Runs an aggregate query which returns a single count, returning the result, or an error.
+async fn aggregate(
+    context: &AppContext,
+    query: Query<'_, Postgres, PgArguments>,
+) -> Result<i64, ModelError> {
+    query
+        .fetch_one(&*context.clients.postgres)
+        .await?
+        .try_get::<Option<i64>, _>("count")
+        .map(|count| count.unwrap_or(0))
+        .map_err(ModelError::from)
+}
+
+/// Counts all items, returning the result, or an error.
+pub(crate) async fn count_items(context: &AppContext) -> Result<i64, ModelError> {
+    aggregate(context, sqlx::query(r#"select count(id) from items"#)).await
+}
+
+/// Counts all locations, returning the result, or an error.
+pub(crate) async fn count_locations(context: &AppContext) -> Result<i64, ModelError> {
+    aggregate(context, sqlx::query(r#"select count(id) from locations"#)).await
+}
+
+/// Sums the quantities of all items, returning the result, or an error.
+pub(crate) async fn sum_quantities(context: &AppContext) -> Result<i64, ModelError> {
+    aggregate(
+        context,
+        sqlx::query(r#"select sum(quantity) as count from transactions"#),
+    )
+    .await
+}
+
+/// Counts the items below their reorder point, returning the result, or an error.
+pub(crate) async fn count_low_stock(context: &AppContext) -> Result<i64, ModelError> {
+    aggregate(
+        context,
+        sqlx::query(
+            r#"
+            select count(id) from items
+            where reorder_point is not null
+                and reorder_point > (
+                    select coalesce(sum(quantity), 0) from transactions where item_id = items.id
+                )
+        "#,
+        ),
+    )
+    .await
+}
+
+/// Counts the transactions dated within the last number of days, returning the result, or an
+/// error.
+pub(crate) async fn count_recent_transactions(
+    context: &AppContext,
+    days: i32,
+) -> Result<i64, ModelError> {
+    aggregate(
+        context,
+        sqlx::query(
+            r#"
+            select count(id) from transactions
+            where transaction_date >= now() - make_interval(days => $1)
+        "#,
+        )
+        .bind(days),
+    )
+    .await
+}
+
+/// An overview of the inventory tracking system.
+#[async_graphql::Object]
+impl Dashboard {
+    /// The number of items.
+    async fn total_items(&self, context: &async_graphql::Context<'_>) -> Result<i64> {
+        count_items(context.data_unchecked::<AppContext>())
+            .await
+            .map_err(Error::from)
+    }
+
+    /// The number of locations.
+    async fn total_locations(&self, context: &async_graphql::Context<'_>) -> Result<i64> {
+        count_locations(context.data_unchecked::<AppContext>())
+            .await
+            .map_err(Error::from)
+    }
+
+    /// The total quantity of all items on hand.
+    async fn total_quantity(&self, context: &async_graphql::Context<'_>) -> Result<i64> {
+        sum_quantities(context.data_unchecked::<AppContext>())
+            .await
+            .map_err(Error::from)
+    }
+
+    /// The number of items below their reorder point.
+    async fn low_stock_count(&self, context: &async_graphql::Context<'_>) -> Result<i64> {
+        count_low_stock(context.data_unchecked::<AppContext>())
+            .await
+            .map_err(Error::from)
+    }
+
+    /// The number of transactions dated within the last number of days.
+    async fn recent_transaction_count(
+        &self,
+        context: &async_graphql::Context<'_>,
+        #[graphql(default = 7, validator(minimum = 1))] days: i32,
+    ) -> Result<i64> {
+        count_recent_transactions(context.data_unchecked::<AppContext>(), days)
+            .await
+            .map_err(Error::from)
+    }
+}
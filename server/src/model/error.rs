@@ -0,0 +1,217 @@
+use async_graphql::{Error, ErrorExtensions};
+
+/// The stable codes of errors, which are set as the `code` extension of every GraphQL error, so
+/// clients do not depend on messages.
+pub(crate) mod code {
+    /// The requested record does not exist.
+    pub(crate) const NOT_FOUND: &str = "NOT_FOUND";
+    /// The input is invalid.
+    pub(crate) const VALIDATION: &str = "VALIDATION";
+    /// The change conflicts with an existing record or a concurrent change.
+    pub(crate) const CONFLICT: &str = "CONFLICT";
+    /// The role of the request does not allow the operation.
+    pub(crate) const FORBIDDEN: &str = "FORBIDDEN";
+    /// The server could not complete the request.
+    pub(crate) const INTERNAL: &str = "INTERNAL";
+    /// The operation took longer than its timeout.
+    pub(crate) const TIMEOUT: &str = "TIMEOUT";
+    /// The request exceeded a limit of its connection.
+    pub(crate) const LIMIT_EXCEEDED: &str = "LIMIT_EXCEEDED";
+    /// The service is in maintenance, so changes are not accepted.
+    pub(crate) const MAINTENANCE: &str = "MAINTENANCE";
+}
+
+/// The SQLSTATE code of a unique constraint violation.
+const UNIQUE_VIOLATION: &str = "23505";
+/// The SQLSTATE code of a foreign key constraint violation.
+const FOREIGN_KEY_VIOLATION: &str = "23503";
+
+/// The unique constraints with friendly messages, with the field which is not unique. Violations of
+/// other unique constraints have a generic message.
+const UNIQUE_CONSTRAINTS: [(&str, &str, &str); 3] = [
+    (
+        "items_sku_key",
+        "sku",
+        "an item with this sku already exists",
+    ),
+    (
+        "items_upper_sku_idx",
+        "sku",
+        "an item with this sku already exists",
+    ),
+    (
+        "items_unique_name_idx",
+        "name",
+        "an item with this name already exists",
+    ),
+];
+
+/// Creates the error for a violation of a unique constraint, with the friendly message of the
+/// constraint if it has one.
+fn unique_violation(constraint: Option<&str>) -> ModelError {
+    let mut fields = Vec::new();
+    let mut message = "record not unique".to_string();
+    if let Some(constraint) = constraint {
+        if let Some((_, field, friendly)) = UNIQUE_CONSTRAINTS
+            .iter()
+            .find(|(name, _, _)| *name == constraint)
+        {
+            message = friendly.to_string();
+            fields.push((*field, friendly.to_string()));
+        }
+        fields.push(("constraint", constraint.to_string()));
+    }
+
+    ModelError::Conflict { message, fields }
+}
+
+/// An error from the model layer, which distinguishes the kind of failure.
+#[derive(Debug)]
+pub(crate) enum ModelError {
+    /// The requested record does not exist.
+    NotFound,
+    /// The change conflicts with an existing record, with messages for the conflicting fields.
+    Conflict {
+        message: String,
+        fields: Vec<(&'static str, String)>,
+    },
+    /// The input is invalid, with messages for the invalid fields.
+    Validation {
+        message: String,
+        fields: Vec<(&'static str, String)>,
+    },
+    /// The database could not complete the request.
+    Db(sqlx::Error),
+}
+
+impl ModelError {
+    /// Creates a validation error with a message and no field messages.
+    pub(crate) fn validation(message: &str) -> Self {
+        ModelError::Validation {
+            message: message.to_string(),
+            fields: Vec::new(),
+        }
+    }
+
+    /// The stable code identifying the kind of error.
+    pub(crate) fn code(&self) -> &'static str {
+        match self {
+            ModelError::NotFound => code::NOT_FOUND,
+            ModelError::Conflict { .. } => code::CONFLICT,
+            ModelError::Validation { .. } => code::VALIDATION,
+            ModelError::Db(_) => code::INTERNAL,
+        }
+    }
+
+    /// The message describing the error.
+    fn message(&self) -> String {
+        match self {
+            ModelError::NotFound => "not found".to_string(),
+            ModelError::Conflict { message, .. } | ModelError::Validation { message, .. } => {
+                message.clone()
+            }
+            ModelError::Db(e) => e.to_string(),
+        }
+    }
+
+    /// The name of the foreign key constraint which the error violates, if it is a foreign key
+    /// violation, such as when a referenced record is deleted concurrently.
+    pub(crate) fn foreign_key_constraint(&self) -> Option<&str> {
+        match self {
+            ModelError::Db(sqlx::Error::Database(db_error))
+                if db_error.code().as_deref() == Some(FOREIGN_KEY_VIOLATION) =>
+            {
+                db_error.constraint()
+            }
+            _ => None,
+        }
+    }
+
+    /// The message describing the error, followed by the messages of its fields, for errors which
+    /// are reported in data rather than as GraphQL errors.
+    pub(crate) fn detailed_message(&self) -> String {
+        let mut message = self.message();
+        if let ModelError::Conflict { fields, .. } | ModelError::Validation { fields, .. } = self {
+            let field_messages = fields
+                .iter()
+                .filter(|(field, _)| *field != "constraint")
+                .map(|(_, field_message)| field_message.as_str())
+                .collect::<Vec<&str>>();
+            if !field_messages.is_empty() {
+                message = format!("{}: {}", message, field_messages.join(", "));
+            }
+        }
+        message
+    }
+}
+
+impl From<sqlx::Error> for ModelError {
+    fn from(e: sqlx::Error) -> Self {
+        match e {
+            sqlx::Error::RowNotFound => ModelError::NotFound,
+            sqlx::Error::Database(db_error)
+                if db_error.code().as_deref() == Some(UNIQUE_VIOLATION) =>
+            {
+                unique_violation(db_error.constraint())
+            }
+            e => ModelError::Db(e),
+        }
+    }
+}
+
+impl ErrorExtensions for ModelError {
+    fn extend(&self) -> Error {
+        Error::new(self.message()).extend_with(|_, e| {
+            e.set("code", self.code());
+            if let ModelError::Conflict { fields, .. } | ModelError::Validation { fields, .. } =
+                self
+            {
+                for (field, message) in fields {
+                    e.set(*field, message.as_str());
+                }
+            }
+        })
+    }
+}
+
+impl From<ModelError> for Error {
+    fn from(e: ModelError) -> Self {
+        e.extend()
+    }
+}
+
+/// Unit tests for model errors.
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_unique_violation_friendly() {
+        let e = unique_violation(Some("items_sku_key"));
+        assert_eq!(e.message(), "an item with this sku already exists");
+        assert_eq!(e.code(), "CONFLICT");
+        match e {
+            ModelError::Conflict { fields, .. } => assert_eq!(
+                fields,
+                vec![
+                    ("sku", "an item with this sku already exists".to_string()),
+                    ("constraint", "items_sku_key".to_string()),
+                ]
+            ),
+            _ => panic!("not a conflict"),
+        }
+    }
+
+    #[test]
+    fn test_unique_violation_generic() {
+        let e = unique_violation(Some("unknown_key"));
+        assert_eq!(e.message(), "record not unique");
+        match e {
+            ModelError::Conflict { fields, .. } => {
+                assert_eq!(fields, vec![("constraint", "unknown_key".to_string())])
+            }
+            _ => panic!("not a conflict"),
+        }
+        assert_eq!(unique_violation(None).message(), "record not unique");
+    }
+}
@@ -1,29 +1,99 @@
 use std::collections::HashMap;
 use std::fmt::Debug;
 
-use async_graphql::{Error, Result};
+use async_graphql::{Error, InputValueError, InputValueResult, Result, ScalarType, Value};
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
 use serde::{Deserialize, Serialize};
-use sqlx::Row;
+use sqlx::{FromRow, Row};
 
 use crate::batcher::id_loader::IdLoader;
-use crate::graphql::{AppContext, Clients};
-use crate::model::modification::{self, ModificationType};
-use crate::model::transaction::Transaction;
+use crate::graphql::{
+    list_complexity, AppContext, Clients, NESTED_LIST_COMPLEXITY_MULTIPLIER,
+    QUANTITY_AS_OF_COMPLEXITY,
+};
+use crate::model::attachment::ItemAttachment;
+use crate::model::config::ModelConfig;
+use crate::model::error::ModelError;
+use crate::model::location::{self, Location};
+use crate::model::modification::{self, ModificationEntity, ModificationType};
+use crate::model::normalize;
+use crate::model::outbox;
+use crate::model::reservation::ReservedQuantity;
+use crate::model::transaction::{
+    qualified_transaction_columns, Transaction, TransactionId, TransactionReason,
+    TRANSACTION_COLUMNS,
+};
 use crate::model::validation;
 
+/// The maximum number of supplier suggestions.
+const MAX_SUPPLIER_SUGGESTIONS: i64 = 10;
+
 /// The id of an item.
 #[derive(PartialEq, Eq, Into, Hash, Copy, Clone, Debug, sqlx::Type, Serialize, Deserialize)]
 #[sqlx(transparent)]
 pub(crate) struct ItemId(i32);
 async_graphql::scalar!(ItemId);
 
-/// The quantity of inventory.
+/// The sku of an item, normalized to uppercase, as skus are unique ignoring case.
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
+pub(crate) struct ItemSku(String);
+
+impl ItemSku {
+    /// Creates a normalized sku.
+    pub(crate) fn new(sku: &str) -> Self {
+        ItemSku(sku.to_uppercase())
+    }
+}
+
+/// The quantity of inventory, which is the standard `Int` scalar in the schema rather than a
+/// scalar of its own, so clients and code generators treat it as a number.
 #[derive(
-    PartialEq, Into, Neg, Copy, Clone, Debug, Add, Sub, sqlx::Type, Serialize, Deserialize,
+    PartialEq, Into, Neg, Copy, Clone, Debug, Default, Add, Sub, sqlx::Type, Serialize, Deserialize,
 )]
 #[sqlx(transparent)]
 pub(crate) struct ItemQuantity(i32);
-async_graphql::scalar!(ItemQuantity);
+
+#[async_graphql::Scalar(name = "Int")]
+impl ScalarType for ItemQuantity {
+    fn parse(value: Value) -> InputValueResult<Self> {
+        <i32 as ScalarType>::parse(value)
+            .map(ItemQuantity)
+            .map_err(InputValueError::propagate)
+    }
+
+    fn is_valid(value: &Value) -> bool {
+        <i32 as ScalarType>::is_valid(value)
+    }
+
+    fn to_value(&self) -> Value {
+        self.0.to_value()
+    }
+}
+
+impl ItemQuantity {
+    /// Adds a quantity, returning none if the sum overflows.
+    #[allow(dead_code)]
+    pub(crate) fn checked_add(self, other: ItemQuantity) -> Option<ItemQuantity> {
+        self.0.checked_add(other.0).map(ItemQuantity)
+    }
+
+    /// Subtracts a quantity, returning none if the difference overflows.
+    pub(crate) fn checked_sub(self, other: ItemQuantity) -> Option<ItemQuantity> {
+        self.0.checked_sub(other.0).map(ItemQuantity)
+    }
+
+    /// Subtracts a quantity, saturating at the bounds of a quantity instead of overflowing.
+    pub(crate) fn saturating_sub(self, other: ItemQuantity) -> ItemQuantity {
+        ItemQuantity(self.0.saturating_sub(other.0))
+    }
+
+    /// Adds a quantity, saturating at the bounds of a quantity instead of overflowing.
+    #[allow(dead_code)]
+    pub(crate) fn saturating_add(self, other: ItemQuantity) -> ItemQuantity {
+        ItemQuantity(self.0.saturating_add(other.0))
+    }
+}
 
 /// Item model returned by a query in the inventory tracking system.
 #[derive(
@@ -31,11 +101,167 @@ async_graphql::scalar!(ItemQuantity);
 )]
 #[graphql(complex)]
 pub(crate) struct Item {
-    id: ItemId,
+    pub(crate) id: ItemId,
     sku: Option<String>,
     name: String,
     supplier: Option<String>,
     description: Option<String>,
+    reorder_point: Option<ItemQuantity>,
+    max_quantity: Option<ItemQuantity>,
+    /// The client which created the item, from the `X-Client-Source` header or the path which
+    /// created it, or null if it is not known.
+    source: Option<String>,
+    /// The quantity of the item if it was joined when the item was listed, so the `quantity` field
+    /// does not load it again.
+    #[graphql(skip)]
+    #[serde(skip)]
+    #[sqlx(default)]
+    joined_quantity: Option<i64>,
+}
+
+/// The columns of items other than the id, with the fields which select them, and the placeholders
+/// fetched when they are not selected.
+const ITEM_COLUMNS: [(&str, &str, &str); 7] = [
+    ("sku", "sku", "null::text"),
+    ("name", "name", "''::text"),
+    ("supplier", "supplier", "null::text"),
+    ("description", "description", "null::text"),
+    ("reorderPoint", "reorder_point", "null::integer"),
+    ("maxQuantity", "max_quantity", "null::integer"),
+    ("source", "source", "null::text"),
+];
+
+/// The SQL to join the quantities of items, which are null for items without transactions.
+const QUANTITIES_JOIN: &str = r#"
+        left join (
+            select item_id, sum(quantity) as quantity from transactions
+            group by item_id
+        ) quantities on quantities.item_id = items.id"#;
+
+/// An item with its quantity, as a row of an export.
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub(crate) struct ItemExport {
+    pub(crate) id: ItemId,
+    pub(crate) sku: Option<String>,
+    pub(crate) name: String,
+    pub(crate) supplier: Option<String>,
+    pub(crate) quantity: i64,
+}
+
+/// The items of a supplier, with their total quantity.
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow, async_graphql::SimpleObject)]
+pub(crate) struct SupplierSummary {
+    /// The supplier, or null for the items without a supplier.
+    supplier: Option<String>,
+    item_count: i64,
+    total_quantity: i64,
+}
+
+/// The window over which the consumption of an item is averaged.
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
+pub(crate) struct ConsumptionWindow {
+    item_id: ItemId,
+    days: i32,
+}
+
+/// The date as of which the quantity of an item is summed, inclusive.
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
+pub(crate) struct QuantityAsOfKey {
+    item_id: ItemId,
+    date: DateTime<Utc>,
+}
+
+/// The first transactions of an item, which are loaded as a page.
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
+pub(crate) struct TransactionPageKey {
+    item_id: ItemId,
+    first: i32,
+}
+
+/// A page of the transactions of an item, most recent first.
+#[derive(Debug, Clone, PartialEq, async_graphql::SimpleObject)]
+pub(crate) struct TransactionPage {
+    transactions: Vec<Transaction>,
+    has_next_page: bool,
+}
+
+/// The activity of an item for a transaction reason.
+#[derive(Debug, Clone, PartialEq, async_graphql::SimpleObject)]
+pub(crate) struct ReasonActivity {
+    reason: Option<TransactionReason>,
+    count: i32,
+    net_quantity: ItemQuantity,
+}
+
+/// The balance of an item after each of its transactions, in chronological order.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub(crate) struct ItemLedger {
+    balances: HashMap<TransactionId, ItemQuantity>,
+}
+
+impl ItemLedger {
+    /// Gets the balance of the item after the transaction with the given id.
+    pub(crate) fn balance_after(&self, id: TransactionId) -> Option<ItemQuantity> {
+        self.balances.get(&id).copied()
+    }
+}
+
+/// A suggestion to reorder an item which is below its reorder point.
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow, async_graphql::SimpleObject)]
+#[graphql(complex)]
+pub(crate) struct ReorderSuggestion {
+    #[graphql(skip)]
+    item_id: ItemId,
+    current_quantity: ItemQuantity,
+    suggested_quantity: ItemQuantity,
+}
+
+/// The quantity of an item recalculated from its transactions, compared to its loaded quantity.
+#[derive(Debug, Clone, PartialEq, async_graphql::SimpleObject)]
+#[graphql(complex)]
+pub(crate) struct QuantityRecalculation {
+    #[graphql(skip)]
+    item_id: ItemId,
+    /// The quantity recalculated from the transactions of the item, or null if it overflows.
+    quantity: Option<ItemQuantity>,
+    /// The loaded quantity of the item, or null if it overflows.
+    loaded_quantity: Option<ItemQuantity>,
+}
+
+/// How an import handles items with skus which already exist.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, async_graphql::Enum)]
+pub(crate) enum ImportConflictMode {
+    /// Items with existing skus are not imported.
+    Skip,
+    /// The existing items are updated with the imported items.
+    Update,
+    /// No items are imported if any sku exists.
+    #[default]
+    Fail,
+}
+
+/// An imported item whose sku collides with an existing item.
+#[derive(Debug, Clone, PartialEq, async_graphql::SimpleObject)]
+pub(crate) struct SkuConflict {
+    /// The index of the item in the import.
+    row: i32,
+    sku: String,
+    existing_item_id: ItemId,
+}
+
+/// The result of an import, with the created or updated items, and the sku conflicts.
+#[derive(Debug, Clone, PartialEq, async_graphql::SimpleObject)]
+pub(crate) struct ItemImport {
+    items: Vec<Item>,
+    conflicts: Vec<SkuConflict>,
+}
+
+/// The result of an upsert by sku, with the created or updated item.
+#[derive(Debug, Clone, PartialEq, async_graphql::SimpleObject)]
+pub(crate) struct ItemUpsert {
+    item: Item,
+    /// Whether the item was created, rather than updated.
+    created: bool,
 }
 
 /// Item model to input to the inventory tracking system.
@@ -44,24 +270,273 @@ pub(crate) struct InsertableItem {
     #[graphql(validator(min_length = 1))]
     pub(crate) sku: Option<String>,
     #[graphql(validator(min_length = 1))]
-    name: String,
+    pub(crate) name: String,
     #[graphql(validator(min_length = 1))]
     supplier: Option<String>,
     #[graphql(validator(min_length = 1))]
     description: Option<String>,
+    reorder_point: Option<ItemQuantity>,
+    max_quantity: Option<ItemQuantity>,
 }
 
-/// Gets all items, returning the result, or an error error.
-pub(crate) async fn get_items(context: &AppContext) -> Result<Vec<Item>> {
-    sqlx::query_as::<_, Item>(
+impl InsertableItem {
+    /// Collapses the whitespace of the sku, name, and supplier, and trims the description, which is
+    /// sanitized if configured, so that the normalized values are validated and stored.
+    pub(crate) fn normalize(&mut self, config: &ModelConfig) {
+        self.sku = normalize::collapse_optional(self.sku.take());
+        self.name = normalize::collapse_whitespace(&self.name);
+        self.supplier = normalize::collapse_optional(self.supplier.take());
+        self.description = normalize_description(config, self.description.take());
+    }
+}
+
+/// The order of a list of items, each of which breaks ties by id, so the order is stable.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, async_graphql::Enum)]
+pub(crate) enum ItemOrderBy {
+    /// Items in alphabetical order of their names.
+    #[default]
+    NameAsc,
+    /// Items in reverse alphabetical order of their names.
+    NameDesc,
+    /// The most recently created items first.
+    NewestFirst,
+    /// The items with the greatest quantity first, then in alphabetical order of their names.
+    QuantityDesc,
+}
+
+impl ItemOrderBy {
+    /// Whether the order requires the quantities of items to be joined.
+    fn joins_quantities(self) -> bool {
+        self == ItemOrderBy::QuantityDesc
+    }
+
+    /// The SQL of the `order by` clause. Columns are qualified, so that they are not the
+    /// placeholders of unselected columns.
+    fn sql(self) -> &'static str {
+        match self {
+            ItemOrderBy::NameAsc => "items.name, items.id",
+            ItemOrderBy::NameDesc => "items.name desc, items.id desc",
+            ItemOrderBy::NewestFirst => "items.id desc",
+            ItemOrderBy::QuantityDesc => {
+                "coalesce(quantities.quantity, 0) desc, items.name, items.id"
+            }
+        }
+    }
+}
+
+/// Changes to apply to many items at once, leaving fields which are not given unchanged.
+#[derive(Debug, PartialEq, Deserialize, async_graphql::InputObject)]
+pub(crate) struct ItemPatch {
+    #[graphql(validator(min_length = 1))]
+    pub(crate) supplier: Option<String>,
+    #[graphql(validator(min_length = 1))]
+    pub(crate) description: Option<String>,
+    pub(crate) reorder_point: Option<ItemQuantity>,
+    pub(crate) max_quantity: Option<ItemQuantity>,
+}
+
+impl ItemPatch {
+    /// Collapses the whitespace of the supplier, and trims the description, which is sanitized if
+    /// configured.
+    fn normalize(&mut self, config: &ModelConfig) {
+        self.supplier = normalize::collapse_optional(self.supplier.take());
+        self.description = normalize_description(config, self.description.take());
+    }
+}
+
+/// Trims a description, and removes its unsafe markup if descriptions are sanitized, so it is not
+/// given if nothing is left.
+fn normalize_description(config: &ModelConfig, description: Option<String>) -> Option<String> {
+    let description = normalize::trim_optional(description);
+    if config.sanitize_item_descriptions {
+        normalize::trim_optional(
+            description.map(|description| normalize::sanitize_html(&description)),
+        )
+    } else {
+        description
+    }
+}
+
+/// Gets all items in the given order, optionally with names containing the given text, names or
+/// skus containing the given search text, created by the given source, and with quantities within
+/// the given bounds, returning the result, or an error. Names are matched ignoring case, and
+/// ignoring accents if supported, while skus are matched ignoring case. Items without transactions
+/// have a quantity of zero. If the quantity is selected or filtered, it is joined, so listing items
+/// with stock is one query.
+#[allow(clippy::too_many_arguments)]
+pub(crate) async fn get_items(
+    context: &AppContext,
+    name_contains: Option<String>,
+    search: Option<String>,
+    source: Option<String>,
+    min_quantity: Option<ItemQuantity>,
+    max_quantity: Option<ItemQuantity>,
+    order_by: ItemOrderBy,
+    columns: &str,
+    with_quantity: bool,
+) -> Result<Vec<Item>, ModelError> {
+    validation::item::validate_quantity_range(min_quantity, max_quantity)?;
+
+    let filters_quantity = min_quantity.is_some() || max_quantity.is_some();
+    let join = if with_quantity || filters_quantity || order_by.joins_quantities() {
+        QUANTITIES_JOIN
+    } else {
+        ""
+    };
+    let columns = if with_quantity {
+        format!(
+            "{}, coalesce(quantities.quantity, 0) as joined_quantity",
+            columns
+        )
+    } else {
+        columns.to_string()
+    };
+    // the bounds are only bound if they are filtered, as the quantities are not joined otherwise
+    let quantity_filter = if filters_quantity {
         r#"
-        select id, sku, name, supplier, description from items
-        order by id
+            and ($4::int is null or coalesce(quantities.quantity, 0) >= $4)
+            and ($5::int is null or coalesce(quantities.quantity, 0) <= $5)"#
+    } else {
+        ""
+    };
+    let query = if context.config.unaccent {
+        format!(
+            r#"
+        select {} from items{}
+        where ($1::text is null
+                or lower(immutable_unaccent(name)) like lower(immutable_unaccent($1)))
+            and ($2::text is null
+                or lower(immutable_unaccent(name)) like lower(immutable_unaccent($2))
+                or sku ilike $2)
+            and ($3::text is null or source = $3){}
+        order by {}
+    "#,
+            columns,
+            join,
+            quantity_filter,
+            order_by.sql()
+        )
+    } else {
+        format!(
+            r#"
+        select {} from items{}
+        where ($1::text is null or name ilike $1)
+            and ($2::text is null or name ilike $2 or sku ilike $2)
+            and ($3::text is null or source = $3){}
+        order by {}
+    "#,
+            columns,
+            join,
+            quantity_filter,
+            order_by.sql()
+        )
+    };
+
+    let contains_pattern = |text: String| format!("%{}%", escape_pattern(&text));
+
+    let mut query = sqlx::query_as::<_, Item>(&query)
+        .bind(name_contains.map(contains_pattern))
+        .bind(search.map(contains_pattern))
+        .bind(source);
+    if filters_quantity {
+        query = query.bind(min_quantity).bind(max_quantity);
+    }
+    query
+        .fetch_all(&*context.clients.postgres)
+        .await
+        .map_err(ModelError::from)
+}
+
+/// Gets the columns to select for items, which only fetches the columns of the selected fields,
+/// and always fetches the id. Unselected columns are replaced by placeholders, so items can still
+/// be decoded.
+pub(crate) fn get_item_columns(is_selected: impl Fn(&str) -> bool) -> String {
+    let mut columns = vec!["id".to_string()];
+    for (field, column, placeholder) in ITEM_COLUMNS {
+        if is_selected(field) {
+            columns.push(column.to_string());
+        } else {
+            columns.push(format!("{} as {}", placeholder, column));
+        }
+    }
+    columns.join(", ")
+}
+
+/// Gets all items with their quantities for an export, in the order of their ids, returning the
+/// result, or an error.
+pub(crate) async fn get_item_exports(context: &AppContext) -> Result<Vec<ItemExport>, ModelError> {
+    sqlx::query_as::<_, ItemExport>(
+        r#"
+        select items.id, items.sku, items.name, items.supplier,
+            coalesce(sum(transactions.quantity), 0)::bigint as quantity
+        from items
+        left join transactions on transactions.item_id = items.id
+        group by items.id
+        order by items.id
     "#,
     )
     .fetch_all(&*context.clients.postgres)
     .await
-    .map_err(Error::from)
+    .map_err(ModelError::from)
+}
+
+/// Gets the number of items and their total quantity for each supplier, with the items without a
+/// supplier grouped together, returning the suppliers with the most items first, then
+/// alphabetically, or an error.
+pub(crate) async fn get_supplier_summaries(
+    context: &AppContext,
+) -> Result<Vec<SupplierSummary>, ModelError> {
+    sqlx::query_as::<_, SupplierSummary>(
+        r#"
+        select items.supplier, count(items.id) as item_count,
+            coalesce(sum(quantities.quantity), 0)::bigint as total_quantity
+        from items
+        left join (
+            select item_id, sum(quantity) as quantity from transactions
+            group by item_id
+        ) quantities on quantities.item_id = items.id
+        group by items.supplier
+        order by item_count desc, items.supplier nulls last
+    "#,
+    )
+    .fetch_all(&*context.clients.postgres)
+    .await
+    .map_err(ModelError::from)
+}
+
+/// Gets the distinct suppliers starting with the given prefix, ignoring case, returning the most
+/// frequent first, then alphabetically, or an error.
+pub(crate) async fn get_supplier_suggestions(
+    context: &AppContext,
+    prefix: String,
+) -> Result<Vec<String>, ModelError> {
+    sqlx::query(
+        r#"
+        select supplier from items
+        where supplier ilike $1
+        group by supplier
+        order by count(id) desc, supplier
+        limit $2
+    "#,
+    )
+    .bind(format!(
+        "{}%",
+        escape_pattern(&normalize::collapse_whitespace(&prefix))
+    ))
+    .bind(MAX_SUPPLIER_SUGGESTIONS)
+    .fetch_all(&*context.clients.postgres)
+    .await?
+    .into_iter()
+    .map(|row| row.try_get("supplier"))
+    .collect::<Result<Vec<String>, _>>()
+    .map_err(ModelError::from)
+}
+
+/// Escapes text so it is matched literally within a like pattern.
+fn escape_pattern(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
 }
 
 /// Gets all items with the given ids.
@@ -71,7 +546,8 @@ pub(crate) async fn get_items_by_ids(
 ) -> Result<HashMap<ItemId, Result<Item>>> {
     sqlx::query_as::<_, Item>(
         r#"
-        select id, sku, name, supplier, description from items
+        select id, sku, name, supplier, description, reorder_point, max_quantity, source
+        from items
         where id = any($1)
     "#,
     )
@@ -82,18 +558,47 @@ pub(crate) async fn get_items_by_ids(
     .map_err(Error::from)
 }
 
-/// Gets all transactions with the given item ids.
+/// Gets all items with the given skus, ignoring case. If skus only differ by case, the first item
+/// is returned.
+pub(crate) async fn get_items_by_skus(
+    clients: &Clients,
+    skus: Vec<ItemSku>,
+) -> Result<HashMap<ItemSku, Result<Item>>> {
+    sqlx::query_as::<_, Item>(
+        r#"
+        select distinct on (upper(sku)) id, sku, name, supplier, description, reorder_point,
+            max_quantity, source
+        from items
+        where upper(sku) = any($1)
+        order by upper(sku), id
+    "#,
+    )
+    .bind(skus.into_iter().map(|sku| sku.0).collect::<Vec<String>>())
+    .fetch_all(&*clients.postgres)
+    .await
+    .map(|items| {
+        items
+            .into_iter()
+            .filter_map(|item| Some((ItemSku::new(item.sku.as_ref()?), Ok(item))))
+            .collect()
+    })
+    .map_err(Error::from)
+}
+
+/// Gets all transactions with the given item ids, most recent first, with undated transactions
+/// last.
 pub(crate) async fn get_transactions_by_item_ids(
     clients: &Clients,
     ids: Vec<ItemId>,
 ) -> Result<HashMap<ItemId, Result<Vec<Transaction>>>> {
-    sqlx::query_as::<_, Transaction>(
+    sqlx::query_as::<_, Transaction>(&format!(
         r#"
-        select id, item_id, location_id, transaction_date, quantity, comment from transactions
+        select {} from transactions
         where item_id = any($1)
-        order by transaction_date desc
+        order by transaction_date desc nulls last, id desc
     "#,
-    )
+        TRANSACTION_COLUMNS
+    ))
     .bind(ids.into_iter().map(|id| id.0).collect::<Vec<i32>>())
     .fetch_all(&*clients.postgres)
     .await
@@ -113,36 +618,410 @@ pub(crate) async fn get_transactions_by_item_ids(
     .map_err(Error::from)
 }
 
+/// Gets pages of the transactions of items, fetching one more transaction than the page size per
+/// item to determine if there is a next page.
+pub(crate) async fn get_transaction_pages_by_keys(
+    clients: &Clients,
+    keys: Vec<TransactionPageKey>,
+) -> Result<HashMap<TransactionPageKey, Result<TransactionPage>>> {
+    // page sizes are usually the same for all items, so query once per distinct page size
+    let mut ids_by_first = HashMap::new();
+    for key in keys {
+        ids_by_first
+            .entry(key.first)
+            .or_insert_with(Vec::new)
+            .push(key.item_id.0);
+    }
+
+    let mut results_map = HashMap::new();
+    for (first, ids) in ids_by_first {
+        let transactions = sqlx::query_as::<_, Transaction>(&format!(
+            r#"
+            select {} from (
+                select {},
+                    row_number() over (
+                        partition by item_id order by transaction_date desc nulls last, id desc
+                    ) as row_number
+                from transactions
+                where item_id = any($1)
+            ) as ranked
+            where row_number <= $2 + 1
+            order by item_id, row_number
+        "#,
+            qualified_transaction_columns("ranked"),
+            TRANSACTION_COLUMNS
+        ))
+        .bind(&ids)
+        .bind(first)
+        .fetch_all(&*clients.postgres)
+        .await
+        .map_err(Error::from)?;
+
+        let mut transactions_map = HashMap::new();
+        transactions.into_iter().for_each(|transaction| {
+            transactions_map
+                .entry(transaction.item_id)
+                .or_insert_with(Vec::new)
+                .push(transaction);
+        });
+
+        // items without transactions have an empty page
+        for id in ids {
+            let mut transactions = transactions_map.remove(&ItemId(id)).unwrap_or_default();
+            let has_next_page = transactions.len() > first as usize;
+            transactions.truncate(first as usize);
+            results_map.insert(
+                TransactionPageKey {
+                    item_id: ItemId(id),
+                    first,
+                },
+                Ok(TransactionPage {
+                    transactions,
+                    has_next_page,
+                }),
+            );
+        }
+    }
+
+    Ok(results_map)
+}
+
 /// Gets the item quantities for items with the given item ids.
 pub(crate) async fn get_quantities_by_item_ids(
     clients: &Clients,
     ids: Vec<ItemId>,
 ) -> Result<HashMap<ItemId, Result<ItemQuantity>>> {
-    let results = sqlx::query(
+    let mut results = sqlx::query(
         r#"
         select item_id, coalesce(sum(quantity), 0) from transactions
         where item_id = any($1)
         group by item_id
     "#,
     )
-    .bind(&ids.into_iter().map(|id| id.0).collect::<Vec<i32>>())
+    .bind(ids.into_iter().map(|id| id.0).collect::<Vec<i32>>())
+    .fetch(&*clients.postgres);
+
+    // rows are converted as they are streamed, so an item which overflows only fails itself
+    let mut results_map = HashMap::new();
+    while let Some(result) = results.try_next().await? {
+        let quantity = result
+            .try_get::<Option<i64>, _>("coalesce")
+            .map_err(Error::from)
+            .and_then(|quantity| {
+                i32::try_from(quantity.unwrap_or(0))
+                    .map(ItemQuantity)
+                    .map_err(|_| Error::from(ModelError::validation("item quantity overflows")))
+            });
+        results_map.insert(ItemId(result.try_get("item_id")?), quantity);
+    }
+
+    Ok(results_map)
+}
+
+/// Locks the quantity of an item until the database transaction ends, so changes to the quantity
+/// of the item are serialized while changes to other items proceed, returning the current
+/// quantity.
+pub(crate) async fn lock_quantity(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    id: ItemId,
+) -> Result<i64, ModelError> {
+    sqlx::query(r#"select pg_advisory_xact_lock($1)"#)
+        .bind(i64::from(id.0))
+        .execute(&mut *tx)
+        .await?;
+
+    Ok(sqlx::query(
+        r#"
+        select coalesce(sum(quantity), 0)::bigint as quantity from transactions
+        where item_id = $1
+    "#,
+    )
+    .bind(id)
+    .fetch_one(&mut *tx)
+    .await?
+    .try_get("quantity")?)
+}
+
+/// Narrows a quantity summed by the database to a quantity, failing if it overflows.
+fn narrow_quantity(quantity: i64) -> Result<ItemQuantity> {
+    i32::try_from(quantity)
+        .map(ItemQuantity)
+        .map_err(|_| Error::from(ModelError::validation("item quantity overflows")))
+}
+
+/// Gets the quantities of items as of dates, from their transactions dated up to and including
+/// the dates. Items without transactions by a date have a quantity of 0 as of the date.
+pub(crate) async fn get_quantities_as_of_keys(
+    clients: &Clients,
+    keys: Vec<QuantityAsOfKey>,
+) -> Result<HashMap<QuantityAsOfKey, Result<ItemQuantity>>> {
+    let mut results_map = keys
+        .iter()
+        .map(|key| (*key, Ok(ItemQuantity(0))))
+        .collect::<HashMap<_, _>>();
+
+    // dates are usually the same for all items, so query once per distinct date
+    let mut ids_by_date = HashMap::new();
+    for key in keys {
+        ids_by_date
+            .entry(key.date)
+            .or_insert_with(Vec::new)
+            .push(key.item_id.0);
+    }
+
+    for (date, ids) in ids_by_date {
+        let results = sqlx::query(
+            r#"
+            select item_id, sum(quantity) as quantity from transactions
+            where item_id = any($1) and transaction_date <= $2
+            group by item_id
+        "#,
+        )
+        .bind(ids)
+        .bind(date)
+        .fetch_all(&*clients.postgres)
+        .await
+        .map_err(Error::from)?;
+
+        for result in results {
+            let key = QuantityAsOfKey {
+                item_id: ItemId(result.try_get("item_id")?),
+                date,
+            };
+            results_map.insert(key, narrow_quantity(result.try_get("quantity")?));
+        }
+    }
+
+    Ok(results_map)
+}
+
+/// Gets the ledgers of all items with the given ids. Transactions are ordered by date, with
+/// undated transactions first, so backdated transactions are placed by their date, and undated
+/// transactions are the oldest, as they are in the listings with undated transactions last.
+pub(crate) async fn get_ledgers_by_item_ids(
+    clients: &Clients,
+    ids: Vec<ItemId>,
+) -> Result<HashMap<ItemId, Result<ItemLedger>>> {
+    let results = sqlx::query(
+        r#"
+        select item_id, id, sum(quantity) over (
+            partition by item_id
+            order by transaction_date nulls first, id
+        ) as balance
+        from transactions
+        where item_id = any($1)
+    "#,
+    )
+    .bind(ids.into_iter().map(|id| id.0).collect::<Vec<i32>>())
     .fetch_all(&*clients.postgres)
     .await
     .map_err(Error::from)?;
 
-    let mut results_map = HashMap::new();
+    // a balance which overflows fails the ledger of its item, rather than the whole batch
+    let mut ledgers_map = HashMap::<ItemId, Result<ItemLedger>>::new();
     for result in results {
-        results_map.insert(
-            ItemId(result.try_get("item_id")?),
-            i32::try_from(result.try_get::<Option<i64>, _>("coalesce")?.unwrap_or(0))
-                .map(ItemQuantity)
-                .map_err(Error::from),
-        );
+        let ledger = ledgers_map
+            .entry(ItemId(result.try_get("item_id")?))
+            .or_insert_with(|| Ok(ItemLedger::default()));
+        match narrow_quantity(result.try_get("balance")?) {
+            Ok(balance) => {
+                if let Ok(ledger) = ledger {
+                    ledger.balances.insert(result.try_get("id")?, balance);
+                }
+            }
+            Err(e) => *ledger = Err(e),
+        }
+    }
+
+    Ok(ledgers_map)
+}
+
+/// Gets the activity of items grouped by transaction reason.
+pub(crate) async fn get_activities_by_item_ids(
+    clients: &Clients,
+    ids: Vec<ItemId>,
+) -> Result<HashMap<ItemId, Result<Vec<ReasonActivity>>>> {
+    let results = sqlx::query(
+        r#"
+        select item_id, reason, count(id), sum(quantity) from transactions
+        where item_id = any($1)
+        group by item_id, reason
+        order by item_id, reason
+    "#,
+    )
+    .bind(ids.into_iter().map(|id| id.0).collect::<Vec<i32>>())
+    .fetch_all(&*clients.postgres)
+    .await
+    .map_err(Error::from)?;
+
+    // an activity which overflows fails the activities of its item, rather than the whole batch
+    let mut activities_map = HashMap::<ItemId, Result<Vec<ReasonActivity>>>::new();
+    for result in results {
+        let activities = activities_map
+            .entry(ItemId(result.try_get("item_id")?))
+            .or_insert_with(|| Ok(Vec::new()));
+        let count = i32::try_from(result.try_get::<i64, _>("count")?)
+            .map_err(|_| Error::from(ModelError::validation("transaction count overflows")));
+        let activity = count.and_then(|count| {
+            Ok(ReasonActivity {
+                reason: result.try_get("reason")?,
+                count,
+                net_quantity: narrow_quantity(result.try_get("sum")?)?,
+            })
+        });
+        match activity {
+            Ok(activity) => {
+                if let Ok(activities) = activities {
+                    activities.push(activity);
+                }
+            }
+            Err(e) => *activities = Err(e),
+        }
+    }
+
+    Ok(activities_map)
+}
+
+/// Gets the average daily consumption of items over the given windows, counting only outbound
+/// transactions.
+pub(crate) async fn get_consumptions_by_windows(
+    clients: &Clients,
+    windows: Vec<ConsumptionWindow>,
+) -> Result<HashMap<ConsumptionWindow, Result<f64>>> {
+    // items without outbound transactions have no consumption
+    let mut results_map = windows
+        .iter()
+        .map(|window| (*window, Ok(0.0)))
+        .collect::<HashMap<_, _>>();
+
+    // windows are usually the same for all items, so query once per distinct window
+    let mut ids_by_days = HashMap::new();
+    for window in windows {
+        ids_by_days
+            .entry(window.days)
+            .or_insert_with(Vec::new)
+            .push(window.item_id.0);
+    }
+
+    for (days, ids) in ids_by_days {
+        let results = sqlx::query(
+            r#"
+            select item_id, -sum(quantity)::float8 / $2 as consumption from transactions
+            where item_id = any($1) and quantity < 0
+                and transaction_date >= now() - make_interval(days => $2)
+            group by item_id
+        "#,
+        )
+        .bind(ids)
+        .bind(days)
+        .fetch_all(&*clients.postgres)
+        .await
+        .map_err(Error::from)?;
+
+        for result in results {
+            let window = ConsumptionWindow {
+                item_id: ItemId(result.try_get("item_id")?),
+                days,
+            };
+            results_map.insert(window, Ok(result.try_get("consumption")?));
+        }
     }
 
     Ok(results_map)
 }
 
+/// Gets suggestions to reorder items below their reorder point, returning the result, or an error.
+/// Items are restocked to their max quantity if set, otherwise to their reorder point plus their
+/// consumption over the given number of days.
+pub(crate) async fn get_reorder_suggestions(
+    context: &AppContext,
+    window_days: i32,
+) -> Result<Vec<ReorderSuggestion>, ModelError> {
+    sqlx::query_as::<_, ReorderSuggestion>(
+        r#"
+        with quantities as (
+            select items.id, items.reorder_point, items.max_quantity,
+                coalesce(sum(transactions.quantity), 0) as current_quantity
+            from items
+            left join transactions on transactions.item_id = items.id
+            where items.reorder_point is not null
+            group by items.id
+        ), consumption as (
+            select item_id, -sum(quantity) as consumed from transactions
+            where quantity < 0 and transaction_date >= now() - make_interval(days => $1)
+            group by item_id
+        )
+        select quantities.id as item_id,
+            quantities.current_quantity::integer as current_quantity,
+            coalesce(
+                quantities.max_quantity - quantities.current_quantity,
+                quantities.reorder_point - quantities.current_quantity
+                    + coalesce(consumption.consumed, 0)
+            )::integer as suggested_quantity
+        from quantities
+        left join consumption on consumption.item_id = quantities.id
+        where quantities.current_quantity < quantities.reorder_point
+        order by quantities.id
+    "#,
+    )
+    .bind(window_days)
+    .fetch_all(&*context.clients.postgres)
+    .await
+    .map_err(ModelError::from)
+}
+
+/// Recalculates the quantities of all items from their transactions, returning the quantities, or
+/// only those which differ from the loaded quantities or overflow, or an error. An item whose
+/// quantity overflows is reported without a quantity, rather than failing every item.
+pub(crate) async fn recalculate_quantities(
+    context: &AppContext,
+    discrepancies_only: bool,
+) -> Result<Vec<QuantityRecalculation>, ModelError> {
+    let results = sqlx::query(
+        r#"
+        select items.id, coalesce(sum(transactions.quantity), 0) as quantity from items
+        left join transactions on transactions.item_id = items.id
+        group by items.id
+        order by items.id
+    "#,
+    )
+    .fetch_all(&*context.clients.postgres)
+    .await?;
+
+    let mut quantities = Vec::new();
+    for result in results {
+        let quantity = i32::try_from(result.try_get::<i64, _>("quantity")?)
+            .ok()
+            .map(ItemQuantity);
+        quantities.push((ItemId(result.try_get("id")?), quantity));
+    }
+
+    // items without transactions are not loaded, and have no quantity
+    let loaded_quantities = context
+        .loaders
+        .get::<IdLoader<ItemId, ItemQuantity, Clients>>()
+        .unwrap()
+        .load_many(quantities.iter().map(|(id, _)| *id).collect())
+        .await;
+
+    Ok(quantities
+        .into_iter()
+        .map(|(item_id, quantity)| QuantityRecalculation {
+            item_id,
+            quantity,
+            loaded_quantity: match loaded_quantities.get(&item_id) {
+                Some(loaded) => loaded.as_ref().ok().copied(),
+                None => Some(ItemQuantity(0)),
+            },
+        })
+        .filter(|recalculation| {
+            !discrepancies_only
+                || recalculation.quantity.is_none()
+                || recalculation.quantity != recalculation.loaded_quantity
+        })
+        .collect())
+}
+
 /// Gets an item, given an id, returning the result, or an error.
 pub(crate) async fn get_item(context: &AppContext, id: ItemId) -> Result<Item> {
     context
@@ -153,28 +1032,80 @@ pub(crate) async fn get_item(context: &AppContext, id: ItemId) -> Result<Item> {
         .await
 }
 
-/// Creates an item, given an insertable item, returning the result, or an error.
-pub(crate) async fn create_item(context: &AppContext, item: InsertableItem) -> Result<Item> {
-    // check that the sku is unique
+/// Fetches an item in a database transaction, given an id, bypassing the loaders, so the changes of
+/// the transaction are seen, returning the result, or an error.
+pub(crate) async fn fetch_item(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    id: ItemId,
+) -> Result<Item, ModelError> {
+    sqlx::query_as::<_, Item>(
+        r#"
+        select id, sku, name, supplier, description, reorder_point, max_quantity, source
+        from items
+        where id = $1
+    "#,
+    )
+    .bind(id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(ModelError::from)
+}
+
+/// Gets an item by sku, ignoring case.
+pub(crate) async fn get_item_by_sku(context: &AppContext, sku: &str) -> Result<Item> {
+    context
+        .loaders
+        .get::<IdLoader<ItemSku, Item, Clients>>()
+        .unwrap()
+        .load(ItemSku::new(sku))
+        .await
+}
+
+/// Creates an item, given an insertable item and its source, if known, returning the result, or an
+/// error.
+pub(crate) async fn create_item(
+    context: &AppContext,
+    mut item: InsertableItem,
+    source: Option<&str>,
+) -> Result<Item, ModelError> {
+    item.normalize(&context.config);
+
+    // check that the sku and name are unique, where a concurrent write of the same sku or name
+    // passes this check, but is rejected by the unique indexes with the same error
     validation::item::validate_sku(context, &item, None).await?;
+    validation::item::validate_name(context, &item, None).await?;
 
+    let mut tx = context.clients.postgres.begin().await?;
     let created = sqlx::query_as::<_, Item>(
         r#"
-        insert into items (sku, name, supplier, description)
-        values ($1, $2, $3, $4)
-        returning id, sku, name, supplier, description
+        insert into items (sku, name, supplier, description, reorder_point, max_quantity,
+            unique_name, source)
+        values ($1, $2, $3, $4, $5, $6, $7, $8)
+        returning id, sku, name, supplier, description, reorder_point, max_quantity, source
     "#,
     )
-    .bind(item.sku)
-    .bind(item.name)
-    .bind(item.supplier)
-    .bind(item.description)
-    .fetch_one(&*context.clients.postgres)
+    .bind(&item.sku)
+    .bind(&item.name)
+    .bind(&item.supplier)
+    .bind(&item.description)
+    .bind(item.reorder_point)
+    .bind(item.max_quantity)
+    .bind(context.config.enforce_unique_item_name)
+    .bind(source)
+    .fetch_one(&mut tx)
     .await
-    .map_err(Error::from)?;
+    .map_err(|e| validation::item::map_conflict(e.into(), &item))?;
 
-    // publish the created event using redis pubsub and send the created item data
-    modification::broadcast(context, "items", ModificationType::Create, &created).await;
+    // enqueue the created event with the created item data, which is published once committed
+    modification::enqueue(
+        &mut tx,
+        ModificationEntity::Item,
+        ModificationType::Create,
+        &created,
+    )
+    .await?;
+    tx.commit().await?;
+    outbox::flush(context).await;
 
     Ok(created)
 }
@@ -183,70 +1114,535 @@ pub(crate) async fn create_item(context: &AppContext, item: InsertableItem) -> R
 pub(crate) async fn update_item(
     context: &AppContext,
     id: ItemId,
-    item: InsertableItem,
-) -> Result<Item, Error> {
-    // check that the sku is unique
+    mut item: InsertableItem,
+) -> Result<Item, ModelError> {
+    item.normalize(&context.config);
+
+    // check that the sku and name are unique, where a concurrent write of the same sku or name
+    // passes this check, but is rejected by the unique indexes with the same error
     validation::item::validate_sku(context, &item, Some(id)).await?;
+    validation::item::validate_name(context, &item, Some(id)).await?;
 
+    let mut tx = context.clients.postgres.begin().await?;
     let updated = sqlx::query_as::<_, Item>(
         r#"
         update items
-        set sku = $1, name = $2, supplier = $3, description = $4
-        where id = $5
-        returning id, sku, name, supplier, description
+        set sku = $1, name = $2, supplier = $3, description = $4, reorder_point = $5,
+            max_quantity = $6, unique_name = $7
+        where id = $8
+        returning id, sku, name, supplier, description, reorder_point, max_quantity, source
     "#,
     )
-    .bind(item.sku)
-    .bind(item.name)
-    .bind(item.supplier)
-    .bind(item.description)
+    .bind(&item.sku)
+    .bind(&item.name)
+    .bind(&item.supplier)
+    .bind(&item.description)
+    .bind(item.reorder_point)
+    .bind(item.max_quantity)
+    .bind(context.config.enforce_unique_item_name)
     .bind(id)
-    .fetch_one(&*context.clients.postgres)
+    .fetch_one(&mut tx)
     .await
-    .map_err(Error::from)?;
+    .map_err(|e| validation::item::map_conflict(e.into(), &item))?;
+
+    // enqueue the updated event with the item data, which is published once committed
+    modification::enqueue(
+        &mut tx,
+        ModificationEntity::Item,
+        ModificationType::Update,
+        &updated,
+    )
+    .await?;
+    tx.commit().await?;
+    outbox::flush(context).await;
+
+    Ok(updated)
+}
+
+/// Updates the items with the given ids, given a patch, returning the updated items, or an error.
+/// Either all of the items are updated, or none of them are.
+pub(crate) async fn bulk_update_items(
+    context: &AppContext,
+    ids: Vec<ItemId>,
+    mut patch: ItemPatch,
+) -> Result<Vec<Item>, ModelError> {
+    // check the ids and the patch once for all items, before normalizing, so a field which is
+    // normalized away, such as a description which is only unsafe markup, is not updated rather
+    // than leaving the patch empty
+    validation::item::validate_bulk_update(&ids, &patch)?;
+    patch.normalize(&context.config);
+
+    let mut ids = ids.into_iter().map(|id| id.0).collect::<Vec<i32>>();
+    ids.sort_unstable();
+    ids.dedup();
+
+    let mut tx = context.clients.postgres.begin().await?;
+    let mut updated = sqlx::query_as::<_, Item>(
+        r#"
+        update items
+        set supplier = coalesce($1, supplier), description = coalesce($2, description),
+            reorder_point = coalesce($3, reorder_point), max_quantity = coalesce($4, max_quantity)
+        where id = any($5)
+        returning id, sku, name, supplier, description, reorder_point, max_quantity, source
+    "#,
+    )
+    .bind(patch.supplier)
+    .bind(patch.description)
+    .bind(patch.reorder_point)
+    .bind(patch.max_quantity)
+    .bind(&ids)
+    .fetch_all(&mut tx)
+    .await?;
 
-    // publish the updated event using redis pubsub and send the item data
-    modification::broadcast(context, "items", ModificationType::Update, &updated).await;
+    // dropping the transaction rolls back the update if any item does not exist
+    if updated.len() != ids.len() {
+        return Err(ModelError::NotFound);
+    }
+
+    // enqueue an updated event for each item with the item data, which are published once committed
+    updated.sort_unstable_by_key(|item| item.id.0);
+    for item in &updated {
+        modification::enqueue(
+            &mut tx,
+            ModificationEntity::Item,
+            ModificationType::Update,
+            item,
+        )
+        .await?;
+    }
+    tx.commit().await?;
+    outbox::flush(context).await;
 
     Ok(updated)
 }
 
-/// Deletes an item, given an id, returning the result, or an error.
-pub(crate) async fn delete_item(context: &AppContext, id: ItemId) -> Result<Item> {
+/// Imports items, given insertable items and the source of the import, returning the created or
+/// updated items and the items whose skus collide with existing items, ignoring case, or an error.
+/// Either all of the imported items are written, or none of them are. Updated items keep the source
+/// which created them.
+pub(crate) async fn import_items(
+    context: &AppContext,
+    mut items: Vec<InsertableItem>,
+    on_conflict: ImportConflictMode,
+    source: &str,
+) -> Result<ItemImport, ModelError> {
+    for item in &mut items {
+        item.normalize(&context.config);
+    }
+
+    // check the number of items and that skus are not repeated within the import
+    validation::item::validate_import(&items, &context.config)?;
+
+    // find the existing items of all of the skus in one query rather than per item
+    let skus = items
+        .iter()
+        .filter_map(|item| item.sku.as_ref().map(|sku| sku.to_uppercase()))
+        .collect::<Vec<String>>();
+    let existing = sqlx::query(
+        r#"
+        select id, upper(sku) as sku from items
+        where upper(sku) = any($1)
+    "#,
+    )
+    .bind(&skus)
+    .fetch_all(&*context.clients.postgres)
+    .await?
+    .into_iter()
+    .map(|r| Ok((r.try_get("sku")?, r.try_get("id")?)))
+    .collect::<Result<HashMap<String, ItemId>, sqlx::Error>>()?;
+
+    let conflicts = items
+        .iter()
+        .enumerate()
+        .filter_map(|(row, item)| {
+            let sku = item.sku.as_ref()?;
+            existing
+                .get(&sku.to_uppercase())
+                .map(|existing_item_id| SkuConflict {
+                    row: row as i32,
+                    sku: sku.clone(),
+                    existing_item_id: *existing_item_id,
+                })
+        })
+        .collect::<Vec<SkuConflict>>();
+
+    if on_conflict == ImportConflictMode::Fail && !conflicts.is_empty() {
+        return Ok(ItemImport {
+            items: Vec::new(),
+            conflicts,
+        });
+    }
+
+    let mut tx = context.clients.postgres.begin().await?;
+    let mut imported = Vec::new();
+    for item in &items {
+        let existing_item_id = item
+            .sku
+            .as_ref()
+            .and_then(|sku| existing.get(&sku.to_uppercase()));
+        let query = match (existing_item_id, on_conflict) {
+            (None, _) => sqlx::query_as::<_, Item>(
+                r#"
+                insert into items (sku, name, supplier, description, reorder_point, max_quantity,
+                    unique_name, source)
+                values ($1, $2, $3, $4, $5, $6, $7, $8)
+                returning id, sku, name, supplier, description, reorder_point, max_quantity, source
+            "#,
+            ),
+            (Some(_), ImportConflictMode::Update) => sqlx::query_as::<_, Item>(
+                r#"
+                update items
+                set sku = $1, name = $2, supplier = $3, description = $4, reorder_point = $5,
+                    max_quantity = $6, unique_name = $7
+                where id = $8
+                returning id, sku, name, supplier, description, reorder_point, max_quantity, source
+            "#,
+            ),
+            (Some(_), _) => continue,
+        };
+
+        let query = query
+            .bind(&item.sku)
+            .bind(&item.name)
+            .bind(&item.supplier)
+            .bind(&item.description)
+            .bind(item.reorder_point)
+            .bind(item.max_quantity)
+            .bind(context.config.enforce_unique_item_name);
+        let query = match existing_item_id {
+            Some(existing_item_id) => query.bind(existing_item_id),
+            None => query.bind(source),
+        };
+        let written = query
+            .fetch_one(&mut tx)
+            .await
+            .map_err(|e| validation::item::map_conflict(e.into(), item))?;
+        imported.push((existing_item_id.is_some(), written));
+    }
+
+    // enqueue a created or updated event for each item with the item data, which are published once
+    // committed
+    for (is_update, item) in &imported {
+        let modification_type = if *is_update {
+            ModificationType::Update
+        } else {
+            ModificationType::Create
+        };
+        modification::enqueue(&mut tx, ModificationEntity::Item, modification_type, item).await?;
+    }
+    tx.commit().await?;
+    outbox::flush(context).await;
+
+    Ok(ItemImport {
+        items: imported.into_iter().map(|(_, item)| item).collect(),
+        conflicts,
+    })
+}
+
+/// Creates an item, or updates the item with the same sku, ignoring case, given an insertable item
+/// with a sku and its source, if known, returning the result and whether the item was created, or
+/// an error. Updated items keep the source which created them.
+pub(crate) async fn upsert_item_by_sku(
+    context: &AppContext,
+    mut item: InsertableItem,
+    source: Option<&str>,
+) -> Result<ItemUpsert, ModelError> {
+    item.normalize(&context.config);
+
+    let sku = item.sku.as_deref().ok_or_else(|| ModelError::Validation {
+        message: "validation errors on item".to_string(),
+        fields: vec![("sku", "sku must be given to upsert an item".to_string())],
+    })?;
+    validation::item::validate_sku_format(&context.config, sku)?;
+
+    // the name may belong to the item being updated
+    let existing_item_id = sqlx::query(
+        r#"
+        select id from items
+        where upper(sku) = upper($1)
+    "#,
+    )
+    .bind(sku)
+    .fetch_optional(&*context.clients.postgres)
+    .await?
+    .map(|row| row.try_get("id").map(ItemId))
+    .transpose()?;
+    validation::item::validate_name(context, &item, existing_item_id).await?;
+
+    // rows inserted by this statement have no deleting transaction id, unlike updated rows
+    let mut tx = context.clients.postgres.begin().await?;
+    let row = sqlx::query(
+        r#"
+        insert into items (sku, name, supplier, description, reorder_point, max_quantity,
+            unique_name, source)
+        values ($1, $2, $3, $4, $5, $6, $7, $8)
+        on conflict (upper(sku)) do update
+        set sku = excluded.sku, name = excluded.name, supplier = excluded.supplier,
+            description = excluded.description, reorder_point = excluded.reorder_point,
+            max_quantity = excluded.max_quantity, unique_name = excluded.unique_name
+        returning id, sku, name, supplier, description, reorder_point, max_quantity, source,
+            xmax = 0 as created
+    "#,
+    )
+    .bind(&item.sku)
+    .bind(&item.name)
+    .bind(&item.supplier)
+    .bind(&item.description)
+    .bind(item.reorder_point)
+    .bind(item.max_quantity)
+    .bind(context.config.enforce_unique_item_name)
+    .bind(source)
+    .fetch_one(&mut tx)
+    .await
+    .map_err(|e| validation::item::map_conflict(e.into(), &item))?;
+    let upserted = ItemUpsert {
+        item: Item::from_row(&row)?,
+        created: row.try_get("created")?,
+    };
+
+    // enqueue the created or updated event with the item data, which is published once committed
+    let modification_type = if upserted.created {
+        ModificationType::Create
+    } else {
+        ModificationType::Update
+    };
+    modification::enqueue(
+        &mut tx,
+        ModificationEntity::Item,
+        modification_type,
+        &upserted.item,
+    )
+    .await?;
+    tx.commit().await?;
+    outbox::flush(context).await;
+
+    Ok(upserted)
+}
+
+/// Deletes an item, given an id, returning the result, or an error. Transactions of the item are
+/// also deleted.
+pub(crate) async fn delete_item(context: &AppContext, id: ItemId) -> Result<Item, ModelError> {
+    let mut tx = context.clients.postgres.begin().await?;
+    // delete the transactions explicitly, so that the affected transactions are known
+    let deleted_transactions = sqlx::query_as::<_, Transaction>(&format!(
+        r#"
+        delete from transactions
+        where item_id = $1
+        returning {}
+    "#,
+        TRANSACTION_COLUMNS
+    ))
+    .bind(id)
+    .fetch_all(&mut tx)
+    .await?;
+
     let deleted = sqlx::query_as::<_, Item>(
         r#"
         delete from items
         where id = $1
-        returning id, sku, name, supplier, description
+        returning id, sku, name, supplier, description, reorder_point, max_quantity, source
     "#,
     )
     .bind(id)
-    .fetch_one(&*context.clients.postgres)
-    .await
-    .map_err(Error::from)?;
+    .fetch_one(&mut tx)
+    .await?;
 
-    // publish the deleted event using redis pubsub and send the item data
-    modification::broadcast(context, "items", ModificationType::Delete, &deleted).await;
+    // enqueue the deleted events with the item and transaction data, which are published once
+    // committed
+    modification::enqueue(
+        &mut tx,
+        ModificationEntity::Item,
+        ModificationType::Delete,
+        &deleted,
+    )
+    .await?;
+    for transaction in &deleted_transactions {
+        modification::enqueue(
+            &mut tx,
+            ModificationEntity::Transaction,
+            ModificationType::Delete,
+            transaction,
+        )
+        .await?;
+    }
+
+    // the locations of the deleted transactions have changed
+    let mut location_ids = deleted_transactions
+        .iter()
+        .filter_map(|transaction| transaction.location_id)
+        .collect::<Vec<_>>();
+    location_ids.sort_unstable_by_key(|id| i32::from(*id));
+    location_ids.dedup();
+    for location_id in location_ids {
+        if let Ok(location) = location::fetch_location(&mut tx, location_id).await {
+            modification::enqueue(
+                &mut tx,
+                ModificationEntity::Location,
+                ModificationType::Update,
+                &location,
+            )
+            .await?;
+        }
+    }
+    tx.commit().await?;
+    outbox::flush(context).await;
 
     Ok(deleted)
 }
 
+impl Item {
+    async fn get_quantity(&self, context: &AppContext) -> ItemQuantity {
+        // a joined quantity which overflows is loaded instead, so it fails the same way
+        if let Some(quantity) = self
+            .joined_quantity
+            .and_then(|quantity| i32::try_from(quantity).ok())
+        {
+            return ItemQuantity(quantity);
+        }
+
+        context
+            .loaders
+            .get::<IdLoader<ItemId, ItemQuantity, Clients>>()
+            .unwrap()
+            .load(self.id)
+            .await
+            .unwrap_or(ItemQuantity(0))
+    }
+
+    async fn get_quantity_reserved(&self, context: &AppContext) -> ItemQuantity {
+        context
+            .loaders
+            .get::<IdLoader<ItemId, ReservedQuantity, Clients>>()
+            .unwrap()
+            .load(self.id)
+            .await
+            .map(|reserved| reserved.0)
+            .unwrap_or(ItemQuantity(0))
+    }
+}
+
 /// An item in the inventory tracking system.
 #[async_graphql::ComplexObject]
 impl Item {
     /// The quantity of the item.
     async fn quantity(&self, context: &async_graphql::Context<'_>) -> ItemQuantity {
+        self.get_quantity(context.data_unchecked::<AppContext>())
+            .await
+    }
+
+    /// The quantity of the item as of a date, inclusive, from its dated transactions, which is 0
+    /// before its first transaction.
+    #[graphql(complexity = "QUANTITY_AS_OF_COMPLEXITY")]
+    async fn quantity_as_of(
+        &self,
+        context: &async_graphql::Context<'_>,
+        #[graphql(desc = "The date in RFC 3339 format.")] date: DateTime<Utc>,
+    ) -> Result<ItemQuantity> {
         context
             .data_unchecked::<AppContext>()
             .loaders
-            .get::<IdLoader<ItemId, ItemQuantity, Clients>>()
+            .get::<IdLoader<QuantityAsOfKey, ItemQuantity, Clients>>()
+            .unwrap()
+            .load(QuantityAsOfKey {
+                item_id: self.id,
+                date,
+            })
+            .await
+    }
+
+    /// The quantity of the item reserved by active reservations.
+    async fn quantity_reserved(&self, context: &async_graphql::Context<'_>) -> ItemQuantity {
+        self.get_quantity_reserved(context.data_unchecked::<AppContext>())
+            .await
+    }
+
+    /// The quantity of the item on hand which is not reserved.
+    async fn quantity_available(&self, context: &async_graphql::Context<'_>) -> ItemQuantity {
+        let context = context.data_unchecked::<AppContext>();
+        self.get_quantity(context)
+            .await
+            .saturating_sub(self.get_quantity_reserved(context).await)
+    }
+
+    /// The first page of the transactions of the item, most recent first.
+    async fn transaction_page(
+        &self,
+        context: &async_graphql::Context<'_>,
+        #[graphql(default = 20, validator(minimum = 1, maximum = 100))] first: i32,
+    ) -> Result<TransactionPage> {
+        context
+            .data_unchecked::<AppContext>()
+            .loaders
+            .get::<IdLoader<TransactionPageKey, TransactionPage, Clients>>()
+            .unwrap()
+            .load(TransactionPageKey {
+                item_id: self.id,
+                first,
+            })
+            .await
+    }
+
+    /// The number of transactions and net quantity of the item for each transaction reason.
+    async fn activity_breakdown(
+        &self,
+        context: &async_graphql::Context<'_>,
+    ) -> Vec<ReasonActivity> {
+        context
+            .data_unchecked::<AppContext>()
+            .loaders
+            .get::<IdLoader<ItemId, Vec<ReasonActivity>, Clients>>()
             .unwrap()
             .load(self.id)
             .await
-            .unwrap_or(ItemQuantity(0))
+            .unwrap_or_default()
+    }
+
+    /// The average quantity of the item consumed per day over the given number of days.
+    async fn average_daily_consumption(
+        &self,
+        context: &async_graphql::Context<'_>,
+        #[graphql(validator(minimum = 1))] days: i32,
+    ) -> Result<f64> {
+        context
+            .data_unchecked::<AppContext>()
+            .loaders
+            .get::<IdLoader<ConsumptionWindow, f64, Clients>>()
+            .unwrap()
+            .load(ConsumptionWindow {
+                item_id: self.id,
+                days,
+            })
+            .await
+    }
+
+    /// The attachments of the item, in the order of their sort indexes.
+    #[graphql(complexity = "list_complexity(NESTED_LIST_COMPLEXITY_MULTIPLIER, child_complexity)")]
+    async fn attachments(&self, context: &async_graphql::Context<'_>) -> Vec<ItemAttachment> {
+        context
+            .data_unchecked::<AppContext>()
+            .loaders
+            .get::<IdLoader<ItemId, Vec<ItemAttachment>, Clients>>()
+            .unwrap()
+            .load(self.id)
+            .await
+            .unwrap_or_default()
+    }
+
+    /// The distinct locations which the item has been transacted at, in the order of their names.
+    #[graphql(complexity = "list_complexity(NESTED_LIST_COMPLEXITY_MULTIPLIER, child_complexity)")]
+    async fn locations(&self, context: &async_graphql::Context<'_>) -> Vec<Location> {
+        context
+            .data_unchecked::<AppContext>()
+            .loaders
+            .get::<IdLoader<ItemId, Vec<Location>, Clients>>()
+            .unwrap()
+            .load(self.id)
+            .await
+            .unwrap_or_default()
     }
 
     /// The transactions of the item.
+    #[graphql(complexity = "list_complexity(NESTED_LIST_COMPLEXITY_MULTIPLIER, child_complexity)")]
     async fn transactions(&self, context: &async_graphql::Context<'_>) -> Vec<Transaction> {
         context
             .data_unchecked::<AppContext>()
@@ -258,3 +1654,120 @@ impl Item {
             .unwrap_or_default()
     }
 }
+
+/// A recalculated item quantity.
+#[async_graphql::ComplexObject]
+impl QuantityRecalculation {
+    /// The item whose quantity was recalculated.
+    async fn item(&self, context: &async_graphql::Context<'_>) -> Result<Item> {
+        get_item(context.data_unchecked::<AppContext>(), self.item_id).await
+    }
+}
+
+/// A suggestion to reorder an item.
+#[async_graphql::ComplexObject]
+impl ReorderSuggestion {
+    /// The item to reorder.
+    async fn item(&self, context: &async_graphql::Context<'_>) -> Result<Item> {
+        get_item(context.data_unchecked::<AppContext>(), self.item_id).await
+    }
+}
+
+/// Unit tests for items.
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_item_columns() {
+        assert_eq!(
+            get_item_columns(|field| field == "name"),
+            "id, null::text as sku, name, null::text as supplier, null::text as description, \
+                null::integer as reorder_point, null::integer as max_quantity, null::text as source"
+        );
+        assert_eq!(
+            get_item_columns(|_| true),
+            "id, sku, name, supplier, description, reorder_point, max_quantity, source"
+        );
+        assert!(!get_item_columns(|field| field != "description").contains(", description"));
+    }
+
+    #[test]
+    fn test_quantity_int_scalar() {
+        assert_eq!(
+            <ItemQuantity as ScalarType>::parse(Value::from(-3)).unwrap(),
+            ItemQuantity(-3)
+        );
+        assert!(<ItemQuantity as ScalarType>::parse(Value::from("3")).is_err());
+        assert!(<ItemQuantity as ScalarType>::parse(Value::from(i64::from(i32::MAX) + 1)).is_err());
+        assert_eq!(ItemQuantity(5).to_value(), Value::from(5));
+
+        // check that quantities are the standard int scalar in the schema, while ids are not
+        let sdl = crate::graphql::schema_builder().finish().sdl();
+        assert!(!sdl.contains("ItemQuantity"));
+        assert!(sdl.contains("quantity: Int!"));
+        assert!(sdl.contains("reorderPoint: Int"));
+        assert!(sdl.contains("scalar ItemId"));
+    }
+
+    #[test]
+    fn test_checked_add() {
+        assert_eq!(
+            ItemQuantity(1).checked_add(ItemQuantity(2)),
+            Some(ItemQuantity(3))
+        );
+        assert_eq!(
+            ItemQuantity(i32::MAX - 1).checked_add(ItemQuantity(1)),
+            Some(ItemQuantity(i32::MAX))
+        );
+        assert_eq!(ItemQuantity(i32::MAX).checked_add(ItemQuantity(1)), None);
+        assert_eq!(ItemQuantity(i32::MIN).checked_add(ItemQuantity(-1)), None);
+    }
+
+    #[test]
+    fn test_checked_sub() {
+        assert_eq!(
+            ItemQuantity(1).checked_sub(ItemQuantity(2)),
+            Some(ItemQuantity(-1))
+        );
+        assert_eq!(
+            ItemQuantity(i32::MIN + 1).checked_sub(ItemQuantity(1)),
+            Some(ItemQuantity(i32::MIN))
+        );
+        assert_eq!(ItemQuantity(i32::MIN).checked_sub(ItemQuantity(1)), None);
+        assert_eq!(ItemQuantity(i32::MAX).checked_sub(ItemQuantity(-1)), None);
+        assert_eq!(ItemQuantity(0).checked_sub(ItemQuantity(i32::MIN)), None);
+    }
+
+    #[test]
+    fn test_saturating() {
+        assert_eq!(
+            ItemQuantity(3).saturating_sub(ItemQuantity(2)),
+            ItemQuantity(1)
+        );
+        assert_eq!(
+            ItemQuantity(i32::MIN).saturating_sub(ItemQuantity(1)),
+            ItemQuantity(i32::MIN)
+        );
+        assert_eq!(
+            ItemQuantity(i32::MAX).saturating_sub(ItemQuantity(-1)),
+            ItemQuantity(i32::MAX)
+        );
+        assert_eq!(
+            ItemQuantity(1).saturating_add(ItemQuantity(2)),
+            ItemQuantity(3)
+        );
+        assert_eq!(
+            ItemQuantity(i32::MAX - 1).saturating_add(ItemQuantity(1)),
+            ItemQuantity(i32::MAX)
+        );
+        assert_eq!(
+            ItemQuantity(i32::MAX).saturating_add(ItemQuantity(1)),
+            ItemQuantity(i32::MAX)
+        );
+        assert_eq!(
+            ItemQuantity(i32::MIN).saturating_add(ItemQuantity(-1)),
+            ItemQuantity(i32::MIN)
+        );
+    }
+}
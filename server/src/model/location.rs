@@ -2,12 +2,24 @@ use std::collections::HashMap;
 use std::fmt::Debug;
 
 use async_graphql::{Error, Result};
+use futures::stream::{BoxStream, StreamExt};
 use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Row};
 
 use crate::batcher::id_loader::IdLoader;
-use crate::graphql::{AppContext, Clients};
-use crate::model::modification::{self, ModificationType};
-use crate::model::transaction::Transaction;
+use crate::graphql::{
+    list_complexity, page_complexity, AppContext, Clients, NESTED_LIST_COMPLEXITY_MULTIPLIER,
+};
+use crate::model::error::ModelError;
+use crate::model::item::{self, Item, ItemId, ItemQuantity};
+use crate::model::modification::{self, ModificationEntity, ModificationType};
+use crate::model::normalize;
+use crate::model::outbox;
+use crate::model::transaction::{Transaction, TRANSACTION_COLUMNS};
+use crate::model::validation;
+
+/// The mean radius of the earth in kilometres.
+const EARTH_RADIUS_KM: f64 = 6371.0;
 
 /// The id of a location.
 #[derive(PartialEq, Eq, Into, Hash, Copy, Clone, Debug, sqlx::Type, Serialize, Deserialize)]
@@ -21,31 +33,131 @@ async_graphql::scalar!(LocationId);
 )]
 #[graphql(complex)]
 pub(crate) struct Location {
-    id: LocationId,
+    pub(crate) id: LocationId,
     name: String,
     address: Option<String>,
+    lat: Option<f64>,
+    lng: Option<f64>,
+}
+
+/// A location near a point, with its distance from the point.
+#[derive(Debug, Clone, PartialEq, async_graphql::SimpleObject)]
+pub(crate) struct NearbyLocation {
+    location: Location,
+    distance_km: f64,
+}
+
+/// The first items with stock at a location after an offset, which are loaded as a page.
+#[derive(PartialEq, Eq, Hash, Copy, Clone, Debug)]
+pub(crate) struct StockPageKey {
+    location_id: LocationId,
+    first: i32,
+    offset: i32,
+}
+
+/// An item with stock at a location, with its quantity at the location.
+#[derive(Debug, Clone, PartialEq, async_graphql::SimpleObject)]
+pub(crate) struct LocationStock {
+    item: Item,
+    quantity: ItemQuantity,
+}
+
+/// A page of the items with stock at a location, in the order of their ids.
+#[derive(Debug, Clone, PartialEq, async_graphql::SimpleObject)]
+pub(crate) struct StockPage {
+    stock: Vec<LocationStock>,
+    has_next_page: bool,
+}
+
+/// The stock of an item at a location, for a snapshot of all stock.
+#[derive(Debug, Clone, PartialEq, FromRow)]
+pub(crate) struct StockSnapshotRow {
+    pub(crate) item_id: ItemId,
+    pub(crate) sku: Option<String>,
+    pub(crate) name: String,
+    /// The id of the location, or none for the stock of transactions without a location.
+    pub(crate) location_id: Option<LocationId>,
+    pub(crate) location_name: Option<String>,
+    pub(crate) quantity: i64,
 }
 
 /// Location model to input to the inventory tracking system.
 #[derive(Debug, PartialEq, Deserialize, async_graphql::InputObject)]
 pub(crate) struct InsertableLocation {
     #[graphql(validator(min_length = 1))]
-    name: String,
+    pub(crate) name: String,
     #[graphql(validator(min_length = 1))]
     address: Option<String>,
+    pub(crate) lat: Option<f64>,
+    pub(crate) lng: Option<f64>,
+}
+
+impl InsertableLocation {
+    /// Collapses the whitespace of the name, and trims the address, so that the normalized values
+    /// are validated and stored.
+    fn normalize(&mut self) {
+        self.name = normalize::collapse_whitespace(&self.name);
+        self.address = normalize::trim_optional(self.address.take());
+    }
 }
 
 /// Gets all locations, returning the result, or an error.
-pub(crate) async fn get_locations(context: &AppContext) -> Result<Vec<Location>> {
+pub(crate) async fn get_locations(context: &AppContext) -> Result<Vec<Location>, ModelError> {
     sqlx::query_as::<_, Location>(
         r#"
-        select id, name, address from locations
+        select id, name, address, lat, lng from locations
         order by name
     "#,
     )
     .fetch_all(&*context.clients.postgres)
     .await
-    .map_err(Error::from)
+    .map_err(ModelError::from)
+}
+
+/// Gets all locations within a radius of a point, nearest first, returning the result, or an error.
+/// Locations without coordinates are skipped.
+pub(crate) async fn get_locations_near(
+    context: &AppContext,
+    lat: f64,
+    lng: f64,
+    radius_km: f64,
+) -> Result<Vec<NearbyLocation>, ModelError> {
+    validation::location::validate_coordinates(Some(lat), Some(lng))?;
+    if radius_km < 0.0 {
+        return Err(ModelError::validation("radius must not be negative"));
+    }
+
+    // the haversine distance is only computed for locations within the latitude band of the radius
+    let rows = sqlx::query(
+        r#"
+        select * from (
+            select id, name, address, lat, lng,
+                2 * $4 * asin(sqrt(
+                    power(sin(radians(lat - $1) / 2), 2)
+                        + cos(radians($1)) * cos(radians(lat)) * power(sin(radians(lng - $2) / 2), 2)
+                )) as distance_km
+            from locations
+            where lat between $1 - degrees($3 / $4) and $1 + degrees($3 / $4) and lng is not null
+        ) as distances
+        where distance_km <= $3
+        order by distance_km, id
+    "#,
+    )
+    .bind(lat)
+    .bind(lng)
+    .bind(radius_km)
+    .bind(EARTH_RADIUS_KM)
+    .fetch_all(&*context.clients.postgres)
+    .await?;
+
+    rows.iter()
+        .map(|row| {
+            Ok(NearbyLocation {
+                location: Location::from_row(row)?,
+                distance_km: row.try_get("distance_km")?,
+            })
+        })
+        .collect()
 }
 
 /// Gets all locations with the given ids.
@@ -55,7 +167,7 @@ pub(crate) async fn get_locations_by_ids(
 ) -> Result<HashMap<LocationId, Result<Location>>> {
     sqlx::query_as::<_, Location>(
         r#"
-        select id, name, address from locations
+        select id, name, address, lat, lng from locations
         where id = any($1)
     "#,
     )
@@ -71,35 +183,186 @@ pub(crate) async fn get_locations_by_ids(
     .map_err(Error::from)
 }
 
-/// Gets all transactions with the given location ids.
+/// Gets the distinct locations which the transactions of each of the given items are at, in the
+/// order of their names. Transactions without a location are ignored.
+pub(crate) async fn get_locations_by_item_ids(
+    clients: &Clients,
+    ids: Vec<ItemId>,
+) -> Result<HashMap<ItemId, Result<Vec<Location>>>> {
+    let results = sqlx::query(
+        r#"
+        select item_locations.item_id, locations.id, locations.name, locations.address,
+            locations.lat, locations.lng
+        from (
+            select distinct item_id, location_id from transactions
+            where item_id = any($1) and location_id is not null
+        ) as item_locations
+        join locations on locations.id = item_locations.location_id
+        order by locations.name, locations.id
+    "#,
+    )
+    .bind(ids.into_iter().map(i32::from).collect::<Vec<i32>>())
+    .fetch_all(&*clients.postgres)
+    .await
+    .map_err(Error::from)?;
+
+    let mut locations_map = HashMap::new();
+    for result in results {
+        locations_map
+            .entry(result.try_get::<ItemId, _>("item_id")?)
+            .or_insert_with(Vec::new)
+            .push(Location::from_row(&result)?);
+    }
+
+    Ok(locations_map
+        .into_iter()
+        .map(|(key, value)| (key, Ok(value)))
+        .collect())
+}
+
+/// Gets all transactions with the given location ids, most recent first, with undated transactions
+/// last.
 pub(crate) async fn get_transactions_by_location_ids(
     clients: &Clients,
     ids: Vec<LocationId>,
 ) -> Result<HashMap<LocationId, Result<Vec<Transaction>>>> {
-    sqlx::query_as::<_, Transaction>(
+    sqlx::query_as::<_, Transaction>(&format!(
         r#"
-        select id, item_id, location_id, transaction_date, quantity, comment from transactions
+        select {} from transactions
         where location_id = any($1)
-        order by transaction_date desc
+        order by transaction_date desc nulls last, id desc
     "#,
-    )
+        TRANSACTION_COLUMNS
+    ))
     .bind(ids.into_iter().map(|id| id.0).collect::<Vec<i32>>())
     .fetch_all(&*clients.postgres)
     .await
-    .map(|transactions| {
-        let mut transactions_map = HashMap::new();
-        transactions.into_iter().for_each(|transaction| {
+    .map(group_by_location)
+    .map_err(Error::from)
+}
+
+/// Streams the nonzero stock of every item at each location from one grouped query, in the order
+/// of the item and location ids, with the stock of transactions without a location last for each
+/// item.
+pub(crate) fn get_stock_snapshot(
+    context: &AppContext,
+) -> BoxStream<'_, Result<StockSnapshotRow, ModelError>> {
+    sqlx::query_as::<_, StockSnapshotRow>(
+        r#"
+        select items.id as item_id, items.sku, items.name, locations.id as location_id,
+            locations.name as location_name, sum(transactions.quantity)::bigint as quantity
+        from transactions
+        join items on items.id = transactions.item_id
+        left join locations on locations.id = transactions.location_id
+        group by items.id, locations.id
+        having sum(transactions.quantity) <> 0
+        order by items.id, locations.id nulls last
+    "#,
+    )
+    .fetch(&*context.clients.postgres)
+    .map(|row| row.map_err(ModelError::from))
+    .boxed()
+}
+
+/// Gets pages of the items with stock at locations, fetching one more item than the page size per
+/// location to determine if there is a next page. Items whose transactions at a location net to
+/// zero are not stocked there.
+pub(crate) async fn get_stock_pages_by_keys(
+    clients: &Clients,
+    keys: Vec<StockPageKey>,
+) -> Result<HashMap<StockPageKey, Result<StockPage>>> {
+    // pages are usually the same for all locations, so query once per distinct page
+    let mut ids_by_page = HashMap::new();
+    for key in keys {
+        ids_by_page
+            .entry((key.first, key.offset))
+            .or_insert_with(Vec::new)
+            .push(key.location_id.0);
+    }
+
+    let mut results_map = HashMap::new();
+    for ((first, offset), ids) in ids_by_page {
+        let results = sqlx::query(
+            r#"
+            select location_id, quantity, id, sku, name, supplier, description, reorder_point,
+                max_quantity, source
+            from (
+                select stock.location_id, stock.quantity, items.id, items.sku, items.name,
+                    items.supplier, items.description, items.reorder_point, items.max_quantity,
+                    items.source,
+                    row_number() over (
+                        partition by stock.location_id order by items.id
+                    ) as row_number
+                from (
+                    select location_id, item_id, sum(quantity)::integer as quantity
+                    from transactions
+                    where location_id = any($1)
+                    group by location_id, item_id
+                    having sum(quantity) <> 0
+                ) as stock
+                join items on items.id = stock.item_id
+            ) as ranked
+            where row_number > $2 and row_number <= $2 + $3 + 1
+            order by location_id, row_number
+        "#,
+        )
+        .bind(&ids)
+        .bind(offset)
+        .bind(first)
+        .fetch_all(&*clients.postgres)
+        .await
+        .map_err(Error::from)?;
+
+        let mut stock_map = HashMap::new();
+        for result in results {
+            stock_map
+                .entry(result.try_get::<LocationId, _>("location_id")?)
+                .or_insert_with(Vec::new)
+                .push(LocationStock {
+                    item: Item::from_row(&result)?,
+                    quantity: result.try_get("quantity")?,
+                });
+        }
+
+        // locations without stock have an empty page
+        for id in ids {
+            let mut stock = stock_map.remove(&LocationId(id)).unwrap_or_default();
+            let has_next_page = stock.len() > first as usize;
+            stock.truncate(first as usize);
+            results_map.insert(
+                StockPageKey {
+                    location_id: LocationId(id),
+                    first,
+                    offset,
+                },
+                Ok(StockPage {
+                    stock,
+                    has_next_page,
+                }),
+            );
+        }
+    }
+
+    Ok(results_map)
+}
+
+/// Groups transactions by location, skipping transactions without a location.
+fn group_by_location(
+    transactions: Vec<Transaction>,
+) -> HashMap<LocationId, Result<Vec<Transaction>>> {
+    let mut transactions_map = HashMap::new();
+    for transaction in transactions {
+        if let Some(location_id) = transaction.location_id {
             transactions_map
-                .entry(transaction.location_id.unwrap())
+                .entry(location_id)
                 .or_insert_with(Vec::new)
                 .push(transaction);
-        });
-        transactions_map
-            .into_iter()
-            .map(|(key, value)| (key, Ok(value)))
-            .collect()
-    })
-    .map_err(Error::from)
+        }
+    }
+    transactions_map
+        .into_iter()
+        .map(|(key, value)| (key, Ok(value)))
+        .collect()
 }
 
 /// Gets an location, given an id, returning the result, or an error.
@@ -112,26 +375,130 @@ pub(crate) async fn get_location(context: &AppContext, id: LocationId) -> Result
         .await
 }
 
+/// Gets the locations with the given ids in one round-trip, in the order of the ids. Ids of
+/// locations which do not exist resolve to `None`, rather than failing the other locations.
+pub(crate) async fn get_locations_in_order(
+    context: &AppContext,
+    ids: Vec<LocationId>,
+) -> Result<Vec<Option<Location>>> {
+    let locations = get_locations_by_ids(&context.clients, ids.clone()).await?;
+    Ok(ids
+        .iter()
+        .map(|id| {
+            locations
+                .get(id)
+                .and_then(|location| location.as_ref().ok())
+                .cloned()
+        })
+        .collect())
+}
+
+/// Fetches a location in a database transaction, given an id, bypassing the loaders, so the changes
+/// of the transaction are seen, returning the result, or an error.
+pub(crate) async fn fetch_location(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    id: LocationId,
+) -> Result<Location, ModelError> {
+    sqlx::query_as::<_, Location>(
+        r#"
+        select id, name, address, lat, lng from locations
+        where id = $1
+    "#,
+    )
+    .bind(id)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(ModelError::from)
+}
+
 /// Creates an location, given an insertable location, returning the result, or an error.
 pub(crate) async fn create_location(
     context: &AppContext,
-    location: InsertableLocation,
-) -> Result<Location> {
+    mut location: InsertableLocation,
+) -> Result<Location, ModelError> {
+    location.normalize();
+    validation::location::validate_location(&location)?;
+
+    let mut tx = context.clients.postgres.begin().await?;
     let created = sqlx::query_as::<_, Location>(
         r#"
-        insert into locations (name, address)
-        values ($1, $2)
-        returning id, name, address
+        insert into locations (name, address, lat, lng)
+        values ($1, $2, $3, $4)
+        returning id, name, address, lat, lng
     "#,
     )
     .bind(location.name)
     .bind(location.address)
-    .fetch_one(&*context.clients.postgres)
-    .await
-    .map_err(Error::from)?;
+    .bind(location.lat)
+    .bind(location.lng)
+    .fetch_one(&mut tx)
+    .await?;
+
+    // enqueue the created event with the created location data, which is published once committed
+    modification::enqueue(
+        &mut tx,
+        ModificationEntity::Location,
+        ModificationType::Create,
+        &created,
+    )
+    .await?;
+    tx.commit().await?;
+    outbox::flush(context).await;
+
+    Ok(created)
+}
+
+/// Creates locations in one insert, given insertable locations, returning the results in the
+/// given order, or an error naming the index of the first invalid location.
+pub(crate) async fn create_locations(
+    context: &AppContext,
+    mut locations: Vec<InsertableLocation>,
+) -> Result<Vec<Location>, ModelError> {
+    for location in &mut locations {
+        location.normalize();
+    }
+    validation::location::validate_locations(&locations)?;
+
+    let mut names = Vec::new();
+    let mut addresses = Vec::new();
+    let mut lats = Vec::new();
+    let mut lngs = Vec::new();
+    for location in locations {
+        names.push(location.name);
+        addresses.push(location.address);
+        lats.push(location.lat);
+        lngs.push(location.lng);
+    }
+
+    let mut tx = context.clients.postgres.begin().await?;
+    let mut created = sqlx::query_as::<_, Location>(
+        r#"
+        insert into locations (name, address, lat, lng)
+        select * from unnest($1::text[], $2::text[], $3::double precision[],
+            $4::double precision[])
+        returning id, name, address, lat, lng
+    "#,
+    )
+    .bind(names)
+    .bind(addresses)
+    .bind(lats)
+    .bind(lngs)
+    .fetch_all(&mut tx)
+    .await?;
 
-    // publish the created event using redis pubsub and send the created location data
-    modification::broadcast(context, "locations", ModificationType::Create, &created).await;
+    // ids are assigned in the order of the input
+    created.sort_by_key(|location| location.id.0);
+    for location in &created {
+        modification::enqueue(
+            &mut tx,
+            ModificationEntity::Location,
+            ModificationType::Create,
+            location,
+        )
+        .await?;
+    }
+    tx.commit().await?;
+    outbox::flush(context).await;
 
     Ok(created)
 }
@@ -140,45 +507,113 @@ pub(crate) async fn create_location(
 pub(crate) async fn update_location(
     context: &AppContext,
     id: LocationId,
-    location: InsertableLocation,
-) -> Result<Location> {
+    mut location: InsertableLocation,
+) -> Result<Location, ModelError> {
+    location.normalize();
+    validation::location::validate_location(&location)?;
+
+    let mut tx = context.clients.postgres.begin().await?;
     let updated = sqlx::query_as::<_, Location>(
         r#"
         update locations
-        set name = $1, address = $2
-        where id = $3
-        returning id, name, address
+        set name = $1, address = $2, lat = $3, lng = $4
+        where id = $5
+        returning id, name, address, lat, lng
     "#,
     )
     .bind(location.name)
     .bind(location.address)
+    .bind(location.lat)
+    .bind(location.lng)
     .bind(id)
-    .fetch_one(&*context.clients.postgres)
-    .await
-    .map_err(Error::from)?;
+    .fetch_one(&mut tx)
+    .await?;
 
-    // publish the updated event using redis pubsub and send the created location data
-    modification::broadcast(context, "locations", ModificationType::Update, &updated).await;
+    // enqueue the updated event with the location data, which is published once committed
+    modification::enqueue(
+        &mut tx,
+        ModificationEntity::Location,
+        ModificationType::Update,
+        &updated,
+    )
+    .await?;
+    tx.commit().await?;
+    outbox::flush(context).await;
 
     Ok(updated)
 }
 
-/// Deletes an location, given an id, returning the result, or an error.
-pub(crate) async fn delete_location(context: &AppContext, id: LocationId) -> Result<Location> {
+/// Deletes an location, given an id, returning the result, or an error. Transactions at the location
+/// are kept, but no longer have a location.
+pub(crate) async fn delete_location(
+    context: &AppContext,
+    id: LocationId,
+) -> Result<Location, ModelError> {
+    let mut tx = context.clients.postgres.begin().await?;
+    // unassign the transactions explicitly, so that the affected transactions are known
+    let unassigned = sqlx::query_as::<_, Transaction>(&format!(
+        r#"
+        update transactions
+        set location_id = null
+        where location_id = $1
+        returning {}
+    "#,
+        TRANSACTION_COLUMNS
+    ))
+    .bind(id)
+    .fetch_all(&mut tx)
+    .await?;
+
     let deleted = sqlx::query_as::<_, Location>(
         r#"
         delete from locations
         where id = $1
-        returning id, name, address
+        returning id, name, address, lat, lng
     "#,
     )
     .bind(id)
-    .fetch_one(&*context.clients.postgres)
-    .await
-    .map_err(Error::from)?;
+    .fetch_one(&mut tx)
+    .await?;
+
+    // enqueue the deleted event with the location data, and the updated events of the unassigned
+    // transactions, which are published once committed
+    modification::enqueue(
+        &mut tx,
+        ModificationEntity::Location,
+        ModificationType::Delete,
+        &deleted,
+    )
+    .await?;
+    for transaction in &unassigned {
+        modification::enqueue(
+            &mut tx,
+            ModificationEntity::Transaction,
+            ModificationType::Update,
+            transaction,
+        )
+        .await?;
+    }
 
-    // publish the deleted event using redis pubsub and send the location data
-    modification::broadcast(context, "locations", ModificationType::Delete, &deleted).await;
+    // the items of the unassigned transactions no longer have stock at the location
+    let mut item_ids = unassigned
+        .iter()
+        .map(|transaction| transaction.item_id)
+        .collect::<Vec<_>>();
+    item_ids.sort_unstable_by_key(|id| i32::from(*id));
+    item_ids.dedup();
+    for item_id in item_ids {
+        if let Ok(item) = item::fetch_item(&mut tx, item_id).await {
+            modification::enqueue(
+                &mut tx,
+                ModificationEntity::Item,
+                ModificationType::Update,
+                &item,
+            )
+            .await?;
+        }
+    }
+    tx.commit().await?;
+    outbox::flush(context).await;
 
     Ok(deleted)
 }
@@ -187,6 +622,7 @@ pub(crate) async fn delete_location(context: &AppContext, id: LocationId) -> Res
 #[async_graphql::ComplexObject]
 impl Location {
     /// The transactions at the location.
+    #[graphql(complexity = "list_complexity(NESTED_LIST_COMPLEXITY_MULTIPLIER, child_complexity)")]
     async fn transactions(&self, context: &async_graphql::Context<'_>) -> Vec<Transaction> {
         context
             .data_unchecked::<AppContext>()
@@ -197,4 +633,70 @@ impl Location {
             .await
             .unwrap_or_default()
     }
+
+    /// A page of the items with stock at the location, with their quantities at the location, in
+    /// the order of their ids.
+    #[graphql(complexity = "page_complexity(Some(i64::from(first)), child_complexity)")]
+    async fn items_with_stock(
+        &self,
+        context: &async_graphql::Context<'_>,
+        #[graphql(default = 20, validator(minimum = 1, maximum = 100))] first: i32,
+        #[graphql(default = 0, validator(minimum = 0))] offset: i32,
+    ) -> Result<StockPage> {
+        context
+            .data_unchecked::<AppContext>()
+            .loaders
+            .get::<IdLoader<StockPageKey, StockPage, Clients>>()
+            .unwrap()
+            .load(StockPageKey {
+                location_id: self.id,
+                first,
+                offset,
+            })
+            .await
+    }
+}
+
+/// Unit tests for locations.
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Creates a transaction at the given location.
+    fn transaction_fake(id: i32, location_id: Option<i32>) -> Transaction {
+        serde_json::from_value(serde_json::json!({
+            "id": id,
+            "item_id": 1,
+            "location_id": location_id,
+            "transaction_date": null,
+            "quantity": 10,
+            "comment": null,
+            "metadata": null,
+            "reason": null,
+            "source": null,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_group_by_location_skips_null() {
+        let transactions_map = group_by_location(vec![
+            transaction_fake(1, Some(1)),
+            transaction_fake(2, None),
+            transaction_fake(3, Some(1)),
+            transaction_fake(4, Some(2)),
+        ]);
+
+        assert_eq!(transactions_map.len(), 2);
+        let ids = |location_id: i32| {
+            transactions_map[&LocationId(location_id)]
+                .as_ref()
+                .unwrap()
+                .iter()
+                .map(|transaction| i32::from(transaction.id))
+                .collect::<Vec<i32>>()
+        };
+        assert_eq!(ids(1), vec![1, 3]);
+        assert_eq!(ids(2), vec![4]);
+    }
 }
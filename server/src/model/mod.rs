@@ -1,5 +1,13 @@
+pub(crate) mod attachment;
+pub(crate) mod config;
+pub(crate) mod dashboard;
+pub(crate) mod error;
 pub(crate) mod item;
 pub(crate) mod location;
 pub(crate) mod modification;
+pub(crate) mod normalize;
+pub(crate) mod outbox;
+pub(crate) mod reservation;
+pub(crate) mod reset;
 pub(crate) mod transaction;
 pub(crate) mod validation;
@@ -1,17 +1,85 @@
-use redis::{AsyncCommands, RedisError};
+use async_graphql::Result;
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use sqlx::postgres::{PgHasArrayType, PgTypeInfo};
 
 use crate::graphql::AppContext;
+use crate::model::error::ModelError;
 use crate::model::item::Item;
 use crate::model::location::Location;
 use crate::model::transaction::Transaction;
 
 /// The type of modification.
-#[derive(Copy, Clone, PartialEq, Eq, Serialize, Deserialize, async_graphql::Enum)]
+#[derive(
+    Copy, Clone, Debug, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, async_graphql::Enum,
+)]
+#[sqlx(type_name = "modification_type", rename_all = "snake_case")]
 pub(crate) enum ModificationType {
     Create,
     Update,
     Delete,
+    /// A deleted record was restored with its previous id and values.
+    Restore,
+}
+
+impl PgHasArrayType for ModificationType {
+    fn array_type_info() -> PgTypeInfo {
+        PgTypeInfo::with_name("_modification_type")
+    }
+}
+
+/// The kind of record which is modified.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, sqlx::Type, async_graphql::Enum)]
+#[sqlx(type_name = "modification_entity", rename_all = "snake_case")]
+pub(crate) enum ModificationEntity {
+    Item,
+    Location,
+    Transaction,
+}
+
+impl ModificationEntity {
+    /// The name of the channel modifications of the entity are published to.
+    pub(crate) fn channel_name(self) -> &'static str {
+        match self {
+            ModificationEntity::Item => "items",
+            ModificationEntity::Location => "locations",
+            ModificationEntity::Transaction => "transactions",
+        }
+    }
+}
+
+/// The id of a recorded modification.
+#[derive(PartialEq, Eq, Into, Hash, Copy, Clone, Debug, sqlx::Type, Serialize, Deserialize)]
+#[sqlx(transparent)]
+pub(crate) struct ModificationId(i32);
+async_graphql::scalar!(ModificationId);
+
+/// The data of a record at the time of a modification.
+#[derive(Clone, Debug, PartialEq, sqlx::Type, Serialize, Deserialize)]
+#[sqlx(transparent)]
+pub(crate) struct ModificationData(serde_json::Value);
+async_graphql::scalar!(
+    ModificationData,
+    "ModificationData",
+    "A JSON object of the data of a modified record."
+);
+
+/// A modification recorded in the audit feed.
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow, async_graphql::SimpleObject)]
+pub(crate) struct RecordedModification {
+    id: ModificationId,
+    entity: ModificationEntity,
+    modification: ModificationType,
+    data: ModificationData,
+    modified_at: DateTime<Utc>,
+}
+
+/// The filter of the audit feed.
+#[derive(Default)]
+pub(crate) struct ModificationFilter {
+    pub(crate) entity: Option<ModificationEntity>,
+    pub(crate) types: Option<Vec<ModificationType>>,
+    pub(crate) since: Option<DateTime<Utc>>,
 }
 
 /// The modification to broadcast to subscribers.
@@ -24,21 +92,74 @@ pub(crate) struct Modification<T: Serialize + async_graphql::OutputType> {
     pub(crate) data: T,
 }
 
-/// Broadcasts a modification to subscribers to a given channel, containing the modification type and data.
-pub(crate) async fn broadcast<T: Serialize + async_graphql::OutputType>(
+/// Gets the recorded modifications, most recent first, which are optionally of an entity, of the
+/// given types, or made since a date. The page starts after the modification with the given id, if
+/// any, so pages are stable while modifications are recorded.
+pub(crate) async fn get_recent_modifications(
     context: &AppContext,
-    channel_name: &str,
+    filter: ModificationFilter,
+    after: Option<ModificationId>,
+    first: i64,
+) -> Result<Vec<RecordedModification>, ModelError> {
+    sqlx::query_as::<_, RecordedModification>(
+        r#"
+        select id, entity, modification, data, modified_at from modifications
+        where ($1::modification_entity is null or entity = $1)
+            and ($2::modification_type[] is null or modification = any($2))
+            and ($3::timestamptz is null or modified_at >= $3)
+            and ($4::integer is null or (modified_at, id) < (
+                select modified_at, id from modifications where id = $4
+            ))
+        order by modified_at desc, id desc
+        limit $5
+    "#,
+    )
+    .bind(filter.entity)
+    .bind(filter.types)
+    .bind(filter.since)
+    .bind(after)
+    .bind(first)
+    .fetch_all(&*context.clients.postgres)
+    .await
+    .map_err(ModelError::from)
+}
+
+/// Enqueues a modification for subscribers to the channel of an entity in the database transaction
+/// of the change, containing the modification type and data, and records it in the audit feed. The
+/// modification is published once the change is committed and the outbox is delivered, so it is
+/// not lost if the server stops in between.
+pub(crate) async fn enqueue<T: Serialize + async_graphql::OutputType>(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    entity: ModificationEntity,
     modification: ModificationType,
-    created: &T,
-) {
-    let modification = Modification {
-        modification,
-        data: created,
-    };
-
-    if let Ok(mut redis_conn) = context.clients.redis.get_async_connection().await {
-        let _: Result<(), RedisError> = redis_conn
-            .publish(channel_name, serde_json::to_string(&modification).unwrap())
-            .await;
-    }
+    data: &T,
+) -> Result<(), ModelError> {
+    sqlx::query(r#"insert into modifications (entity, modification, data) values ($1, $2, $3)"#)
+        .bind(entity)
+        .bind(modification)
+        .bind(serde_json::to_value(data).unwrap())
+        .execute(&mut *tx)
+        .await?;
+
+    enqueue_channel(tx, entity.channel_name(), modification, data).await
+}
+
+/// Enqueues a modification for subscribers to a channel in the database transaction of the change,
+/// containing the modification type and data, without recording it, for channels which carry a
+/// subset of the modifications of an entity.
+pub(crate) async fn enqueue_channel<T: Serialize + async_graphql::OutputType>(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    channel: &str,
+    modification: ModificationType,
+    data: &T,
+) -> Result<(), ModelError> {
+    let modification = Modification { modification, data };
+
+    sqlx::query(r#"insert into outbox (channel, payload) values ($1, $2)"#)
+        .bind(channel)
+        .bind(serde_json::to_string(&modification).unwrap())
+        .execute(&mut *tx)
+        .await?;
+
+    Ok(())
 }
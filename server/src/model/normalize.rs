@@ -0,0 +1,108 @@
+use std::collections::HashSet;
+
+/// Trims text and collapses its whitespace into single spaces, so the same name, sku, or supplier
+/// is not entered with different spacing.
+pub(crate) fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/// Collapses the whitespace of optional text, which is not given if it is blank.
+pub(crate) fn collapse_optional(text: Option<String>) -> Option<String> {
+    text.map(|text| collapse_whitespace(&text))
+        .filter(|text| !text.is_empty())
+}
+
+/// Trims optional free text, such as a comment, keeping its internal whitespace, which is not given
+/// if it is blank.
+pub(crate) fn trim_optional(text: Option<String>) -> Option<String> {
+    text.map(|text| text.trim().to_string())
+        .filter(|text| !text.is_empty())
+}
+
+/// The tags which are kept when markup is sanitized, for basic formatting.
+const SANITIZED_TAGS: [&str; 14] = [
+    "a",
+    "b",
+    "blockquote",
+    "br",
+    "code",
+    "em",
+    "i",
+    "li",
+    "ol",
+    "p",
+    "pre",
+    "strong",
+    "u",
+    "ul",
+];
+
+/// Removes unsafe markup from text, such as scripts, event handlers and `javascript:` links, while
+/// keeping basic formatting. The text of removed tags is kept, except for scripts and styles.
+pub(crate) fn sanitize_html(text: &str) -> String {
+    ammonia::Builder::new()
+        .tags(SANITIZED_TAGS.iter().copied().collect::<HashSet<&str>>())
+        .clean(text)
+        .to_string()
+}
+
+/// Unit tests for normalizing text.
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_collapse_whitespace() {
+        assert_eq!(collapse_whitespace(" Widget "), "Widget");
+        assert_eq!(
+            collapse_whitespace("\tBlue \n Widget  Co"),
+            "Blue Widget Co"
+        );
+        assert_eq!(collapse_whitespace("   "), "");
+    }
+
+    #[test]
+    fn test_collapse_optional() {
+        assert_eq!(
+            collapse_optional(Some("  ACME   Corp ".to_string())),
+            Some("ACME Corp".to_string())
+        );
+        assert_eq!(collapse_optional(Some(" \t ".to_string())), None);
+        assert_eq!(collapse_optional(None), None);
+    }
+
+    #[test]
+    fn test_trim_optional() {
+        assert_eq!(
+            trim_optional(Some("  line one\n\n  line two  ".to_string())),
+            Some("line one\n\n  line two".to_string())
+        );
+        assert_eq!(trim_optional(Some("\n".to_string())), None);
+        assert_eq!(trim_optional(None), None);
+    }
+
+    #[test]
+    fn test_sanitize_html() {
+        assert_eq!(
+            sanitize_html("<p><b>Blue</b> <em>widget</em></p><ul><li>10 mm</li></ul>"),
+            "<p><b>Blue</b> <em>widget</em></p><ul><li>10 mm</li></ul>"
+        );
+        assert_eq!(
+            sanitize_html(r#"Widget<script>alert("xss")</script>"#),
+            "Widget"
+        );
+        assert_eq!(
+            sanitize_html(r#"<img src="x" onerror="alert(1)">Widget"#),
+            "Widget"
+        );
+        assert_eq!(
+            sanitize_html(r#"<p onclick="alert(1)">Widget</p>"#),
+            "<p>Widget</p>"
+        );
+        assert_eq!(
+            sanitize_html(r#"<a href="javascript:alert(1)">Widget</a>"#),
+            r#"<a rel="noopener noreferrer">Widget</a>"#
+        );
+        assert_eq!(sanitize_html("1 < 2 & 3"), "1 &lt; 2 &amp; 3");
+    }
+}
@@ -0,0 +1,167 @@
+use std::time::Duration;
+
+use std::sync::atomic::Ordering;
+
+use sqlx::Row;
+
+use crate::graphql::{AppContext, Clients};
+use crate::metrics;
+
+/// The interval at which the relay checks for modifications which were not delivered after their
+/// change was committed, such as when the server stopped or Redis was unavailable.
+const RELAY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The maximum number of modifications which are published in one batch of a delivery.
+pub(crate) const DELIVERY_BATCH_SIZE: i64 = 100;
+
+/// How long delivered modifications are kept in the outbox before they are pruned.
+const DELIVERED_RETENTION_SECS: i32 = 24 * 60 * 60;
+
+/// The key of the advisory lock held while a batch of the outbox is delivered, which is outside the
+/// range of the ids of items, whose quantities are locked by their ids.
+pub(crate) const DELIVERY_LOCK_KEY: i64 = 0x6f75_7462_6f78;
+
+/// The result of a delivery of the outbox.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct Delivery {
+    /// The number of modifications which were published.
+    pub(crate) delivered: usize,
+    /// The number of connections which were opened to Redis, which is at most one, and none if
+    /// there was nothing to deliver.
+    pub(crate) connections: usize,
+    /// The number of pipelines which were published, which is one for each batch.
+    pub(crate) pipelines: usize,
+}
+
+/// Publishes the undelivered modifications of the outbox to Redis in the order they were enqueued,
+/// and marks them as delivered, returning what was delivered, or an error. If publishing fails the
+/// rows are not marked, so they are published again, and each modification is delivered at least
+/// once. One connection is opened for the whole delivery, and the modifications of each batch are
+/// published in one pipeline, so the cascade of modifications of a change is published in one
+/// round-trip.
+pub(crate) async fn deliver(clients: &Clients) -> Result<Delivery, sqlx::Error> {
+    deliver_batches(clients, usize::MAX).await
+}
+
+/// Delivers up to the given number of batches of the outbox, returning what was delivered, or an
+/// error. Each batch is delivered while holding a lock, so batches are published one at a time in
+/// the order they were enqueued, and a delivery which finds another in progress leaves the outbox
+/// to it, rather than publishing later modifications first.
+async fn deliver_batches(clients: &Clients, batches: usize) -> Result<Delivery, sqlx::Error> {
+    let mut delivery = Delivery::default();
+    let mut redis_conn = None;
+    for _ in 0..batches {
+        let mut tx = clients.postgres.begin().await?;
+        let locked = sqlx::query_scalar::<_, bool>("select pg_try_advisory_xact_lock($1)")
+            .bind(DELIVERY_LOCK_KEY)
+            .fetch_one(&mut tx)
+            .await?;
+        if !locked {
+            return Ok(delivery);
+        }
+        let rows = sqlx::query(
+            r#"
+            select id, channel, payload from outbox
+            where delivered_at is null
+            order by id
+            limit $1
+        "#,
+        )
+        .bind(DELIVERY_BATCH_SIZE)
+        .fetch_all(&mut tx)
+        .await?;
+        if rows.is_empty() {
+            return Ok(delivery);
+        }
+
+        // the connection is only opened once there is something to deliver
+        if redis_conn.is_none() {
+            match clients.redis.get_async_connection().await {
+                Ok(conn) => {
+                    delivery.connections += 1;
+                    metrics::OUTBOX.connections.fetch_add(1, Ordering::Relaxed);
+                    redis_conn = Some(conn);
+                }
+                Err(e) => {
+                    log::warn!("unable to deliver the outbox: {}", e);
+                    return Ok(delivery);
+                }
+            }
+        }
+        let mut pipe = redis::pipe();
+        let mut ids = Vec::with_capacity(rows.len());
+        for row in &rows {
+            let channel: String = row.try_get("channel")?;
+            let payload: String = row.try_get("payload")?;
+            pipe.publish(channel, payload).ignore();
+            ids.push(row.try_get::<i64, _>("id")?);
+        }
+        // dropping the transaction releases the lock, so the rows are delivered again later
+        if let Err(e) = pipe
+            .query_async::<_, ()>(redis_conn.as_mut().unwrap())
+            .await
+        {
+            log::warn!("unable to deliver the outbox: {}", e);
+            return Ok(delivery);
+        }
+
+        sqlx::query(r#"update outbox set delivered_at = now() where id = any($1)"#)
+            .bind(&ids)
+            .execute(&mut tx)
+            .await?;
+        tx.commit().await?;
+        delivery.pipelines += 1;
+        delivery.delivered += ids.len();
+        metrics::OUTBOX.pipelines.fetch_add(1, Ordering::Relaxed);
+        metrics::OUTBOX
+            .delivered
+            .fetch_add(ids.len() as u64, Ordering::Relaxed);
+    }
+
+    Ok(delivery)
+}
+
+/// Delivers a batch of the outbox after a change is committed, so subscribers receive its
+/// modifications without waiting for the relay. The relay delivers the rest of a backlog, and the
+/// modifications of the change if this fails, or if another delivery is in progress, so a change
+/// never pays for delivering more than one batch.
+pub(crate) async fn flush(context: &AppContext) {
+    if let Err(e) = deliver_batches(&context.clients, 1).await {
+        log::warn!("unable to deliver the outbox: {}", e);
+    }
+}
+
+/// Deletes the modifications which were delivered longer ago than they are retained, returning the
+/// number which were deleted, or an error.
+async fn prune(clients: &Clients) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        delete from outbox
+        where delivered_at < now() - make_interval(secs => $1)
+    "#,
+    )
+    .bind(DELIVERED_RETENTION_SECS)
+    .execute(&*clients.postgres)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+/// Relays the modifications of the outbox which were not delivered when their change was committed,
+/// and prunes delivered modifications, until the server stops. Modifications which were enqueued
+/// before the server last stopped are delivered when it starts.
+pub(crate) async fn relay(clients: Clients) {
+    loop {
+        match deliver(&clients).await {
+            Ok(Delivery { delivered: 0, .. }) => {}
+            Ok(Delivery { delivered, .. }) => {
+                log::info!("relayed {} modifications from the outbox", delivered)
+            }
+            Err(e) => log::warn!("unable to deliver the outbox: {}", e),
+        }
+        if let Err(e) = prune(&clients).await {
+            log::warn!("unable to prune the outbox: {}", e);
+        }
+        tokio::time::sleep(RELAY_INTERVAL).await;
+    }
+}
@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+
+use async_graphql::{Error, Result};
+use serde::{Deserialize, Serialize};
+use sqlx::Row;
+
+use crate::graphql::{AppContext, Clients};
+use crate::model::error::ModelError;
+use crate::model::item::{self, Item, ItemId, ItemQuantity};
+use crate::model::location::{self, Location, LocationId};
+use crate::model::modification::{self, ModificationEntity, ModificationType};
+use crate::model::outbox;
+use crate::model::transaction::{
+    self, Transaction, TransactionId, TransactionReason, TRANSACTION_COLUMNS,
+};
+use crate::model::validation;
+
+/// The id of a reservation.
+#[derive(PartialEq, Eq, Into, Hash, Copy, Clone, Debug, sqlx::Type, Serialize, Deserialize)]
+#[sqlx(transparent)]
+pub(crate) struct ReservationId(i32);
+async_graphql::scalar!(ReservationId);
+
+/// The status of a reservation.
+#[derive(
+    Copy, Clone, Debug, PartialEq, Eq, Hash, sqlx::Type, Serialize, Deserialize, async_graphql::Enum,
+)]
+#[sqlx(type_name = "reservation_status", rename_all = "snake_case")]
+pub(crate) enum ReservationStatus {
+    /// The stock is reserved, and is not available.
+    Active,
+    /// The reserved stock has been taken out by a transaction.
+    Committed,
+    /// The reserved stock is available again.
+    Released,
+}
+
+/// The quantity of an item reserved by active reservations.
+#[derive(Copy, Clone, Debug, PartialEq, sqlx::Type)]
+#[sqlx(transparent)]
+pub(crate) struct ReservedQuantity(pub(crate) ItemQuantity);
+
+/// Reservation model returned by a query in the inventory tracking system.
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow, async_graphql::SimpleObject)]
+#[graphql(complex)]
+pub(crate) struct Reservation {
+    id: ReservationId,
+    item_id: ItemId,
+    location_id: Option<LocationId>,
+    quantity: ItemQuantity,
+    pub(crate) status: ReservationStatus,
+    transaction_id: Option<TransactionId>,
+}
+
+/// Gets the reserved quantities of all items with the given ids.
+pub(crate) async fn get_reserved_quantities_by_item_ids(
+    clients: &Clients,
+    ids: Vec<ItemId>,
+) -> Result<HashMap<ItemId, Result<ReservedQuantity>>> {
+    let results = sqlx::query(
+        r#"
+        select item_id, sum(quantity)::integer as reserved from reservations
+        where item_id = any($1) and status = 'active'
+        group by item_id
+    "#,
+    )
+    .bind(ids.into_iter().map(i32::from).collect::<Vec<i32>>())
+    .fetch_all(&*clients.postgres)
+    .await
+    .map_err(Error::from)?;
+
+    let mut results_map = HashMap::new();
+    for result in results {
+        results_map.insert(
+            result.try_get("item_id")?,
+            Ok(result.try_get::<ReservedQuantity, _>("reserved")?),
+        );
+    }
+
+    Ok(results_map)
+}
+
+/// Reserves a quantity of an item, which is no longer available but is still on hand, returning
+/// the result, or an error if the item does not have enough available stock.
+pub(crate) async fn reserve_stock(
+    context: &AppContext,
+    item_id: ItemId,
+    location_id: Option<LocationId>,
+    quantity: ItemQuantity,
+) -> Result<Reservation, ModelError> {
+    validation::reservation::validate_reservation(context, item_id, location_id, quantity).await?;
+
+    let mut tx = context.clients.postgres.begin().await?;
+    // lock the quantity of the item, so concurrent reservations and transactions cannot both take
+    // the same available stock
+    let on_hand = item::lock_quantity(&mut tx, item_id).await?;
+    let reserved = sqlx::query(
+        r#"
+        select coalesce(sum(quantity), 0)::bigint as reserved from reservations
+        where item_id = $1 and status = 'active'
+    "#,
+    )
+    .bind(item_id)
+    .fetch_one(&mut tx)
+    .await?
+    .try_get::<i64, _>("reserved")?;
+    let available = on_hand - reserved;
+    validation::reservation::validate_available(item_id, quantity, available)?;
+
+    let created = sqlx::query_as::<_, Reservation>(
+        r#"
+        insert into reservations (item_id, location_id, quantity)
+        values ($1, $2, $3)
+        returning id, item_id, location_id, quantity, status, transaction_id
+    "#,
+    )
+    .bind(item_id)
+    .bind(location_id)
+    .bind(quantity)
+    .fetch_one(&mut tx)
+    .await?;
+    created.enqueue_update(&mut tx).await?;
+    tx.commit().await?;
+    outbox::flush(context).await;
+
+    Ok(created)
+}
+
+/// Commits an active reservation, taking the reserved quantity out with a sale transaction,
+/// returning the result, or an error.
+pub(crate) async fn commit_reservation(
+    context: &AppContext,
+    id: ReservationId,
+) -> Result<Reservation, ModelError> {
+    let mut tx = context.clients.postgres.begin().await?;
+    let reservation = fetch_active_reservation(&mut tx, id).await?;
+    let quantity = item::lock_quantity(&mut tx, reservation.item_id).await?;
+    validation::transaction::validate_transaction_count(context, &mut tx, reservation.item_id, 1)
+        .await?;
+    validation::transaction::validate_quantity_change(
+        context,
+        reservation.item_id,
+        quantity,
+        -i64::from(i32::from(reservation.quantity)),
+    )?;
+
+    let created = sqlx::query_as::<_, Transaction>(&format!(
+        r#"
+        insert into transactions (item_id, location_id, transaction_date, quantity, reason)
+        values ($1, $2, now(), $3, $4)
+        returning {}
+    "#,
+        TRANSACTION_COLUMNS
+    ))
+    .bind(reservation.item_id)
+    .bind(reservation.location_id)
+    .bind(-reservation.quantity)
+    .bind(TransactionReason::Sale)
+    .fetch_one(&mut tx)
+    .await?;
+
+    let committed = sqlx::query_as::<_, Reservation>(
+        r#"
+        update reservations
+        set status = 'committed', transaction_id = $1
+        where id = $2
+        returning id, item_id, location_id, quantity, status, transaction_id
+    "#,
+    )
+    .bind(created.id)
+    .bind(id)
+    .fetch_one(&mut tx)
+    .await?;
+
+    // the transaction event includes an update of the item, so availability is also updated
+    created
+        .enqueue_update(&mut tx, ModificationType::Create)
+        .await?;
+    tx.commit().await?;
+    outbox::flush(context).await;
+
+    Ok(committed)
+}
+
+/// Releases an active reservation, making the reserved quantity available again, returning the
+/// result, or an error.
+pub(crate) async fn release_reservation(
+    context: &AppContext,
+    id: ReservationId,
+) -> Result<Reservation, ModelError> {
+    let mut tx = context.clients.postgres.begin().await?;
+    fetch_active_reservation(&mut tx, id).await?;
+
+    let released = sqlx::query_as::<_, Reservation>(
+        r#"
+        update reservations
+        set status = 'released'
+        where id = $1
+        returning id, item_id, location_id, quantity, status, transaction_id
+    "#,
+    )
+    .bind(id)
+    .fetch_one(&mut tx)
+    .await?;
+    released.enqueue_update(&mut tx).await?;
+    tx.commit().await?;
+    outbox::flush(context).await;
+
+    Ok(released)
+}
+
+/// Fetches and locks a reservation, returning the result, or an error if it is not active.
+async fn fetch_active_reservation(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    id: ReservationId,
+) -> Result<Reservation, ModelError> {
+    let reservation = sqlx::query_as::<_, Reservation>(
+        r#"
+        select id, item_id, location_id, quantity, status, transaction_id from reservations
+        where id = $1
+        for update
+    "#,
+    )
+    .bind(id)
+    .fetch_one(tx)
+    .await?;
+
+    validation::reservation::validate_active(&reservation)?;
+
+    Ok(reservation)
+}
+
+impl Reservation {
+    async fn enqueue_update(
+        &self,
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    ) -> Result<(), ModelError> {
+        // the available quantity of the item has changed, so enqueue an update of the item
+        if let Ok(item) = item::fetch_item(tx, self.item_id).await {
+            modification::enqueue(
+                tx,
+                ModificationEntity::Item,
+                ModificationType::Update,
+                &item,
+            )
+            .await?;
+        }
+        Ok(())
+    }
+}
+
+/// A reservation of stock in the inventory tracking system.
+#[async_graphql::ComplexObject]
+impl Reservation {
+    /// The reserved item.
+    async fn item(&self, context: &async_graphql::Context<'_>) -> Result<Item> {
+        item::get_item(context.data_unchecked::<AppContext>(), self.item_id).await
+    }
+
+    /// The location the stock is reserved at.
+    async fn location(&self, context: &async_graphql::Context<'_>) -> Option<Location> {
+        match self.location_id {
+            Some(location_id) => {
+                location::get_location(context.data_unchecked::<AppContext>(), location_id)
+                    .await
+                    .ok()
+            }
+            None => None,
+        }
+    }
+
+    /// The transaction which took out the reserved stock, if the reservation is committed.
+    async fn transaction(&self, context: &async_graphql::Context<'_>) -> Option<Transaction> {
+        match self.transaction_id {
+            Some(transaction_id) => {
+                transaction::get_transaction(context.data_unchecked::<AppContext>(), transaction_id)
+                    .await
+                    .ok()
+            }
+            None => None,
+        }
+    }
+}
@@ -0,0 +1,32 @@
+use crate::graphql::AppContext;
+use crate::model::error::ModelError;
+
+/// The tables which are emptied by a reset, with the tables which reference others before the
+/// tables they reference. Tables which are referenced can only be truncated together with the
+/// tables which reference them, so they are truncated in one statement.
+const RESET_TABLES: [&str; 8] = [
+    "item_attachments",
+    "reservations",
+    "transaction_revisions",
+    "transactions",
+    "locations",
+    "items",
+    "modifications",
+    "outbox",
+];
+
+/// Deletes all items, locations and transactions, with their reservations, attachments and history,
+/// restarting their ids, in one database transaction, returning the result, or an error. Changes
+/// are not broadcast to subscribers.
+pub(crate) async fn reset_all_data(context: &AppContext) -> Result<(), ModelError> {
+    let mut tx = context.clients.postgres.begin().await?;
+    sqlx::query(&format!(
+        "truncate table {} restart identity",
+        RESET_TABLES.join(", ")
+    ))
+    .execute(&mut tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(())
+}
@@ -1,15 +1,19 @@
 use std::collections::HashMap;
 use std::fmt::Debug;
 
-use async_graphql::{Error, Result};
+use async_graphql::{Error, ErrorExtensions, Result};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use sqlx::{FromRow, Row};
 
 use crate::batcher::id_loader::IdLoader;
-use crate::graphql::{AppContext, Clients};
-use crate::model::item::{self, Item, ItemId, ItemQuantity};
+use crate::graphql::{list_complexity, AppContext, Clients, NESTED_LIST_COMPLEXITY_MULTIPLIER};
+use crate::model::error::{code, ModelError};
+use crate::model::item::{self, Item, ItemId, ItemLedger, ItemQuantity};
 use crate::model::location::{self, Location, LocationId};
-use crate::model::modification::{self, ModificationType};
+use crate::model::modification::{self, ModificationEntity, ModificationType};
+use crate::model::normalize;
+use crate::model::outbox;
 use crate::model::validation;
 
 /// The id of a transaction.
@@ -18,18 +22,163 @@ use crate::model::validation;
 pub(crate) struct TransactionId(i32);
 async_graphql::scalar!(TransactionId);
 
+/// Arbitrary metadata attached to a transaction.
+#[derive(PartialEq, Clone, Debug, sqlx::Type, Serialize, Deserialize)]
+#[sqlx(transparent)]
+pub(crate) struct TransactionMetadata(pub(crate) serde_json::Value);
+async_graphql::scalar!(
+    TransactionMetadata,
+    "JSON",
+    "A JSON object of arbitrary metadata."
+);
+
+/// The reason for a transaction.
+#[derive(
+    Copy, Clone, Debug, PartialEq, Eq, Hash, sqlx::Type, Serialize, Deserialize, async_graphql::Enum,
+)]
+#[sqlx(type_name = "transaction_reason", rename_all = "snake_case")]
+pub(crate) enum TransactionReason {
+    Receipt,
+    Sale,
+    Return,
+    Adjustment,
+    Damage,
+    Transfer,
+    OpeningBalance,
+}
+
+impl TransactionReason {
+    /// The reasons with their names in the database.
+    pub(crate) const NAMES: [(&'static str, TransactionReason); 7] = [
+        ("receipt", TransactionReason::Receipt),
+        ("sale", TransactionReason::Sale),
+        ("return", TransactionReason::Return),
+        ("adjustment", TransactionReason::Adjustment),
+        ("damage", TransactionReason::Damage),
+        ("transfer", TransactionReason::Transfer),
+        ("opening_balance", TransactionReason::OpeningBalance),
+    ];
+
+    /// Gets a reason from its name in the database, or none if there is no reason with the name.
+    pub(crate) fn from_name(name: &str) -> Option<TransactionReason> {
+        TransactionReason::NAMES
+            .iter()
+            .find(|(reason_name, _)| *reason_name == name)
+            .map(|(_, reason)| *reason)
+    }
+}
+
+/// The columns of a transaction, in the order of its fields, which every query returning a
+/// transaction selects or returns, so their lists cannot drift apart.
+pub(crate) const TRANSACTION_COLUMNS: &str =
+    "id, item_id, location_id, transaction_date, quantity, \
+    comment, metadata, reason, source, transfer_group";
+
+/// The columns of a transaction qualified by a table, for queries which select them alongside the
+/// columns of other tables.
+pub(crate) fn qualified_transaction_columns(table: &str) -> String {
+    TRANSACTION_COLUMNS
+        .split(", ")
+        .map(|column| format!("{}.{}", table, column))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The columns of a revision which are the columns of its transaction, with the id of the
+/// transaction as `transaction_id`.
+fn revised_columns() -> String {
+    TRANSACTION_COLUMNS.replacen("id", "transaction_id", 1)
+}
+
+/// The columns of a revision, which every query returning a revision selects or returns.
+fn revision_columns() -> String {
+    format!("id, revision, {}, revised_at", revised_columns())
+}
+
+/// The maximum number of groups of a transaction summary.
+const MAX_SUMMARY_GROUPS: i64 = 1000;
+
 /// Transaction model returned by a query in the inventory tracking system.
 #[derive(
     Debug, Clone, PartialEq, sqlx::FromRow, Serialize, Deserialize, async_graphql::SimpleObject,
 )]
 #[graphql(complex)]
 pub(crate) struct Transaction {
-    id: TransactionId,
+    pub(crate) id: TransactionId,
     pub(crate) item_id: ItemId,
     pub(crate) location_id: Option<LocationId>,
     transaction_date: Option<DateTime<Utc>>,
     quantity: ItemQuantity,
     comment: Option<String>,
+    metadata: Option<TransactionMetadata>,
+    reason: Option<TransactionReason>,
+    /// The client which created the transaction, from the `X-Client-Source` header or the path
+    /// which created it, or null if it is not known.
+    source: Option<String>,
+    /// The transfer which the transaction is a part of, with the other transaction of the
+    /// transfer, or null if it is not part of a transfer.
+    transfer_group: Option<TransferGroupId>,
+}
+
+/// The id of a transfer, which is shared by the transactions out of and into locations it is made
+/// of.
+#[derive(PartialEq, Eq, Into, Hash, Copy, Clone, Debug, sqlx::Type, Serialize, Deserialize)]
+#[sqlx(transparent)]
+pub(crate) struct TransferGroupId(i64);
+async_graphql::scalar!(TransferGroupId);
+
+/// The id of a revision of a transaction.
+#[derive(PartialEq, Eq, Into, Hash, Copy, Clone, Debug, sqlx::Type, Serialize, Deserialize)]
+#[sqlx(transparent)]
+pub(crate) struct TransactionRevisionId(i32);
+async_graphql::scalar!(TransactionRevisionId);
+
+/// The values of a transaction before it was updated or deleted.
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow, async_graphql::SimpleObject)]
+pub(crate) struct TransactionRevision {
+    id: TransactionRevisionId,
+    transaction_id: TransactionId,
+    /// Whether the transaction was updated or deleted.
+    revision: ModificationType,
+    item_id: ItemId,
+    location_id: Option<LocationId>,
+    transaction_date: Option<DateTime<Utc>>,
+    quantity: ItemQuantity,
+    comment: Option<String>,
+    metadata: Option<TransactionMetadata>,
+    reason: Option<TransactionReason>,
+    source: Option<String>,
+    transfer_group: Option<TransferGroupId>,
+    /// The date the transaction was updated or deleted.
+    revised_at: DateTime<Utc>,
+}
+
+/// How a bulk create handles transactions which cannot be created.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, async_graphql::Enum)]
+pub(crate) enum BulkCreateMode {
+    /// No transactions are created if any cannot be created.
+    #[default]
+    AllOrNothing,
+    /// The transactions which can be created are, and the others are reported.
+    BestEffort,
+}
+
+/// A transaction of a bulk create which could not be created.
+#[derive(Debug, Clone, PartialEq, async_graphql::SimpleObject)]
+pub(crate) struct TransactionCreateError {
+    /// The index of the transaction in the bulk create.
+    index: i32,
+    /// The stable code of the error.
+    code: String,
+    message: String,
+}
+
+/// The result of a bulk create, with the created transactions, and the errors of those which
+/// could not be created.
+#[derive(Debug, Clone, PartialEq, async_graphql::SimpleObject)]
+pub(crate) struct TransactionBulkCreate {
+    created: Vec<Transaction>,
+    errors: Vec<TransactionCreateError>,
 }
 
 /// Transaction model to input to the inventory tracking system.
@@ -45,19 +194,469 @@ pub(crate) struct InsertableTransaction {
     quantity: ItemQuantity,
     #[graphql(validator(min_length = 1))]
     comment: Option<String>,
+    #[graphql(validator(custom = "validation::transaction::TransactionMetadataValidator {}"))]
+    metadata: Option<TransactionMetadata>,
+    reason: Option<TransactionReason>,
+}
+
+impl InsertableTransaction {
+    /// Trims the comment, keeping its internal whitespace.
+    fn normalize(&mut self) {
+        self.comment = normalize::trim_optional(self.comment.take());
+    }
+}
+
+/// Opening balance of an item at a location to input to the inventory tracking system, which is
+/// the quantity the item is set to at the location.
+#[derive(Debug, PartialEq, async_graphql::InputObject)]
+pub(crate) struct InsertableOpeningBalance {
+    pub(crate) item_id: ItemId,
+    pub(crate) location_id: LocationId,
+    pub(crate) quantity: ItemQuantity,
+}
+
+/// Transfer of stock between locations to input to the inventory tracking system.
+#[derive(Debug, PartialEq, async_graphql::InputObject)]
+pub(crate) struct InsertableTransfer {
+    pub(crate) item_id: ItemId,
+    pub(crate) from_location_id: LocationId,
+    pub(crate) to_location_id: LocationId,
+    pub(crate) quantity: ItemQuantity,
+    #[graphql(validator(min_length = 1))]
+    pub(crate) comment: Option<String>,
+}
+
+/// A transfer of stock between locations, made of a transaction out of the location it is from and
+/// a transaction into the location it is to, which share a transfer group.
+#[derive(Debug, Clone, PartialEq, async_graphql::SimpleObject)]
+#[graphql(complex)]
+pub(crate) struct Transfer {
+    transfer_group: TransferGroupId,
+    from: Transaction,
+    to: Transaction,
+    /// The quantity which was transferred, which is the quantity into the location it is to.
+    quantity: ItemQuantity,
+    /// The date of the transaction out of the location it is from.
+    date: Option<DateTime<Utc>>,
+}
+
+impl Transfer {
+    /// Pairs the transactions of a transfer, given the transaction out of a location and the
+    /// transaction into the other, which share a transfer group.
+    fn new(transfer_group: TransferGroupId, from: Transaction, to: Transaction) -> Self {
+        Transfer {
+            transfer_group,
+            quantity: to.quantity,
+            date: from.transaction_date,
+            from,
+            to,
+        }
+    }
+}
+
+#[async_graphql::ComplexObject]
+impl Transfer {
+    /// The item which was transferred.
+    async fn item(&self, context: &async_graphql::Context<'_>) -> Item {
+        item::get_item(context.data_unchecked::<AppContext>(), self.from.item_id)
+            .await
+            .expect("dangling transfer has no item")
+    }
+}
+
+/// The direction of stock moved by transactions, by the sign of their quantities.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, async_graphql::Enum)]
+pub(crate) enum TransactionDirection {
+    /// Transactions which receive stock, with positive quantities.
+    Inbound,
+    /// Transactions which issue stock, with negative quantities.
+    Outbound,
+    /// Transactions in either direction.
+    #[default]
+    Any,
 }
 
-/// Gets all transactions, returning the result, or a field error.
-pub(crate) async fn get_transactions(context: &AppContext) -> Result<Vec<Transaction>> {
-    sqlx::query_as::<_, Transaction>(
+impl TransactionDirection {
+    /// The sign of the quantities of transactions in the direction, or none for any direction.
+    /// Legacy transactions with a quantity of 0 have neither sign.
+    fn sign(self) -> Option<i32> {
+        match self {
+            TransactionDirection::Inbound => Some(1),
+            TransactionDirection::Outbound => Some(-1),
+            TransactionDirection::Any => None,
+        }
+    }
+}
+
+/// Where transactions without a date are placed when transactions are sorted by date.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default, async_graphql::Enum)]
+pub(crate) enum NullsOrder {
+    /// Undated transactions are placed before the dated transactions.
+    First,
+    /// Undated transactions are placed after the dated transactions, so they do not come before
+    /// the most recent transactions.
+    #[default]
+    Last,
+}
+
+impl NullsOrder {
+    /// The SQL for the placement of nulls in an `order by` clause.
+    fn sql(self) -> &'static str {
+        match self {
+            NullsOrder::First => "nulls first",
+            NullsOrder::Last => "nulls last",
+        }
+    }
+}
+
+/// The filters of a list of transactions, each of which is ignored when it is not given.
+#[derive(Debug, Default)]
+pub(crate) struct TransactionFilter {
+    pub(crate) item_id: Option<ItemId>,
+    pub(crate) metadata_contains: Option<TransactionMetadata>,
+    pub(crate) unassigned_only: bool,
+    pub(crate) reason: Option<TransactionReason>,
+    pub(crate) since: Option<DateTime<Utc>>,
+    pub(crate) until: Option<DateTime<Utc>>,
+    pub(crate) direction: TransactionDirection,
+    pub(crate) source: Option<String>,
+}
+
+/// The SQL conditions of a transaction filter, whose parameters are bound first, in the order of the
+/// fields of the filter.
+const FILTER_CONDITIONS: &str = r#"
+        ($1::integer is null or item_id = $1)
+            and ($2::jsonb is null or metadata @> $2) and (not $3 or location_id is null)
+            and ($4::transaction_reason is null or reason = $4)
+            and ($5::timestamptz is null or transaction_date >= $5)
+            and ($6::timestamptz is null or transaction_date < $6)
+            and ($7::integer is null or sign(quantity) = $7)
+            and ($8::text is null or source = $8)"#;
+
+/// A page of the transactions matching a filter, with the number of transactions on all pages.
+#[derive(Debug, Clone, PartialEq, async_graphql::SimpleObject)]
+pub(crate) struct TransactionConnection {
+    transactions: Vec<Transaction>,
+    total_count: i64,
+}
+
+/// Gets the transactions matching a filter, most recent first, with undated transactions placed as
+/// given, optionally skipping some and limiting the number returned, returning the result, or a
+/// field error. When an id is given to
+/// continue after, transactions with greater ids are returned in the order of their ids instead, so
+/// the table can be synced with a cursor which does not depend on dates.
+pub(crate) async fn get_transactions(
+    context: &AppContext,
+    filter: TransactionFilter,
+    nulls: NullsOrder,
+    after_id: Option<TransactionId>,
+    first: Option<i64>,
+    offset: i64,
+) -> Result<Vec<Transaction>, ModelError> {
+    let order = if after_id.is_some() {
+        "id".to_string()
+    } else {
+        format!("transaction_date desc {}, id desc", nulls.sql())
+    };
+
+    sqlx::query_as::<_, Transaction>(&format!(
         r#"
-        select id, item_id, location_id, transaction_date, quantity, comment from transactions
-        order by transaction_date desc
+        select {} from transactions
+        where {}
+            and ($9::integer is null or id > $9)
+        order by {}
+        limit $10 offset $11
     "#,
-    )
+        TRANSACTION_COLUMNS, FILTER_CONDITIONS, order
+    ))
+    .bind(filter.item_id)
+    .bind(filter.metadata_contains)
+    .bind(filter.unassigned_only)
+    .bind(filter.reason)
+    .bind(filter.since)
+    .bind(filter.until)
+    .bind(filter.direction.sign())
+    .bind(filter.source)
+    .bind(after_id)
+    .bind(first)
+    .bind(offset)
     .fetch_all(&*context.clients.postgres)
     .await
-    .map_err(Error::from)
+    .map_err(ModelError::from)
+}
+
+/// Gets a page of the transactions matching a filter, most recent first, with undated transactions
+/// placed as given, and the number of matching transactions, returning the result, or an error.
+/// The number is counted by a window over the rows of the page query, so it is only counted again
+/// when a page past the first has no rows to carry it.
+pub(crate) async fn get_transaction_connection(
+    context: &AppContext,
+    filter: TransactionFilter,
+    nulls: NullsOrder,
+    first: i64,
+    offset: i64,
+) -> Result<TransactionConnection, ModelError> {
+    let rows = sqlx::query(&format!(
+        r#"
+        select {}, count(*) over () as total_count from transactions
+        where {}
+        order by transaction_date desc {}, id desc
+        limit $9 offset $10
+    "#,
+        TRANSACTION_COLUMNS,
+        FILTER_CONDITIONS,
+        nulls.sql()
+    ))
+    .bind(filter.item_id)
+    .bind(&filter.metadata_contains)
+    .bind(filter.unassigned_only)
+    .bind(filter.reason)
+    .bind(filter.since)
+    .bind(filter.until)
+    .bind(filter.direction.sign())
+    .bind(&filter.source)
+    .bind(first)
+    .bind(offset)
+    .fetch_all(&*context.clients.postgres)
+    .await?;
+
+    // the window is counted before the limit and offset, so every row carries the same total
+    let total_count = match rows.first() {
+        Some(row) => row.try_get("total_count")?,
+        None if offset == 0 => 0,
+        None => count_transactions(context, filter).await?,
+    };
+    let transactions = rows
+        .iter()
+        .map(Transaction::from_row)
+        .collect::<Result<Vec<Transaction>, sqlx::Error>>()?;
+
+    Ok(TransactionConnection {
+        transactions,
+        total_count,
+    })
+}
+
+/// Gets the number of transactions matching a filter, returning the result, or an error.
+async fn count_transactions(
+    context: &AppContext,
+    filter: TransactionFilter,
+) -> Result<i64, ModelError> {
+    sqlx::query(&format!(
+        "select count(*) as total_count from transactions where {}",
+        FILTER_CONDITIONS
+    ))
+    .bind(filter.item_id)
+    .bind(filter.metadata_contains)
+    .bind(filter.unassigned_only)
+    .bind(filter.reason)
+    .bind(filter.since)
+    .bind(filter.until)
+    .bind(filter.direction.sign())
+    .bind(filter.source)
+    .fetch_one(&*context.clients.postgres)
+    .await?
+    .try_get("total_count")
+    .map_err(ModelError::from)
+}
+
+/// A dimension by which transactions are grouped in a summary.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, async_graphql::Enum)]
+pub(crate) enum TransactionGroupDimension {
+    Item,
+    Location,
+    Reason,
+    /// The day of the transaction date, in the time zone of the summary.
+    Day,
+    /// The week of the transaction date, starting on Monday, in the time zone of the summary.
+    Week,
+    /// The month of the transaction date, in the time zone of the summary.
+    Month,
+}
+
+impl TransactionGroupDimension {
+    /// The name of the column of the dimension in a summary.
+    fn column(self) -> &'static str {
+        match self {
+            TransactionGroupDimension::Item => "item_id",
+            TransactionGroupDimension::Location => "location_id",
+            TransactionGroupDimension::Reason => "reason",
+            TransactionGroupDimension::Day
+            | TransactionGroupDimension::Week
+            | TransactionGroupDimension::Month => "period",
+        }
+    }
+
+    /// The SQL expression of the column of the dimension. Only these fixed expressions are used to
+    /// build summary queries, and date buckets are truncated in the time zone bound as `$4`, so they
+    /// start at local midnight.
+    fn expression(self) -> &'static str {
+        match self {
+            TransactionGroupDimension::Item => "item_id",
+            TransactionGroupDimension::Location => "location_id",
+            TransactionGroupDimension::Reason => "reason",
+            TransactionGroupDimension::Day => {
+                "date_trunc('day', transaction_date at time zone $4) at time zone $4"
+            }
+            TransactionGroupDimension::Week => {
+                "date_trunc('week', transaction_date at time zone $4) at time zone $4"
+            }
+            TransactionGroupDimension::Month => {
+                "date_trunc('month', transaction_date at time zone $4) at time zone $4"
+            }
+        }
+    }
+}
+
+/// The columns of transaction summaries, with the placeholders fetched when transactions are not
+/// grouped by them.
+const SUMMARY_COLUMNS: [(&str, &str); 4] = [
+    ("item_id", "null::integer"),
+    ("location_id", "null::integer"),
+    ("reason", "null::transaction_reason"),
+    ("period", "null::timestamptz"),
+];
+
+/// The count and total quantity of a group of transactions. The dimensions which the transactions
+/// are not grouped by are null.
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow, async_graphql::SimpleObject)]
+pub(crate) struct TransactionSummary {
+    item_id: Option<ItemId>,
+    location_id: Option<LocationId>,
+    reason: Option<TransactionReason>,
+    /// The start of the day, week or month of the transactions.
+    period: Option<DateTime<Utc>>,
+    count: i64,
+    total_quantity: i64,
+}
+
+/// Gets the counts and total quantities of the transactions dated within a range, grouped by the
+/// given dimensions in order, returning the result, or an error if the dimensions have more than
+/// one date bucket, the time zone is not known, or there are more than the maximum number of
+/// groups. Date buckets are in the given IANA time zone. Grouping by no dimensions summarizes all
+/// of the transactions in one group.
+pub(crate) async fn get_transaction_summaries(
+    context: &AppContext,
+    group_by: Vec<TransactionGroupDimension>,
+    from: Option<DateTime<Utc>>,
+    to: Option<DateTime<Utc>>,
+    timezone: String,
+) -> Result<Vec<TransactionSummary>, ModelError> {
+    validation::transaction::validate_timezone(context, &timezone).await?;
+
+    let mut dimensions = Vec::new();
+    for dimension in group_by {
+        if !dimensions.contains(&dimension) {
+            dimensions.push(dimension);
+        }
+    }
+    if dimensions
+        .iter()
+        .filter(|dimension| dimension.column() == "period")
+        .count()
+        > 1
+    {
+        return Err(ModelError::Validation {
+            message: "validation errors on transaction summary".to_string(),
+            fields: vec![(
+                "groupBy",
+                "only one of DAY, WEEK and MONTH can be grouped by".to_string(),
+            )],
+        });
+    }
+
+    // each column is either grouped by, or a placeholder
+    let columns = SUMMARY_COLUMNS
+        .iter()
+        .map(|(column, placeholder)| {
+            let expression = dimensions
+                .iter()
+                .find(|dimension| dimension.column() == *column)
+                .map_or(*placeholder, |dimension| dimension.expression());
+            format!("{} as {}", expression, column)
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    let grouping = if dimensions.is_empty() {
+        String::new()
+    } else {
+        let grouped = dimensions
+            .iter()
+            .map(|dimension| dimension.column())
+            .collect::<Vec<_>>()
+            .join(", ");
+        let ordered = dimensions
+            .iter()
+            .map(|dimension| format!("{} nulls last", dimension.column()))
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("group by {} order by {}", grouped, ordered)
+    };
+
+    // one more group than the maximum is fetched to find if there are too many
+    let summaries = sqlx::query_as::<_, TransactionSummary>(&format!(
+        r#"
+        select {}, count(id) as count, coalesce(sum(quantity), 0)::bigint as total_quantity
+        from transactions
+        where ($1::timestamptz is null or transaction_date >= $1)
+            and ($2::timestamptz is null or transaction_date < $2)
+        {}
+        limit $3
+    "#,
+        columns, grouping
+    ))
+    .bind(from)
+    .bind(to)
+    .bind(MAX_SUMMARY_GROUPS + 1)
+    .bind(timezone)
+    .fetch_all(&*context.clients.postgres)
+    .await?;
+
+    if summaries.len() as i64 > MAX_SUMMARY_GROUPS {
+        return Err(ModelError::Validation {
+            message: "validation errors on transaction summary".to_string(),
+            fields: vec![(
+                "groupBy",
+                format!(
+                    "more than {} groups, so narrow the dates or group by fewer dimensions",
+                    MAX_SUMMARY_GROUPS
+                ),
+            )],
+        });
+    }
+
+    Ok(summaries)
+}
+
+/// The name of the channel the modifications of the transactions of an item are published to, in
+/// addition to the channel of all transactions.
+pub(crate) fn item_channel_name(item_id: ItemId) -> String {
+    format!("transactions:item:{}", i32::from(item_id))
+}
+
+/// Gets the earliest transactions dated at or after the given date, optionally of an item, up to
+/// a limit, in chronological order.
+pub(crate) async fn get_transactions_since(
+    clients: &Clients,
+    since: DateTime<Utc>,
+    item_id: Option<ItemId>,
+    limit: i64,
+) -> Result<Vec<Transaction>, ModelError> {
+    sqlx::query_as::<_, Transaction>(&format!(
+        r#"
+        select {} from transactions
+        where transaction_date >= $1 and ($3::integer is null or item_id = $3)
+        order by transaction_date, id
+        limit $2
+    "#,
+        TRANSACTION_COLUMNS
+    ))
+    .bind(since)
+    .bind(limit)
+    .bind(item_id)
+    .fetch_all(&*clients.postgres)
+    .await
+    .map_err(ModelError::from)
 }
 
 /// Gets all transactions with the given ids.
@@ -65,12 +664,13 @@ pub(crate) async fn get_transactions_by_ids(
     clients: &Clients,
     ids: Vec<TransactionId>,
 ) -> Result<HashMap<TransactionId, Result<Transaction>>> {
-    sqlx::query_as::<_, Transaction>(
+    sqlx::query_as::<_, Transaction>(&format!(
         r#"
-        select id, item_id, location_id, transaction_date, quantity, comment from transactions
+        select {} from transactions
         where id = any($1)
     "#,
-    )
+        TRANSACTION_COLUMNS
+    ))
     .bind(ids.into_iter().map(|id| id.0).collect::<Vec<i32>>())
     .fetch_all(&*clients.postgres)
     .await
@@ -83,6 +683,87 @@ pub(crate) async fn get_transactions_by_ids(
     .map_err(Error::from)
 }
 
+/// Gets the revisions of all transactions with the given ids, oldest first.
+pub(crate) async fn get_revisions_by_transaction_ids(
+    clients: &Clients,
+    ids: Vec<TransactionId>,
+) -> Result<HashMap<TransactionId, Result<Vec<TransactionRevision>>>> {
+    let revisions = sqlx::query_as::<_, TransactionRevision>(&format!(
+        r#"
+        select {} from transaction_revisions
+        where transaction_id = any($1)
+        order by id
+    "#,
+        revision_columns()
+    ))
+    .bind(ids.into_iter().map(|id| id.0).collect::<Vec<i32>>())
+    .fetch_all(&*clients.postgres)
+    .await
+    .map_err(Error::from)?;
+
+    let mut revisions_map = HashMap::new();
+    for revision in revisions {
+        revisions_map
+            .entry(revision.transaction_id)
+            .or_insert_with(Vec::new)
+            .push(revision);
+    }
+
+    Ok(revisions_map
+        .into_iter()
+        .map(|(key, value)| (key, Ok(value)))
+        .collect())
+}
+
+/// Records the current values of a transaction as a revision, locking the transaction until the
+/// database transaction ends, so the revision is of the values which are then changed, returning
+/// the revision.
+async fn record_revision(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    id: TransactionId,
+    revision: ModificationType,
+) -> Result<TransactionRevision, ModelError> {
+    sqlx::query_as::<_, TransactionRevision>(&format!(
+        r#"
+        with previous as (
+            select {columns} from transactions
+            where id = $1
+            for update
+        )
+        insert into transaction_revisions (revision, {revised_columns})
+        select $2, {columns} from previous
+        returning {revision_columns}
+    "#,
+        columns = TRANSACTION_COLUMNS,
+        revised_columns = revised_columns(),
+        revision_columns = revision_columns()
+    ))
+    .bind(id)
+    .bind(revision)
+    .fetch_one(tx)
+    .await
+    .map_err(ModelError::from)
+}
+
+/// Creates the error for a transaction which cannot be restored, as it exists.
+fn not_deleted(id: TransactionId) -> ModelError {
+    ModelError::Conflict {
+        message: "validation errors on transaction".to_string(),
+        fields: vec![("id", format!("transaction with id {:?} is not deleted", id))],
+    }
+}
+
+/// Creates the error for a transaction whose item was changed after it was fetched.
+fn concurrent_change(id: TransactionId) -> ModelError {
+    ModelError::Conflict {
+        message: "validation errors on transaction".to_string(),
+        fields: vec![(
+            "id",
+            format!("transaction with id {:?} was changed concurrently", id),
+        )],
+    }
+}
+
 /// Gets an transaction, given an id, returning the result, or a field error.
 pub(crate) async fn get_transaction(
     context: &AppContext,
@@ -96,40 +777,325 @@ pub(crate) async fn get_transaction(
         .await
 }
 
-/// Creates an transaction, given an insertable transaction, returning the result, or a field error.
-pub(crate) async fn create_transaction(
+/// Gets the transactions with the given ids in one round-trip, in the order of the ids. Ids of
+/// transactions which do not exist resolve to `None`, rather than failing the other transactions.
+pub(crate) async fn get_transactions_in_order(
     context: &AppContext,
-    transaction: InsertableTransaction,
-) -> Result<Transaction> {
+    ids: Vec<TransactionId>,
+) -> Result<Vec<Option<Transaction>>> {
+    let transactions = get_transactions_by_ids(&context.clients, ids.clone()).await?;
+    Ok(ids
+        .iter()
+        .map(|id| {
+            transactions
+                .get(id)
+                .and_then(|transaction| transaction.as_ref().ok())
+                .cloned()
+        })
+        .collect())
+}
+
+/// Gets a transaction directly from the database, given an id, returning the result, or an error.
+async fn fetch_transaction(
+    context: &AppContext,
+    id: TransactionId,
+) -> Result<Transaction, ModelError> {
+    sqlx::query_as::<_, Transaction>(&format!(
+        r#"
+        select {} from transactions
+        where id = $1
+    "#,
+        TRANSACTION_COLUMNS
+    ))
+    .bind(id)
+    .fetch_one(&*context.clients.postgres)
+    .await
+    .map_err(ModelError::from)
+}
+
+/// Inserts a transaction from a source in a database transaction, which holds the lock of the
+/// quantity of its item until it is committed, returning the result, or a field error.
+async fn insert_transaction(
+    context: &AppContext,
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    mut transaction: InsertableTransaction,
+    source: Option<&str>,
+) -> Result<Transaction, ModelError> {
+    transaction.normalize();
     // check that the item and location exist
     validation::transaction::validate_ids(context, &transaction).await?;
-    validation::transaction::validate_item_quantities(
+    validation::transaction::validate_transaction_date(context, transaction.transaction_date)?;
+    validation::transaction::validate_comment(
         context,
-        transaction.item_id,
-        transaction.quantity,
-    )
-    .await?;
+        transaction.reason,
+        transaction.comment.as_deref(),
+    )?;
 
-    let created = sqlx::query_as::<_, Transaction>(
+    // the quantity and number of transactions are checked and changed while the quantity is
+    // locked, so concurrent changes cannot both pass the checks
+    let quantity = item::lock_quantity(tx, transaction.item_id).await?;
+    validation::transaction::validate_transaction_count(context, tx, transaction.item_id, 1)
+        .await?;
+    validation::transaction::validate_quantity_change(
+        context,
+        transaction.item_id,
+        quantity,
+        i32::from(transaction.quantity).into(),
+    )?;
+    sqlx::query_as::<_, Transaction>(&format!(
         r#"
-        insert into transactions (item_id, location_id, transaction_date, quantity, comment)
-        values ($1, $2, $3, $4, $5)
-        returning id, item_id, location_id, transaction_date, quantity, comment
+        insert into transactions (item_id, location_id, transaction_date, quantity, comment, metadata,
+            reason, source)
+        values ($1, $2, coalesce($3, now()), $4, $5, $6, $7, $8)
+        returning {}
     "#,
-    )
+        TRANSACTION_COLUMNS
+    ))
     .bind(transaction.item_id)
     .bind(transaction.location_id)
     .bind(transaction.transaction_date)
     .bind(transaction.quantity)
     .bind(transaction.comment)
-    .fetch_one(&*context.clients.postgres)
+    .bind(transaction.metadata)
+    .bind(transaction.reason)
+    .bind(source)
+    .fetch_one(tx)
     .await
-    .map_err(Error::from)?;
+    .map_err(|e| {
+        validation::transaction::map_foreign_key_violation(
+            ModelError::from(e),
+            transaction.item_id,
+            transaction.location_id,
+        )
+    })
+}
 
-    // publish the created event using redis pubsub and send the created transaction data
+/// Creates an transaction, given an insertable transaction and its source, if known, returning the
+/// result, or a field error. Transactions created without a date are dated now.
+pub(crate) async fn create_transaction(
+    context: &AppContext,
+    transaction: InsertableTransaction,
+    source: Option<&str>,
+) -> Result<Transaction, ModelError> {
+    let mut tx = context.clients.postgres.begin().await?;
+    let created = insert_transaction(context, &mut tx, transaction, source).await?;
+    // the created event is enqueued with the transaction, and is published once it is committed
     created
-        .broadcast_update(context, ModificationType::Create)
-        .await;
+        .enqueue_update(&mut tx, ModificationType::Create)
+        .await?;
+    tx.commit().await?;
+    outbox::flush(context).await;
+
+    Ok(created)
+}
+
+/// Creates many transactions, given insertable transactions and their source, if known, returning
+/// the created transactions in the given order and the errors of those which could not be created,
+/// or an error. Each transaction is validated as if it were created alone. In `AllOrNothing` mode,
+/// the first invalid transaction fails the request with its index, and none are created, while in
+/// `BestEffort` mode, each valid transaction is created and committed on its own.
+pub(crate) async fn create_transactions(
+    context: &AppContext,
+    transactions: Vec<InsertableTransaction>,
+    mode: BulkCreateMode,
+    source: Option<&str>,
+) -> Result<TransactionBulkCreate, ModelError> {
+    validation::transaction::validate_create_count(&transactions)?;
+
+    let mut created = Vec::new();
+    let mut errors = Vec::new();
+    match mode {
+        BulkCreateMode::AllOrNothing => {
+            // the quantities of the items are locked in the order of their ids before any are
+            // changed, so concurrent changes cannot deadlock
+            let mut tx = context.clients.postgres.begin().await?;
+            let mut item_ids = transactions
+                .iter()
+                .map(|transaction| transaction.item_id)
+                .collect::<Vec<ItemId>>();
+            item_ids.sort_by_key(|item_id| i32::from(*item_id));
+            item_ids.dedup();
+            for item_id in item_ids {
+                item::lock_quantity(&mut tx, item_id).await?;
+            }
+
+            for (index, transaction) in transactions.into_iter().enumerate() {
+                match insert_transaction(context, &mut tx, transaction, source).await {
+                    Ok(transaction) => created.push(transaction),
+                    Err(ModelError::Validation {
+                        message,
+                        mut fields,
+                    }) => {
+                        fields.push(("index", index.to_string()));
+                        return Err(ModelError::Validation { message, fields });
+                    }
+                    Err(ModelError::Conflict {
+                        message,
+                        mut fields,
+                    }) => {
+                        fields.push(("index", index.to_string()));
+                        return Err(ModelError::Conflict { message, fields });
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+            for transaction in &created {
+                transaction
+                    .enqueue_update(&mut tx, ModificationType::Create)
+                    .await?;
+            }
+            tx.commit().await?;
+        }
+        BulkCreateMode::BestEffort => {
+            for (index, transaction) in transactions.into_iter().enumerate() {
+                let mut tx = context.clients.postgres.begin().await?;
+                match insert_transaction(context, &mut tx, transaction, source).await {
+                    Ok(transaction) => {
+                        transaction
+                            .enqueue_update(&mut tx, ModificationType::Create)
+                            .await?;
+                        tx.commit().await?;
+                        created.push(transaction);
+                    }
+                    // errors of the database fail the request, as they are not caused by the input
+                    Err(ModelError::Db(e)) => return Err(ModelError::Db(e)),
+                    Err(e) => errors.push(TransactionCreateError {
+                        index: index as i32,
+                        code: e.code().to_string(),
+                        message: e.detailed_message(),
+                    }),
+                }
+            }
+        }
+    }
+
+    outbox::flush(context).await;
+
+    Ok(TransactionBulkCreate { created, errors })
+}
+
+/// Sets the opening balances of items at locations and its source, if known, creating an
+/// `OPENING_BALANCE` transaction for each balance which differs from the current quantity of its
+/// item at its location, by the difference, returning the created transactions in the given order,
+/// or a field error. The transactions are all created, or none are, and balances equal to the
+/// current quantity are skipped, so setting the same balances again creates no transactions.
+pub(crate) async fn set_opening_balances(
+    context: &AppContext,
+    balances: Vec<InsertableOpeningBalance>,
+    source: Option<&str>,
+) -> Result<Vec<Transaction>, ModelError> {
+    validation::transaction::validate_opening_balances(context, &balances).await?;
+
+    // the quantities of the items are locked in the order of their ids before any are changed, so
+    // concurrent changes cannot deadlock, and the current balances cannot change until committed
+    let mut tx = context.clients.postgres.begin().await?;
+    let mut item_ids = balances
+        .iter()
+        .map(|balance| balance.item_id)
+        .collect::<Vec<ItemId>>();
+    item_ids.sort_by_key(|item_id| i32::from(*item_id));
+    item_ids.dedup();
+    let mut quantities = HashMap::new();
+    for item_id in &item_ids {
+        quantities.insert(*item_id, item::lock_quantity(&mut tx, *item_id).await?);
+    }
+    let current = sqlx::query_as::<_, (ItemId, LocationId, i64)>(
+        r#"
+        select item_id, location_id, sum(quantity)::bigint from transactions
+        where item_id = any($1) and location_id = any($2)
+        group by item_id, location_id
+    "#,
+    )
+    .bind(
+        item_ids
+            .iter()
+            .map(|id| i32::from(*id))
+            .collect::<Vec<i32>>(),
+    )
+    .bind(
+        balances
+            .iter()
+            .map(|balance| i32::from(balance.location_id))
+            .collect::<Vec<i32>>(),
+    )
+    .fetch_all(&mut tx)
+    .await?
+    .into_iter()
+    .map(|(item_id, location_id, quantity)| ((item_id, location_id), quantity))
+    .collect::<HashMap<_, _>>();
+
+    let mut created = Vec::new();
+    for (index, balance) in balances.into_iter().enumerate() {
+        let current_quantity = current
+            .get(&(balance.item_id, balance.location_id))
+            .copied()
+            .unwrap_or_default();
+        // the quantity at a location can be out of the bounds of a quantity, as only the quantity
+        // of the item is checked, so the change is computed from the sum before it is narrowed
+        let change = i64::from(i32::from(balance.quantity))
+            .checked_sub(current_quantity)
+            .and_then(|change| i32::try_from(change).ok())
+            .ok_or_else(|| {
+                ModelError::validation("Opening balance causes quantity to overflow.")
+            })?;
+        if change == 0 {
+            continue;
+        }
+
+        let quantity = quantities.get_mut(&balance.item_id).unwrap();
+        validation::transaction::validate_quantity_change(
+            context,
+            balance.item_id,
+            *quantity,
+            i64::from(change),
+        )
+        .map_err(|e| match e {
+            ModelError::Validation {
+                message,
+                mut fields,
+            } => {
+                fields.push(("index", index.to_string()));
+                ModelError::Validation { message, fields }
+            }
+            e => e,
+        })?;
+        *quantity += i64::from(change);
+        validation::transaction::validate_transaction_count(context, &mut tx, balance.item_id, 1)
+            .await?;
+
+        created.push(
+            sqlx::query_as::<_, Transaction>(&format!(
+                r#"
+                insert into transactions (item_id, location_id, transaction_date, quantity, reason,
+                    source)
+                values ($1, $2, now(), $3, $4, $5)
+                returning {}
+            "#,
+                TRANSACTION_COLUMNS
+            ))
+            .bind(balance.item_id)
+            .bind(balance.location_id)
+            .bind(change)
+            .bind(TransactionReason::OpeningBalance)
+            .bind(source)
+            .fetch_one(&mut tx)
+            .await
+            .map_err(|e| {
+                validation::transaction::map_foreign_key_violation(
+                    ModelError::from(e),
+                    balance.item_id,
+                    Some(balance.location_id),
+                )
+            })?,
+        );
+    }
+    for transaction in &created {
+        transaction
+            .enqueue_update(&mut tx, ModificationType::Create)
+            .await?;
+    }
+    tx.commit().await?;
+    outbox::flush(context).await;
 
     Ok(created)
 }
@@ -138,77 +1104,356 @@ pub(crate) async fn create_transaction(
 pub(crate) async fn update_transaction(
     context: &AppContext,
     id: TransactionId,
-    transaction: InsertableTransaction,
-) -> Result<Transaction> {
-    let previous_transaction = get_transaction(context, id).await?;
+    mut transaction: InsertableTransaction,
+) -> Result<Transaction, ModelError> {
+    transaction.normalize();
+    let previous_transaction = fetch_transaction(context, id).await?;
     // check that the item and location exist
     validation::transaction::validate_ids(context, &transaction).await?;
-    validation::transaction::validate_item_quantities(
+    validation::transaction::validate_transaction_date(context, transaction.transaction_date)?;
+    validation::transaction::validate_comment(
         context,
-        transaction.item_id,
-        transaction.quantity - previous_transaction.quantity,
-    )
-    .await?;
+        transaction.reason,
+        transaction.comment.as_deref(),
+    )?;
 
-    let updated = sqlx::query_as::<_, Transaction>(
+    // the quantities of the previous and new items are locked in the order of their ids, so
+    // concurrent changes cannot deadlock
+    let mut tx = context.clients.postgres.begin().await?;
+    let mut item_ids = vec![previous_transaction.item_id, transaction.item_id];
+    item_ids.sort_by_key(|item_id| i32::from(*item_id));
+    item_ids.dedup();
+    let mut quantities = HashMap::new();
+    for item_id in item_ids {
+        quantities.insert(item_id, item::lock_quantity(&mut tx, item_id).await?);
+    }
+
+    // the revision is recorded with the update, so the history cannot diverge from it
+    let previous = record_revision(&mut tx, id, ModificationType::Update).await?;
+    if previous.item_id != previous_transaction.item_id {
+        return Err(concurrent_change(id));
+    }
+    let mut changes = HashMap::new();
+    *changes.entry(previous.item_id).or_insert(0) -= i64::from(i32::from(previous.quantity));
+    *changes.entry(transaction.item_id).or_insert(0) += i64::from(i32::from(transaction.quantity));
+    for (item_id, change) in changes {
+        validation::transaction::validate_quantity_change(
+            context,
+            item_id,
+            quantities[&item_id],
+            change,
+        )?;
+    }
+
+    let updated = sqlx::query_as::<_, Transaction>(&format!(
         r#"
         update transactions
-        set item_id = $1, location_id = $2, transaction_date = $3, quantity = $4, comment = $5
-        where id = $6
-        returning id, item_id, location_id, quantity, transaction_date, comment
+        set item_id = $1, location_id = $2, transaction_date = $3, quantity = $4, comment = $5,
+            metadata = $6, reason = $7
+        where id = $8
+        returning {}
     "#,
-    )
+        TRANSACTION_COLUMNS
+    ))
     .bind(transaction.item_id)
     .bind(transaction.location_id)
     .bind(transaction.transaction_date)
     .bind(transaction.quantity)
     .bind(transaction.comment)
+    .bind(transaction.metadata)
+    .bind(transaction.reason)
     .bind(id)
-    .fetch_one(&*context.clients.postgres)
+    .fetch_one(&mut tx)
     .await
-    .map_err(Error::from)?;
-
-    // publish the deleted event using redis pubsub and send the transaction data
+    .map_err(|e| {
+        validation::transaction::map_foreign_key_violation(
+            ModelError::from(e),
+            transaction.item_id,
+            transaction.location_id,
+        )
+    })?;
     updated
-        .broadcast_update(context, ModificationType::Update)
-        .await;
+        .enqueue_update(&mut tx, ModificationType::Update)
+        .await?;
+    tx.commit().await?;
+    outbox::flush(context).await;
 
     Ok(updated)
 }
 
+/// Transfers stock of an item between locations, creating a transaction out of one location and a
+/// transaction into the other together, returning the result, or a field error.
+pub(crate) async fn transfer_stock(
+    context: &AppContext,
+    transfer: InsertableTransfer,
+    source: Option<&str>,
+) -> Result<Transfer, ModelError> {
+    validation::transaction::validate_transfer(
+        transfer.from_location_id,
+        transfer.to_location_id,
+        transfer.quantity,
+    )?;
+
+    let comment = normalize::trim_optional(transfer.comment);
+    validation::transaction::validate_comment(
+        context,
+        Some(TransactionReason::Transfer),
+        comment.as_deref(),
+    )?;
+    let leg = |location_id, quantity| InsertableTransaction {
+        item_id: transfer.item_id,
+        location_id: Some(location_id),
+        transaction_date: None,
+        quantity,
+        comment: comment.clone(),
+        metadata: None,
+        reason: Some(TransactionReason::Transfer),
+    };
+    let legs = [
+        leg(transfer.from_location_id, -transfer.quantity),
+        leg(transfer.to_location_id, transfer.quantity),
+    ];
+    // check that the item and both locations exist
+    for leg in &legs {
+        validation::transaction::validate_ids(context, leg).await?;
+    }
+
+    // both transactions are created in the same transfer group, or neither is, and the number of
+    // transactions is checked while the quantity of the item is locked
+    let mut tx = context.clients.postgres.begin().await?;
+    item::lock_quantity(&mut tx, transfer.item_id).await?;
+    validation::transaction::validate_transaction_count(context, &mut tx, transfer.item_id, 2)
+        .await?;
+    let transfer_group =
+        sqlx::query_scalar::<_, TransferGroupId>("select nextval('transfer_groups')")
+            .fetch_one(&mut tx)
+            .await?;
+    let mut created = Vec::new();
+    for leg in legs {
+        created.push(
+            sqlx::query_as::<_, Transaction>(&format!(
+                r#"
+                insert into transactions (item_id, location_id, transaction_date, quantity,
+                    comment, reason, source, transfer_group)
+                values ($1, $2, now(), $3, $4, $5, $6, $7)
+                returning {}
+            "#,
+                TRANSACTION_COLUMNS
+            ))
+            .bind(leg.item_id)
+            .bind(leg.location_id)
+            .bind(leg.quantity)
+            .bind(leg.comment)
+            .bind(leg.reason)
+            .bind(source)
+            .bind(transfer_group)
+            .fetch_one(&mut tx)
+            .await
+            .map_err(|e| {
+                validation::transaction::map_foreign_key_violation(
+                    ModelError::from(e),
+                    leg.item_id,
+                    leg.location_id,
+                )
+            })?,
+        );
+    }
+    for transaction in &created {
+        transaction
+            .enqueue_update(&mut tx, ModificationType::Create)
+            .await?;
+    }
+    tx.commit().await?;
+    outbox::flush(context).await;
+
+    let to = created.pop().unwrap();
+    let from = created.pop().unwrap();
+    Ok(Transfer::new(transfer_group, from, to))
+}
+
+/// Gets the transfers of stock between locations, most recent first, optionally of an item, dated
+/// within a range, or of a transfer group, optionally skipping some and limiting the number
+/// returned, returning the result, or an error. The transactions of each transfer are paired by
+/// joining them on their transfer group, so transfers which no longer have both transactions, such
+/// as when one was deleted, are not returned.
+pub(crate) async fn get_transfers(
+    context: &AppContext,
+    item_id: Option<ItemId>,
+    since: Option<DateTime<Utc>>,
+    until: Option<DateTime<Utc>>,
+    transfer_group: Option<TransferGroupId>,
+    first: Option<i64>,
+    offset: i64,
+) -> Result<Vec<Transfer>, ModelError> {
+    // the groups are paged, then both transactions of each group are fetched in the same order,
+    // with the transaction out of a location first
+    let transactions = sqlx::query_as::<_, Transaction>(&format!(
+        r#"
+        with groups as (
+            select from_leg.transfer_group as group_id, from_leg.transaction_date as group_date
+            from transactions from_leg
+            join transactions to_leg
+                on to_leg.transfer_group = from_leg.transfer_group and to_leg.quantity > 0
+            where from_leg.quantity < 0
+                and ($1::integer is null or from_leg.item_id = $1)
+                and ($2::timestamptz is null or from_leg.transaction_date >= $2)
+                and ($3::timestamptz is null or from_leg.transaction_date < $3)
+                and ($4::bigint is null or from_leg.transfer_group = $4)
+            order by group_date desc nulls last, group_id desc
+            limit $5 offset $6
+        )
+        select {} from groups
+        join transactions on transactions.transfer_group = groups.group_id
+        order by group_date desc nulls last, group_id desc, quantity
+    "#,
+        TRANSACTION_COLUMNS
+    ))
+    .bind(item_id)
+    .bind(since)
+    .bind(until)
+    .bind(transfer_group)
+    .bind(first)
+    .bind(offset)
+    .fetch_all(&*context.clients.postgres)
+    .await?;
+
+    let mut transfers = Vec::with_capacity(transactions.len() / 2);
+    let mut transactions = transactions.into_iter();
+    while let (Some(from), Some(to)) = (transactions.next(), transactions.next()) {
+        // the transfer group is the same for both, as they were joined on it
+        let transfer_group = from.transfer_group.unwrap();
+        transfers.push(Transfer::new(transfer_group, from, to));
+    }
+    Ok(transfers)
+}
+
+/// Gets a transfer of stock between locations, given its transfer group, returning the result, or
+/// a field error.
+pub(crate) async fn get_transfer(
+    context: &AppContext,
+    transfer_group: TransferGroupId,
+) -> Result<Transfer, ModelError> {
+    get_transfers(context, None, None, None, Some(transfer_group), None, 0)
+        .await?
+        .pop()
+        .ok_or(ModelError::NotFound)
+}
+
 /// Deletes an transaction, given an id, returning the result, or a field error.
 pub(crate) async fn delete_transaction(
     context: &AppContext,
     id: TransactionId,
-) -> Result<Transaction> {
-    let transaction = get_transaction(context, id).await?;
-    validation::transaction::validate_item_quantities(
-        context,
-        transaction.item_id,
-        -transaction.quantity,
-    )
-    .await?;
+) -> Result<Transaction, ModelError> {
+    let transaction = fetch_transaction(context, id).await?;
 
-    let deleted = sqlx::query_as::<_, Transaction>(
+    let mut tx = context.clients.postgres.begin().await?;
+    let quantity = item::lock_quantity(&mut tx, transaction.item_id).await?;
+    let previous = record_revision(&mut tx, id, ModificationType::Delete).await?;
+    if previous.item_id != transaction.item_id {
+        return Err(concurrent_change(id));
+    }
+    validation::transaction::validate_quantity_change(
+        context,
+        previous.item_id,
+        quantity,
+        -i64::from(i32::from(previous.quantity)),
+    )?;
+    let deleted = sqlx::query_as::<_, Transaction>(&format!(
         r#"
         delete from transactions
         where id = $1
-        returning id, item_id, location_id, transaction_date, quantity, comment
+        returning {}
     "#,
-    )
+        TRANSACTION_COLUMNS
+    ))
     .bind(id)
-    .fetch_one(&*context.clients.postgres)
-    .await
-    .map_err(Error::from)?;
-
-    // publish the deleted event using redis pubsub and send the transaction data
+    .fetch_one(&mut tx)
+    .await?;
     deleted
-        .broadcast_update(context, ModificationType::Delete)
-        .await;
+        .enqueue_update(&mut tx, ModificationType::Delete)
+        .await?;
+    tx.commit().await?;
+    outbox::flush(context).await;
 
     Ok(deleted)
 }
 
+/// Restores a deleted transaction with its id and the values it had when it was deleted, given an
+/// id, returning the result, or a field error. The item and location of the transaction must still
+/// exist.
+pub(crate) async fn restore_transaction(
+    context: &AppContext,
+    id: TransactionId,
+) -> Result<Transaction, ModelError> {
+    if fetch_transaction(context, id).await.is_ok() {
+        return Err(not_deleted(id));
+    }
+    // the last revision of a deleted transaction is its deletion
+    let revision = sqlx::query_as::<_, TransactionRevision>(&format!(
+        r#"
+        select {} from transaction_revisions
+        where transaction_id = $1
+        order by id desc
+        limit 1
+    "#,
+        revision_columns()
+    ))
+    .bind(id)
+    .fetch_optional(&*context.clients.postgres)
+    .await?
+    .ok_or(ModelError::NotFound)?;
+    if revision.revision != ModificationType::Delete {
+        return Err(not_deleted(id));
+    }
+
+    let mut tx = context.clients.postgres.begin().await?;
+    let quantity = item::lock_quantity(&mut tx, revision.item_id).await?;
+    validation::transaction::validate_quantity_change(
+        context,
+        revision.item_id,
+        quantity,
+        i64::from(i32::from(revision.quantity)),
+    )?;
+    validation::transaction::validate_transaction_count(context, &mut tx, revision.item_id, 1)
+        .await?;
+    // a concurrent restore of the same transaction is rejected by the primary key
+    let restored = sqlx::query_as::<_, Transaction>(&format!(
+        r#"
+        insert into transactions (id, item_id, location_id, transaction_date, quantity, comment,
+            metadata, reason, source, transfer_group)
+        values ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        returning {}
+    "#,
+        TRANSACTION_COLUMNS
+    ))
+    .bind(id)
+    .bind(revision.item_id)
+    .bind(revision.location_id)
+    .bind(revision.transaction_date)
+    .bind(revision.quantity)
+    .bind(&revision.comment)
+    .bind(&revision.metadata)
+    .bind(revision.reason)
+    .bind(&revision.source)
+    .bind(revision.transfer_group)
+    .fetch_one(&mut tx)
+    .await
+    .map_err(|e| {
+        validation::transaction::map_foreign_key_violation(
+            e.into(),
+            revision.item_id,
+            revision.location_id,
+        )
+    })?;
+    restored
+        .enqueue_update(&mut tx, ModificationType::Restore)
+        .await?;
+    tx.commit().await?;
+    outbox::flush(context).await;
+
+    Ok(restored)
+}
+
 impl Transaction {
     async fn get_item(&self, context: &AppContext) -> Option<Item> {
         item::get_item(context, self.item_id).await.ok()
@@ -221,16 +1466,53 @@ impl Transaction {
         }
     }
 
-    async fn broadcast_update(&self, context: &AppContext, modification: ModificationType) {
-        // publish the event using redis pubsub and send the transaction data
-        modification::broadcast(context, "transactions", modification, self).await;
-        if let Some(item) = self.get_item(context).await {
-            modification::broadcast(context, "items", ModificationType::Update, &item).await;
-        }
-        if let Some(location) = self.get_location(context).await {
-            modification::broadcast(context, "locations", ModificationType::Update, &location)
-                .await;
+    async fn get_balance_after(&self, context: &AppContext) -> Result<ItemQuantity> {
+        context
+            .loaders
+            .get::<IdLoader<ItemId, ItemLedger, Clients>>()
+            .unwrap()
+            .load(self.item_id)
+            .await?
+            .balance_after(self.id)
+            .ok_or_else(|| {
+                Error::new("transaction not in the ledger of its item")
+                    .extend_with(|_, e| e.set("code", code::INTERNAL))
+            })
+    }
+
+    /// Enqueues the event of a change to the transaction in a database transaction, to all
+    /// transaction subscribers and to the subscribers of the transactions of its item, with the
+    /// updates of its item and location.
+    pub(crate) async fn enqueue_update(
+        &self,
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+        modification: ModificationType,
+    ) -> Result<(), ModelError> {
+        modification::enqueue(tx, ModificationEntity::Transaction, modification, self).await?;
+        modification::enqueue_channel(tx, &item_channel_name(self.item_id), modification, self)
+            .await?;
+        // the item and location are fetched in the database transaction, bypassing the loaders,
+        // so subscribers never receive data from before the change
+        let item = item::fetch_item(tx, self.item_id).await?;
+        modification::enqueue(
+            tx,
+            ModificationEntity::Item,
+            ModificationType::Update,
+            &item,
+        )
+        .await?;
+        if let Some(location_id) = self.location_id {
+            let location = location::fetch_location(tx, location_id).await?;
+            modification::enqueue(
+                tx,
+                ModificationEntity::Location,
+                ModificationType::Update,
+                &location,
+            )
+            .await?;
         }
+
+        Ok(())
     }
 }
 
@@ -249,4 +1531,72 @@ impl Transaction {
         self.get_location(context.data_unchecked::<AppContext>())
             .await
     }
+
+    /// The quantity of the item after the transaction, in the order of transaction dates.
+    async fn balance_after(&self, context: &async_graphql::Context<'_>) -> Result<ItemQuantity> {
+        self.get_balance_after(context.data_unchecked::<AppContext>())
+            .await
+    }
+
+    /// The quantity of the item before the transaction, in the order of transaction dates.
+    async fn quantity_before(&self, context: &async_graphql::Context<'_>) -> Result<ItemQuantity> {
+        self.get_balance_after(context.data_unchecked::<AppContext>())
+            .await?
+            .checked_sub(self.quantity)
+            .ok_or_else(|| Error::from(ModelError::validation("item quantity overflows")))
+    }
+
+    /// The history of the transaction, with its values before each update, and before it was
+    /// deleted, oldest first.
+    #[graphql(complexity = "list_complexity(NESTED_LIST_COMPLEXITY_MULTIPLIER, child_complexity)")]
+    async fn revisions(&self, context: &async_graphql::Context<'_>) -> Vec<TransactionRevision> {
+        context
+            .data_unchecked::<AppContext>()
+            .loaders
+            .get::<IdLoader<TransactionId, Vec<TransactionRevision>, Clients>>()
+            .unwrap()
+            .load(self.id)
+            .await
+            .unwrap_or_default()
+    }
+}
+
+/// Unit tests for transactions.
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_transaction_columns() {
+        let transaction: Transaction = serde_json::from_value(serde_json::json!({
+            "id": 1,
+            "item_id": 2,
+            "location_id": 3,
+            "transaction_date": "2022-01-01T00:00:00Z",
+            "quantity": 4,
+            "comment": "comment",
+            "metadata": { "key": "value" },
+            "reason": "Sale",
+            "source": "test",
+            "transfer_group": 5,
+        }))
+        .unwrap();
+        let fields = serde_json::to_value(transaction).unwrap();
+        let mut fields = fields.as_object().unwrap().keys().collect::<Vec<_>>();
+        let mut columns = TRANSACTION_COLUMNS.split(", ").collect::<Vec<_>>();
+        fields.sort();
+        columns.sort();
+        // every field of a transaction is returned, so none are left to their defaults
+        assert_eq!(fields, columns);
+    }
+
+    #[test]
+    fn test_qualified_columns() {
+        assert!(qualified_transaction_columns("ranked").starts_with("ranked.id, ranked.item_id, "));
+        assert_eq!(
+            qualified_transaction_columns("ranked").split(", ").count(),
+            TRANSACTION_COLUMNS.split(", ").count()
+        );
+        assert!(revision_columns().starts_with("id, revision, transaction_id, item_id, "));
+    }
 }
@@ -1,74 +1,273 @@
-use std::collections::HashMap;
-
-use async_graphql::{Error, ErrorExtensions, Result};
+use async_graphql::Result;
 use sqlx::Row;
 
 use crate::graphql::AppContext;
+use crate::model::error::ModelError;
 
 pub(crate) mod transaction {
     use super::*;
 
-    use async_graphql::{CustomValidator, InputValueError};
+    use std::collections::HashSet;
+
+    use async_graphql::{CustomValidator, InputType, InputValueError};
+    use chrono::{DateTime, Utc};
 
-    use crate::batcher::id_loader::IdLoader;
-    use crate::graphql::Clients;
     use crate::model::item::{ItemId, ItemQuantity};
-    use crate::model::transaction::InsertableTransaction;
+    use crate::model::location::LocationId;
+    use crate::model::transaction::{
+        InsertableOpeningBalance, InsertableTransaction, TransactionMetadata, TransactionReason,
+    };
+
+    /// The maximum number of transactions which can be created at once.
+    const MAX_CREATE_TRANSACTIONS: usize = 100;
+    /// The foreign key constraint of the item of a transaction.
+    const ITEM_CONSTRAINT: &str = "transactions_item_id_fkey";
+    /// The foreign key constraint of the location of a transaction.
+    const LOCATION_CONSTRAINT: &str = "transactions_location_id_fkey";
 
     pub(crate) struct TransactionQuantityValidator {}
 
     impl CustomValidator<ItemQuantity> for TransactionQuantityValidator {
         fn check(&self, value: &ItemQuantity) -> Result<(), InputValueError<ItemQuantity>> {
             if i32::from(*value) == 0 {
-                Err(InputValueError::custom("Transaction cannot have quantity of 0.".to_string()))
+                Err(InputValueError::custom(
+                    "Transaction cannot have quantity of 0.".to_string(),
+                ))
             } else {
                 Ok(())
             }
         }
     }
 
-    /// Validates that the item does not exceed integer bounds after this transaction.
-    pub(crate) async fn validate_item_quantities(
+    pub(crate) struct TransactionMetadataValidator {}
+
+    impl CustomValidator<TransactionMetadata> for TransactionMetadataValidator {
+        fn check(
+            &self,
+            value: &TransactionMetadata,
+        ) -> Result<(), InputValueError<TransactionMetadata>> {
+            if value.0.is_object() {
+                Ok(())
+            } else {
+                Err(InputValueError::custom(
+                    "Transaction metadata must be an object.".to_string(),
+                ))
+            }
+        }
+    }
+
+    /// Validates the number of transactions to create at once.
+    pub(crate) fn validate_create_count(
+        transactions: &[InsertableTransaction],
+    ) -> Result<(), ModelError> {
+        if transactions.is_empty() || transactions.len() > MAX_CREATE_TRANSACTIONS {
+            return Err(ModelError::Validation {
+                message: "validation errors on transactions".to_string(),
+                fields: vec![(
+                    "transactions",
+                    format!(
+                        "between 1 and {} transactions must be given",
+                        MAX_CREATE_TRANSACTIONS
+                    ),
+                )],
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Validates that the item has room for the given number of transactions below the configured
+    /// maximum, if any. The transactions are counted in the database transaction, which must hold
+    /// the lock of the quantity of the item, so concurrent changes cannot both pass the check, and
+    /// the transactions it already inserted are counted.
+    pub(crate) async fn validate_transaction_count(
         context: &AppContext,
+        tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
         item_id: ItemId,
-        quantity: ItemQuantity,
-    ) -> Result<()> {
-        let current_quantity = context
-            .loaders
-            .get::<IdLoader<ItemId, ItemQuantity, Clients>>()
-            .unwrap()
-            .load(item_id)
-            .await
-            .map(i32::from)
-            .unwrap_or(0);
-        if current_quantity.checked_add(i32::from(quantity)).is_none() {
-            Err(Error::new("Transaction causes item quantity to overflow."))
+        count: i64,
+    ) -> Result<(), ModelError> {
+        if let Some(max_transactions) = context.config.max_transactions_per_item {
+            let transaction_count =
+                sqlx::query(r#"select count(id) from transactions where item_id = $1"#)
+                    .bind(i32::from(item_id))
+                    .fetch_one(&mut *tx)
+                    .await?
+                    .try_get::<Option<i64>, _>("count")?
+                    .unwrap_or(0);
+
+            if transaction_count + count > max_transactions {
+                return Err(ModelError::Validation {
+                    message: "validation errors on transaction".to_string(),
+                    fields: vec![(
+                        "itemId",
+                        format!(
+                            "item with id {:?} has reached the maximum of {} transactions",
+                            item_id, max_transactions
+                        ),
+                    )],
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Validates that the quantity of an item does not exceed integer bounds after a change, and
+    /// does not become negative if negative stock is not allowed.
+    pub(crate) fn validate_quantity_change(
+        context: &AppContext,
+        item_id: ItemId,
+        quantity: i64,
+        change: i64,
+    ) -> Result<(), ModelError> {
+        let changed = quantity + change;
+        if i32::try_from(changed).is_err() {
+            Err(ModelError::validation(
+                "Transaction causes item quantity to overflow.",
+            ))
+        } else if !context.config.allow_negative_stock && change < 0 && changed < 0 {
+            Err(ModelError::Validation {
+                message: "validation errors on transaction".to_string(),
+                fields: vec![(
+                    "quantity",
+                    format!("item with id {:?} has only {} in stock", item_id, quantity),
+                )],
+            })
         } else {
             Ok(())
         }
     }
 
+    /// Validates that the transaction date is not further in the future than the configured skew,
+    /// if future transactions are not allowed.
+    pub(crate) fn validate_transaction_date(
+        context: &AppContext,
+        transaction_date: Option<DateTime<Utc>>,
+    ) -> Result<(), ModelError> {
+        if let (Some(max_skew), Some(transaction_date)) =
+            (context.config.max_future_transaction_skew, transaction_date)
+        {
+            if transaction_date > Utc::now() + max_skew {
+                return Err(ModelError::Validation {
+                    message: "validation errors on transaction".to_string(),
+                    fields: vec![(
+                        "transactionDate",
+                        format!(
+                            "transaction date {} is more than {} seconds in the future",
+                            transaction_date.to_rfc3339(),
+                            max_skew.num_seconds()
+                        ),
+                    )],
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Validates that a transaction has a comment if its reason is configured to require one.
+    // `Option::is_none_or` needs Rust 1.82, newer than the 1.66 the images are built with
+    #[allow(unknown_lints, clippy::unnecessary_map_or)]
+    pub(crate) fn validate_comment(
+        context: &AppContext,
+        reason: Option<TransactionReason>,
+        comment: Option<&str>,
+    ) -> Result<(), ModelError> {
+        match reason {
+            Some(reason)
+                if context.config.comment_required_reasons.contains(&reason)
+                    && comment.map_or(true, |comment| comment.trim().is_empty()) =>
+            {
+                Err(ModelError::Validation {
+                    message: "validation errors on transaction".to_string(),
+                    fields: vec![(
+                        "comment",
+                        format!(
+                            "transactions with reason {} require a comment",
+                            reason.to_value()
+                        ),
+                    )],
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Validates that a time zone is a known IANA time zone name, such as `America/Toronto`.
+    pub(crate) async fn validate_timezone(
+        context: &AppContext,
+        timezone: &str,
+    ) -> Result<(), ModelError> {
+        let known =
+            sqlx::query(r#"select exists(select 1 from pg_timezone_names where name = $1)"#)
+                .bind(timezone)
+                .fetch_one(&*context.clients.postgres)
+                .await?
+                .try_get::<bool, _>("exists")?;
+
+        if known {
+            Ok(())
+        } else {
+            Err(ModelError::Validation {
+                message: "validation errors on transaction summary".to_string(),
+                fields: vec![(
+                    "timezone",
+                    format!("time zone {:?} is not a known IANA time zone", timezone),
+                )],
+            })
+        }
+    }
+
+    /// Validates that a transfer is between different locations, and of a positive quantity.
+    pub(crate) fn validate_transfer(
+        from_location_id: LocationId,
+        to_location_id: LocationId,
+        quantity: ItemQuantity,
+    ) -> Result<(), ModelError> {
+        let mut errors = Vec::new();
+        if from_location_id == to_location_id {
+            errors.push((
+                "toLocationId",
+                format!(
+                    "location with id {:?} is also the location transferred from",
+                    to_location_id
+                ),
+            ));
+        }
+        if i32::from(quantity) <= 0 {
+            errors.push((
+                "quantity",
+                format!("quantity {} must be positive", i32::from(quantity)),
+            ));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ModelError::Validation {
+                message: "validation errors on transfer".to_string(),
+                fields: errors,
+            })
+        }
+    }
+
     /// Validates that the item and location for a transaction exist.
     pub(crate) async fn validate_ids(
         context: &AppContext,
         transaction: &InsertableTransaction,
-    ) -> Result<()> {
-        let mut errors = HashMap::new();
+    ) -> Result<(), ModelError> {
+        let mut errors = Vec::new();
 
         // check item exists
         let item_count = sqlx::query(r#"select count(id) from items where id = $1"#)
             .bind(i32::from(transaction.item_id))
             .fetch_one(&*context.clients.postgres)
-            .await
-            .map_err(Error::from)?
+            .await?
             .try_get::<Option<i64>, _>("count")?
             .unwrap_or(0);
 
         if item_count != 1 {
-            errors.insert(
-                "itemId",
-                format!("item with id {:?} not found", transaction.item_id),
-            );
+            errors.push(item_not_found(transaction.item_id));
         }
 
         // check location exists
@@ -76,41 +275,588 @@ pub(crate) mod transaction {
             let location_count = sqlx::query(r#"select count(id) from locations where id = $1"#)
                 .bind(i32::from(location_id))
                 .fetch_one(&*context.clients.postgres)
-                .await
-                .map_err(Error::from)?
+                .await?
+                .try_get::<Option<i64>, _>("count")?
+                .unwrap_or(0);
+
+            if location_count != 1 {
+                errors.push(location_not_found(location_id));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ModelError::Validation {
+                message: "validation errors on transaction".to_string(),
+                fields: errors,
+            })
+        }
+    }
+
+    /// Validates the number of opening balances to set at once, that each item and location is
+    /// given at most once, that no balance is negative if negative stock is not allowed, and that
+    /// the items and locations exist, which are looked up together. The first invalid balance is
+    /// reported with its index.
+    pub(crate) async fn validate_opening_balances(
+        context: &AppContext,
+        balances: &[InsertableOpeningBalance],
+    ) -> Result<(), ModelError> {
+        if balances.is_empty() || balances.len() > MAX_CREATE_TRANSACTIONS {
+            return Err(ModelError::Validation {
+                message: "validation errors on opening balances".to_string(),
+                fields: vec![(
+                    "balances",
+                    format!(
+                        "between 1 and {} opening balances must be given",
+                        MAX_CREATE_TRANSACTIONS
+                    ),
+                )],
+            });
+        }
+
+        let item_ids =
+            sqlx::query_scalar::<_, ItemId>(r#"select id from items where id = any($1)"#)
+                .bind(
+                    balances
+                        .iter()
+                        .map(|balance| i32::from(balance.item_id))
+                        .collect::<Vec<i32>>(),
+                )
+                .fetch_all(&*context.clients.postgres)
+                .await?
+                .into_iter()
+                .collect::<HashSet<ItemId>>();
+        let location_ids =
+            sqlx::query_scalar::<_, LocationId>(r#"select id from locations where id = any($1)"#)
+                .bind(
+                    balances
+                        .iter()
+                        .map(|balance| i32::from(balance.location_id))
+                        .collect::<Vec<i32>>(),
+                )
+                .fetch_all(&*context.clients.postgres)
+                .await?
+                .into_iter()
+                .collect::<HashSet<LocationId>>();
+
+        let mut seen = HashSet::new();
+        for (index, balance) in balances.iter().enumerate() {
+            let mut errors = Vec::new();
+            if !item_ids.contains(&balance.item_id) {
+                errors.push(item_not_found(balance.item_id));
+            }
+            if !location_ids.contains(&balance.location_id) {
+                errors.push(location_not_found(balance.location_id));
+            } else if !seen.insert((balance.item_id, balance.location_id)) {
+                errors.push((
+                    "locationId",
+                    format!(
+                        "item with id {:?} is given more than once at location with id {:?}",
+                        balance.item_id, balance.location_id
+                    ),
+                ));
+            }
+            if !context.config.allow_negative_stock && i32::from(balance.quantity) < 0 {
+                errors.push((
+                    "quantity",
+                    format!(
+                        "opening balance {} must not be negative",
+                        i32::from(balance.quantity)
+                    ),
+                ));
+            }
+
+            if !errors.is_empty() {
+                errors.push(("index", index.to_string()));
+                return Err(ModelError::Validation {
+                    message: "validation errors on opening balances".to_string(),
+                    fields: errors,
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Creates the field error for an item which does not exist.
+    fn item_not_found(item_id: ItemId) -> (&'static str, String) {
+        ("itemId", format!("item with id {:?} not found", item_id))
+    }
+
+    /// Creates the field error for a location which does not exist.
+    fn location_not_found(location_id: LocationId) -> (&'static str, String) {
+        (
+            "locationId",
+            format!("location with id {:?} not found", Some(location_id)),
+        )
+    }
+
+    /// Maps a violation of the foreign key of the item or location of a transaction, which is
+    /// deleted after its id is validated, to the same error as if it did not exist when validated.
+    pub(crate) fn map_foreign_key_violation(
+        e: ModelError,
+        item_id: ItemId,
+        location_id: Option<LocationId>,
+    ) -> ModelError {
+        let field = match (e.foreign_key_constraint(), location_id) {
+            (Some(ITEM_CONSTRAINT), _) => item_not_found(item_id),
+            (Some(LOCATION_CONSTRAINT), Some(location_id)) => location_not_found(location_id),
+            _ => return e,
+        };
+
+        ModelError::Validation {
+            message: "validation errors on transaction".to_string(),
+            fields: vec![field],
+        }
+    }
+}
+
+pub(crate) mod reservation {
+    use super::*;
+    use crate::model::item::{ItemId, ItemQuantity};
+    use crate::model::location::LocationId;
+    use crate::model::reservation::{Reservation, ReservationStatus};
+
+    /// Validates that the reserved quantity is positive, and that the item and location exist.
+    pub(crate) async fn validate_reservation(
+        context: &AppContext,
+        item_id: ItemId,
+        location_id: Option<LocationId>,
+        quantity: ItemQuantity,
+    ) -> Result<(), ModelError> {
+        let mut errors = Vec::new();
+        if i32::from(quantity) <= 0 {
+            errors.push(("quantity", "quantity must be positive".to_string()));
+        }
+
+        // check item exists
+        let item_count = sqlx::query(r#"select count(id) from items where id = $1"#)
+            .bind(i32::from(item_id))
+            .fetch_one(&*context.clients.postgres)
+            .await?
+            .try_get::<Option<i64>, _>("count")?
+            .unwrap_or(0);
+
+        if item_count != 1 {
+            errors.push(("itemId", format!("item with id {:?} not found", item_id)));
+        }
+
+        // check location exists
+        if let Some(location_id) = location_id {
+            let location_count = sqlx::query(r#"select count(id) from locations where id = $1"#)
+                .bind(i32::from(location_id))
+                .fetch_one(&*context.clients.postgres)
+                .await?
                 .try_get::<Option<i64>, _>("count")?
                 .unwrap_or(0);
 
             if location_count != 1 {
-                errors.insert(
+                errors.push((
                     "locationId",
-                    format!("location with id {:?} not found", transaction.location_id),
-                );
+                    format!("location with id {:?} not found", location_id),
+                ));
             }
         }
 
         if errors.is_empty() {
             Ok(())
         } else {
-            let mut error = Error::new("validation errors on transaction");
-            for (k, v) in errors {
-                error = error.extend_with(|_, e| e.set(k, v));
+            Err(ModelError::Validation {
+                message: "validation errors on reservation".to_string(),
+                fields: errors,
+            })
+        }
+    }
+
+    /// Validates that an item has enough available stock for a reservation.
+    pub(crate) fn validate_available(
+        item_id: ItemId,
+        quantity: ItemQuantity,
+        available: i64,
+    ) -> Result<(), ModelError> {
+        if i64::from(i32::from(quantity)) > available {
+            Err(ModelError::Validation {
+                message: "validation errors on reservation".to_string(),
+                fields: vec![(
+                    "quantity",
+                    format!(
+                        "item with id {:?} has only {} available",
+                        item_id, available
+                    ),
+                )],
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Validates that a reservation is active, so it can be committed or released.
+    pub(crate) fn validate_active(reservation: &Reservation) -> Result<(), ModelError> {
+        match reservation.status {
+            ReservationStatus::Active => Ok(()),
+            status => Err(ModelError::Conflict {
+                message: "validation errors on reservation".to_string(),
+                fields: vec![("status", format!("reservation is {:?}", status))],
+            }),
+        }
+    }
+}
+
+pub(crate) mod location {
+    use super::*;
+
+    use crate::model::location::InsertableLocation;
+
+    /// The maximum number of locations which can be created at once.
+    const MAX_CREATE_LOCATIONS: usize = 100;
+    /// The range of latitudes, inclusive.
+    pub(crate) const LATITUDES: (f64, f64) = (-90.0, 90.0);
+    /// The range of longitudes, inclusive.
+    pub(crate) const LONGITUDES: (f64, f64) = (-180.0, 180.0);
+
+    /// Validates the number of locations to create, and each location, naming the index of the
+    /// first invalid location.
+    pub(crate) fn validate_locations(locations: &[InsertableLocation]) -> Result<(), ModelError> {
+        if locations.is_empty() || locations.len() > MAX_CREATE_LOCATIONS {
+            return Err(ModelError::Validation {
+                message: "validation errors on locations".to_string(),
+                fields: vec![(
+                    "locations",
+                    format!(
+                        "between 1 and {} locations must be given",
+                        MAX_CREATE_LOCATIONS
+                    ),
+                )],
+            });
+        }
+
+        for (index, location) in locations.iter().enumerate() {
+            if let Err(ModelError::Validation {
+                message,
+                mut fields,
+            }) = validate_location(location)
+            {
+                fields.push(("index", index.to_string()));
+                return Err(ModelError::Validation { message, fields });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Validates that the name of a location is not blank, and its coordinates.
+    pub(crate) fn validate_location(location: &InsertableLocation) -> Result<(), ModelError> {
+        if location.name.is_empty() {
+            return Err(ModelError::Validation {
+                message: "validation errors on location".to_string(),
+                fields: vec![("name", "name must not be blank".to_string())],
+            });
+        }
+
+        validate_coordinates(location.lat, location.lng)
+    }
+
+    /// Validates that coordinates are either both given or both not, and are within range.
+    pub(crate) fn validate_coordinates(
+        lat: Option<f64>,
+        lng: Option<f64>,
+    ) -> Result<(), ModelError> {
+        let mut errors = Vec::new();
+        match (lat, lng) {
+            (Some(lat), Some(lng)) => {
+                let (min_lat, max_lat) = LATITUDES;
+                let (min_lng, max_lng) = LONGITUDES;
+                if !(min_lat..=max_lat).contains(&lat) {
+                    errors.push((
+                        "lat",
+                        format!("latitude {} not within {} and {}", lat, min_lat, max_lat),
+                    ));
+                }
+                if !(min_lng..=max_lng).contains(&lng) {
+                    errors.push((
+                        "lng",
+                        format!("longitude {} not within {} and {}", lng, min_lng, max_lng),
+                    ));
+                }
             }
-            Err(error)
+            (None, None) => {}
+            _ => errors.push((
+                "lat",
+                "latitude and longitude must be given together".to_string(),
+            )),
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ModelError::Validation {
+                message: "validation errors on location".to_string(),
+                fields: errors,
+            })
         }
     }
 }
 
 pub(crate) mod item {
     use super::*;
-    use crate::model::item::{InsertableItem, ItemId};
 
+    use crate::model::config::ModelConfig;
+    use crate::model::item::{InsertableItem, ItemId, ItemPatch, ItemQuantity};
+    use regex::Regex;
+    use std::collections::HashSet;
+
+    /// The maximum number of items which can be updated at once.
+    const MAX_BULK_UPDATE_ITEMS: usize = 500;
+    /// The maximum number of items which can be imported at once.
+    const MAX_IMPORT_ITEMS: usize = 1000;
+
+    /// The unique index on item skus.
+    const SKU_CONSTRAINT: &str = "items_sku_key";
+    /// The unique index on item skus, ignoring case.
+    const UPPER_SKU_CONSTRAINT: &str = "items_upper_sku_idx";
+    /// The partial unique index on item names, which applies when uniqueness is enforced.
+    const NAME_CONSTRAINT: &str = "items_unique_name_idx";
+
+    /// Creates the error for a sku which is not unique.
+    fn sku_conflict(sku: &str) -> ModelError {
+        ModelError::Conflict {
+            message: "validation errors on item".to_string(),
+            fields: vec![("itemId", format!("sku {:?} not unique", sku))],
+        }
+    }
+
+    /// Creates the error for a name which is not unique.
+    fn name_conflict(name: &str) -> ModelError {
+        ModelError::Conflict {
+            message: "validation errors on item".to_string(),
+            fields: vec![("name", format!("name {:?} not unique", name))],
+        }
+    }
+
+    /// Maps a conflict on the sku or name of an item to a sku or name conflict.
+    pub(crate) fn map_conflict(e: ModelError, item: &InsertableItem) -> ModelError {
+        let constraint = match &e {
+            ModelError::Conflict { fields, .. } => fields
+                .iter()
+                .find(|(field, _)| *field == "constraint")
+                .map(|(_, constraint)| constraint.as_str()),
+            _ => None,
+        };
+
+        match (constraint, &item.sku) {
+            (Some(SKU_CONSTRAINT | UPPER_SKU_CONSTRAINT), Some(sku)) => sku_conflict(sku),
+            (Some(NAME_CONSTRAINT), _) => name_conflict(&item.name),
+            _ => e,
+        }
+    }
+
+    /// Validates that a bulk update has a bounded number of ids and changes at least one field.
+    pub(crate) fn validate_bulk_update(
+        ids: &[ItemId],
+        patch: &ItemPatch,
+    ) -> Result<(), ModelError> {
+        let mut errors = Vec::new();
+        if ids.is_empty() {
+            errors.push(("ids", "no ids given".to_string()));
+        } else if ids.len() > MAX_BULK_UPDATE_ITEMS {
+            errors.push((
+                "ids",
+                format!("more than {} ids given", MAX_BULK_UPDATE_ITEMS),
+            ));
+        }
+
+        if patch.supplier.is_none()
+            && patch.description.is_none()
+            && patch.reorder_point.is_none()
+            && patch.max_quantity.is_none()
+        {
+            errors.push(("patch", "no fields to update".to_string()));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ModelError::Validation {
+                message: "validation errors on item".to_string(),
+                fields: errors,
+            })
+        }
+    }
+
+    /// Validates that the minimum quantity of a filter is at most its maximum quantity, if both
+    /// are given.
+    pub(crate) fn validate_quantity_range(
+        min_quantity: Option<ItemQuantity>,
+        max_quantity: Option<ItemQuantity>,
+    ) -> Result<(), ModelError> {
+        match (min_quantity, max_quantity) {
+            (Some(min_quantity), Some(max_quantity))
+                if i32::from(min_quantity) > i32::from(max_quantity) =>
+            {
+                Err(ModelError::Validation {
+                    message: "validation errors on items".to_string(),
+                    fields: vec![(
+                        "minQuantity",
+                        format!(
+                            "minimum quantity {} is greater than the maximum quantity {}",
+                            i32::from(min_quantity),
+                            i32::from(max_quantity)
+                        ),
+                    )],
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Validates that a sku matches the configured pattern, if there is one.
+    pub(crate) fn validate_sku_pattern(
+        sku_pattern: Option<&Regex>,
+        sku: &str,
+    ) -> Result<(), ModelError> {
+        match sku_pattern {
+            Some(sku_pattern) if !sku_pattern.is_match(sku) => Err(ModelError::Validation {
+                message: "validation errors on item".to_string(),
+                fields: vec![(
+                    "sku",
+                    format!("sku {:?} does not match the pattern {}", sku, sku_pattern),
+                )],
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether a character is allowed in skus, which are limited to the characters which barcode
+    /// systems commonly support.
+    fn is_sku_char(c: char) -> bool {
+        c.is_ascii_alphanumeric() || c == '-' || c == '_'
+    }
+
+    /// Validates that a sku has at most the configured number of characters, only has letters,
+    /// digits, `-` and `_`, and matches the configured pattern, if there is one. Skus are
+    /// validated after they are normalized.
+    pub(crate) fn validate_sku_format(config: &ModelConfig, sku: &str) -> Result<(), ModelError> {
+        let sku_error = |error: String| ModelError::Validation {
+            message: "validation errors on item".to_string(),
+            fields: vec![("sku", error)],
+        };
+        if sku.chars().count() > config.sku_max_length {
+            return Err(sku_error(format!(
+                "sku {:?} is longer than {} characters",
+                sku, config.sku_max_length
+            )));
+        }
+        if let Some(c) = sku.chars().find(|c| !is_sku_char(*c)) {
+            return Err(sku_error(format!(
+                "sku {:?} has the character {:?}, but only letters, digits, '-' and '_' are allowed",
+                sku, c
+            )));
+        }
+
+        validate_sku_pattern(config.sku_pattern.as_ref(), sku)
+    }
+
+    /// Validates that an import has a bounded number of items, that skus have the configured
+    /// format, that names are not blank, and that skus are not repeated, ignoring case.
+    pub(crate) fn validate_import(
+        items: &[InsertableItem],
+        config: &ModelConfig,
+    ) -> Result<(), ModelError> {
+        let mut errors = Vec::new();
+        if items.is_empty() {
+            errors.push(("items", "no items given".to_string()));
+        } else if items.len() > MAX_IMPORT_ITEMS {
+            errors.push((
+                "items",
+                format!("more than {} items given", MAX_IMPORT_ITEMS),
+            ));
+        }
+
+        if let Some(Err(ModelError::Validation { fields, .. })) = items
+            .iter()
+            .filter_map(|item| item.sku.as_ref())
+            .map(|sku| validate_sku_format(config, sku))
+            .find(Result::is_err)
+        {
+            errors.extend(fields);
+        }
+
+        if items.iter().any(|item| item.name.is_empty()) {
+            errors.push(("name", "name must not be blank".to_string()));
+        }
+
+        let mut skus = HashSet::new();
+        if let Some(sku) = items
+            .iter()
+            .filter_map(|item| item.sku.as_ref())
+            .find(|sku| !skus.insert(sku.to_uppercase()))
+        {
+            errors.push(("sku", format!("sku {:?} repeated in import", sku)));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ModelError::Validation {
+                message: "validation errors on item".to_string(),
+                fields: errors,
+            })
+        }
+    }
+
+    /// Validates that the name of an item is not blank, and is unique, ignoring case, if
+    /// uniqueness is enforced.
+    pub(crate) async fn validate_name(
+        context: &AppContext,
+        item: &InsertableItem,
+        id: Option<ItemId>,
+    ) -> Result<(), ModelError> {
+        if item.name.is_empty() {
+            return Err(ModelError::Validation {
+                message: "validation errors on item".to_string(),
+                fields: vec![("name", "name must not be blank".to_string())],
+            });
+        }
+        if !context.config.enforce_unique_item_name {
+            return Ok(());
+        }
+
+        let id_match = sqlx::query(
+            r#"
+            select id from items
+            where upper(name) = upper($1)
+            "#,
+        )
+        .bind(&item.name)
+        .fetch_all(&*context.clients.postgres)
+        .await?
+        .into_iter()
+        .map(|r| r.try_get("id"))
+        .collect::<Result<Vec<i32>, _>>()?;
+
+        if id_match
+            .iter()
+            .all(|id_match| id.map(i32::from) == Some(*id_match))
+        {
+            Ok(())
+        } else {
+            Err(name_conflict(&item.name))
+        }
+    }
+
+    /// Validates that the sku of an item has the configured format, and then that it is unique,
+    /// ignoring case, so the format is reported first. The uniqueness check only reports conflicts
+    /// early, as a concurrent request can insert the sku after it, so writes must also map
+    /// violations of the unique indexes with [`map_conflict`] to the same error.
     pub(crate) async fn validate_sku(
         context: &AppContext,
         item: &InsertableItem,
         id: Option<ItemId>,
-    ) -> Result<()> {
+    ) -> Result<(), ModelError> {
         if let Some(sku) = &item.sku {
+            validate_sku_format(&context.config, sku)?;
+
             let id_match = sqlx::query(
                 r#"
                 select id from items
@@ -119,19 +865,398 @@ pub(crate) mod item {
             )
             .bind(sku)
             .fetch_optional(&*context.clients.postgres)
-            .await
-            .map_err(Error::from)?
+            .await?
             .map(|r| r.try_get("id"))
             .map_or(Ok(None), |v| v.map(Some))?;
 
             if id_match.is_none() || id.map(i32::from) == id_match {
                 Ok(())
             } else {
-                Err(Error::new("validation errors on item")
-                    .extend_with(|_, e| e.set("itemId", format!("sku {:?} not unique", sku))))
+                Err(sku_conflict(sku))
             }
         } else {
             Ok(())
         }
     }
 }
+
+pub(crate) mod attachment {
+    use super::*;
+
+    use crate::model::attachment::InsertableItemAttachment;
+
+    /// Validates that the url of an attachment is an absolute http or https url, and that the item
+    /// exists.
+    pub(crate) async fn validate_attachment(
+        context: &AppContext,
+        attachment: &InsertableItemAttachment,
+    ) -> Result<(), ModelError> {
+        let mut errors = Vec::new();
+        if let Err(error) = validate_url(&attachment.url) {
+            errors.push(("url", error));
+        }
+
+        let item_count = sqlx::query(r#"select count(id) from items where id = $1"#)
+            .bind(attachment.item_id)
+            .fetch_one(&*context.clients.postgres)
+            .await?
+            .try_get::<Option<i64>, _>("count")?
+            .unwrap_or(0);
+        if item_count == 0 {
+            errors.push((
+                "itemId",
+                format!("item with id {:?} not found", attachment.item_id),
+            ));
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ModelError::Validation {
+                message: "validation errors on attachment".to_string(),
+                fields: errors,
+            })
+        }
+    }
+
+    /// Validates that a url is an absolute http or https url with a host, returning the reason if
+    /// it is not.
+    pub(crate) fn validate_url(url: &str) -> Result<(), String> {
+        match url::Url::parse(url) {
+            Ok(parsed)
+                if matches!(parsed.scheme(), "http" | "https") && parsed.host().is_some() =>
+            {
+                Ok(())
+            }
+            _ => Err(format!(
+                "url {:?} is not an absolute http or https url",
+                url
+            )),
+        }
+    }
+}
+
+pub(crate) mod rules {
+    use super::*;
+
+    /// A constraint on a field of an input, which clients can check before sending the input.
+    #[derive(Debug, Clone, PartialEq, async_graphql::SimpleObject)]
+    pub(crate) struct ValidationRule {
+        /// The type the input creates, such as `Item`.
+        #[graphql(name = "type")]
+        type_name: String,
+        /// The name of the input object in the schema, such as `InsertableItem`.
+        input: String,
+        /// The name of the field in the input.
+        field: String,
+        /// Whether the field must be given.
+        required: bool,
+        /// The minimum length of the field, if it is text.
+        min_length: Option<i32>,
+        /// The maximum length of the field, if it is text.
+        max_length: Option<i32>,
+        /// The minimum value of the field, inclusive, if it is a number.
+        minimum: Option<f64>,
+        /// The maximum value of the field, inclusive, if it is a number.
+        maximum: Option<f64>,
+        /// Whether the field must not be zero.
+        non_zero: bool,
+        /// The regular expression the field must match in full.
+        pattern: Option<String>,
+    }
+
+    /// A constraint on a field of an input object, before the configuration is applied.
+    #[derive(Debug)]
+    pub(crate) struct InputRule {
+        pub(crate) input: &'static str,
+        type_name: &'static str,
+        pub(crate) field: &'static str,
+        pub(crate) required: bool,
+        min_length: Option<i32>,
+        range: Option<(f64, f64)>,
+        non_zero: bool,
+        /// Whether the field must have the configured sku format.
+        sku_format: bool,
+    }
+
+    impl InputRule {
+        const fn new(
+            input: &'static str,
+            type_name: &'static str,
+            field: &'static str,
+            required: bool,
+        ) -> Self {
+            InputRule {
+                input,
+                type_name,
+                field,
+                required,
+                min_length: None,
+                range: None,
+                non_zero: false,
+                sku_format: false,
+            }
+        }
+
+        /// Text must not be empty, matching `validator(min_length = 1)` on the input object.
+        const fn not_empty(self) -> Self {
+            InputRule {
+                min_length: Some(1),
+                ..self
+            }
+        }
+
+        const fn range(self, range: (f64, f64)) -> Self {
+            InputRule {
+                range: Some(range),
+                ..self
+            }
+        }
+
+        const fn non_zero(self) -> Self {
+            InputRule {
+                non_zero: true,
+                ..self
+            }
+        }
+
+        const fn sku_format(self) -> Self {
+            InputRule {
+                sku_format: true,
+                ..self
+            }
+        }
+    }
+
+    /// The constraints on the fields of inputs, which must be kept in line with the validators of
+    /// the input objects and the validation functions.
+    pub(crate) const INPUT_RULES: [InputRule; 12] = [
+        InputRule::new("InsertableItem", "Item", "sku", false)
+            .not_empty()
+            .sku_format(),
+        InputRule::new("InsertableItem", "Item", "name", true).not_empty(),
+        InputRule::new("InsertableItem", "Item", "supplier", false).not_empty(),
+        InputRule::new("InsertableItem", "Item", "description", false).not_empty(),
+        InputRule::new("InsertableLocation", "Location", "name", true).not_empty(),
+        InputRule::new("InsertableLocation", "Location", "address", false).not_empty(),
+        InputRule::new("InsertableLocation", "Location", "lat", false).range(location::LATITUDES),
+        InputRule::new("InsertableLocation", "Location", "lng", false).range(location::LONGITUDES),
+        InputRule::new("InsertableTransaction", "Transaction", "quantity", true).non_zero(),
+        InputRule::new("InsertableTransaction", "Transaction", "comment", false).not_empty(),
+        InputRule::new("InsertableTransfer", "Transfer", "quantity", true)
+            .range((1.0, i32::MAX as f64)),
+        InputRule::new("InsertableTransfer", "Transfer", "comment", false).not_empty(),
+    ];
+
+    /// Gets the constraints on the fields of inputs, with the configuration applied. The allowed
+    /// characters of skus are not reported, as they are fixed.
+    pub(crate) fn get_validation_rules(context: &AppContext) -> Vec<ValidationRule> {
+        let sku_pattern = context
+            .config
+            .sku_pattern
+            .as_ref()
+            .map(|sku_pattern| sku_pattern.as_str().to_string());
+
+        INPUT_RULES
+            .iter()
+            .map(|rule| ValidationRule {
+                type_name: rule.type_name.to_string(),
+                input: rule.input.to_string(),
+                field: rule.field.to_string(),
+                required: rule.required,
+                min_length: rule.min_length,
+                max_length: rule
+                    .sku_format
+                    .then(|| i32::try_from(context.config.sku_max_length).unwrap_or(i32::MAX)),
+                minimum: rule.range.map(|(minimum, _)| minimum),
+                maximum: rule.range.map(|(_, maximum)| maximum),
+                non_zero: rule.non_zero,
+                pattern: rule.sku_format.then(|| sku_pattern.clone()).flatten(),
+            })
+            .collect()
+    }
+}
+
+/// Unit tests for validation.
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use crate::graphql;
+    use crate::model::config::ModelConfig;
+    use crate::model::item::ItemQuantity;
+    use crate::model::location::LocationId;
+
+    /// Gets the field errors of a validation error.
+    fn fields(e: ModelError) -> Vec<(&'static str, String)> {
+        match e {
+            ModelError::Validation { fields, .. } => fields,
+            e => panic!("{:?} is not a validation error", e),
+        }
+    }
+
+    /// Creates the arguments of a transfer.
+    fn transfer_fake(from: i32, to: i32, quantity: i32) -> (LocationId, LocationId, ItemQuantity) {
+        (
+            serde_json::from_value(serde_json::json!(from)).unwrap(),
+            serde_json::from_value(serde_json::json!(to)).unwrap(),
+            serde_json::from_value(serde_json::json!(quantity)).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_validate_transfer() {
+        let (from, to, quantity) = transfer_fake(1, 2, 5);
+        assert!(transaction::validate_transfer(from, to, quantity).is_ok());
+    }
+
+    #[test]
+    fn test_validate_transfer_same_location() {
+        let (from, to, quantity) = transfer_fake(1, 1, 5);
+        assert_eq!(
+            fields(transaction::validate_transfer(from, to, quantity).unwrap_err()),
+            vec![(
+                "toLocationId",
+                "location with id LocationId(1) is also the location transferred from".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_validate_transfer_non_positive_quantity() {
+        for quantity in [0, -5] {
+            let (from, to, quantity) = transfer_fake(1, 2, quantity);
+            assert_eq!(
+                fields(transaction::validate_transfer(from, to, quantity).unwrap_err()),
+                vec![(
+                    "quantity",
+                    format!("quantity {} must be positive", i32::from(quantity))
+                )]
+            );
+        }
+
+        // both arguments are reported when both are invalid
+        let (from, to, quantity) = transfer_fake(1, 1, 0);
+        let fields = fields(transaction::validate_transfer(from, to, quantity).unwrap_err());
+        assert_eq!(
+            fields.iter().map(|(field, _)| *field).collect::<Vec<_>>(),
+            vec!["toLocationId", "quantity"]
+        );
+    }
+
+    #[test]
+    fn test_validate_url() {
+        assert!(attachment::validate_url("https://cdn.example.com/items/1.png").is_ok());
+        assert!(attachment::validate_url("http://localhost:9000/bucket/key").is_ok());
+        for url in [
+            "",
+            "items/1.png",
+            "ftp://example.com/1.png",
+            "javascript:alert(1)",
+        ] {
+            assert_eq!(
+                attachment::validate_url(url),
+                Err(format!(
+                    "url {:?} is not an absolute http or https url",
+                    url
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_quantity_range() {
+        let quantity =
+            |quantity: i32| Some(serde_json::from_value(serde_json::json!(quantity)).unwrap());
+        assert!(item::validate_quantity_range(quantity(1), quantity(5)).is_ok());
+        assert!(item::validate_quantity_range(quantity(5), quantity(5)).is_ok());
+        assert!(item::validate_quantity_range(quantity(5), None).is_ok());
+        assert!(item::validate_quantity_range(None, quantity(-5)).is_ok());
+        assert_eq!(
+            fields(item::validate_quantity_range(quantity(5), quantity(1)).unwrap_err()),
+            vec![(
+                "minQuantity",
+                "minimum quantity 5 is greater than the maximum quantity 1".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_validate_sku_pattern() {
+        let sku_pattern = regex::Regex::new("^(?:[A-Z]{3}-[0-9]+)$").unwrap();
+        assert!(item::validate_sku_pattern(Some(&sku_pattern), "ABC-12").is_ok());
+        assert!(item::validate_sku_pattern(None, "abc").is_ok());
+        assert_eq!(
+            fields(item::validate_sku_pattern(Some(&sku_pattern), "ABC-12x").unwrap_err()),
+            vec![(
+                "sku",
+                r#"sku "ABC-12x" does not match the pattern ^(?:[A-Z]{3}-[0-9]+)$"#.to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_validate_sku_format() {
+        let config = ModelConfig {
+            sku_max_length: 8,
+            ..ModelConfig::default()
+        };
+        for sku in ["ABC-12_x", "a"] {
+            assert!(item::validate_sku_format(&config, sku).is_ok(), "{}", sku);
+        }
+        assert_eq!(
+            fields(item::validate_sku_format(&config, "ABC-12345").unwrap_err()),
+            vec![(
+                "sku",
+                r#"sku "ABC-12345" is longer than 8 characters"#.to_string()
+            )]
+        );
+        for (sku, c) in [("ABC 12", ' '), ("ABC/12", '/'), ("ÄBC-12", 'Ä')] {
+            assert_eq!(
+                fields(item::validate_sku_format(&config, sku).unwrap_err()),
+                vec![(
+                    "sku",
+                    format!(
+                        "sku {:?} has the character {:?}, but only letters, digits, '-' and '_' are allowed",
+                        sku, c
+                    )
+                )]
+            );
+        }
+
+        // the length and characters are checked before the pattern
+        let config = ModelConfig {
+            sku_pattern: Some(regex::Regex::new("^(?:[A-Z]+)$").unwrap()),
+            ..config
+        };
+        assert!(
+            fields(item::validate_sku_format(&config, "ABC 12").unwrap_err())[0]
+                .1
+                .contains("has the character")
+        );
+    }
+
+    #[test]
+    fn test_input_rules_match_schema() {
+        // the rules are written by hand, so check they name fields of the inputs, which are
+        // non-null exactly when the rules require them
+        let sdl = graphql::schema_builder().finish().sdl();
+        for rule in &rules::INPUT_RULES {
+            let start = sdl
+                .find(&format!("input {} {{", rule.input))
+                .unwrap_or_else(|| panic!("no input {}", rule.input));
+            let input = &sdl[start..start + sdl[start..].find('}').unwrap()];
+            let field_type = input
+                .lines()
+                .find_map(|line| line.trim().strip_prefix(&format!("{}: ", rule.field)))
+                .unwrap_or_else(|| panic!("no field {} of {}", rule.field, rule.input));
+            assert_eq!(
+                field_type.ends_with('!'),
+                rule.required,
+                "{}.{}",
+                rule.input,
+                rule.field
+            );
+        }
+    }
+}
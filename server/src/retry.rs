@@ -0,0 +1,105 @@
+use std::fmt::Display;
+use std::future::Future;
+use std::time::Duration;
+
+/// How often to attempt an operation, and how long to wait before the first retry, which doubles
+/// after each failed attempt.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub(crate) struct RetryPolicy {
+    pub(crate) attempts: u32,
+    pub(crate) delay: Duration,
+}
+
+/// Attempts an operation until it succeeds or the attempts run out, logging each failure,
+/// returning the result, or the error of the last attempt.
+pub(crate) async fn with_backoff<T, E, F, Fut>(
+    name: &str,
+    policy: RetryPolicy,
+    mut operation: F,
+) -> Result<T, E>
+where
+    E: Display,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let attempts = policy.attempts.max(1);
+    let mut delay = policy.delay;
+    let mut attempt = 1;
+    loop {
+        match operation().await {
+            Ok(result) => return Ok(result),
+            Err(e) if attempt < attempts => {
+                log::warn!(
+                    "unable to connect to {} (attempt {} of {}), retrying in {:?}: {}",
+                    name,
+                    attempt,
+                    attempts,
+                    delay,
+                    e
+                );
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            Err(e) => {
+                log::error!(
+                    "unable to connect to {} after {} attempts: {}",
+                    name,
+                    attempts,
+                    e
+                );
+                return Err(e);
+            }
+        }
+    }
+}
+
+/// Unit tests for retrying.
+#[cfg(test)]
+mod test {
+    use std::cell::Cell;
+
+    use super::*;
+
+    /// A policy which retries quickly.
+    const POLICY: RetryPolicy = RetryPolicy {
+        attempts: 3,
+        delay: Duration::from_millis(1),
+    };
+
+    /// A fake that fails until the given attempt.
+    async fn connect_fake(calls: &Cell<u32>, succeed_on: u32) -> Result<u32, String> {
+        calls.set(calls.get() + 1);
+        if calls.get() >= succeed_on {
+            Ok(calls.get())
+        } else {
+            Err(format!("attempt {} failed", calls.get()))
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_retry_then_succeed() {
+        let calls = Cell::new(0);
+        let result = with_backoff("fake", POLICY, || connect_fake(&calls, 3)).await;
+        assert_eq!(result, Ok(3));
+    }
+
+    #[actix_rt::test]
+    async fn test_retry_give_up() {
+        let calls = Cell::new(0);
+        let result = with_backoff("fake", POLICY, || connect_fake(&calls, 4)).await;
+        assert_eq!(result, Err("attempt 3 failed".to_string()));
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[actix_rt::test]
+    async fn test_retry_at_least_once() {
+        let calls = Cell::new(0);
+        let policy = RetryPolicy {
+            attempts: 0,
+            ..POLICY
+        };
+        let result = with_backoff("fake", policy, || connect_fake(&calls, 1)).await;
+        assert_eq!(result, Ok(1));
+    }
+}
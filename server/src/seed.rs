@@ -0,0 +1,147 @@
+use std::fs;
+
+use serde::Deserialize;
+use sqlx::{Pool, Postgres, Row};
+
+use crate::config::Config;
+
+/// The built-in seed dataset, with a default location and sample items.
+const DEFAULT_SEED: &str = include_str!("../seed.json");
+
+/// The key of the advisory lock held while seeding, so servers starting together seed once.
+const SEED_LOCK_KEY: i64 = 0x5eed;
+
+/// A location of a seed dataset, which is identified by its name.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub(crate) struct SeedLocation {
+    pub(crate) name: String,
+    pub(crate) address: Option<String>,
+}
+
+/// An item of a seed dataset, which is identified by its sku, ignoring case, or by its name if it
+/// has no sku.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub(crate) struct SeedItem {
+    pub(crate) sku: Option<String>,
+    pub(crate) name: String,
+    pub(crate) supplier: Option<String>,
+    pub(crate) description: Option<String>,
+}
+
+/// The baseline data of a development or demo environment.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub(crate) struct SeedDataset {
+    #[serde(default)]
+    pub(crate) locations: Vec<SeedLocation>,
+    #[serde(default)]
+    pub(crate) items: Vec<SeedItem>,
+}
+
+/// The names of the locations and items which were inserted by seeding.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct SeedReport {
+    pub(crate) locations: Vec<String>,
+    pub(crate) items: Vec<String>,
+}
+
+/// Gets the configured seed dataset, which is read from `SEED_FILE` if it is set, or is the
+/// built-in dataset otherwise.
+pub(crate) fn get_dataset(config: &Config) -> SeedDataset {
+    let source = match &config.seed_file {
+        Some(seed_file) => fs::read_to_string(seed_file)
+            .unwrap_or_else(|e| panic!("unable to read seed file {}: {}", seed_file, e)),
+        None => DEFAULT_SEED.to_string(),
+    };
+    serde_json::from_str(&source).expect("unable to parse seed dataset")
+}
+
+/// Inserts the locations and items of a dataset which do not exist yet, in one database
+/// transaction, returning the names of those which were inserted, or an error. Seeding again
+/// inserts nothing, and changes are not broadcast to subscribers.
+pub(crate) async fn seed(
+    pool: &Pool<Postgres>,
+    dataset: &SeedDataset,
+) -> Result<SeedReport, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("select pg_advisory_xact_lock($1)")
+        .bind(SEED_LOCK_KEY)
+        .execute(&mut tx)
+        .await?;
+
+    let mut report = SeedReport::default();
+    for location in &dataset.locations {
+        let inserted = sqlx::query(
+            r#"
+            insert into locations (name, address)
+            select $1, $2
+            where not exists (select 1 from locations where name = $1)
+            returning name
+        "#,
+        )
+        .bind(&location.name)
+        .bind(&location.address)
+        .fetch_optional(&mut tx)
+        .await?;
+        if let Some(row) = inserted {
+            report.locations.push(row.try_get("name")?);
+        }
+    }
+    for item in &dataset.items {
+        let inserted = sqlx::query(
+            r#"
+            insert into items (sku, name, supplier, description)
+            select $1, $2, $3, $4
+            where not exists (
+                select 1 from items
+                where ($1::text is not null and upper(sku) = upper($1))
+                    or ($1::text is null and name = $2)
+            )
+            returning name
+        "#,
+        )
+        .bind(&item.sku)
+        .bind(&item.name)
+        .bind(&item.supplier)
+        .bind(&item.description)
+        .fetch_optional(&mut tx)
+        .await?;
+        if let Some(row) = inserted {
+            report.items.push(row.try_get("name")?);
+        }
+    }
+    tx.commit().await?;
+
+    Ok(report)
+}
+
+/// Seeds the configured dataset if `SEED_ON_STARTUP` is set, logging what was seeded.
+pub(crate) async fn seed_on_startup(pool: &Pool<Postgres>, config: &Config) {
+    if !config.seed_on_startup {
+        return;
+    }
+
+    let report = seed(pool, &get_dataset(config))
+        .await
+        .expect("unable to seed the database");
+    log::info!(
+        "seeded {} locations {:?} and {} items {:?}",
+        report.locations.len(),
+        report.locations,
+        report.items.len(),
+        report.items
+    );
+}
+
+/// Unit tests for seeding.
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_default_dataset() {
+        let dataset: SeedDataset = serde_json::from_str(DEFAULT_SEED).unwrap();
+        assert!(!dataset.locations.is_empty());
+        assert!(!dataset.items.is_empty());
+        assert!(dataset.items.iter().all(|item| item.sku.is_some()));
+    }
+}
@@ -1,8 +1,17 @@
-use std::env;
-
 use redis::RedisError;
 
-/// Gets the Redis client, returning the result of the client, or the Redis error.
-pub(crate) async fn get_client() -> Result<redis::Client, RedisError> {
-    redis::Client::open(env::var("REDIS_URL").expect("REDIS_URL must be set"))
+use crate::config::Config;
+use crate::retry;
+
+/// Gets the Redis client once Redis is ready, returning the result of the client, or the Redis
+/// error.
+pub(crate) async fn get_client(config: &Config) -> Result<redis::Client, RedisError> {
+    let client = redis::Client::open(config.redis_url.as_str())?;
+    // opening the client does not connect, so check that redis can be connected to
+    retry::with_backoff("redis", config.retry_policy(), || async {
+        client.get_async_connection().await.map(|_| ())
+    })
+    .await?;
+
+    Ok(client)
 }
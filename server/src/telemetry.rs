@@ -0,0 +1,87 @@
+use std::future::Future;
+
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse};
+use actix_web::http::header::HeaderMap;
+use actix_web::Error;
+use opentelemetry::propagation::{Extractor, TextMapPropagator};
+use opentelemetry::sdk::propagation::TraceContextPropagator;
+use opentelemetry::trace::TraceError;
+use opentelemetry_otlp::WithExportConfig;
+use tracing::Instrument;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::layer::SubscriberExt;
+
+/// The name of the span of each HTTP request.
+pub(crate) const REQUEST_SPAN_NAME: &str = "HTTP request";
+
+/// Exports spans to an OpenTelemetry collector over OTLP, which is configured by the standard
+/// `OTEL_EXPORTER_OTLP_ENDPOINT` and `OTEL_SERVICE_NAME` variables, returning an error if the
+/// exporter cannot be built. Spans are exported in batches in the background, so this must be
+/// called in the runtime of the server.
+pub(crate) fn init() -> Result<(), TraceError> {
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(opentelemetry_otlp::new_exporter().tonic().with_env())
+        .install_batch(opentelemetry::runtime::Tokio)?;
+    let subscriber =
+        tracing_subscriber::registry().with(tracing_opentelemetry::layer().with_tracer(tracer));
+
+    tracing::subscriber::set_global_default(subscriber).map_err(|e| TraceError::from(e.to_string()))
+}
+
+/// Exports the spans which have not been exported yet, so they are not lost when the server stops.
+pub(crate) fn shutdown() {
+    opentelemetry::global::shutdown_tracer_provider();
+}
+
+/// The headers of a request, which the context of the trace of its caller is extracted from.
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl<'a> Extractor for HeaderExtractor<'a> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
+
+/// Creates the span of a request, whose parent is the span of the caller from the W3C
+/// `traceparent` header of the request, if it has one, so the request joins the trace of its
+/// caller. The span is disabled if spans are not exported, so the header is only parsed if they are.
+fn request_span(req: &ServiceRequest) -> tracing::Span {
+    let span = tracing::info_span!(
+        REQUEST_SPAN_NAME,
+        otel.kind = "server",
+        http.method = %req.method(),
+        http.target = %req.uri(),
+        http.status_code = tracing::field::Empty,
+    );
+    if !span.is_disabled() {
+        span.set_parent(TraceContextPropagator::new().extract(&HeaderExtractor(req.headers())));
+    }
+
+    span
+}
+
+/// Handles a request in its span, recording the status of its response, for `App::wrap_fn`.
+pub(crate) fn trace_request<S, B>(
+    req: ServiceRequest,
+    service: &S,
+) -> impl Future<Output = Result<ServiceResponse<B>, Error>>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+{
+    let span = request_span(&req);
+    let response = service.call(req);
+    async move {
+        let response = response.await;
+        if let Ok(response) = &response {
+            tracing::Span::current().record("http.status_code", response.status().as_u16());
+        }
+
+        response
+    }
+    .instrument(span)
+}